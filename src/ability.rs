@@ -0,0 +1,206 @@
+//
+// ability.rs -- typed model of ABILITY and ABILITYCATEGORY data
+//
+// Copyright (c) 2024 Jeff Garzik
+//
+// This file is part of the pcgtoolssoftware project covered under
+// the MIT License.  For the full license text, please see the LICENSE
+// file in the root directory of this project.
+// SPDX-License-Identifier: MIT
+
+use crate::pcc::{Pcc, PccElem};
+use crate::token;
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+// first value of a tag, if present
+fn first<'a>(elem: &'a PccElem, tag: &str) -> Option<&'a str> {
+    elem.values(tag).first().copied()
+}
+
+fn is_yes(elem: &PccElem, tag: &str) -> bool {
+    first(elem, tag).is_some_and(|v| v.eq_ignore_ascii_case("YES"))
+}
+
+fn strings(values: Vec<&str>) -> Vec<String> {
+    values.into_iter().map(String::from).collect()
+}
+
+// an ABILITY element
+#[derive(Clone, Debug, Serialize)]
+pub struct Ability {
+    // KEY, else name
+    pub key: String,
+    pub name: String,
+    pub category: String,
+    pub types: Vec<String>,
+    // MULT:YES, may be taken more than once
+    pub multiple: bool,
+    // STACK:YES, repeated choices stack
+    pub stackable: bool,
+    pub choose: Option<String>,
+    pub cost: Option<f64>,
+    pub source: String,
+    pub line: usize,
+}
+
+impl Ability {
+    pub fn new(key: &str, elem: &PccElem) -> Ability {
+        Ability {
+            key: key.to_string(),
+            name: elem.name().to_string(),
+            category: first(elem, "CATEGORY").unwrap_or("").to_string(),
+            types: strings(elem.values("TYPE")),
+            multiple: is_yes(elem, "MULT"),
+            stackable: is_yes(elem, "STACK"),
+            choose: first(elem, "CHOOSE").map(String::from),
+            cost: first(elem, "COST").and_then(|v| v.trim().parse().ok()),
+            source: elem.source().to_string(),
+            line: elem.line(),
+        }
+    }
+
+    pub fn has_type(&self, t: &str) -> bool {
+        self.types.iter().any(|ty| ty.eq_ignore_ascii_case(t))
+    }
+}
+
+// an ABILITYCATEGORY element.  A category either holds abilities
+// directly, or is a subset of another category's abilities, selected
+// by TYPE or by ABILITYLIST.
+#[derive(Clone, Debug, Serialize)]
+pub struct AbilityCategory {
+    pub name: String,
+    // CATEGORY: the category whose abilities this one draws upon
+    pub parent: String,
+    // TYPE: abilities of any of these types belong; "ALL" for all
+    pub types: Vec<String>,
+    // ABILITYLIST: abilities belonging by name
+    pub abilities: Vec<String>,
+    pub plural: Option<String>,
+    pub visible: bool,
+    pub editable: bool,
+    pub source: String,
+    pub line: usize,
+}
+
+impl AbilityCategory {
+    fn new(name: &str, elem: &PccElem) -> AbilityCategory {
+        AbilityCategory {
+            name: name.to_string(),
+            parent: first(elem, "CATEGORY").unwrap_or(name).to_string(),
+            types: strings(elem.values("TYPE")),
+            abilities: elem
+                .values("ABILITYLIST")
+                .iter()
+                .flat_map(|v| token::items(v))
+                .map(String::from)
+                .collect(),
+            plural: first(elem, "PLURAL").map(String::from),
+            visible: first(elem, "VISIBLE").is_none_or(|v| !v.eq_ignore_ascii_case("NO")),
+            editable: first(elem, "EDITABLE").is_none_or(|v| !v.eq_ignore_ascii_case("NO")),
+            source: elem.source().to_string(),
+            line: elem.line(),
+        }
+    }
+
+    // does an ability of the parent category belong to this category?
+    pub fn includes(&self, ability: &Ability) -> bool {
+        if !ability.category.eq_ignore_ascii_case(&self.parent) {
+            return false;
+        }
+        if self.types.is_empty() && self.abilities.is_empty() {
+            return true;
+        }
+        self.types
+            .iter()
+            .any(|t| t.eq_ignore_ascii_case("ALL") || ability.has_type(t))
+            || self
+                .abilities
+                .iter()
+                .any(|a| a.eq_ignore_ascii_case(&ability.key))
+    }
+}
+
+// the loaded abilities, linked to their categories
+#[derive(Debug, Default, Serialize)]
+pub struct Abilities {
+    // lowercased category name => category
+    pub categories: BTreeMap<String, AbilityCategory>,
+    pub abilities: Vec<Ability>,
+}
+
+impl Abilities {
+    pub fn new(pcc: &Pcc) -> Abilities {
+        let mut model = Abilities::default();
+
+        if let Some(lst) = pcc.list("ABILITYCATEGORY") {
+            for (name, elem) in &lst.props {
+                let cat = AbilityCategory::new(name, elem);
+                model.categories.insert(name.to_lowercase(), cat);
+            }
+        }
+
+        if let Some(lst) = pcc.list("ABILITY") {
+            model.abilities = lst
+                .props
+                .iter()
+                .map(|(key, elem)| Ability::new(key, elem))
+                .collect();
+            model.abilities.sort_by(|a, b| a.key.cmp(&b.key));
+        }
+
+        model
+    }
+
+    pub fn category(&self, name: &str) -> Option<&AbilityCategory> {
+        self.categories.get(&name.to_lowercase())
+    }
+
+    // abilities belonging to a category, either directly or, for a
+    // category drawing upon another, by TYPE or ABILITYLIST
+    pub fn in_category(&self, name: &str) -> Vec<&Ability> {
+        match self.category(name) {
+            Some(cat) => self.abilities.iter().filter(|a| cat.includes(a)).collect(),
+            None => self
+                .abilities
+                .iter()
+                .filter(|a| a.category.eq_ignore_ascii_case(name))
+                .collect(),
+        }
+    }
+
+    // an ability by KEY or name, within a category
+    pub fn get(&self, category: &str, key: &str) -> Option<&Ability> {
+        self.in_category(category)
+            .into_iter()
+            .find(|a| a.key.eq_ignore_ascii_case(key) || a.name.eq_ignore_ascii_case(key))
+    }
+}
+
+// an ABILITY tag of another element, granting abilities, e.g.
+// "ABILITY:Special Ability|AUTOMATIC|Darkvision|Low-Light Vision"
+#[derive(Clone, Debug, Serialize)]
+pub struct AbilityGrant {
+    pub category: String,
+    // NORMAL, AUTOMATIC or VIRTUAL
+    pub nature: String,
+    pub names: Vec<String>,
+}
+
+impl AbilityGrant {
+    pub fn parse(value: &str) -> Option<AbilityGrant> {
+        let mut items = token::items(value).into_iter();
+        let category = items.next()?.to_string();
+        let nature = items.next()?.to_string();
+        let names = items
+            .filter(|n| !n.is_empty() && !token::is_prereq(n))
+            .map(String::from)
+            .collect();
+        Some(AbilityGrant {
+            category,
+            nature,
+            names,
+        })
+    }
+}
@@ -0,0 +1,103 @@
+//
+// auto.rs -- AUTO tags, granting proficiencies, equipment, languages
+// and feats
+//
+// Copyright (c) 2024 Jeff Garzik
+//
+// This file is part of the pcgtoolssoftware project covered under
+// the MIT License.  For the full license text, please see the LICENSE
+// file in the root directory of this project.
+// SPDX-License-Identifier: MIT
+
+use crate::prereq::Prereq;
+use crate::token;
+use serde::Serialize;
+
+// what an AUTO tag grants
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub enum AutoKind {
+    WeaponProf,
+    ArmorProf,
+    ShieldProf,
+    Equip,
+    Lang,
+    // deprecated; data now grants abilities with ABILITY
+    Feat,
+}
+
+// AUTO subtags, the kind of each, and the list the names granted are
+// of
+const AUTO_KINDS: &[(&str, AutoKind, &str)] = &[
+    ("WEAPONPROF", AutoKind::WeaponProf, "WEAPONPROF"),
+    ("ARMORPROF", AutoKind::ArmorProf, "ARMORPROF"),
+    ("SHIELDPROF", AutoKind::ShieldProf, "SHIELDPROF"),
+    ("EQUIP", AutoKind::Equip, "EQUIPMENT"),
+    ("LANG", AutoKind::Lang, "LANGUAGE"),
+    ("FEAT", AutoKind::Feat, "ABILITY"),
+];
+
+impl AutoKind {
+    // the list the names granted are of, e.g. LANGUAGE
+    pub fn list(self) -> &'static str {
+        AUTO_KINDS
+            .iter()
+            .find(|(_, kind, _)| *kind == self)
+            .map(|(_, _, list)| *list)
+            .expect("AUTO kind listed")
+    }
+}
+
+// a name granted, with the conditions in brackets after it, if any,
+// e.g. "Longsword[PRERACE:1,Elf]"
+#[derive(Clone, Debug, Serialize)]
+pub struct AutoItem {
+    pub name: String,
+    pub prereqs: Vec<Prereq>,
+}
+
+// an AUTO tag, e.g. AUTO:WEAPONPROF|Longsword|Dagger|PRELEVEL:2.  The
+// conditions of the tag apply to all its names.
+#[derive(Clone, Debug, Serialize)]
+pub struct AutoGrant {
+    pub kind: AutoKind,
+    pub items: Vec<AutoItem>,
+    pub prereqs: Vec<Prereq>,
+}
+
+impl AutoGrant {
+    // parse the value of an AUTO tag; None for subtags not modelled
+    pub fn parse(value: &str) -> Option<AutoGrant> {
+        let items = token::items(value);
+        let (subtag, rest) = items.split_first()?;
+        let kind = AUTO_KINDS
+            .iter()
+            .find(|(name, _, _)| subtag.eq_ignore_ascii_case(name))
+            .map(|(_, kind, _)| *kind)?;
+
+        let mut grant = AutoGrant {
+            kind,
+            items: Vec::new(),
+            prereqs: Vec::new(),
+        };
+        for item in rest.iter().filter(|item| !item.is_empty()) {
+            if token::is_prereq(item) {
+                grant.prereqs.extend(Prereq::parse(item));
+                continue;
+            }
+            let (name, prereqs) = match item.strip_suffix(']').and_then(|i| i.split_once('[')) {
+                Some((name, conds)) => (name, token::items(conds)),
+                None => (*item, Vec::new()),
+            };
+            grant.items.push(AutoItem {
+                name: name.trim().to_string(),
+                prereqs: prereqs.into_iter().filter_map(Prereq::parse).collect(),
+            });
+        }
+        Some(grant)
+    }
+
+    // the names granted, less their conditions
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.items.iter().map(|item| item.name.as_str())
+    }
+}
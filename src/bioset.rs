@@ -0,0 +1,320 @@
+//
+// bioset.rs -- typed model of BIOSET data: age brackets, height and
+// weight, and other biographical settings by race
+//
+// Copyright (c) 2024 Jeff Garzik
+//
+// This file is part of the pcgtoolssoftware project covered under
+// the MIT License.  For the full license text, please see the LICENSE
+// file in the root directory of this project.
+// SPDX-License-Identifier: MIT
+
+use crate::dice;
+use crate::formula;
+use crate::pcc::{Pcc, PccElem};
+use serde::Serialize;
+
+// split "Name[K:V|K:V]Name[K:V]" into names and their tags
+fn bracketed(value: &str) -> Vec<(String, Vec<(String, String)>)> {
+    let mut out = Vec::new();
+    let mut rest = value;
+    while let Some(open) = rest.find('[') {
+        let name = rest[..open].trim_start_matches('|').to_string();
+        let Some(close) = rest[open..].find(']') else {
+            break;
+        };
+        let tags = rest[open + 1..open + close]
+            .split('|')
+            .filter_map(|t| t.split_once(':'))
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        out.push((name, tags));
+        rest = &rest[open + close + 1..];
+    }
+    out
+}
+
+fn tag_value<'a>(tags: &'a [(String, String)], tag: &str) -> Option<&'a str> {
+    tags.iter().find(|(k, _)| k == tag).map(|(_, v)| v.as_str())
+}
+
+// height and weight of one sex, e.g.
+// "Male[BASEHT:58|HTDIEROLL:2d10|BASEWT:120|WTDIEROLL:2d4]"
+#[derive(Clone, Debug, Serialize)]
+pub struct SexBio {
+    pub sex: String,
+    pub base_height: Option<i64>,
+    pub height_dice: Option<String>,
+    pub base_weight: Option<i64>,
+    pub weight_dice: Option<String>,
+    // other tags, e.g. TOTALWT
+    pub tags: Vec<(String, String)>,
+}
+
+// extra starting age dice for a group of classes, e.g.
+// "Barbarian,Rogue[BASEAGEADD:1d4]"
+#[derive(Clone, Debug, Serialize)]
+pub struct ClassAge {
+    pub classes: Vec<String>,
+    pub dice: String,
+}
+
+// the settings of one race within an age set, from its RACENAME lines
+#[derive(Clone, Debug, Serialize)]
+pub struct RaceBio {
+    // race name; a trailing '%' matches any race of that prefix
+    pub race: String,
+    // the age bracket
+    pub base_age: Option<i64>,
+    pub max_age: Option<i64>,
+    pub age_dice: Option<String>,
+    pub class_ages: Vec<ClassAge>,
+    pub sexes: Vec<SexBio>,
+    // other tags, e.g. HAIR, EYES, SKINTONE
+    pub tags: Vec<(String, String)>,
+}
+
+impl RaceBio {
+    fn new(race: &str) -> RaceBio {
+        RaceBio {
+            race: race.to_string(),
+            base_age: None,
+            max_age: None,
+            age_dice: None,
+            class_ages: Vec::new(),
+            sexes: Vec::new(),
+            tags: Vec::new(),
+        }
+    }
+
+    // add the tags of a stored RACENAME line, "Race<tab>TAG:value..."
+    fn add_line(&mut self, tokens: std::str::Split<'_, char>) {
+        for token in tokens {
+            let (tag, value) = token.split_once(':').unwrap_or((token, ""));
+            match tag {
+                "BASEAGE" => self.base_age = value.trim().parse().ok(),
+                "MAXAGE" => self.max_age = value.trim().parse().ok(),
+                "AGEDIEROLL" => self.age_dice = Some(value.to_string()),
+                "CLASS" => {
+                    for (classes, tags) in bracketed(value) {
+                        self.class_ages.push(ClassAge {
+                            classes: classes.split(',').map(String::from).collect(),
+                            dice: tag_value(&tags, "BASEAGEADD").unwrap_or("").to_string(),
+                        });
+                    }
+                }
+                "SEX" => {
+                    for (sex, tags) in bracketed(value) {
+                        let num = |t: &str| tag_value(&tags, t).and_then(|v| v.trim().parse().ok());
+                        let text = |t: &str| tag_value(&tags, t).map(String::from);
+                        self.sexes.push(SexBio {
+                            base_height: num("BASEHT"),
+                            height_dice: text("HTDIEROLL"),
+                            base_weight: num("BASEWT"),
+                            weight_dice: text("WTDIEROLL"),
+                            tags: tags
+                                .iter()
+                                .filter(|(k, _)| {
+                                    !["BASEHT", "HTDIEROLL", "BASEWT", "WTDIEROLL"]
+                                        .contains(&k.as_str())
+                                })
+                                .cloned()
+                                .collect(),
+                            sex,
+                        });
+                    }
+                }
+                _ => self.tags.push((tag.to_string(), value.to_string())),
+            }
+        }
+    }
+
+    // does this entry apply to a race?
+    pub fn applies_to(&self, race: &str) -> bool {
+        match self.race.strip_suffix('%') {
+            Some(prefix) => race.to_lowercase().starts_with(&prefix.to_lowercase()),
+            None => self.race.eq_ignore_ascii_case(race),
+        }
+    }
+
+    pub fn sex(&self, sex: &str) -> Option<&SexBio> {
+        self.sexes.iter().find(|s| s.sex.eq_ignore_ascii_case(sex))
+    }
+}
+
+// an AGESET block, e.g. "AGESET:0|Adulthood"
+#[derive(Clone, Debug, Serialize)]
+pub struct AgeSet {
+    pub index: u32,
+    pub name: String,
+    // BONUS tags applying to characters of this age
+    pub bonuses: Vec<String>,
+    pub races: Vec<RaceBio>,
+    pub source: String,
+    pub line: usize,
+}
+
+impl AgeSet {
+    pub fn new(ident: &str, elem: &PccElem) -> AgeSet {
+        let (index, name) = ident.split_once('|').unwrap_or(("0", ident));
+        let mut ageset = AgeSet {
+            index: index.trim().parse().unwrap_or(0),
+            name: name.to_string(),
+            bonuses: elem.values("BONUS").into_iter().map(String::from).collect(),
+            races: Vec::new(),
+            source: elem.source().to_string(),
+            line: elem.line(),
+        };
+
+        // a race's settings are spread over several RACENAME lines
+        for (tag, value) in &elem.attribs {
+            if tag != "RACENAME" {
+                continue;
+            }
+            let mut tokens = value.split('\t');
+            let race = tokens.next().unwrap_or("");
+            let idx = match ageset.races.iter().position(|r| r.race == race) {
+                Some(idx) => idx,
+                None => {
+                    ageset.races.push(RaceBio::new(race));
+                    ageset.races.len() - 1
+                }
+            };
+            ageset.races[idx].add_line(tokens);
+        }
+
+        ageset
+    }
+
+    // the most specific entry applying to a race: an exact name in
+    // preference to a '%' pattern, and a longer pattern to a shorter
+    pub fn race(&self, race: &str) -> Option<&RaceBio> {
+        self.races
+            .iter()
+            .filter(|r| r.applies_to(race))
+            .max_by_key(|r| (!r.race.ends_with('%'), r.race.len()))
+    }
+}
+
+// a rolled age, height and weight, e.g. for an NPC
+#[derive(Clone, Debug, Serialize)]
+pub struct Bio {
+    pub race: String,
+    pub class: Option<String>,
+    pub sex: String,
+    pub age: i64,
+    // the age set the age falls in, e.g. Adulthood
+    pub ageset: String,
+    // inches
+    pub height: i64,
+    // pounds
+    pub weight: i64,
+}
+
+// all loaded age sets, in index order
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct BioSet {
+    pub agesets: Vec<AgeSet>,
+}
+
+impl BioSet {
+    pub fn new(pcc: &Pcc) -> BioSet {
+        let Some(lst) = pcc.list("BIOSET") else {
+            return BioSet::default();
+        };
+
+        let mut agesets: Vec<AgeSet> = lst
+            .props
+            .iter()
+            .map(|(ident, elem)| AgeSet::new(ident, elem))
+            .collect();
+        agesets.sort_by_key(|a| a.index);
+        BioSet { agesets }
+    }
+
+    // the age sets applying to a race, with that race's settings
+    pub fn for_race(&self, race: &str) -> Vec<(&AgeSet, &RaceBio)> {
+        self.agesets
+            .iter()
+            .filter_map(|a| a.race(race).map(|r| (a, r)))
+            .collect()
+    }
+
+    // Roll the age, height and weight of a character of a race, and
+    // optionally class and sex; a missing sex is picked at random.
+    // Age is the race's BASEAGE plus the BASEAGEADD dice of the class,
+    // or AGEDIEROLL without a class.  Height is BASEHT plus HTDIEROLL,
+    // and weight is TOTALWT, by default BASEWT plus the height roll
+    // times WTDIEROLL.
+    pub fn generate(
+        &self,
+        race: &str,
+        class: Option<&str>,
+        sex: Option<&str>,
+        rng: &mut fastrand::Rng,
+    ) -> Result<Bio, String> {
+        let entries = self.for_race(race);
+        let Some((_, aged)) = entries.iter().find(|(_, r)| r.base_age.is_some()) else {
+            return Err(format!("no BIOSET age for race {}", race));
+        };
+
+        let class_dice = class.and_then(|class| {
+            entries
+                .iter()
+                .flat_map(|(_, r)| &r.class_ages)
+                .find(|ca| ca.classes.iter().any(|c| c.eq_ignore_ascii_case(class)))
+                .map(|ca| ca.dice.as_str())
+        });
+        let mut age = aged.base_age.unwrap_or(0);
+        if let Some(expr) = class_dice.or(aged.age_dice.as_deref()) {
+            age += dice::roll(expr, rng)?;
+        }
+        if let Some(max) = aged.max_age {
+            age = age.min(max);
+        }
+        let ageset = entries
+            .iter()
+            .rfind(|(_, r)| r.base_age.is_some_and(|b| b <= age))
+            .map_or(String::new(), |(a, _)| a.name.clone());
+
+        let sexes: Vec<&SexBio> = entries.iter().flat_map(|(_, r)| &r.sexes).collect();
+        let bio = match sex {
+            Some(sex) => sexes
+                .iter()
+                .find(|s| s.sex.eq_ignore_ascii_case(sex))
+                .ok_or_else(|| format!("no BIOSET SEX {} for race {}", sex, race))?,
+            None => rng
+                .choice(&sexes)
+                .ok_or_else(|| format!("no BIOSET SEX for race {}", race))?,
+        };
+
+        let roll = |expr: &Option<String>, rng: &mut fastrand::Rng| match expr {
+            Some(expr) => dice::roll(expr, rng),
+            None => Ok(0),
+        };
+        let height_roll = roll(&bio.height_dice, rng)?;
+        let weight_roll = roll(&bio.weight_dice, rng)?;
+        let base_height = bio.base_height.unwrap_or(0);
+        let base_weight = bio.base_weight.unwrap_or(0);
+
+        let total = tag_value(&bio.tags, "TOTALWT").unwrap_or("BASEWT+(HTDIEROLL*WTDIEROLL)");
+        let weight = formula::eval(total, &|var: &str| match var {
+            "BASEHT" => Some(base_height as f64),
+            "HTDIEROLL" => Some(height_roll as f64),
+            "BASEWT" => Some(base_weight as f64),
+            "WTDIEROLL" => Some(weight_roll as f64),
+            _ => None,
+        })
+        .map_err(|e| format!("TOTALWT:{}: {}", total, e))?;
+
+        Ok(Bio {
+            race: race.to_string(),
+            class: class.map(String::from),
+            sex: bio.sex.clone(),
+            age,
+            ageset,
+            height: base_height + height_roll,
+            weight: weight as i64,
+        })
+    }
+}
@@ -0,0 +1,522 @@
+//
+// bonus.rs -- BONUS tags, and their aggregation for a character under
+// the stacking rules
+//
+// Copyright (c) 2024 Jeff Garzik
+//
+// This file is part of the pcgtoolssoftware project covered under
+// the MIT License.  For the full license text, please see the LICENSE
+// file in the root directory of this project.
+// SPDX-License-Identifier: MIT
+
+use crate::class::Class;
+use crate::define::VarContext;
+use crate::formula;
+use crate::pcc::Pcc;
+use crate::prereq::Prereq;
+use crate::qualify::{self, Profile};
+use crate::token;
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+// bonus types which stack with themselves; untyped bonuses always do
+const STACKING_TYPES: [&str; 1] = ["Dodge"];
+
+// saving throws and the stat modifying each
+const SAVES: [(&str, &str); 3] = [("Fortitude", "CON"), ("Reflex", "DEX"), ("Will", "WIS")];
+
+// a BONUS tag, e.g. "BONUS:COMBAT|AC|2|TYPE=Armor"
+#[derive(Clone, Debug, Serialize)]
+pub struct Bonus {
+    // COMBAT, SAVE, SKILL, STAT, ...
+    pub category: String,
+    // e.g. AC, or Fortitude and Reflex
+    pub targets: Vec<String>,
+    pub formula: String,
+    // TYPE=..., without a .STACK or .REPLACE suffix
+    pub bonus_type: Option<String>,
+    // TYPE=Morale.STACK: stacks despite having a type
+    pub stacks: bool,
+    pub prereqs: Vec<Prereq>,
+}
+
+impl Bonus {
+    pub fn parse(value: &str) -> Option<Bonus> {
+        let items = token::items(value);
+        let [category, targets, formula, rest @ ..] = items.as_slice() else {
+            return None;
+        };
+
+        let mut bonus = Bonus {
+            category: category.to_ascii_uppercase(),
+            targets: token::sublist(targets)
+                .into_iter()
+                .map(String::from)
+                .collect(),
+            formula: formula.to_string(),
+            bonus_type: None,
+            stacks: false,
+            prereqs: Vec::new(),
+        };
+        for item in rest {
+            if let Some(t) = item.strip_prefix("TYPE=") {
+                let mut parts = t.split('.');
+                bonus.bonus_type = parts.next().map(String::from);
+                bonus.stacks = parts.any(|p| p.eq_ignore_ascii_case("STACK"));
+            } else if let Some(p) = Prereq::parse(item) {
+                bonus.prereqs.push(p);
+            }
+        }
+        Some(bonus)
+    }
+
+    fn stacks_with_itself(&self) -> bool {
+        match &self.bonus_type {
+            None => true,
+            Some(t) => self.stacks || STACKING_TYPES.iter().any(|s| s.eq_ignore_ascii_case(t)),
+        }
+    }
+}
+
+// a bonus of the character, and the element granting it
+#[derive(Clone, Debug, Serialize)]
+struct Granted {
+    // e.g. "RACE Elf"
+    source: String,
+    // the class granting it, whose level is CL in its formula
+    class: Option<String>,
+    bonus: Bonus,
+}
+
+// a bonus counted toward a total, or left out of it
+#[derive(Clone, Debug, Serialize)]
+pub struct Applied {
+    pub source: String,
+    pub bonus_type: Option<String>,
+    pub value: f64,
+    // why the bonus was not counted, if it was not
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ignored: Option<String>,
+}
+
+// the final value of a target, e.g. COMBAT|AC, and its parts
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct Total {
+    pub value: f64,
+    pub bonuses: Vec<Applied>,
+}
+
+// the totals of a character
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct Summary {
+    // stat => score
+    pub stats: BTreeMap<String, Total>,
+    pub ac: Total,
+    // save name => bonus
+    pub saves: BTreeMap<String, Total>,
+    // skill name => bonus, for skills with ranks or bonuses
+    pub skills: BTreeMap<String, Total>,
+}
+
+// the modifier of a stat score, e.g. 14 => +2
+pub fn stat_mod(score: f64) -> f64 {
+    ((score - 10.0) / 2.0).floor()
+}
+
+// the tags of one element of a character, e.g. its race, or a level
+// of one of its classes
+pub(crate) struct Grantor {
+    // e.g. "RACE Elf"
+    pub source: String,
+    // the class, for class lines and levels
+    pub class: Option<String>,
+    pub tags: Vec<(String, String)>,
+}
+
+// the elements of a character which may grant it bonuses: its race,
+// its classes and their levels, its abilities and its equipment
+pub(crate) fn grantors(pcc: &Pcc, profile: &Profile) -> Vec<Grantor> {
+    let elem = |list: &str, name: &str| {
+        pcc.lookup_in(list, name).map(|elem| Grantor {
+            source: format!("{} {}", list, name),
+            class: None,
+            tags: elem
+                .attribs()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+        })
+    };
+
+    let mut out = Vec::new();
+    out.extend(profile.race.as_ref().and_then(|race| elem("RACE", race)));
+    for (name, level) in &profile.classes {
+        let Some(class) = pcc.get::<Class>(name) else {
+            continue;
+        };
+        out.push(Grantor {
+            source: format!("CLASS {}", name),
+            class: Some(name.clone()),
+            tags: class.attribs.clone(),
+        });
+        for cl in class.levels_to(*level) {
+            out.push(Grantor {
+                source: format!("CLASS {} level {}", name, cl.level),
+                class: Some(name.clone()),
+                tags: cl.tags.clone(),
+            });
+        }
+    }
+    out.extend(profile.abilities.iter().filter_map(|a| elem("ABILITY", a)));
+    out.extend(
+        profile
+            .equipment
+            .iter()
+            .filter_map(|i| elem("EQUIPMENT", i)),
+    );
+    out
+}
+
+// collects the bonuses granted to a character by its race, classes,
+// abilities and equipment
+pub struct Engine<'a> {
+    pcc: &'a Pcc,
+    profile: &'a Profile,
+    granted: Vec<Granted>,
+    // the character's variables, e.g. MonkACBonus, with stat bonuses
+    // applied to its stat scores
+    vars: VarContext<'a>,
+}
+
+impl<'a> Engine<'a> {
+    pub fn new(pcc: &'a Pcc, profile: &'a Profile) -> Engine<'a> {
+        let mut engine = Engine {
+            pcc,
+            profile,
+            granted: Vec::new(),
+            vars: VarContext::new(pcc, profile),
+        };
+
+        for g in grantors(pcc, profile) {
+            for (tag, value) in &g.tags {
+                if tag != "BONUS" {
+                    continue;
+                }
+                match Bonus::parse(value) {
+                    Some(bonus) => engine.granted.push(Granted {
+                        source: g.source.clone(),
+                        class: g.class.clone(),
+                        bonus,
+                    }),
+                    None => tracing::warn!("{}: malformed BONUS:{}", g.source, value),
+                }
+            }
+        }
+
+        // stat bonuses first, as other formulas may use the stats; the
+        // formulas of stat bonuses see the character's scores
+        let stats: Vec<(String, f64)> = profile
+            .stats
+            .iter()
+            .map(|(stat, score)| {
+                (
+                    stat.clone(),
+                    *score as f64 + engine.total(&["STAT"], stat).value,
+                )
+            })
+            .collect();
+        for (stat, score) in stats {
+            engine.vars.set_score(&stat, score);
+        }
+        engine
+    }
+
+    // the character's variables, with stat bonuses applied
+    pub fn vars(&self) -> &VarContext<'a> {
+        &self.vars
+    }
+
+    // value of a variable in a bonus formula; see VarContext::resolve
+    fn var(&self, class: Option<&str>, name: &str) -> Option<f64> {
+        self.vars
+            .resolve(class, name)
+            .map_err(|e| tracing::debug!("{}", e))
+            .ok()
+    }
+
+    // the bonuses to a target of any of some categories, e.g.
+    // (["COMBAT"], "AC"), stacked: of bonuses of one type, only the
+    // largest counts, except for untyped and dodge bonuses, and
+    // penalties, which all count
+    pub fn total(&self, categories: &[&str], target: &str) -> Total {
+        let mut total = Total::default();
+        // bonus type => index in total.bonuses of the largest so far
+        let mut best: BTreeMap<String, usize> = BTreeMap::new();
+
+        for g in &self.granted {
+            let b = &g.bonus;
+            let aimed = b.targets.iter().any(|t| {
+                t.eq_ignore_ascii_case(target)
+                    || t.strip_prefix("BASE.")
+                        .is_some_and(|t| t.eq_ignore_ascii_case(target))
+            });
+            let in_category = categories
+                .iter()
+                .any(|c| b.category.eq_ignore_ascii_case(c));
+            if !in_category || !aimed {
+                continue;
+            }
+
+            let failed = b
+                .prereqs
+                .iter()
+                .find(|p| qualify::evaluate(self.pcc, self.profile, p).passed == Some(false));
+            let mut applied = Applied {
+                source: g.source.clone(),
+                bonus_type: b.bonus_type.clone(),
+                value: 0.0,
+                ignored: failed.map(|p| format!("{} not met", p.describe())),
+            };
+            match formula::eval(&b.formula, &|v: &str| self.var(g.class.as_deref(), v)) {
+                // bonuses are whole numbers, e.g. CL/3 at level 4 is 1
+                Ok(value) => applied.value = value.trunc(),
+                Err(e) => applied.ignored = Some(format!("{}: {}", b.formula, e)),
+            }
+
+            if applied.ignored.is_none() && !b.stacks_with_itself() && applied.value > 0.0 {
+                let t = b
+                    .bonus_type
+                    .clone()
+                    .unwrap_or_default()
+                    .to_ascii_lowercase();
+                if let Some(&i) = best.get(&t) {
+                    let prev = &mut total.bonuses[i];
+                    if prev.value >= applied.value {
+                        applied.ignored = Some(format!("does not stack with {}", prev.source));
+                    } else {
+                        prev.ignored = Some(format!("does not stack with {}", applied.source));
+                        best.insert(t, total.bonuses.len());
+                    }
+                } else {
+                    best.insert(t, total.bonuses.len());
+                }
+            }
+            total.bonuses.push(applied);
+        }
+
+        total.value = total
+            .bonuses
+            .iter()
+            .filter(|a| a.ignored.is_none())
+            .fold(0.0, |sum, a| sum + a.value);
+        total
+    }
+
+    // a stat modifier, as a bonus
+    fn stat_bonus(&self, stat: &str) -> Option<Applied> {
+        self.vars.score(stat).map(|score| Applied {
+            source: format!("STAT {}", stat),
+            bonus_type: None,
+            value: stat_mod(score),
+            ignored: None,
+        })
+    }
+
+    // a total with a base value and a stat modifier added
+    fn with_base(&self, mut total: Total, base: Option<Applied>, stat: Option<&str>) -> Total {
+        total.bonuses.extend(base);
+        total.bonuses.extend(stat.and_then(|s| self.stat_bonus(s)));
+        total.value = total
+            .bonuses
+            .iter()
+            .filter(|a| a.ignored.is_none())
+            .fold(0.0, |sum, a| sum + a.value);
+        total
+    }
+
+    // armor class: 10, plus DEX, plus COMBAT|AC bonuses
+    pub fn ac(&self) -> Total {
+        let base = Applied {
+            source: String::from("base"),
+            bonus_type: None,
+            value: 10.0,
+            ignored: None,
+        };
+        self.with_base(self.total(&["COMBAT"], "AC"), Some(base), Some("DEX"))
+    }
+
+    // a saving throw, from SAVE or, in older data, CHECKS bonuses, plus
+    // its stat
+    pub fn save(&self, name: &str) -> Total {
+        let total = self.total(&["SAVE", "CHECKS"], name);
+        let stat = SAVES
+            .iter()
+            .find(|(s, _)| s.eq_ignore_ascii_case(name))
+            .map(|(_, stat)| *stat);
+        self.with_base(total, None, stat)
+    }
+
+    // a skill: ranks, plus its KEYSTAT, plus SKILL bonuses
+    pub fn skill(&self, name: &str) -> Total {
+        let ranks = self.profile.skills.get(name).map(|r| Applied {
+            source: String::from("ranks"),
+            bonus_type: None,
+            value: *r,
+            ignored: None,
+        });
+        let keystat = self
+            .pcc
+            .lookup_in("SKILL", name)
+            .and_then(|e| e.get("KEYSTAT"))
+            .map(|s| s.to_ascii_uppercase());
+        self.with_base(self.total(&["SKILL"], name), ranks, keystat.as_deref())
+    }
+
+    pub fn summary(&self) -> Summary {
+        let stats = self
+            .profile
+            .stats
+            .iter()
+            .map(|(stat, score)| {
+                let base = Applied {
+                    source: String::from("base"),
+                    bonus_type: None,
+                    value: *score as f64,
+                    ignored: None,
+                };
+                let total = self.with_base(self.total(&["STAT"], stat), Some(base), None);
+                (stat.clone(), total)
+            })
+            .collect();
+
+        let saves = SAVES
+            .iter()
+            .map(|(name, _)| (name.to_string(), self.save(name)))
+            .collect();
+
+        // skills with ranks, or named by a SKILL bonus
+        let mut skill_names: Vec<&str> = self.profile.skills.keys().map(|s| s.as_str()).collect();
+        for g in &self.granted {
+            if g.bonus.category == "SKILL" {
+                skill_names.extend(g.bonus.targets.iter().map(|t| t.as_str()));
+            }
+        }
+        skill_names.sort();
+        skill_names.dedup();
+        let skills = skill_names
+            .into_iter()
+            .map(|s| (s.to_string(), self.skill(s)))
+            .collect();
+
+        Summary {
+            stats,
+            ac: self.ac(),
+            saves,
+            skills,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pcc::tests::load;
+
+    const PCC: &str = "CAMPAIGN:T\nGAMEMODE:35e\nRACE:r.lst\nCLASS:c.lst\nABILITY:a.lst\n";
+    const RACES: &str = "Elf\tBONUS:STAT|DEX|2\tDEFINE:DexVar|DEX\n";
+    const CLASSES: &str = "CLASS:Wizard\tHD:4\n\
+                           1\tBONUS:SAVE|Will|1|TYPE=Resistance\tBONUS:CHECKS|Will|2|TYPE=Resistance\n";
+    const ABILITIES: &str = "Armor\tCATEGORY:FEAT\tBONUS:COMBAT|AC|2|TYPE=Armor\n\
+                             Better Armor\tCATEGORY:FEAT\tBONUS:COMBAT|AC|3|TYPE=Armor\n\
+                             Dodge\tCATEGORY:FEAT\tBONUS:COMBAT|AC|1|TYPE=Dodge\n\
+                             Mobility\tCATEGORY:FEAT\tBONUS:COMBAT|AC|1|TYPE=Dodge\n\
+                             Luck\tCATEGORY:FEAT\tBONUS:COMBAT|AC|1|TYPE=Luck.STACK\n\
+                             More Luck\tCATEGORY:FEAT\tBONUS:COMBAT|AC|1|TYPE=Luck.STACK\n\
+                             Plain\tCATEGORY:FEAT\tBONUS:COMBAT|AC|1\n\
+                             Plainer\tCATEGORY:FEAT\tBONUS:COMBAT|AC|1\n\
+                             Vars\tCATEGORY:FEAT\tBONUS:SKILL|Climb|CL=Wizard\tBONUS:SKILL|Jump|DexVar\n";
+
+    fn load_bonuses(name: &str) -> Pcc {
+        load(
+            name,
+            &[
+                ("t.pcc", PCC),
+                ("r.lst", RACES),
+                ("c.lst", CLASSES),
+                ("a.lst", ABILITIES),
+            ],
+        )
+    }
+
+    fn profile(abilities: &[&str]) -> Profile {
+        Profile {
+            race: Some(String::from("Elf")),
+            stats: BTreeMap::from([(String::from("DEX"), 14)]),
+            classes: BTreeMap::from([(String::from("Wizard"), 3)]),
+            abilities: abilities.iter().map(|a| a.to_string()).collect(),
+            ..Profile::default()
+        }
+    }
+
+    fn ac_bonuses(name: &str, abilities: &[&str]) -> Total {
+        let pcc = load_bonuses(name);
+        let profile = profile(abilities);
+        Engine::new(&pcc, &profile).total(&["COMBAT"], "AC")
+    }
+
+    #[test]
+    fn parse_stack_suffix() {
+        let b = Bonus::parse("COMBAT|AC|1|TYPE=Luck.STACK").unwrap();
+        assert_eq!(b.bonus_type.as_deref(), Some("Luck"));
+        assert!(b.stacks && b.stacks_with_itself());
+        assert!(!Bonus::parse("COMBAT|AC|1|TYPE=Armor")
+            .unwrap()
+            .stacks_with_itself());
+        assert!(Bonus::parse("COMBAT|AC|1").unwrap().stacks_with_itself());
+        assert!(Bonus::parse("COMBAT|AC|1|TYPE=dodge")
+            .unwrap()
+            .stacks_with_itself());
+    }
+
+    #[test]
+    fn typed_bonuses_do_not_stack() {
+        let total = ac_bonuses("typed", &["Armor", "Better Armor"]);
+        assert_eq!(total.value, 3.0);
+        let ignored: Vec<&str> = total
+            .bonuses
+            .iter()
+            .filter(|a| a.ignored.is_some())
+            .map(|a| a.source.as_str())
+            .collect();
+        assert_eq!(ignored, ["ABILITY Armor"]);
+    }
+
+    #[test]
+    fn untyped_dodge_and_stack_bonuses_stack() {
+        assert_eq!(ac_bonuses("untyped", &["Plain", "Plainer"]).value, 2.0);
+        assert_eq!(ac_bonuses("dodge", &["Dodge", "Mobility"]).value, 2.0);
+        assert_eq!(ac_bonuses("stack", &["Luck", "More Luck"]).value, 2.0);
+        let total = ac_bonuses(
+            "mixed",
+            &["Armor", "Better Armor", "Dodge", "Luck", "Plain"],
+        );
+        assert_eq!(total.value, 6.0);
+    }
+
+    #[test]
+    fn save_and_checks_bonuses_stack_as_one() {
+        let pcc = load_bonuses("saves");
+        let profile = profile(&[]);
+        let total = Engine::new(&pcc, &profile).total(&["SAVE", "CHECKS"], "Will");
+        assert_eq!(total.value, 2.0);
+    }
+
+    #[test]
+    fn formulas_see_stat_bonuses_and_class_levels() {
+        let pcc = load_bonuses("vars");
+        let profile = profile(&["Vars"]);
+        let engine = Engine::new(&pcc, &profile);
+        assert_eq!(engine.vars().score("DEX"), Some(16.0));
+        assert_eq!(engine.total(&["SKILL"], "Climb").value, 3.0);
+        assert_eq!(engine.total(&["SKILL"], "Jump").value, 3.0);
+    }
+}
@@ -0,0 +1,193 @@
+//
+// cache.rs -- on-disk cache of parsed campaign data
+//
+// Copyright (c) 2024 Jeff Garzik
+//
+// This file is part of the pcgtoolssoftware project covered under
+// the MIT License.  For the full license text, please see the LICENSE
+// file in the root directory of this project.
+// SPDX-License-Identifier: MIT
+
+use crate::files;
+use crate::paths;
+use crate::pcc::{Pcc, PccConfig};
+use crate::text::fnv1a;
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::{self, File},
+    io,
+    io::{BufReader, BufWriter, Error, Write},
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+
+// bump when the cached data layout changes
+const CACHE_VERSION: u32 = 16;
+
+// game mode system files, which are also inputs to a load
+const GAMEMODE_FILES: [&str; 3] = ["miscinfo.lst", "statsandchecks.lst", "load.lst"];
+
+// identity of an input file at the time it was read.  None for a file
+// that did not exist.
+#[derive(PartialEq, Serialize, Deserialize)]
+struct FileStamp {
+    path: String,
+    stamp: Option<(u64, u128)>,
+}
+
+impl FileStamp {
+    fn new(path: &str) -> FileStamp {
+        // a remote file is stamped by its contents, as fetched now
+        if paths::is_url(path) {
+            let stamp = files::read(path)
+                .ok()
+                .map(|data| (data.len() as u64, u128::from(fnv1a(&data))));
+            return FileStamp {
+                path: String::from(path),
+                stamp,
+            };
+        }
+
+        // a file within a ZIP archive is stamped as the archive
+        let stamp = Path::new(path)
+            .ancestors()
+            .find_map(|p| fs::metadata(p).ok().filter(|md| md.is_file()))
+            .map(|md| {
+                let mtime = md
+                    .modified()
+                    .ok()
+                    .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+                    .map_or(0, |d| d.as_nanos());
+                (md.len(), mtime)
+            });
+        FileStamp {
+            path: String::from(path),
+            stamp,
+        }
+    }
+}
+
+// written ahead of the data, so stale entries are rejected without
+// decoding the data itself
+#[derive(Serialize, Deserialize)]
+struct CacheHeader {
+    version: u32,
+    files: Vec<FileStamp>,
+}
+
+// cache file for a toplevel PCC file and load configuration
+fn cache_path(cachedir: &str, cfg: &PccConfig, pccfiles: &[String]) -> PathBuf {
+    let key = format!(
+        "{}\0{}\0{}\0{}\0{}\0{}\0{}\0{}\0{}\0{}\0{}\0{}\0{}\0{}\0{}",
+        env!("CARGO_PKG_VERSION"),
+        pccfiles.join("\n"),
+        cfg.datadirs.join("\n"),
+        cfg.gamemode.as_deref().unwrap_or(""),
+        cfg.systemdir(),
+        cfg.vendordir(),
+        cfg.homebrewdir(),
+        cfg.encoding,
+        cfg.provenance,
+        cfg.duplicates,
+        cfg.allow_unknown_tags,
+        cfg.schema.as_deref().unwrap_or(""),
+        cfg.migrate,
+        cfg.migrations.as_deref().unwrap_or(""),
+        cfg.compat,
+    );
+    Path::new(cachedir).join(format!("{:016x}.bin.zst", fnv1a(key.as_bytes())))
+}
+
+// every file the loaded data was read from
+fn input_files(pcc: &Pcc) -> Vec<FileStamp> {
+    let mut files: Vec<FileStamp> = pcc
+        .files()
+        .iter()
+        .map(|f| FileStamp::new(&f.path))
+        .collect();
+
+    if let Some(schema) = &pcc.config().schema {
+        files.push(FileStamp::new(schema));
+    }
+    if let Some(rules) = &pcc.config().migrations {
+        files.push(FileStamp::new(rules));
+    }
+
+    if let Some(gm) = pcc.gamemode() {
+        for name in GAMEMODE_FILES {
+            let path = Path::new(&gm.dir).join(name);
+            files.push(FileStamp::new(&path.to_string_lossy()));
+        }
+    }
+
+    files
+}
+
+fn read_cache(path: &Path) -> io::Result<Option<Pcc>> {
+    let file = File::open(path)?;
+    let mut rdr = zstd::Decoder::new(BufReader::new(file))?;
+
+    let header: CacheHeader = bincode::deserialize_from(&mut rdr).map_err(Error::other)?;
+    if header.version != CACHE_VERSION {
+        tracing::debug!("cache: {} has version {}", path.display(), header.version);
+        return Ok(None);
+    }
+    for stamp in &header.files {
+        if FileStamp::new(&stamp.path) != *stamp {
+            tracing::debug!("cache: {} changed", stamp.path);
+            return Ok(None);
+        }
+    }
+
+    let mut pcc: Pcc = bincode::deserialize_from(&mut rdr).map_err(Error::other)?;
+    pcc.repool();
+    Ok(Some(pcc))
+}
+
+// return cached data for pccfiles, if present and no input file has
+// changed since it was cached
+pub fn load(cachedir: &str, cfg: &PccConfig, pccfiles: &[String]) -> Option<Pcc> {
+    let path = cache_path(cachedir, cfg, pccfiles);
+    if !path.exists() {
+        tracing::debug!("cache: miss for {}", pccfiles.join(" "));
+        return None;
+    }
+
+    match read_cache(&path) {
+        Ok(Some(pcc)) => {
+            tracing::debug!("cache: hit for {}", path.display());
+            Some(pcc)
+        }
+        Ok(None) => None,
+        Err(e) => {
+            tracing::warn!("Ignoring unreadable cache {}: {}", path.display(), e);
+            None
+        }
+    }
+}
+
+// cache loaded data for pccfiles
+pub fn store(cachedir: &str, cfg: &PccConfig, pccfiles: &[String], pcc: &Pcc) -> io::Result<()> {
+    fs::create_dir_all(cachedir)?;
+
+    let path = cache_path(cachedir, cfg, pccfiles);
+    let header = CacheHeader {
+        version: CACHE_VERSION,
+        files: input_files(pcc),
+    };
+
+    // write to a temporary file, then rename into place, so that a
+    // concurrent reader never sees a partial cache file
+    let tmp_path = path.with_extension("tmp");
+    {
+        let file = File::create(&tmp_path)?;
+        let mut w = zstd::Encoder::new(BufWriter::new(file), 0)?;
+        bincode::serialize_into(&mut w, &header).map_err(Error::other)?;
+        bincode::serialize_into(&mut w, pcc).map_err(Error::other)?;
+        w.finish()?.flush()?;
+    }
+    fs::rename(&tmp_path, &path)?;
+
+    tracing::debug!("cache: stored {}", path.display());
+    Ok(())
+}
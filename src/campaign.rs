@@ -0,0 +1,126 @@
+//
+// campaign.rs -- discovery of the campaigns in a data directory
+//
+// Copyright (c) 2024 Jeff Garzik
+//
+// This file is part of the pcgtoolssoftware project covered under
+// the MIT License.  For the full license text, please see the LICENSE
+// file in the root directory of this project.
+// SPDX-License-Identifier: MIT
+
+use crate::files;
+use crate::pcc::precampaign_args;
+use crate::prereq::Prereq;
+use crate::text::{self, Encoding};
+use serde::{Deserialize, Serialize};
+use std::{io, path::Path};
+
+// identifying metadata of a PCC file, read without loading its data
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CampaignInfo {
+    pub path: String,
+    pub name: String,
+    pub key: Option<String>,
+    pub gamemodes: Vec<String>,
+    pub rank: Option<i64>,
+    pub status: Option<String>,
+    pub pubnameshort: Option<String>,
+    pub requires: Vec<CampaignReq>,
+}
+
+// a PRECAMPAIGN requirement: so many of the campaigns named, by name
+// or KEY
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CampaignReq {
+    pub line: usize,
+    pub count: usize,
+    pub names: Vec<String>,
+}
+
+impl CampaignInfo {
+    // read the metadata tags of a PCC file
+    pub fn read(path: &Path, encoding: Encoding) -> io::Result<CampaignInfo> {
+        let mut info = CampaignInfo {
+            path: path.to_string_lossy().to_string(),
+            name: String::new(),
+            key: None,
+            gamemodes: Vec::new(),
+            rank: None,
+            status: None,
+            pubnameshort: None,
+            requires: Vec::new(),
+        };
+
+        for (idx, line) in text::read_lines(path, encoding)?.iter().enumerate() {
+            let Some((tag, value)) = line.split_once(':') else {
+                continue;
+            };
+            match tag {
+                "CAMPAIGN" => info.name = value.to_string(),
+                "KEY" => info.key = Some(value.to_string()),
+                "GAMEMODE" => info
+                    .gamemodes
+                    .extend(value.split('|').map(|m| m.to_string())),
+                "RANK" => info.rank = value.trim().parse().ok(),
+                "STATUS" => info.status = Some(value.to_string()),
+                "PUBNAMESHORT" => info.pubnameshort = Some(value.to_string()),
+                "PRECAMPAIGN" => {
+                    let prereq = Prereq::parse(line).expect("PRECAMPAIGN prereq");
+                    let (count, names) = precampaign_args(&prereq.arg_list());
+                    info.requires.push(CampaignReq {
+                        line: idx + 1,
+                        count,
+                        names: names.into_iter().map(String::from).collect(),
+                    });
+                }
+                _ => {}
+            }
+        }
+
+        Ok(info)
+    }
+
+    // does a campaign name, or KEY, identify this campaign?
+    pub fn is_named(&self, name: &str) -> bool {
+        self.name.eq_ignore_ascii_case(name)
+            || self
+                .key
+                .as_deref()
+                .is_some_and(|k| k.eq_ignore_ascii_case(name))
+    }
+}
+
+fn scan_dir(dir: &Path, encoding: Encoding, out: &mut Vec<CampaignInfo>) -> io::Result<()> {
+    let mut entries = files::read_dir(dir)?;
+    entries.sort();
+
+    for entry in entries {
+        let path = dir.join(entry);
+        if files::is_dir(&path) {
+            scan_dir(&path, encoding, out)?;
+            continue;
+        }
+
+        let is_pcc = path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("pcc"));
+        if is_pcc {
+            match CampaignInfo::read(&path, encoding) {
+                Ok(info) => out.push(info),
+                Err(e) => tracing::warn!("{}: {}", path.display(), e),
+            }
+        }
+    }
+
+    Ok(())
+}
+
+// recursively find all PCC files in data directories, in order, and
+// in path order within each
+pub fn discover(datadirs: &[String], encoding: Encoding) -> io::Result<Vec<CampaignInfo>> {
+    let mut campaigns = Vec::new();
+    for datadir in datadirs {
+        scan_dir(Path::new(datadir), encoding, &mut campaigns)?;
+    }
+    Ok(campaigns)
+}
@@ -0,0 +1,255 @@
+//
+// choose.rs -- CHOOSE tags, and the selections they offer
+//
+// Copyright (c) 2024 Jeff Garzik
+//
+// This file is part of the pcgtoolssoftware project covered under
+// the MIT License.  For the full license text, please see the LICENSE
+// file in the root directory of this project.
+// SPDX-License-Identifier: MIT
+
+use crate::pcc::{Pcc, PccElem};
+use crate::token;
+use serde::Serialize;
+use std::collections::BTreeSet;
+
+// a NUMBER chooser offering more values than this is not enumerated
+const MAX_NUMBERS: i64 = 1000;
+
+// the choosers selecting elements of a list, by chooser name, with the
+// list, and the ability category, if fixed
+const ELEMENT_CHOOSERS: &[(&str, &str, Option<&str>)] = &[
+    ("ARMORPROFICIENCY", "ARMORPROF", None),
+    ("CLASS", "CLASS", None),
+    ("DEITY", "DEITY", None),
+    ("DOMAIN", "DOMAIN", None),
+    ("EQUIPMENT", "EQUIPMENT", None),
+    ("FEAT", "ABILITY", Some("FEAT")),
+    ("LANG", "LANGUAGE", None),
+    ("RACE", "RACE", None),
+    ("SHIELDPROFICIENCY", "SHIELDPROF", None),
+    ("SKILL", "SKILL", None),
+    ("SPELLS", "SPELL", None),
+    ("TEMPLATE", "TEMPLATE", None),
+    ("WEAPONPROFICIENCY", "WEAPONPROF", None),
+];
+
+// one item of an element chooser, e.g. "Climb", "TYPE=Knowledge" or
+// "ALL"
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub enum Selector {
+    All,
+    Name(String),
+    // TYPE=Martial.Melee: elements having every type
+    Type(Vec<String>),
+    // !TYPE=Exotic: excludes elements having every type
+    NotType(Vec<String>),
+    // an item not evaluated at load time, e.g. QUALIFY or %LIST
+    Other(String),
+}
+
+impl Selector {
+    fn parse(item: &str) -> Selector {
+        let upper = item.to_ascii_uppercase();
+        let types = |rest: &str| token::dotted(rest).into_iter().map(String::from).collect();
+        if upper == "ALL" || upper == "ANY" {
+            Selector::All
+        } else if let Some(rest) = upper.strip_prefix("TYPE=").or(upper.strip_prefix("TYPE.")) {
+            Selector::Type(types(&item[item.len() - rest.len()..]))
+        } else if let Some(rest) = upper
+            .strip_prefix("!TYPE=")
+            .or(upper.strip_prefix("!TYPE."))
+        {
+            Selector::NotType(types(&item[item.len() - rest.len()..]))
+        } else if item.starts_with('%') || item.contains('=') || item == upper {
+            // e.g. %LIST, RANKS=1, QUALIFY, CROSSCLASS: keywords are
+            // written in capitals, names not
+            Selector::Other(item.to_string())
+        } else {
+            Selector::Name(item.to_string())
+        }
+    }
+
+    // Does an element of the list match?  None if unknown.
+    fn matches(&self, elem: &PccElem) -> Option<bool> {
+        let has_types = |types: &[String]| {
+            let have = elem.values("TYPE");
+            types
+                .iter()
+                .all(|t| have.iter().any(|h| h.eq_ignore_ascii_case(t)))
+        };
+        match self {
+            Selector::All => Some(true),
+            Selector::Name(name) => Some(
+                elem.name().eq_ignore_ascii_case(name) || elem.ident().eq_ignore_ascii_case(name),
+            ),
+            Selector::Type(types) => Some(has_types(types)),
+            Selector::NotType(_) | Selector::Other(_) => None,
+        }
+    }
+}
+
+// what a CHOOSE tag selects from
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub enum Chooser {
+    // CHOOSE:NUMBER|MIN=1|MAX=10|INCREMENT=1
+    Number {
+        min: i64,
+        max: i64,
+        increment: i64,
+    },
+    // CHOOSE:STRING|Fire|Cold|Acid
+    String(Vec<String>),
+    // elements of a list, e.g. CHOOSE:SKILL|Climb|TYPE=Knowledge, or
+    // of an ability category, e.g. CHOOSE:ABILITY|FEAT|TYPE=Fighter
+    Elements {
+        list: String,
+        category: Option<String>,
+        selectors: Vec<Selector>,
+    },
+    // CHOOSE:USERINPUT: text typed by the user
+    UserInput,
+    // CHOOSE:NOCHOICE: the element may be taken more than once, with
+    // nothing chosen
+    NoChoice,
+    // choosers not modelled, e.g. ALIGNMENT or SCHOOLS, with their
+    // arguments
+    Other {
+        kind: String,
+        args: Vec<String>,
+    },
+}
+
+// a CHOOSE tag: its chooser, how many selections it makes, and the
+// title of its dialog
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Choose {
+    pub chooser: Chooser,
+    pub numchoices: Option<String>,
+    pub title: Option<String>,
+}
+
+impl Choose {
+    // Parse the value of a CHOOSE tag, e.g. "SKILL|Climb|Swim"; None
+    // if it names no chooser.
+    pub fn parse(value: &str) -> Option<Choose> {
+        let mut numchoices = None;
+        let mut title = None;
+        let mut items = Vec::new();
+        for item in token::items(value) {
+            if let Some(n) = item.strip_prefix("NUMCHOICES=") {
+                numchoices = Some(n.to_string());
+            } else if let Some(t) = item.strip_prefix("TITLE=") {
+                title = Some(t.to_string());
+            } else if !item.is_empty() && !token::is_prereq(item) {
+                items.push(item);
+            }
+        }
+
+        let (kind, args) = items.split_first()?;
+        let kind = kind.to_ascii_uppercase();
+        let mut args: Vec<&str> = args.iter().flat_map(|a| token::sublist(a)).collect();
+        args.retain(|a| !a.is_empty());
+
+        let chooser = match kind.as_str() {
+            "NUMBER" => {
+                let arg = |name: &str| {
+                    args.iter()
+                        .find_map(|a| a.strip_prefix(name)?.strip_prefix('='))
+                        .and_then(|v| v.trim().parse().ok())
+                };
+                Chooser::Number {
+                    min: arg("MIN").unwrap_or(0),
+                    max: arg("MAX").unwrap_or(0),
+                    increment: arg("INCREMENT").unwrap_or(1),
+                }
+            }
+            "STRING" => Chooser::String(args.iter().map(|a| a.to_string()).collect()),
+            "USERINPUT" => Chooser::UserInput,
+            "NOCHOICE" => Chooser::NoChoice,
+            "ABILITY" => {
+                let (category, rest) = args.split_first()?;
+                Chooser::Elements {
+                    list: String::from("ABILITY"),
+                    category: Some(category.to_string()),
+                    selectors: rest.iter().map(|a| Selector::parse(a)).collect(),
+                }
+            }
+            _ => match ELEMENT_CHOOSERS.iter().find(|(name, _, _)| *name == kind) {
+                Some((_, list, category)) => Chooser::Elements {
+                    list: list.to_string(),
+                    category: category.map(String::from),
+                    selectors: args.iter().map(|a| Selector::parse(a)).collect(),
+                },
+                None => Chooser::Other {
+                    kind,
+                    args: args.iter().map(|a| a.to_string()).collect(),
+                },
+            },
+        };
+
+        Some(Choose {
+            chooser,
+            numchoices,
+            title,
+        })
+    }
+
+    // The legal selections, in order, where they can be determined
+    // from the loaded data alone: the names of the matching elements of
+    // an element chooser, the strings of a STRING chooser, the values
+    // of a NUMBER chooser, or none for NOCHOICE.  None where the
+    // selections depend on a character, or on input.
+    pub fn options(&self, pcc: &Pcc) -> Option<Vec<String>> {
+        match &self.chooser {
+            Chooser::Number {
+                min,
+                max,
+                increment,
+            } => {
+                let step = (*increment).max(1);
+                if (max - min) / step > MAX_NUMBERS {
+                    return None;
+                }
+                let values = (*min..=*max).step_by(step as usize);
+                Some(values.map(|v| v.to_string()).collect())
+            }
+            Chooser::String(strings) => Some(strings.clone()),
+            Chooser::NoChoice => Some(Vec::new()),
+            Chooser::UserInput | Chooser::Other { .. } => None,
+            Chooser::Elements {
+                list,
+                category,
+                selectors,
+            } => {
+                if selectors.iter().any(|s| matches!(s, Selector::Other(_))) {
+                    return None;
+                }
+                let lst = pcc.list(list)?;
+                let in_category = |elem: &PccElem| {
+                    category.as_ref().is_none_or(|cat| {
+                        elem.get("CATEGORY")
+                            .is_some_and(|c| c.eq_ignore_ascii_case(cat))
+                    })
+                };
+                let excluded = |elem: &PccElem| {
+                    selectors.iter().any(|s| match s {
+                        Selector::NotType(types) => {
+                            Selector::Type(types.clone()).matches(elem).unwrap_or(false)
+                        }
+                        _ => false,
+                    })
+                };
+
+                let names: BTreeSet<&str> = lst
+                    .iter()
+                    .map(|(_, elem)| elem)
+                    .filter(|elem| in_category(elem) && !excluded(elem))
+                    .filter(|elem| selectors.iter().any(|s| s.matches(elem) == Some(true)))
+                    .map(|elem| elem.name())
+                    .collect();
+                Some(names.into_iter().map(String::from).collect())
+            }
+        }
+    }
+}
@@ -0,0 +1,128 @@
+//
+// class.rs -- typed model of CLASS data and its level progression
+//
+// Copyright (c) 2024 Jeff Garzik
+//
+// This file is part of the pcgtoolssoftware project covered under
+// the MIT License.  For the full license text, please see the LICENSE
+// file in the root directory of this project.
+// SPDX-License-Identifier: MIT
+
+use crate::pcc::{Pcc, PccElem};
+use serde::Serialize;
+
+// the tags a class gains at one level, from its numbered level lines
+#[derive(Clone, Debug, Serialize)]
+pub struct ClassLevel {
+    pub level: u32,
+    pub tags: Vec<(String, String)>,
+}
+
+impl ClassLevel {
+    // values of a tag at this level
+    pub fn values(&self, tag: &str) -> Vec<&str> {
+        self.tags
+            .iter()
+            .filter(|(k, _)| k == tag)
+            .map(|(_, v)| v.as_str())
+            .collect()
+    }
+
+    // BONUS:COMBAT|BASEAB|... and other BONUS tags
+    pub fn bonuses(&self) -> Vec<&str> {
+        self.values("BONUS")
+    }
+
+    // abilities granted, e.g. ABILITY:FEAT|AUTOMATIC|Bonus Feat
+    pub fn abilities(&self) -> Vec<&str> {
+        self.values("ABILITY")
+    }
+}
+
+// a class, with its level table in level order
+#[derive(Clone, Debug, Serialize)]
+pub struct Class {
+    pub name: String,
+    pub types: Vec<String>,
+    // HD, the hit die size
+    pub hit_die: Option<u32>,
+    pub max_level: Option<u32>,
+    // tags of the class lines, other than those modelled above
+    pub attribs: Vec<(String, String)>,
+    pub levels: Vec<ClassLevel>,
+    // SUBCLASS and SUBSTITUTIONCLASS names
+    pub subclasses: Vec<String>,
+    pub source: String,
+    pub line: usize,
+}
+
+impl Class {
+    pub fn new(name: &str, elem: &PccElem) -> Class {
+        let mut class = Class {
+            name: name.to_string(),
+            types: elem.values("TYPE").into_iter().map(String::from).collect(),
+            hit_die: None,
+            max_level: None,
+            attribs: Vec::new(),
+            levels: Vec::new(),
+            subclasses: Vec::new(),
+            source: elem.source().to_string(),
+            line: elem.line(),
+        };
+
+        for (tag, value) in &elem.attribs {
+            match tag.as_str() {
+                "HD" => class.hit_die = value.trim().parse().ok(),
+                "MAXLEVEL" => class.max_level = value.trim().parse().ok(),
+                "TYPE" => {}
+
+                // "3<tab>BONUS:x<tab>ABILITY:y"; several lines may
+                // contribute to one level
+                "LEVEL" => {
+                    let mut tokens = value.split('\t');
+                    let Some(level) = tokens.next().and_then(|l| l.parse().ok()) else {
+                        continue;
+                    };
+                    let tags = tokens.map(|t| {
+                        let (k, v) = t.split_once(':').unwrap_or((t, ""));
+                        (k.to_string(), v.to_string())
+                    });
+                    match class.levels.iter_mut().find(|l| l.level == level) {
+                        Some(cl) => cl.tags.extend(tags),
+                        None => class.levels.push(ClassLevel {
+                            level,
+                            tags: tags.collect(),
+                        }),
+                    }
+                }
+
+                "SUBCLASS" | "SUBSTITUTIONCLASS" => {
+                    let name = value.split('\t').next().unwrap_or("");
+                    class.subclasses.push(name.to_string());
+                }
+
+                _ => class.attribs.push((tag.to_string(), value.to_string())),
+            }
+        }
+
+        class.levels.sort_by_key(|l| l.level);
+        class
+    }
+
+    pub fn level(&self, level: u32) -> Option<&ClassLevel> {
+        self.levels.iter().find(|l| l.level == level)
+    }
+
+    // the levels up to and including a level, as gained when taking
+    // the class to that level
+    pub fn levels_to(&self, level: u32) -> impl Iterator<Item = &ClassLevel> {
+        self.levels.iter().filter(move |l| l.level <= level)
+    }
+}
+
+// all loaded classes, in name order
+pub fn classes(pcc: &Pcc) -> Vec<Class> {
+    let mut classes: Vec<Class> = pcc.classes().collect();
+    classes.sort_by(|a, b| a.name.cmp(&b.name));
+    classes
+}
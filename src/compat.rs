@@ -0,0 +1,137 @@
+//
+// compat.rs -- PCGen releases whose data sets are supported
+//
+// Copyright (c) 2024 Jeff Garzik
+//
+// This file is part of the pcgtoolssoftware project covered under
+// the MIT License.  For the full license text, please see the LICENSE
+// file in the root directory of this project.
+// SPDX-License-Identifier: MIT
+
+use serde::{Deserialize, Serialize};
+use std::{fmt, str::FromStr};
+
+// the PCGen release a data set was written for, in release order
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum DataCompat {
+    V5_16,
+    V6_06,
+    #[default]
+    V6_08,
+}
+
+// PCC tags, and the release first reading them
+const PCC_TAGS_ADDED: [(&str, DataCompat); 8] = [
+    ("ALIGNMENT", DataCompat::V6_06),
+    ("DATACONTROL", DataCompat::V6_06),
+    ("GLOBALMODIFIER", DataCompat::V6_06),
+    ("SAVE", DataCompat::V6_06),
+    ("STAT", DataCompat::V6_06),
+    ("VARIABLE", DataCompat::V6_06),
+    ("DATATABLE", DataCompat::V6_08),
+    ("DYNAMIC", DataCompat::V6_08),
+];
+
+// an LST tag replaced by another
+pub struct Deprecation {
+    pub tag: &'static str,
+    pub replacement: &'static str,
+    // the release deprecating the tag, and the release no longer
+    // reading it, if any
+    pub deprecated: DataCompat,
+    pub removed: Option<DataCompat>,
+}
+
+const DEPRECATIONS: [Deprecation; 6] = [
+    Deprecation {
+        tag: "FEAT",
+        replacement: "ABILITY:FEAT|NORMAL|...",
+        deprecated: DataCompat::V6_06,
+        removed: Some(DataCompat::V6_08),
+    },
+    Deprecation {
+        tag: "LANGAUTO",
+        replacement: "AUTO:LANG|...",
+        deprecated: DataCompat::V6_06,
+        removed: Some(DataCompat::V6_08),
+    },
+    Deprecation {
+        tag: "MFEAT",
+        replacement: "ABILITY:FEAT|VIRTUAL|...",
+        deprecated: DataCompat::V6_06,
+        removed: Some(DataCompat::V6_08),
+    },
+    Deprecation {
+        tag: "SA",
+        replacement: "ABILITY with SAB",
+        deprecated: DataCompat::V6_06,
+        removed: Some(DataCompat::V6_08),
+    },
+    Deprecation {
+        tag: "SPELL",
+        replacement: "SPELLS",
+        deprecated: DataCompat::V6_06,
+        removed: None,
+    },
+    Deprecation {
+        tag: "VFEAT",
+        replacement: "ABILITY:FEAT|VIRTUAL|...",
+        deprecated: DataCompat::V6_06,
+        removed: Some(DataCompat::V6_08),
+    },
+];
+
+impl DataCompat {
+    // PCC tags of the built-in schema this release does not read
+    pub fn unread_pcc_tags(&self) -> impl Iterator<Item = &'static str> + '_ {
+        PCC_TAGS_ADDED
+            .iter()
+            .filter(|(_, added)| added > self)
+            .map(|(tag, _)| *tag)
+    }
+
+    // the deprecation of an LST tag as of this release, if deprecated
+    pub fn deprecation(&self, tag: &str) -> Option<&'static Deprecation> {
+        DEPRECATIONS
+            .iter()
+            .find(|d| d.tag == tag && d.deprecated <= *self)
+    }
+
+    // Is the tag no longer read by this release?
+    pub fn removed(&self, deprecation: &Deprecation) -> bool {
+        deprecation.removed.is_some_and(|r| r <= *self)
+    }
+
+    // Releases before 6.06 read the tags it deprecated; their data is
+    // loaded with those tags rewritten to the forms pcgtools reads.
+    pub fn reads_legacy_tags(&self) -> bool {
+        *self < DataCompat::V6_06
+    }
+}
+
+impl FromStr for DataCompat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "5.16" => Ok(DataCompat::V5_16),
+            "6.06" => Ok(DataCompat::V6_06),
+            "6.08" => Ok(DataCompat::V6_08),
+            _ => Err(format!(
+                "unknown PCGen release {} (expected 5.16, 6.06 or 6.08)",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for DataCompat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            DataCompat::V5_16 => "5.16",
+            DataCompat::V6_06 => "6.06",
+            DataCompat::V6_08 => "6.08",
+        };
+        write!(f, "{}", name)
+    }
+}
@@ -0,0 +1,145 @@
+//
+// datacontrol.rs -- custom facts and scopes defined by DATACONTROL files
+//
+// Copyright (c) 2024 Jeff Garzik
+//
+// This file is part of the pcgtoolssoftware project covered under
+// the MIT License.  For the full license text, please see the LICENSE
+// file in the root directory of this project.
+// SPDX-License-Identifier: MIT
+
+use crate::pcc::{Pcc, PccElem};
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+
+// a FACTDEF or FACTSETDEF line, e.g.
+// "FACTDEF:DEITY|Title<tab>DATAFORMAT:STRING<tab>REQUIRED:NO"
+#[derive(Clone, Debug, Serialize)]
+pub struct FactDef {
+    // list whose elements carry the fact, e.g. DEITY
+    pub list: String,
+    pub name: String,
+    // STRING, NUMBER, BOOLEAN, or the name of a list
+    pub format: String,
+    pub required: bool,
+    pub selectable: bool,
+    pub visible: bool,
+    pub file: String,
+    pub line: usize,
+}
+
+impl FactDef {
+    fn new(def: &str, elem: &PccElem) -> Option<FactDef> {
+        let (list, name) = def.split_once('|')?;
+        let tag = |name: &str| elem.values(name).first().map(|v| v.to_string());
+        let flag = |name: &str| tag(name).is_some_and(|v| v.eq_ignore_ascii_case("YES"));
+        Some(FactDef {
+            list: list.to_ascii_uppercase(),
+            name: name.to_string(),
+            format: tag("DATAFORMAT")
+                .unwrap_or_else(|| String::from("STRING"))
+                .to_ascii_uppercase(),
+            required: flag("REQUIRED"),
+            selectable: flag("SELECTABLE"),
+            visible: flag("VISIBLE"),
+            file: elem.source().to_string(),
+            line: elem.line(),
+        })
+    }
+
+    // does a value have the declared format?  values of list formats
+    // are references, checked by the validator.
+    pub fn accepts(&self, value: &str) -> bool {
+        match self.format.as_str() {
+            "NUMBER" => value.trim().parse::<f64>().is_ok(),
+            "BOOLEAN" => ["TRUE", "FALSE", "YES", "NO"]
+                .iter()
+                .any(|b| value.eq_ignore_ascii_case(b)),
+            _ => true,
+        }
+    }
+}
+
+// all definitions of the loaded DATACONTROL files
+#[derive(Debug, Default)]
+pub struct DataControl {
+    // (list, lowercased fact name) => definition
+    pub facts: BTreeMap<(String, String), FactDef>,
+    pub factsets: BTreeMap<(String, String), FactDef>,
+    // DYNAMICSCOPE categories, for DYNAMIC files
+    pub scopes: BTreeSet<String>,
+    // FUNCTION names
+    pub functions: BTreeSet<String>,
+    // DEFAULTVARIABLEVALUE, variable format => default value
+    pub defaults: BTreeMap<String, String>,
+}
+
+impl DataControl {
+    pub fn new(pcc: &Pcc) -> DataControl {
+        let mut dc = DataControl::default();
+        let Some(lst) = pcc.list("DATACONTROL") else {
+            return dc;
+        };
+
+        for (ident, elem) in &lst.props {
+            let Some((tag, value)) = ident.split_once(':') else {
+                continue;
+            };
+            match tag {
+                "FACTDEF" | "FACTSETDEF" => {
+                    let Some(def) = FactDef::new(value, elem) else {
+                        tracing::warn!("{}:{}: malformed {}", elem.source(), elem.line(), ident);
+                        continue;
+                    };
+                    let key = (def.list.clone(), def.name.to_lowercase());
+                    if tag == "FACTDEF" {
+                        dc.facts.insert(key, def);
+                    } else {
+                        dc.factsets.insert(key, def);
+                    }
+                }
+                "DYNAMICSCOPE" => {
+                    dc.scopes.insert(value.to_ascii_uppercase());
+                }
+                "FUNCTION" => {
+                    dc.functions.insert(value.to_string());
+                }
+                "DEFAULTVARIABLEVALUE" => match value.split_once('|') {
+                    Some((format, default)) => {
+                        dc.defaults
+                            .insert(format.to_ascii_uppercase(), default.to_string());
+                    }
+                    None => {
+                        tracing::warn!("{}:{}: malformed {}", elem.source(), elem.line(), ident)
+                    }
+                },
+                _ => {}
+            }
+        }
+
+        dc
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.facts.is_empty()
+            && self.factsets.is_empty()
+            && self.scopes.is_empty()
+            && self.functions.is_empty()
+            && self.defaults.is_empty()
+    }
+
+    pub fn fact(&self, list: &str, name: &str) -> Option<&FactDef> {
+        self.facts.get(&(list.to_string(), name.to_lowercase()))
+    }
+
+    pub fn factset(&self, list: &str, name: &str) -> Option<&FactDef> {
+        self.factsets.get(&(list.to_string(), name.to_lowercase()))
+    }
+
+    // facts that every element of a list must carry
+    pub fn required_facts<'a>(&'a self, list: &'a str) -> impl Iterator<Item = &'a FactDef> {
+        self.facts
+            .values()
+            .filter(move |def| def.required && def.list == list)
+    }
+}
@@ -0,0 +1,195 @@
+//
+// datatable.rs -- typed model of DATATABLE tables, with row lookup
+//
+// Copyright (c) 2024 Jeff Garzik
+//
+// This file is part of the pcgtoolssoftware project covered under
+// the MIT License.  For the full license text, please see the LICENSE
+// file in the root directory of this project.
+// SPDX-License-Identifier: MIT
+
+use crate::pcc::{Pcc, PccElem};
+use serde::Serialize;
+
+// Split a comma-separated line into cells.  A cell may be quoted, with
+// "" standing for a quote within it.  Trailing empty cells, as
+// spreadsheet exports write, are dropped.
+fn split_csv(line: &str) -> Vec<String> {
+    let mut cells = Vec::new();
+    let mut cell = String::new();
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                cell.push('"');
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => cells.push(std::mem::take(&mut cell)),
+            _ => cell.push(c),
+        }
+    }
+    cells.push(cell);
+
+    while cells.last().is_some_and(|c| c.trim().is_empty()) {
+        cells.pop();
+    }
+    cells.iter().map(|c| c.trim().to_string()).collect()
+}
+
+// a table cell, typed by its column's format
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(untagged)]
+pub enum Value {
+    Number(f64),
+    Text(String),
+}
+
+impl Value {
+    pub fn as_number(&self) -> Option<f64> {
+        match self {
+            Value::Number(n) => Some(*n),
+            Value::Text(_) => None,
+        }
+    }
+
+    // does this cell hold a row key?  text compares ignoring case
+    fn matches(&self, key: &str) -> bool {
+        match self {
+            Value::Number(n) => key.trim().parse::<f64>().is_ok_and(|k| k == *n),
+            Value::Text(s) => s.eq_ignore_ascii_case(key.trim()),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct Column {
+    pub name: String,
+    // NUMBER, STRING, or another format, held as text
+    pub format: String,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct DataTable {
+    pub name: String,
+    pub columns: Vec<Column>,
+    // rows of cells, one per column; the first column is the row key
+    pub rows: Vec<Vec<Value>>,
+    pub source: String,
+    pub line: usize,
+}
+
+impl DataTable {
+    pub fn new(name: &str, elem: &PccElem) -> DataTable {
+        let names = elem
+            .values("COLUMNS")
+            .first()
+            .map_or(Vec::new(), |v| split_csv(v));
+        let formats = elem
+            .values("FORMATS")
+            .first()
+            .map_or(Vec::new(), |v| split_csv(v));
+        let columns: Vec<Column> = names
+            .into_iter()
+            .enumerate()
+            .map(|(i, name)| Column {
+                name,
+                format: formats
+                    .get(i)
+                    .map_or(String::from("STRING"), |f| f.to_ascii_uppercase()),
+            })
+            .collect();
+
+        let mut rows = Vec::new();
+        for row in elem.values("ROW") {
+            let cells = split_csv(row);
+            if cells.len() > columns.len() {
+                tracing::warn!(
+                    "{}: DATATABLE {} row {} has more cells than columns",
+                    elem.source(),
+                    name,
+                    cells[0]
+                );
+            }
+            let values = columns
+                .iter()
+                .enumerate()
+                .map(|(i, col)| {
+                    let cell = cells.get(i).map_or("", |c| c.as_str());
+                    if col.format != "NUMBER" {
+                        return Value::Text(cell.to_string());
+                    }
+                    match cell.parse() {
+                        Ok(n) => Value::Number(n),
+                        Err(_) => {
+                            tracing::warn!(
+                                "{}: DATATABLE {} column {}: {} is not a NUMBER",
+                                elem.source(),
+                                name,
+                                col.name,
+                                cell
+                            );
+                            Value::Text(cell.to_string())
+                        }
+                    }
+                })
+                .collect();
+            rows.push(values);
+        }
+
+        DataTable {
+            name: name.to_string(),
+            columns,
+            rows,
+            source: elem.source().to_string(),
+            line: elem.line(),
+        }
+    }
+
+    // index of a column, by name ignoring case
+    pub fn column(&self, name: &str) -> Option<usize> {
+        self.columns
+            .iter()
+            .position(|c| c.name.eq_ignore_ascii_case(name))
+    }
+
+    // the row whose first column holds a key
+    pub fn row(&self, row_key: &str) -> Option<&[Value]> {
+        self.rows
+            .iter()
+            .find(|r| r.first().is_some_and(|v| v.matches(row_key)))
+            .map(|r| r.as_slice())
+    }
+
+    // the cell of a column in the row with a key, e.g.
+    // lookup("Large", "Modifier")
+    pub fn lookup(&self, row_key: &str, column: &str) -> Option<&Value> {
+        let col = self.column(column)?;
+        self.row(row_key)?.get(col)
+    }
+}
+
+// all loaded tables, in name order
+pub fn tables(pcc: &Pcc) -> Vec<DataTable> {
+    let Some(lst) = pcc.list("DATATABLE") else {
+        return Vec::new();
+    };
+
+    let mut tables: Vec<DataTable> = lst
+        .props
+        .iter()
+        .map(|(name, elem)| DataTable::new(name, elem))
+        .collect();
+    tables.sort_by(|a, b| a.name.cmp(&b.name));
+    tables
+}
+
+pub fn table(pcc: &Pcc, name: &str) -> Option<DataTable> {
+    let lst = pcc.list("DATATABLE")?;
+    let (name, elem) = lst
+        .props
+        .iter()
+        .find(|(n, _)| n.eq_ignore_ascii_case(name))?;
+    Some(DataTable::new(name, elem))
+}
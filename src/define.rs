@@ -0,0 +1,242 @@
+//
+// define.rs -- DEFINE variables of a character, and their evaluation
+//
+// Copyright (c) 2024 Jeff Garzik
+//
+// This file is part of the pcgtoolssoftware project covered under
+// the MIT License.  For the full license text, please see the LICENSE
+// file in the root directory of this project.
+// SPDX-License-Identifier: MIT
+
+use crate::bonus::{grantors, stat_mod, Bonus};
+use crate::formula;
+use crate::pcc::Pcc;
+use crate::prereq::Prereq;
+use crate::qualify::{self, Profile};
+use serde::Serialize;
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
+
+// a formula contributing to a variable, and where it came from
+#[derive(Clone, Debug, Serialize)]
+pub struct Term {
+    pub formula: String,
+    // e.g. "CLASS Monk level 3"
+    pub source: String,
+    // the class granting it, whose level is CL in the formula
+    #[serde(skip)]
+    class: Option<String>,
+    // PRExxx of a BONUS:VAR; the term counts only if all are met
+    #[serde(skip)]
+    prereqs: Vec<Prereq>,
+}
+
+// a variable: its DEFINE, and the BONUS:VAR terms added to it
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct Define {
+    pub initial: Option<Term>,
+    pub bonuses: Vec<Term>,
+}
+
+// The variables of a character: each DEFINE:Name|formula of its race,
+// classes and class levels, abilities and equipment, plus the
+// BONUS:VAR|Name|formula terms of the same.  Formulas may use CL, the
+// level of the class granting them, CL=Class, the level of a named
+// class, TL, the total level, stat modifiers, e.g. DEX, stat scores,
+// e.g. DEXSCORE, and other variables.  The same variables are those
+// of BONUS formulas; see resolve.
+pub struct VarContext<'a> {
+    pcc: &'a Pcc,
+    profile: &'a Profile,
+    pub defines: BTreeMap<String, Define>,
+    // upper-case stat => score; the character's scores, until the
+    // bonus engine applies stat bonuses to them
+    stats: BTreeMap<String, f64>,
+    // computed values
+    values: RefCell<HashMap<String, f64>>,
+}
+
+impl<'a> VarContext<'a> {
+    pub fn new(pcc: &'a Pcc, profile: &'a Profile) -> VarContext<'a> {
+        let mut defines: BTreeMap<String, Define> = BTreeMap::new();
+
+        for g in grantors(pcc, profile) {
+            let term = |formula: &str| Term {
+                formula: formula.to_string(),
+                source: g.source.clone(),
+                class: g.class.clone(),
+                prereqs: Vec::new(),
+            };
+            for (tag, value) in &g.tags {
+                match tag.as_str() {
+                    "DEFINE" => {
+                        let Some((name, formula)) = value.split_once('|') else {
+                            tracing::warn!("{}: malformed DEFINE:{}", g.source, value);
+                            continue;
+                        };
+                        let define = defines.entry(name.to_string()).or_default();
+                        match &define.initial {
+                            Some(first) => tracing::debug!(
+                                "{}: DEFINE:{} already defined by {}",
+                                g.source,
+                                name,
+                                first.source
+                            ),
+                            None => define.initial = Some(term(formula)),
+                        }
+                    }
+                    "BONUS" => {
+                        let Some(bonus) = Bonus::parse(value) else {
+                            continue;
+                        };
+                        if bonus.category != "VAR" {
+                            continue;
+                        }
+                        for var in &bonus.targets {
+                            defines
+                                .entry(var.to_string())
+                                .or_default()
+                                .bonuses
+                                .push(Term {
+                                    prereqs: bonus.prereqs.clone(),
+                                    ..term(&bonus.formula)
+                                });
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        VarContext {
+            pcc,
+            profile,
+            defines,
+            stats: profile
+                .stats
+                .iter()
+                .map(|(stat, score)| (stat.to_ascii_uppercase(), *score as f64))
+                .collect(),
+            values: RefCell::new(HashMap::new()),
+        }
+    }
+
+    // the score of a stat, e.g. DEX
+    pub(crate) fn score(&self, stat: &str) -> Option<f64> {
+        self.stats.get(&stat.to_ascii_uppercase()).copied()
+    }
+
+    // set the score of a stat, e.g. with stat bonuses applied; values
+    // computed from the old score are forgotten
+    pub(crate) fn set_score(&mut self, stat: &str, score: f64) {
+        self.stats.insert(stat.to_ascii_uppercase(), score);
+        self.values.get_mut().clear();
+    }
+
+    // built-in variables: class levels and stats
+    fn builtin(&self, class: Option<&str>, name: &str) -> Option<f64> {
+        let class_level = |c: &str| {
+            self.profile
+                .classes
+                .iter()
+                .find(|(n, _)| n.eq_ignore_ascii_case(c))
+                .map_or(0.0, |(_, l)| *l as f64)
+        };
+        match name {
+            "CL" => class.map(class_level),
+            "TL" => Some(self.profile.level() as f64),
+            _ => match name.strip_prefix("CL=") {
+                Some(c) => Some(class_level(c)),
+                None => match name.strip_suffix("SCORE") {
+                    Some(stat) => self.score(stat),
+                    None => self.score(name).map(stat_mod),
+                },
+            },
+        }
+    }
+
+    // The value of a variable of a formula granted by an element of
+    // the character, of a class if given: a built-in variable, else a
+    // DEFINE variable.  DEFINE, BONUS:VAR and other BONUS formulas all
+    // resolve their variables here.
+    pub fn resolve(&self, class: Option<&str>, name: &str) -> Result<f64, String> {
+        self.resolve_with(class, name, &mut Vec::new())
+    }
+
+    fn resolve_with(
+        &self,
+        class: Option<&str>,
+        name: &str,
+        stack: &mut Vec<String>,
+    ) -> Result<f64, String> {
+        match self.builtin(class, name) {
+            Some(value) => Ok(value),
+            None => self.value_of(name, stack),
+        }
+    }
+
+    // the value of a variable: its DEFINE, plus its BONUS:VAR terms
+    pub fn value(&self, name: &str) -> Result<f64, String> {
+        self.value_of(name, &mut Vec::new())
+    }
+
+    fn value_of(&self, name: &str, stack: &mut Vec<String>) -> Result<f64, String> {
+        if let Some(value) = self.values.borrow().get(name) {
+            return Ok(*value);
+        }
+        let Some(define) = self.defines.get(name) else {
+            return Err(format!("variable {} not defined", name));
+        };
+        if stack.iter().any(|s| s == name) {
+            stack.push(name.to_string());
+            return Err(format!("variable cycle: {}", stack.join(" -> ")));
+        }
+
+        stack.push(name.to_string());
+        let mut value = 0.0;
+        for term in define.initial.iter().chain(&define.bonuses) {
+            let unmet = term
+                .prereqs
+                .iter()
+                .any(|p| qualify::evaluate(self.pcc, self.profile, p).passed == Some(false));
+            if unmet {
+                continue;
+            }
+            value += self
+                .eval_with(&term.formula, term.class.as_deref(), stack)
+                .map_err(|e| format!("{}: {}: {}", term.source, term.formula, e))?;
+        }
+        stack.pop();
+
+        self.values.borrow_mut().insert(name.to_string(), value);
+        Ok(value)
+    }
+
+    fn eval_with(
+        &self,
+        expr: &str,
+        class: Option<&str>,
+        stack: &mut Vec<String>,
+    ) -> Result<f64, String> {
+        let mut resolved = HashMap::new();
+        for name in formula::variables(expr)? {
+            let value = self.resolve_with(class, &name, stack)?;
+            resolved.insert(name, value);
+        }
+        formula::eval(expr, &|name: &str| resolved.get(name).copied())
+    }
+
+    // evaluate a formula for the character, e.g. "MonkACBonus+DEX"
+    pub fn eval(&self, expr: &str) -> Result<f64, String> {
+        self.eval_with(expr, None, &mut Vec::new())
+    }
+
+    // values of all variables; a variable which cannot be computed is
+    // reported by name
+    pub fn values(&self) -> BTreeMap<String, Result<f64, String>> {
+        self.defines
+            .keys()
+            .map(|name| (name.clone(), self.value(name)))
+            .collect()
+    }
+}
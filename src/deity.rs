@@ -0,0 +1,131 @@
+//
+// deity.rs -- typed model of DEITY and DOMAIN data, cross-linked
+//
+// Copyright (c) 2024 Jeff Garzik
+//
+// This file is part of the pcgtoolssoftware project covered under
+// the MIT License.  For the full license text, please see the LICENSE
+// file in the root directory of this project.
+// SPDX-License-Identifier: MIT
+
+use crate::pcc::{Pcc, PccElem};
+use serde::Serialize;
+
+fn names(values: Vec<&str>, sep: char) -> Vec<String> {
+    values
+        .iter()
+        .flat_map(|v| v.split(sep))
+        .filter(|n| !n.is_empty() && !n.starts_with("PRE") && !n.starts_with("!PRE"))
+        .map(String::from)
+        .collect()
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct Deity {
+    pub name: String,
+    pub title: Option<String>,
+    pub alignment: Option<String>,
+    // DOMAINS, as written
+    pub domains: Vec<String>,
+    // DEITYWEAP, favored weapons
+    pub weapons: Vec<String>,
+    pub pantheons: Vec<String>,
+    pub symbol: Option<String>,
+    pub source: String,
+    pub line: usize,
+}
+
+impl Deity {
+    pub fn new(name: &str, elem: &PccElem) -> Deity {
+        let first = |tag: &str| elem.values(tag).first().map(|v| v.to_string());
+        Deity {
+            name: name.to_string(),
+            title: first("TITLE"),
+            alignment: first("ALIGN"),
+            domains: names(elem.values("DOMAINS"), ','),
+            weapons: names(elem.values("DEITYWEAP"), '|'),
+            pantheons: names(elem.values("PANTHEON"), '|'),
+            symbol: first("SYMBOL"),
+            source: elem.source().to_string(),
+            line: elem.line(),
+        }
+    }
+
+    // does this deity grant a domain?  "ALL" grants every domain.
+    pub fn has_domain(&self, domain: &str) -> bool {
+        self.domains
+            .iter()
+            .any(|d| d.eq_ignore_ascii_case(domain) || d.eq_ignore_ascii_case("ALL"))
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct Domain {
+    pub name: String,
+    // deities granting this domain
+    pub deities: Vec<String>,
+    pub source: String,
+    pub line: usize,
+}
+
+// the loaded deities and domains, each linked to the other
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct Pantheon {
+    pub deities: Vec<Deity>,
+    pub domains: Vec<Domain>,
+}
+
+impl Pantheon {
+    pub fn new(pcc: &Pcc) -> Pantheon {
+        let mut deities: Vec<Deity> = match pcc.list("DEITY") {
+            Some(lst) => lst
+                .props
+                .iter()
+                .map(|(name, elem)| Deity::new(name, elem))
+                .collect(),
+            None => Vec::new(),
+        };
+        deities.sort_by(|a, b| a.name.cmp(&b.name));
+
+        let mut domains: Vec<Domain> = match pcc.list("DOMAIN") {
+            Some(lst) => lst
+                .props
+                .iter()
+                .map(|(name, elem)| Domain {
+                    name: name.clone(),
+                    deities: deities
+                        .iter()
+                        .filter(|d| d.has_domain(name))
+                        .map(|d| d.name.clone())
+                        .collect(),
+                    source: elem.source().to_string(),
+                    line: elem.line(),
+                })
+                .collect(),
+            None => Vec::new(),
+        };
+        domains.sort_by(|a, b| a.name.cmp(&b.name));
+
+        Pantheon { deities, domains }
+    }
+
+    pub fn deity(&self, name: &str) -> Option<&Deity> {
+        self.deities
+            .iter()
+            .find(|d| d.name.eq_ignore_ascii_case(name))
+    }
+
+    pub fn domain(&self, name: &str) -> Option<&Domain> {
+        self.domains
+            .iter()
+            .find(|d| d.name.eq_ignore_ascii_case(name))
+    }
+
+    // the domains of a deity which are loaded
+    pub fn domains_of(&self, deity: &Deity) -> Vec<&Domain> {
+        self.domains
+            .iter()
+            .filter(|d| deity.has_domain(&d.name))
+            .collect()
+    }
+}
@@ -0,0 +1,98 @@
+//
+// desc.rs -- rendering of DESC and SAB text, with its placeholders
+//
+// Copyright (c) 2024 Jeff Garzik
+//
+// This file is part of the pcgtoolssoftware project covered under
+// the MIT License.  For the full license text, please see the LICENSE
+// file in the root directory of this project.
+// SPDX-License-Identifier: MIT
+
+use crate::define::VarContext;
+use crate::pcc::PccElem;
+use crate::token;
+
+// What placeholders are replaced with: the variables of a character,
+// for "%1" arguments naming formulas, and the selections made for the
+// element's CHOOSE, for "%CHOICE" and "%LIST".  Without a character,
+// arguments are shown as written.
+#[derive(Default)]
+pub struct DescContext<'a> {
+    pub vars: Option<&'a VarContext<'a>>,
+    pub choices: &'a [String],
+}
+
+// an evaluated argument, without a fraction where it has none
+fn format_number(value: f64) -> String {
+    if value.fract() == 0.0 {
+        format!("{}", value as i64)
+    } else {
+        format!("{}", value)
+    }
+}
+
+impl DescContext<'_> {
+    // the text of "%n", for argument arg
+    fn argument(&self, arg: &str) -> String {
+        match self.vars.map(|vars| vars.eval(arg)) {
+            Some(Ok(value)) => format_number(value),
+            Some(Err(e)) => {
+                tracing::debug!("DESC argument {}: {}", arg, e);
+                arg.to_string()
+            }
+            None => arg.to_string(),
+        }
+    }
+}
+
+// Render a DESC or SAB value as text: "%1", "%2", ... are replaced by
+// the value's trailing arguments, "%CHOICE" and "%LIST" by the
+// choices made, and encoded characters are decoded, e.g.
+// "Burns for %1 rounds|TorchDuration" gives "Burns for 6 rounds" for
+// a character whose TorchDuration is 6.  Placeholders with no value
+// are left as written.
+pub fn render(value: &str, ctx: &DescContext) -> String {
+    let (text, args) = token::text_args(value);
+
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(at) = rest.find('%') {
+        out.push_str(&rest[..at]);
+        rest = &rest[at + 1..];
+
+        let digits = rest.len() - rest.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+        let name = ["CHOICE", "LIST"].iter().find(|n| rest.starts_with(*n));
+        let (replacement, len) = match (digits, name) {
+            (0, Some(name)) if !ctx.choices.is_empty() => {
+                (Some(ctx.choices.join(", ")), name.len())
+            }
+            (0, _) => (None, 0),
+            (n, _) => {
+                let arg = rest[..n]
+                    .parse::<usize>()
+                    .ok()
+                    .and_then(|i| args.get(i.checked_sub(1)?));
+                (arg.map(|arg| ctx.argument(arg)), n)
+            }
+        };
+        match replacement {
+            Some(replacement) => out.push_str(&replacement),
+            None => {
+                out.push('%');
+                out.push_str(&rest[..len]);
+            }
+        }
+        rest = &rest[len..];
+    }
+    out.push_str(rest);
+
+    token::decode(&out).into_owned()
+}
+
+// the DESC text of an element, each rendered without a character
+pub fn descriptions(elem: &PccElem) -> Vec<String> {
+    elem.get_all("DESC")
+        .into_iter()
+        .map(|desc| render(desc, &DescContext::default()))
+        .collect()
+}
@@ -0,0 +1,220 @@
+//
+// deserialize.rs -- deserializing list elements into user structs
+//
+// Copyright (c) 2024 Jeff Garzik
+//
+// This file is part of the pcgtoolssoftware project covered under
+// the MIT License.  For the full license text, please see the LICENSE
+// file in the root directory of this project.
+// SPDX-License-Identifier: MIT
+
+use crate::pcc::{Pcc, PccElem};
+use serde::de::{
+    self,
+    value::{Error, MapDeserializer, SeqDeserializer},
+    Deserialize, Deserializer, IntoDeserializer, Visitor,
+};
+use serde::forward_to_deserialize_any;
+
+// the map key holding an element's ident
+pub const IDENT_KEY: &str = "_ident";
+
+// The values of one tag of an element.  A struct field of a sequence
+// type takes every value; any other field takes the only value, parsed
+// as the field's type: numbers, YES or NO for bool, and so on.
+struct Values<'a> {
+    tag: &'a str,
+    values: Vec<&'a str>,
+}
+
+impl<'a> Values<'a> {
+    fn only(&self) -> Result<&'a str, Error> {
+        match self.values.as_slice() {
+            [value] => Ok(value),
+            values => Err(de::Error::custom(format!(
+                "{} has {} values; deserialize it as a sequence",
+                self.tag,
+                values.len()
+            ))),
+        }
+    }
+
+    fn parse<T: std::str::FromStr>(&self, what: &str) -> Result<T, Error> {
+        let value = self.only()?;
+        value
+            .trim()
+            .parse()
+            .map_err(|_| de::Error::custom(format!("{}:{} is not {}", self.tag, value, what)))
+    }
+}
+
+impl<'de> IntoDeserializer<'de, Error> for Values<'de> {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+macro_rules! parse_number {
+    ($($method:ident => $visit:ident, $what:expr;)*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+                visitor.$visit(self.parse($what)?)
+            }
+        )*
+    };
+}
+
+impl<'de> Deserializer<'de> for Values<'de> {
+    type Error = Error;
+
+    // a single value as text, or several as a sequence
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self.values.len() {
+            1 => visitor.visit_borrowed_str(self.values[0]),
+            _ => self.deserialize_seq(visitor),
+        }
+    }
+
+    fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        let value = self.only()?;
+        match value.trim().to_ascii_uppercase().as_str() {
+            "YES" | "TRUE" | "" => visitor.visit_bool(true),
+            "NO" | "FALSE" => visitor.visit_bool(false),
+            _ => Err(de::Error::custom(format!(
+                "{}:{} is not YES or NO",
+                self.tag, value
+            ))),
+        }
+    }
+
+    parse_number! {
+        deserialize_i8 => visit_i8, "an integer";
+        deserialize_i16 => visit_i16, "an integer";
+        deserialize_i32 => visit_i32, "an integer";
+        deserialize_i64 => visit_i64, "an integer";
+        deserialize_u8 => visit_u8, "a whole number";
+        deserialize_u16 => visit_u16, "a whole number";
+        deserialize_u32 => visit_u32, "a whole number";
+        deserialize_u64 => visit_u64, "a whole number";
+        deserialize_f32 => visit_f32, "a number";
+        deserialize_f64 => visit_f64, "a number";
+        deserialize_char => visit_char, "a character";
+    }
+
+    fn deserialize_str<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_borrowed_str(self.only()?)
+    }
+
+    fn deserialize_string<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_str(visitor)
+    }
+
+    // a tag present has a value
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    // each value, parsed as the sequence's element type
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        let tag = self.tag;
+        let values = self.values.into_iter().map(|value| Values {
+            tag,
+            values: vec![value],
+        });
+        visitor.visit_seq(SeqDeserializer::new(values))
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(
+        self,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.deserialize_seq(visitor)
+    }
+
+    // a unit variant, by name
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        let value: de::value::BorrowedStrDeserializer<Error> =
+            de::value::BorrowedStrDeserializer::new(self.only()?);
+        visitor.visit_enum(value)
+    }
+
+    forward_to_deserialize_any! {
+        bytes byte_buf unit unit_struct tuple_struct map struct identifier
+        ignored_any
+    }
+}
+
+// An element as a map of tag => values, plus its ident under
+// IDENT_KEY, in the order tags first appear.
+fn elem_map<'a>(ident: &'a str, elem: &'a PccElem) -> Vec<(&'a str, Values<'a>)> {
+    let mut map: Vec<(&str, Values)> = vec![(
+        IDENT_KEY,
+        Values {
+            tag: IDENT_KEY,
+            values: vec![ident],
+        },
+    )];
+    for (tag, value) in elem.attribs() {
+        match map.iter_mut().find(|(t, _)| *t == tag) {
+            Some((_, values)) => values.values.push(value),
+            None => map.push((
+                tag,
+                Values {
+                    tag,
+                    values: vec![value],
+                },
+            )),
+        }
+    }
+    map
+}
+
+// Deserialize an element, stored under ident, into a type whose fields
+// are named for tags, e.g. with #[serde(rename = "SCHOOL")].  Tags
+// the type lacks fields for are ignored, unless it denies unknown
+// fields.
+pub fn from_elem<'a, T: Deserialize<'a>>(ident: &'a str, elem: &'a PccElem) -> Result<T, Error> {
+    let map = MapDeserializer::new(elem_map(ident, elem).into_iter());
+    T::deserialize(map)
+}
+
+impl Pcc {
+    // an element of a list by KEY, name or alias, ignoring case,
+    // deserialized into a type, e.g.
+    // pcc.deserialize::<MySpell>("SPELL", "Fireball")
+    pub fn deserialize<'a, T: Deserialize<'a>>(
+        &'a self,
+        list: &str,
+        name: &str,
+    ) -> Result<T, Error> {
+        let (_, ident, elem) = self
+            .lookup_idents(name)
+            .into_iter()
+            .find(|(l, _, _)| *l == list)
+            .ok_or_else(|| {
+                de::Error::custom(format!(
+                    "{} {} not found{}",
+                    list,
+                    name,
+                    self.did_you_mean(Some(list), name)
+                ))
+            })?;
+        from_elem(ident, elem)
+    }
+}
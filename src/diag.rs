@@ -0,0 +1,57 @@
+//
+// diag.rs -- diagnostics reported against data files
+//
+// Copyright (c) 2024 Jeff Garzik
+//
+// This file is part of the pcgtoolssoftware project covered under
+// the MIT License.  For the full license text, please see the LICENSE
+// file in the root directory of this project.
+// SPDX-License-Identifier: MIT
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+// a problem found in a data file, at a specific line
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub file: String,
+    pub line: usize,
+    pub message: String,
+}
+
+impl Diagnostic {
+    pub fn error(file: &str, line: usize, message: String) -> Diagnostic {
+        Diagnostic {
+            severity: Severity::Error,
+            file: String::from(file),
+            line,
+            message,
+        }
+    }
+
+    pub fn warning(file: &str, line: usize, message: String) -> Diagnostic {
+        Diagnostic {
+            severity: Severity::Warning,
+            file: String::from(file),
+            line,
+            message,
+        }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let sev = match self.severity {
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        };
+        write!(f, "{}:{}: {}: {}", self.file, self.line, sev, self.message)
+    }
+}
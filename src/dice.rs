@@ -0,0 +1,77 @@
+//
+// dice.rs -- dice expressions, e.g. "2d10" or "1d4+1", and rolling them
+//
+// Copyright (c) 2024 Jeff Garzik
+//
+// This file is part of the pcgtoolssoftware project covered under
+// the MIT License.  For the full license text, please see the LICENSE
+// file in the root directory of this project.
+// SPDX-License-Identifier: MIT
+
+use serde::Serialize;
+use std::fmt;
+
+// NdM+K; a bare number is 0d0+K
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub struct Dice {
+    pub count: u32,
+    pub sides: u32,
+    pub modifier: i64,
+}
+
+impl Dice {
+    pub fn parse(expr: &str) -> Option<Dice> {
+        let expr: String = expr.chars().filter(|c| !c.is_whitespace()).collect();
+        let lower = expr.to_ascii_lowercase();
+
+        // split off a trailing +K or -K
+        let (dice, modifier) = match lower.rfind(['+', '-']).filter(|i| *i > 0) {
+            Some(i) => (&lower[..i], lower[i..].parse().ok()?),
+            None => (lower.as_str(), 0),
+        };
+        match dice.split_once('d') {
+            Some((count, sides)) => Some(Dice {
+                count: if count.is_empty() {
+                    1
+                } else {
+                    count.parse().ok()?
+                },
+                sides: sides.parse().ok()?,
+                modifier,
+            }),
+            None => Some(Dice {
+                count: 0,
+                sides: 0,
+                modifier: modifier + dice.parse::<i64>().ok()?,
+            }),
+        }
+    }
+
+    pub fn roll(&self, rng: &mut fastrand::Rng) -> i64 {
+        let rolled: i64 = (0..self.count)
+            .map(|_| rng.u32(1..=self.sides.max(1)) as i64)
+            .sum();
+        rolled + self.modifier
+    }
+}
+
+impl fmt::Display for Dice {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.count == 0 {
+            return write!(f, "{}", self.modifier);
+        }
+        write!(f, "{}d{}", self.count, self.sides)?;
+        match self.modifier {
+            0 => Ok(()),
+            m if m > 0 => write!(f, "+{}", m),
+            m => write!(f, "{}", m),
+        }
+    }
+}
+
+// roll a dice expression, e.g. from a BIOSET tag
+pub fn roll(expr: &str, rng: &mut fastrand::Rng) -> Result<i64, String> {
+    Dice::parse(expr)
+        .map(|d| d.roll(rng))
+        .ok_or_else(|| format!("invalid dice expression {}", expr))
+}
@@ -0,0 +1,133 @@
+//
+// diff.rs -- compare two loaded datasets
+//
+// Copyright (c) 2024 Jeff Garzik
+//
+// This file is part of the pcgtoolssoftware project covered under
+// the MIT License.  For the full license text, please see the LICENSE
+// file in the root directory of this project.
+// SPDX-License-Identifier: MIT
+
+use crate::intern::Sym;
+use crate::pcc::{Pcc, PccDatum, PccElem, PccList};
+use serde::Serialize;
+use std::{collections::BTreeSet, fmt};
+
+// attribute-level changes to a single element
+#[derive(Serialize)]
+pub struct ElemDiff {
+    pub ident: String,
+    pub added: Vec<(Sym, Sym)>,
+    pub removed: Vec<(Sym, Sym)>,
+}
+
+// element-level changes to a single list type
+#[derive(Serialize)]
+pub struct ListDiff {
+    pub list: String,
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    pub changed: Vec<ElemDiff>,
+}
+
+impl ListDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+impl fmt::Display for ListDiff {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{}:", self.list)?;
+        for ident in &self.added {
+            writeln!(f, "  + {}", ident)?;
+        }
+        for ident in &self.removed {
+            writeln!(f, "  - {}", ident)?;
+        }
+        for ed in &self.changed {
+            writeln!(f, "  ~ {}", ed.ident)?;
+            for (k, v) in &ed.removed {
+                writeln!(f, "      - {}:{}", k, v)?;
+            }
+            for (k, v) in &ed.added {
+                writeln!(f, "      + {}:{}", k, v)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+// attribs in a but not in b, counting repeated attribs individually
+fn attrib_difference(a: &PccElem, b: &PccElem) -> Vec<(Sym, Sym)> {
+    let mut remaining: Vec<&(Sym, Sym)> = b.attribs.iter().collect();
+    let mut out = Vec::new();
+    for attrib in &a.attribs {
+        match remaining.iter().position(|r| *r == attrib) {
+            Some(pos) => {
+                remaining.swap_remove(pos);
+            }
+            None => out.push(attrib.clone()),
+        }
+    }
+    out
+}
+
+fn diff_list(name: &str, old: Option<&PccList>, new: Option<&PccList>) -> ListDiff {
+    let old_idents: BTreeSet<&String> = old.map(|l| l.props.keys().collect()).unwrap_or_default();
+    let new_idents: BTreeSet<&String> = new.map(|l| l.props.keys().collect()).unwrap_or_default();
+
+    let mut ld = ListDiff {
+        list: String::from(name),
+        added: new_idents
+            .difference(&old_idents)
+            .map(|s| s.to_string())
+            .collect(),
+        removed: old_idents
+            .difference(&new_idents)
+            .map(|s| s.to_string())
+            .collect(),
+        changed: Vec::new(),
+    };
+
+    for ident in old_idents.intersection(&new_idents) {
+        let old_elem = &old.unwrap().props[*ident];
+        let new_elem = &new.unwrap().props[*ident];
+        let added = attrib_difference(new_elem, old_elem);
+        let removed = attrib_difference(old_elem, new_elem);
+        if !added.is_empty() || !removed.is_empty() {
+            ld.changed.push(ElemDiff {
+                ident: ident.to_string(),
+                added,
+                removed,
+            });
+        }
+    }
+
+    ld
+}
+
+fn list<'a>(pcc: &'a Pcc, name: &str) -> Option<&'a PccList> {
+    match pcc.dict.get(name) {
+        Some(PccDatum::List(lst)) => Some(lst),
+        _ => None,
+    }
+}
+
+// compare every list type present in either dataset, returning the
+// lists with changes, sorted by list name
+pub fn diff(old: &Pcc, new: &Pcc) -> Vec<ListDiff> {
+    let names: BTreeSet<&String> = old
+        .dict
+        .iter()
+        .chain(new.dict.iter())
+        .filter(|(_, datum)| matches!(datum, PccDatum::List(_)))
+        .map(|(name, _)| name)
+        .collect();
+
+    names
+        .into_iter()
+        .map(|name| diff_list(name, list(old, name), list(new, name)))
+        .filter(|ld| !ld.is_empty())
+        .collect()
+}
@@ -0,0 +1,353 @@
+//
+// equipment.rs -- typed model of EQUIPMENT and EQUIPMOD data, and the
+// application of equipment modifiers to a base item
+//
+// Copyright (c) 2024 Jeff Garzik
+//
+// This file is part of the pcgtoolssoftware project covered under
+// the MIT License.  For the full license text, please see the LICENSE
+// file in the root directory of this project.
+// SPDX-License-Identifier: MIT
+
+use crate::formula;
+use crate::pcc::{Pcc, PccElem};
+use crate::suggest::did_you_mean;
+use crate::token;
+use serde::Serialize;
+
+// first value of a tag, if present
+fn first<'a>(elem: &'a PccElem, tag: &str) -> Option<&'a str> {
+    elem.values(tag).first().copied()
+}
+
+fn strings(values: Vec<&str>) -> Vec<String> {
+    values.into_iter().map(String::from).collect()
+}
+
+fn has(list: &[String], s: &str) -> bool {
+    list.iter().any(|t| t.eq_ignore_ascii_case(s))
+}
+
+// NAMEOPT: how a modifier appears in the name of a modified item
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub enum NameOpt {
+    // the modifier's name
+    Normal,
+    // the modifier's name, without its choices
+    NoList,
+    // the choices, without the modifier's name
+    NoName,
+    // not named at all
+    Nothing,
+    // the modifier's SPROP text
+    Sprop,
+    // TEXT=..., the given text
+    Text(String),
+}
+
+impl NameOpt {
+    fn parse(value: &str) -> NameOpt {
+        match value.to_ascii_uppercase().as_str() {
+            "NOLIST" => NameOpt::NoList,
+            "NONAME" => NameOpt::NoName,
+            "NOTHING" => NameOpt::Nothing,
+            "SPROP" => NameOpt::Sprop,
+            upper if upper.starts_with("TEXT=") => NameOpt::Text(value[5..].to_string()),
+            _ => NameOpt::Normal,
+        }
+    }
+}
+
+// FORMATCAT: where a modifier appears in the name of a modified item
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub enum FormatCat {
+    // before the base name, e.g. "Flaming Longsword"
+    Front,
+    // after front modifiers, immediately before the base name
+    Middle,
+    // after the base name, e.g. "Longsword (Flaming)"
+    Parens,
+}
+
+// an EQUIPMOD element
+#[derive(Clone, Debug, Serialize)]
+pub struct EquipMod {
+    // KEY, else name
+    pub key: String,
+    pub name: String,
+    pub types: Vec<String>,
+    // PLUS, the enhancement bonus granted
+    pub plus: Option<i64>,
+    // COST, a formula of PLUS and BASECOST
+    pub cost: Option<String>,
+    pub nameopt: NameOpt,
+    pub format_cat: FormatCat,
+    pub sprops: Vec<String>,
+    // ITYPE, types added to the modified item
+    pub itypes: Vec<String>,
+    // BONUS:WEAPON|DAMAGE and BONUS:WEAPON|TOHIT formulas
+    pub damage: Vec<String>,
+    pub tohit: Vec<String>,
+    pub source: String,
+    pub line: usize,
+}
+
+impl EquipMod {
+    pub fn new(key: &str, elem: &PccElem) -> EquipMod {
+        let mut eqmod = EquipMod {
+            key: key.to_string(),
+            name: elem.name().to_string(),
+            types: strings(elem.values("TYPE")),
+            plus: first(elem, "PLUS").and_then(|v| v.trim().parse().ok()),
+            cost: first(elem, "COST").map(String::from),
+            nameopt: first(elem, "NAMEOPT").map_or(NameOpt::Normal, NameOpt::parse),
+            format_cat: match first(elem, "FORMATCAT").map(|v| v.to_ascii_uppercase()) {
+                Some(cat) if cat == "FRONT" => FormatCat::Front,
+                Some(cat) if cat == "MIDDLE" => FormatCat::Middle,
+                _ => FormatCat::Parens,
+            },
+            sprops: strings(elem.values("SPROP")),
+            itypes: elem
+                .values("ITYPE")
+                .iter()
+                .flat_map(|v| token::dotted(v))
+                .map(String::from)
+                .collect(),
+            damage: Vec::new(),
+            tohit: Vec::new(),
+            source: elem.source().to_string(),
+            line: elem.line(),
+        };
+
+        // BONUS:WEAPON|DAMAGE,TOHIT|1
+        for bonus in elem.values("BONUS") {
+            let mut items = token::items(bonus).into_iter();
+            if items.next() != Some("WEAPON") {
+                continue;
+            }
+            let targets = items.next().unwrap_or("");
+            let Some(value) = items.next() else {
+                continue;
+            };
+            for target in token::sublist(targets) {
+                match target {
+                    "DAMAGE" => eqmod.damage.push(value.to_string()),
+                    "TOHIT" => eqmod.tohit.push(value.to_string()),
+                    _ => {}
+                }
+            }
+        }
+
+        eqmod
+    }
+
+    // the text this modifier contributes to an item name, if any
+    fn name_text(&self) -> Option<String> {
+        match &self.nameopt {
+            NameOpt::Nothing => None,
+            NameOpt::Sprop => self.sprops.first().cloned(),
+            NameOpt::Text(text) => Some(text.clone()),
+            NameOpt::Normal | NameOpt::NoList | NameOpt::NoName => Some(self.name.clone()),
+        }
+    }
+}
+
+// an EQUIPMENT element, or an item materialized from one by applying
+// equipment modifiers
+#[derive(Clone, Debug, Serialize)]
+pub struct Equipment {
+    pub name: String,
+    // the base item, for a modified item
+    pub base: Option<String>,
+    pub types: Vec<String>,
+    // COST, in gold pieces
+    pub cost: f64,
+    // DAMAGE, e.g. 1d8, plus any damage bonus, e.g. 1d8+1
+    pub damage: Option<String>,
+    pub tohit: i64,
+    pub crit_mult: Option<String>,
+    pub crit_range: Option<String>,
+    // WT, in pounds
+    pub weight: Option<f64>,
+    pub sprops: Vec<String>,
+    // EQMOD keys applied
+    pub eqmods: Vec<String>,
+    pub source: String,
+    pub line: usize,
+}
+
+impl Equipment {
+    pub fn new(name: &str, elem: &PccElem) -> Equipment {
+        let number = |tag: &str| first(elem, tag).and_then(|v| v.trim().parse().ok());
+        Equipment {
+            name: name.to_string(),
+            base: None,
+            types: strings(elem.values("TYPE")),
+            cost: number("COST").unwrap_or(0.0),
+            damage: first(elem, "DAMAGE").map(String::from),
+            tohit: 0,
+            crit_mult: first(elem, "CRITMULT").map(String::from),
+            crit_range: first(elem, "CRITRANGE").map(String::from),
+            weight: number("WT"),
+            sprops: strings(elem.values("SPROP")),
+            eqmods: strings(
+                elem.values("EQMOD")
+                    .iter()
+                    .flat_map(|v| token::dotted(v))
+                    .collect(),
+            ),
+            source: elem.source().to_string(),
+            line: elem.line(),
+        }
+    }
+
+    pub fn has_type(&self, t: &str) -> bool {
+        has(&self.types, t)
+    }
+
+    // Apply a chain of equipment modifiers, in order, producing the
+    // modified item.  Cost formulas see PLUS, the modifier's own
+    // enhancement bonus, TOTALPLUS, the bonus of the whole chain, and
+    // BASECOST, the cost of the unmodified item.
+    pub fn apply(&self, mods: &[&EquipMod]) -> Result<Equipment, String> {
+        let mut item = self.clone();
+        item.base = Some(self.name.clone());
+
+        let total_plus: i64 = mods.iter().filter_map(|m| m.plus).sum();
+        let mut damage_bonus = 0;
+
+        for eqmod in mods {
+            let plus = eqmod.plus.unwrap_or(0);
+            let vars = |name: &str| match name {
+                "PLUS" => Some(plus as f64),
+                "TOTALPLUS" => Some(total_plus as f64),
+                "BASECOST" => Some(self.cost),
+                _ => None,
+            };
+            let eval = |f: &str| {
+                formula::eval(f, &vars).map_err(|e| format!("EQMOD {}: {}: {}", eqmod.key, f, e))
+            };
+
+            if let Some(cost) = &eqmod.cost {
+                item.cost += eval(cost)?;
+            }
+            for f in &eqmod.damage {
+                damage_bonus += eval(f)? as i64;
+            }
+            for f in &eqmod.tohit {
+                item.tohit += eval(f)? as i64;
+            }
+            for t in &eqmod.itypes {
+                if !item.has_type(t) {
+                    item.types.push(t.clone());
+                }
+            }
+            item.sprops.extend(eqmod.sprops.iter().cloned());
+            item.eqmods.push(eqmod.key.clone());
+        }
+
+        if damage_bonus != 0 {
+            if let Some(dmg) = &item.damage {
+                item.damage = Some(format!("{}{:+}", dmg, damage_bonus));
+            }
+        }
+
+        item.name = item_name(&self.name, total_plus, mods);
+        Ok(item)
+    }
+}
+
+// "Flaming Longsword +1 (Keen)": front modifiers, base name and
+// enhancement bonus, then parenthesized modifiers
+fn item_name(base: &str, total_plus: i64, mods: &[&EquipMod]) -> String {
+    let named = |cat: FormatCat| -> Vec<String> {
+        mods.iter()
+            .filter(|m| m.format_cat == cat && m.plus.is_none())
+            .filter_map(|m| m.name_text())
+            .collect()
+    };
+
+    let mut words = named(FormatCat::Front);
+    words.extend(named(FormatCat::Middle));
+    words.push(base.to_string());
+    if total_plus != 0 {
+        words.push(format!("{:+}", total_plus));
+    }
+
+    let mut name = words.join(" ");
+    let parens = named(FormatCat::Parens);
+    if !parens.is_empty() {
+        name = format!("{} ({})", name, parens.join("/"));
+    }
+    name
+}
+
+// the loaded equipment and equipment modifiers
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct Armory {
+    pub equipment: Vec<Equipment>,
+    pub eqmods: Vec<EquipMod>,
+}
+
+impl Armory {
+    pub fn new(pcc: &Pcc) -> Armory {
+        let mut equipment: Vec<Equipment> = match pcc.list("EQUIPMENT") {
+            Some(lst) => lst
+                .props
+                .iter()
+                .map(|(name, elem)| Equipment::new(name, elem))
+                .collect(),
+            None => Vec::new(),
+        };
+        equipment.sort_by(|a, b| a.name.cmp(&b.name));
+
+        let mut eqmods: Vec<EquipMod> = match pcc.list("EQUIPMOD") {
+            Some(lst) => lst
+                .props
+                .iter()
+                .map(|(key, elem)| EquipMod::new(key, elem))
+                .collect(),
+            None => Vec::new(),
+        };
+        eqmods.sort_by(|a, b| a.key.cmp(&b.key));
+
+        Armory { equipment, eqmods }
+    }
+
+    pub fn item(&self, name: &str) -> Option<&Equipment> {
+        self.equipment
+            .iter()
+            .find(|e| e.name.eq_ignore_ascii_case(name))
+    }
+
+    // lookup a modifier by KEY or name
+    pub fn eqmod(&self, ident: &str) -> Option<&EquipMod> {
+        self.eqmods
+            .iter()
+            .find(|m| m.key.eq_ignore_ascii_case(ident))
+            .or_else(|| {
+                self.eqmods
+                    .iter()
+                    .find(|m| m.name.eq_ignore_ascii_case(ident))
+            })
+    }
+
+    // a base item with modifiers applied, e.g. ("Longsword",
+    // ["PLUS1W", "FLM"]) for a +1 flaming longsword
+    pub fn materialize(&self, base: &str, eqmods: &[&str]) -> Result<Equipment, String> {
+        let item = self.item(base).ok_or_else(|| {
+            let names = self.equipment.iter().map(|e| e.name.as_str());
+            format!("EQUIPMENT {} not found{}", base, did_you_mean(base, names))
+        })?;
+        let mods = eqmods
+            .iter()
+            .map(|m| {
+                self.eqmod(m).ok_or_else(|| {
+                    let keys = self.eqmods.iter().map(|e| e.key.as_str());
+                    format!("EQMOD {} not found{}", m, did_you_mean(m, keys))
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        item.apply(&mods)
+    }
+}
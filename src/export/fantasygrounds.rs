@@ -0,0 +1,298 @@
+//
+// export/fantasygrounds.rs -- Fantasy Grounds module export
+//
+// Copyright (c) 2024 Jeff Garzik
+//
+// This file is part of the pcgtoolssoftware project covered under
+// the MIT License.  For the full license text, please see the LICENSE
+// file in the root directory of this project.
+// SPDX-License-Identifier: MIT
+
+use crate::class::Class;
+use crate::desc;
+use crate::equipment::Equipment;
+use crate::pcc::{Pcc, PccDatum, PccElem};
+use crate::race::Race;
+use crate::spell::Spell;
+use crate::tempbonus::{self, TempBonus, TempEffect};
+use std::{
+    fs::{self, File},
+    io,
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+// root element attributes of module files
+const ROOT: &str = "<root version=\"4.1\" release=\"8|CoreRPG:4\">";
+
+// the effect codes of BONUS category|target pairs
+const EFFECT_CODES: &[(&str, &str, &str)] = &[
+    ("COMBAT", "AC", "AC"),
+    ("COMBAT", "TOHIT", "ATK"),
+    ("COMBAT", "DAMAGE", "DMG"),
+    ("SAVE", "ALL", "SAVE"),
+    ("SAVE", "FORTITUDE", "FORT"),
+    ("SAVE", "REFLEX", "REF"),
+    ("SAVE", "WILL", "WILL"),
+];
+
+// the six stats, which are their own effect codes
+const STATS: [&str; 6] = ["STR", "DEX", "CON", "INT", "WIS", "CHA"];
+
+fn escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for ch in s.chars() {
+        match ch {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            _ => out.push(ch),
+        }
+    }
+    out
+}
+
+// one field of a record, e.g. <name type="string">Fireball</name>
+fn field<W: Write>(w: &mut W, tag: &str, kind: &str, value: &str) -> io::Result<()> {
+    writeln!(
+        w,
+        "\t\t\t<{} type=\"{}\">{}</{}>",
+        tag,
+        kind,
+        escape(value),
+        tag
+    )
+}
+
+// DESC text, as a formattedtext field
+fn text<W: Write>(w: &mut W, tag: &str, elem: &PccElem) -> io::Result<()> {
+    let desc = desc::descriptions(elem).join(" ");
+    if desc.is_empty() {
+        return Ok(());
+    }
+    writeln!(
+        w,
+        "\t\t\t<{} type=\"formattedtext\"><p>{}</p></{}>",
+        tag,
+        escape(&desc),
+        tag
+    )
+}
+
+fn write_spell<W: Write>(w: &mut W, spell: &Spell, elem: &PccElem) -> io::Result<()> {
+    field(w, "name", "string", &spell.name)?;
+    if let Some(school) = &spell.school {
+        field(w, "school", "string", school)?;
+    }
+    // e.g. "Cleric 4, Wizard 3"
+    let levels: Vec<String> = spell
+        .classes
+        .iter()
+        .chain(&spell.domains)
+        .map(|(name, level)| format!("{} {}", name, level))
+        .collect();
+    field(w, "level", "string", &levels.join(", "))?;
+    if !spell.descriptors.is_empty() {
+        field(w, "descriptors", "string", &spell.descriptors.join(", "))?;
+    }
+    text(w, "description", elem)
+}
+
+fn write_item<W: Write>(w: &mut W, item: &Equipment, elem: &PccElem) -> io::Result<()> {
+    field(w, "name", "string", elem.display_name())?;
+    // the first TYPE is the item type, e.g. Weapon; the rest its
+    // subtype, e.g. Martial
+    if let Some((first, rest)) = item.types.split_first() {
+        field(w, "type", "string", first)?;
+        if !rest.is_empty() {
+            field(w, "subtype", "string", &rest.join(" "))?;
+        }
+    }
+    field(w, "cost", "string", &format!("{} gp", item.cost))?;
+    if let Some(weight) = item.weight {
+        field(w, "weight", "number", &weight.to_string())?;
+    }
+    if let Some(damage) = &item.damage {
+        field(w, "damage", "string", damage)?;
+    }
+    if let Some(mult) = &item.crit_mult {
+        field(w, "critical", "string", mult)?;
+    }
+    text(w, "description", elem)
+}
+
+fn write_race<W: Write>(w: &mut W, race: &Race, elem: &PccElem) -> io::Result<()> {
+    field(w, "name", "string", &race.name)?;
+    if let Some(size) = &race.size {
+        field(w, "size", "string", size)?;
+    }
+    if let Some(speed) = race.movement.get("Walk") {
+        field(w, "speed", "number", &speed.to_string())?;
+    }
+    // e.g. "DEX +2, CON -2"
+    let stats: Vec<String> = race
+        .stat_mods
+        .iter()
+        .map(|(stat, adj)| format!("{} {:+}", stat, adj))
+        .collect();
+    if !stats.is_empty() {
+        field(w, "abilities", "string", &stats.join(", "))?;
+    }
+    if !race.languages.is_empty() {
+        field(w, "languages", "string", &race.languages.join(", "))?;
+    }
+    let senses: Vec<String> = race
+        .vision
+        .iter()
+        .map(|v| match v.range {
+            Some(range) => format!("{} {} ft.", v.kind, range),
+            None => v.kind.clone(),
+        })
+        .collect();
+    if !senses.is_empty() {
+        field(w, "senses", "string", &senses.join(", "))?;
+    }
+    if let Some(class) = &race.favored_class {
+        field(w, "favoredclass", "string", class)?;
+    }
+    text(w, "text", elem)
+}
+
+fn write_class<W: Write>(w: &mut W, class: &Class, elem: &PccElem) -> io::Result<()> {
+    field(w, "name", "string", &class.name)?;
+    if let Some(hd) = class.hit_die {
+        writeln!(w, "\t\t\t<hitdice>")?;
+        writeln!(w, "\t\t\t\t<dice type=\"dice\">d{}</dice>", hd)?;
+        writeln!(w, "\t\t\t</hitdice>")?;
+    }
+    if let Some(max) = class.max_level {
+        field(w, "maxlevel", "number", &max.to_string())?;
+    }
+    text(w, "text", elem)
+}
+
+// The effect label of a temporary bonus, e.g. "AC: 2 deflection", or
+// for a bonus to several targets, "FORT: 1; REF: 1".  Targets having
+// no effect code are left out.
+fn effect_label(temp: &TempBonus) -> Vec<String> {
+    let bonus = &temp.bonus;
+    let bonus_type = match &bonus.bonus_type {
+        Some(t) => format!(" {}", t.to_ascii_lowercase()),
+        None => String::new(),
+    };
+    let mut labels = Vec::new();
+    for target in &bonus.targets {
+        let code = match bonus.category.as_str() {
+            "STAT" => STATS
+                .iter()
+                .find(|stat| target.eq_ignore_ascii_case(stat))
+                .map(|stat| stat.to_string()),
+            "SKILL" => {
+                labels.push(format!("SKILL: {}{} {}", bonus.formula, bonus_type, target));
+                continue;
+            }
+            category => EFFECT_CODES
+                .iter()
+                .find(|(c, name, _)| *c == category && target.eq_ignore_ascii_case(name))
+                .map(|(_, _, code)| code.to_string()),
+        };
+        if let Some(code) = code {
+            labels.push(format!("{}: {}{}", code, bonus.formula, bonus_type));
+        }
+    }
+    labels
+}
+
+fn write_effect<W: Write>(w: &mut W, effect: &TempEffect, elem: &PccElem) -> io::Result<()> {
+    field(w, "name", "string", &effect.name)?;
+    let labels: Vec<String> = effect.bonuses.iter().flat_map(effect_label).collect();
+    field(w, "label", "string", &labels.join("; "))?;
+    if let Some(duration) = &effect.duration {
+        field(w, "duration", "string", duration)?;
+    }
+    text(w, "description", elem)
+}
+
+// one category of db.xml, e.g. <spell>, with a record per element of
+// a list, in ident order
+fn write_category<W: Write, F>(
+    w: &mut W,
+    pcc: &Pcc,
+    list: &str,
+    tag: &str,
+    mut record: F,
+) -> io::Result<()>
+where
+    F: FnMut(&mut W, &str, &PccElem) -> io::Result<()>,
+{
+    let Some(lst) = pcc.list(list) else {
+        return Ok(());
+    };
+    let mut elems: Vec<(&String, &PccElem)> = lst.props.iter().collect();
+    elems.sort_by(|a, b| a.0.cmp(b.0));
+
+    writeln!(w, "\t<{}>", tag)?;
+    for (n, (ident, elem)) in elems.into_iter().enumerate() {
+        writeln!(w, "\t\t<id-{:05}>", n + 1)?;
+        record(w, ident, elem)?;
+        writeln!(w, "\t\t</id-{:05}>", n + 1)?;
+    }
+    writeln!(w, "\t</{}>", tag)
+}
+
+// Write the files of a Fantasy Grounds module to outdir:
+// definition.xml, naming the module and its ruleset, e.g. 3.5E or
+// PFRPG, and db.xml, holding spell, item, race and class records, and
+// effect records of temporary bonuses.
+// Zipped, they form a .mod file.
+pub fn export(pcc: &Pcc, outdir: &str, ruleset: &str) -> io::Result<()> {
+    let outdir = Path::new(outdir);
+    fs::create_dir_all(outdir)?;
+
+    tracing::debug!("fantasygrounds::export({}, {})", outdir.display(), ruleset);
+
+    let name = match pcc.dict.get("CAMPAIGN") {
+        Some(PccDatum::Text(name)) => name.lines().collect::<Vec<&str>>().join(", "),
+        _ => String::from("Campaign data"),
+    };
+    let mut w = BufWriter::new(File::create(outdir.join("definition.xml"))?);
+    writeln!(w, "<?xml version=\"1.0\" encoding=\"utf-8\"?>")?;
+    writeln!(w, "{}", ROOT)?;
+    writeln!(w, "\t<name>{}</name>", escape(&name))?;
+    writeln!(w, "\t<category>PCGen</category>")?;
+    writeln!(w, "\t<author>pcgtools</author>")?;
+    writeln!(w, "\t<ruleset>{}</ruleset>", escape(ruleset))?;
+    writeln!(w, "</root>")?;
+    w.flush()?;
+
+    let mut w = BufWriter::new(File::create(outdir.join("db.xml"))?);
+    writeln!(w, "<?xml version=\"1.0\" encoding=\"utf-8\"?>")?;
+    writeln!(w, "{}", ROOT)?;
+    write_category(&mut w, pcc, "SPELL", "spell", |w, ident, elem| {
+        write_spell(w, &Spell::new(ident, elem), elem)
+    })?;
+    write_category(&mut w, pcc, "EQUIPMENT", "item", |w, ident, elem| {
+        write_item(w, &Equipment::new(ident, elem), elem)
+    })?;
+    write_category(&mut w, pcc, "RACE", "race", |w, ident, elem| {
+        write_race(w, &Race::new(ident, elem), elem)
+    })?;
+    write_category(&mut w, pcc, "CLASS", "class", |w, ident, elem| {
+        write_class(w, &Class::new(ident, elem), elem)
+    })?;
+
+    // temporary effects, from TEMPBONUS, of elements of any kind
+    let effects = tempbonus::effects(pcc);
+    if !effects.is_empty() {
+        writeln!(w, "\t<effects>")?;
+        for (n, (elem, effect)) in effects.iter().enumerate() {
+            writeln!(w, "\t\t<id-{:05}>", n + 1)?;
+            write_effect(&mut w, effect, elem)?;
+            writeln!(w, "\t\t</id-{:05}>", n + 1)?;
+        }
+        writeln!(w, "\t</effects>")?;
+    }
+    writeln!(w, "</root>")?;
+    w.flush()
+}
@@ -0,0 +1,351 @@
+//
+// export/fivetools.rs -- 5eTools JSON export of spells and items
+//
+// Copyright (c) 2024 Jeff Garzik
+//
+// This file is part of the pcgtoolssoftware project covered under
+// the MIT License.  For the full license text, please see the LICENSE
+// file in the root directory of this project.
+// SPDX-License-Identifier: MIT
+
+use crate::desc;
+use crate::equipment::Equipment;
+use crate::pcc::{Pcc, PccDatum, PccElem};
+use crate::spell::Spell;
+use serde::Serialize;
+use serde_json::{json, Map, Value};
+use std::{
+    collections::BTreeMap,
+    fmt,
+    fs::File,
+    io,
+    io::{BufWriter, Write},
+};
+
+// tags mapped to 5eTools fields, per list
+const SPELL_TAGS: [&str; 7] = [
+    "SCHOOL", "CASTTIME", "DURATION", "RANGE", "COMPS", "CLASSES", "DESC",
+];
+const ITEM_TAGS: [&str; 5] = ["COST", "WT", "DAMAGE", "TYPE", "DESC"];
+
+// tags consumed by the mapping of every list
+const COMMON_TAGS: [&str; 4] = ["SOURCELONG", "SOURCESHORT", "SOURCEPAGE", "SOURCEWEB"];
+
+// PCGen data the export could not represent: tags without a 5eTools
+// field, and values of mapped tags which could not be converted
+#[derive(Debug, Default, Serialize)]
+pub struct Report {
+    // list type => tag => idents of elements with it
+    pub unmapped: BTreeMap<String, BTreeMap<String, Vec<String>>>,
+}
+
+impl Report {
+    fn add(&mut self, list: &str, tag: &str, ident: &str) {
+        let idents = self
+            .unmapped
+            .entry(list.to_string())
+            .or_default()
+            .entry(tag.to_string())
+            .or_default();
+        if idents.last().is_none_or(|i| i != ident) {
+            idents.push(ident.to_string());
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.unmapped.is_empty()
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (list, tags) in &self.unmapped {
+            for (tag, idents) in tags {
+                writeln!(
+                    f,
+                    "{} {}: not exported for {} element(s): {}",
+                    list,
+                    tag,
+                    idents.len(),
+                    idents.join(", ")
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+// 5eTools' one-letter code for a school of magic
+fn school_code(school: &str) -> Option<&'static str> {
+    match school.to_ascii_lowercase().as_str() {
+        "abjuration" => Some("A"),
+        "conjuration" => Some("C"),
+        "divination" => Some("D"),
+        "enchantment" => Some("E"),
+        "evocation" => Some("V"),
+        "illusion" => Some("I"),
+        "necromancy" => Some("N"),
+        "transmutation" => Some("T"),
+        _ => None,
+    }
+}
+
+// a leading count and the word after it, e.g. "10 minutes/level" =>
+// (10, "minutes/level"); a missing count is 1
+fn amount(value: &str) -> (u32, String) {
+    let value = value.trim().to_ascii_lowercase();
+    match value.split_once(' ') {
+        Some((n, rest)) if n.parse::<u32>().is_ok() => (n.parse().unwrap(), rest.to_string()),
+        _ => (1, value),
+    }
+}
+
+// a 5eTools time unit: action, bonus, reaction, round, minute or hour
+fn time_unit(word: &str) -> Option<&'static str> {
+    let word = word.trim_end_matches('s');
+    if word.contains("swift") || word.contains("bonus") {
+        Some("bonus")
+    } else if word.contains("immediate") || word.contains("reaction") {
+        Some("reaction")
+    } else if word.contains("full-round") || word == "round" {
+        Some("round")
+    } else if word.contains("action") {
+        Some("action")
+    } else if word == "minute" {
+        Some("minute")
+    } else if word == "hour" {
+        Some("hour")
+    } else {
+        None
+    }
+}
+
+// CASTTIME, e.g. "1 standard action" => [{number: 1, unit: action}]
+fn cast_time(value: &str) -> Option<Value> {
+    let (number, word) = amount(value);
+    let unit = time_unit(&word)?;
+    Some(json!([{ "number": number, "unit": unit }]))
+}
+
+// DURATION, e.g. "Instantaneous", "Permanent", "10 minutes",
+// "Concentration, up to 1 minute"
+fn duration(value: &str) -> Option<Value> {
+    let lower = value.trim().to_ascii_lowercase();
+    let (concentration, rest) = match lower.strip_prefix("concentration") {
+        Some(rest) => (
+            true,
+            rest.trim_start_matches([',', ' '])
+                .trim_start_matches("up to "),
+        ),
+        None => (false, lower.as_str()),
+    };
+    match rest {
+        "instantaneous" => return Some(json!([{ "type": "instant" }])),
+        "permanent" => return Some(json!([{ "type": "permanent" }])),
+        "" if concentration => return Some(json!([{ "type": "timed", "concentration": true }])),
+        _ => {}
+    }
+
+    // 5eTools durations do not scale, as "1 round/level" does
+    let (number, word) = amount(rest);
+    if word.contains('/') {
+        return None;
+    }
+    let unit = match time_unit(&word)? {
+        "action" | "bonus" | "reaction" => return None,
+        unit => unit,
+    };
+    let mut timed = json!({
+        "type": "timed",
+        "duration": { "type": unit, "amount": number },
+    });
+    if concentration {
+        timed["concentration"] = json!(true);
+    }
+    Some(json!([timed]))
+}
+
+// RANGE, e.g. "Touch", "Personal", "60 ft."
+fn range(value: &str) -> Option<Value> {
+    let lower = value.trim().to_ascii_lowercase();
+    let distance = match lower.as_str() {
+        "touch" => json!({ "type": "touch" }),
+        "personal" | "self" | "you" => json!({ "type": "self" }),
+        "unlimited" => json!({ "type": "unlimited" }),
+        _ => {
+            let (number, word) = amount(&lower);
+            if !word.starts_with("ft") && !word.starts_with("feet") {
+                return None;
+            }
+            json!({ "type": "feet", "amount": number })
+        }
+    };
+    Some(json!({ "type": "point", "distance": distance }))
+}
+
+// COMPS, e.g. "V, S, M" => {v: true, s: true, m: true}
+fn components(value: &str) -> Option<Value> {
+    let mut comps = Map::new();
+    for comp in value.split(',').map(|c| c.trim().to_ascii_lowercase()) {
+        match comp.as_str() {
+            "v" | "s" | "m" => comps.insert(comp, json!(true)),
+            "f" | "df" => comps.insert(String::from("m"), json!(true)),
+            _ => return None,
+        };
+    }
+    Some(Value::Object(comps))
+}
+
+// fields common to spells and items: source, page and entries
+fn common(pcc: &Pcc, elem: &PccElem) -> Map<String, Value> {
+    let campaign = match pcc.dict.get("SOURCESHORT") {
+        Some(PccDatum::Text(s)) if !s.contains('\n') => s.as_str(),
+        _ => "",
+    };
+    let mut obj = Map::new();
+    obj.insert(
+        String::from("source"),
+        json!(elem.get("SOURCESHORT").unwrap_or(campaign)),
+    );
+    if let Some(page) = elem.get("SOURCEPAGE") {
+        let page = page.trim_start_matches("p.").trim();
+        match page.parse::<u32>() {
+            Ok(n) => obj.insert(String::from("page"), json!(n)),
+            Err(_) => obj.insert(String::from("page"), json!(page)),
+        };
+    }
+    let desc = desc::descriptions(elem);
+    if !desc.is_empty() {
+        obj.insert(String::from("entries"), json!(desc));
+    }
+    obj
+}
+
+fn spell_json(pcc: &Pcc, spell: &Spell, elem: &PccElem, report: &mut Report) -> Value {
+    let mut obj = common(pcc, elem);
+    obj.insert(String::from("name"), json!(spell.name));
+
+    // 5eTools has one level per spell; use the lowest class level
+    let level = spell.classes.values().chain(spell.domains.values()).min();
+    obj.insert(String::from("level"), json!(level.copied().unwrap_or(0)));
+
+    // a mapped tag whose value cannot be converted is reported as if
+    // it were unmapped
+    let mut map = |field: &str, tag: &str, convert: fn(&str) -> Option<Value>| {
+        if let Some(value) = elem.get(tag) {
+            match convert(value) {
+                Some(v) => {
+                    obj.insert(field.to_string(), v);
+                }
+                None => report.add("SPELL", tag, &spell.name),
+            }
+        }
+    };
+    map("school", "SCHOOL", |v| school_code(v).map(|c| json!(c)));
+    map("time", "CASTTIME", cast_time);
+    map("duration", "DURATION", duration);
+    map("range", "RANGE", range);
+    map("components", "COMPS", components);
+
+    if !spell.classes.is_empty() {
+        let classes: Vec<Value> = spell
+            .classes
+            .keys()
+            .map(|name| json!({ "name": name, "source": obj["source"] }))
+            .collect();
+        obj.insert(String::from("classes"), json!({ "fromClassList": classes }));
+    }
+
+    Value::Object(obj)
+}
+
+// 5eTools item type: M, R, LA, MA, HA, S or G
+fn item_type(item: &Equipment) -> &'static str {
+    if item.has_type("Weapon") {
+        if item.has_type("Ranged") {
+            "R"
+        } else {
+            "M"
+        }
+    } else if item.has_type("Shield") {
+        "S"
+    } else if item.has_type("Armor") {
+        if item.has_type("Heavy") {
+            "HA"
+        } else if item.has_type("Medium") {
+            "MA"
+        } else {
+            "LA"
+        }
+    } else {
+        "G"
+    }
+}
+
+fn item_json(pcc: &Pcc, item: &Equipment, elem: &PccElem) -> Value {
+    let mut obj = common(pcc, elem);
+    obj.insert(String::from("name"), json!(elem.display_name()));
+    obj.insert(String::from("type"), json!(item_type(item)));
+    // 5eTools values are in copper pieces
+    obj.insert(
+        String::from("value"),
+        json!((item.cost * 100.0).round() as i64),
+    );
+    if let Some(weight) = item.weight {
+        obj.insert(String::from("weight"), json!(weight));
+    }
+    if let Some(damage) = &item.damage {
+        obj.insert(String::from("dmg1"), json!(damage));
+    }
+    Value::Object(obj)
+}
+
+// report each tag of an element which no field was mapped from
+fn report_unmapped(report: &mut Report, list: &str, ident: &str, elem: &PccElem, mapped: &[&str]) {
+    for (tag, _) in elem.attribs() {
+        if !mapped.contains(&tag) && !COMMON_TAGS.contains(&tag) {
+            report.add(list, tag, ident);
+        }
+    }
+}
+
+// the spells and items of the loaded data, as a 5eTools homebrew
+// document, and a report of what was left out
+pub fn convert(pcc: &Pcc) -> (Value, Report) {
+    let mut report = Report::default();
+    let sorted = |list: &str| -> Vec<(&String, &PccElem)> {
+        let mut elems: Vec<(&String, &PccElem)> = pcc
+            .list(list)
+            .map_or(Vec::new(), |lst| lst.props.iter().collect());
+        elems.sort_by(|a, b| a.0.cmp(b.0));
+        elems
+    };
+
+    let mut spells = Vec::new();
+    for (ident, elem) in sorted("SPELL") {
+        let spell = Spell::new(ident, elem);
+        spells.push(spell_json(pcc, &spell, elem, &mut report));
+        report_unmapped(&mut report, "SPELL", ident, elem, &SPELL_TAGS);
+    }
+
+    let mut items = Vec::new();
+    for (ident, elem) in sorted("EQUIPMENT") {
+        items.push(item_json(pcc, &Equipment::new(ident, elem), elem));
+        report_unmapped(&mut report, "EQUIPMENT", ident, elem, &ITEM_TAGS);
+    }
+
+    (json!({ "spell": spells, "item": items }), report)
+}
+
+// write the 5eTools document to path, returning the report of data
+// not exported
+pub fn export(pcc: &Pcc, path: &str) -> io::Result<Report> {
+    tracing::debug!("fivetools::export({})", path);
+
+    let (doc, report) = convert(pcc);
+    let mut w = BufWriter::new(File::create(path)?);
+    serde_json::to_writer_pretty(&mut w, &doc)?;
+    writeln!(w)?;
+    w.flush()?;
+    Ok(report)
+}
@@ -0,0 +1,460 @@
+//
+// export/foundry.rs -- Foundry VTT compendium export
+//
+// Copyright (c) 2024 Jeff Garzik
+//
+// This file is part of the pcgtoolssoftware project covered under
+// the MIT License.  For the full license text, please see the LICENSE
+// file in the root directory of this project.
+// SPDX-License-Identifier: MIT
+
+use crate::ability::Ability;
+use crate::class::Class;
+use crate::desc;
+use crate::equipment::Equipment;
+use crate::pcc::{Pcc, PccDatum, PccElem};
+use crate::spell::Spell;
+use crate::tempbonus::{self, TempBonus, TempEffect};
+use crate::text::fnv1a;
+use serde_json::{json, Value};
+use std::{
+    fmt,
+    fs::{self, File},
+    io,
+    io::{BufWriter, Write},
+    path::Path,
+    str::FromStr,
+};
+
+// the Foundry game system whose item schema is written
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum System {
+    Dnd5e,
+    Pf1,
+}
+
+impl FromStr for System {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<System, String> {
+        match s.to_ascii_lowercase().as_str() {
+            "dnd5e" => Ok(System::Dnd5e),
+            "pf1" => Ok(System::Pf1),
+            _ => Err(format!("unknown Foundry system {}", s)),
+        }
+    }
+}
+
+impl fmt::Display for System {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match self {
+            System::Dnd5e => "dnd5e",
+            System::Pf1 => "pf1",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+// compendium packs written: file name, label, list type
+const PACKS: [(&str, &str, &str); 4] = [
+    ("spells", "Spells", "SPELL"),
+    ("feats", "Feats", "ABILITY"),
+    ("items", "Items", "EQUIPMENT"),
+    ("classes", "Classes", "CLASS"),
+];
+
+// What BONUS category|target pairs change in each system: the dnd5e
+// active effect keys, and the pf1 buff change target, if any.  Stats
+// are mapped by stat_change().
+const CHANGE_TARGETS: &[(&str, &str, &[&str], Option<&str>)] = &[
+    ("COMBAT", "AC", &["system.attributes.ac.bonus"], Some("ac")),
+    (
+        "COMBAT",
+        "TOHIT",
+        &["system.bonuses.mwak.attack", "system.bonuses.rwak.attack"],
+        Some("attack"),
+    ),
+    (
+        "COMBAT",
+        "DAMAGE",
+        &["system.bonuses.mwak.damage", "system.bonuses.rwak.damage"],
+        Some("damage"),
+    ),
+    (
+        "SAVE",
+        "ALL",
+        &["system.bonuses.abilities.save"],
+        Some("allSavingThrows"),
+    ),
+    ("SAVE", "FORTITUDE", &[], Some("fort")),
+    ("SAVE", "REFLEX", &[], Some("ref")),
+    ("SAVE", "WILL", &[], Some("will")),
+];
+
+// the six stats, as Foundry abbreviates them
+const STATS: [&str; 6] = ["str", "dex", "con", "int", "wis", "cha"];
+
+// Foundry document id: 16 alphanumeric characters, derived from the
+// element so that re-exports update documents rather than duplicate
+// them
+fn doc_id(list: &str, ident: &str) -> String {
+    format!("{:016x}", fnv1a(format!("{}\0{}", list, ident).as_bytes()))
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+// DESC text, as an HTML paragraph
+fn description(elem: &PccElem) -> Value {
+    let text = escape(&desc::descriptions(elem).join(" "));
+    let html = if text.is_empty() {
+        text
+    } else {
+        format!("<p>{}</p>", text)
+    };
+    json!({ "value": html })
+}
+
+// e.g. "MB p.42".  An element without a SOURCE of its own takes that
+// of the campaign, when a single campaign is loaded.
+fn source(pcc: &Pcc, elem: &PccElem) -> String {
+    let campaign = match pcc.dict.get("SOURCESHORT") {
+        Some(PccDatum::Text(s)) if !s.contains('\n') => s.as_str(),
+        _ => "",
+    };
+    let book = elem
+        .get("SOURCESHORT")
+        .or_else(|| elem.get("SOURCELONG"))
+        .unwrap_or(campaign);
+    match elem.get("SOURCEPAGE") {
+        Some(page) if !book.is_empty() => format!("{} {}", book, page),
+        Some(page) => page.to_string(),
+        None => book.to_string(),
+    }
+}
+
+// Foundry's three-letter code for a school of magic
+fn school_code(school: &str) -> String {
+    let code = match school.to_ascii_lowercase().as_str() {
+        "abjuration" => "abj",
+        "conjuration" => "con",
+        "divination" => "div",
+        "enchantment" => "enc",
+        "evocation" => "evo",
+        "illusion" => "ill",
+        "necromancy" => "nec",
+        "transmutation" => "trs",
+        "universal" => "uni",
+        _ => school,
+    };
+    code.to_string()
+}
+
+fn spell_doc(pcc: &Pcc, system: System, spell: &Spell, elem: &PccElem) -> Value {
+    // Foundry has one level per spell; use the lowest class level
+    let level = spell
+        .classes
+        .values()
+        .chain(spell.domains.values())
+        .min()
+        .copied()
+        .unwrap_or(0);
+    let school = spell.school.as_deref().map_or(String::new(), school_code);
+    let data = match system {
+        System::Dnd5e => json!({
+            "description": description(elem),
+            "source": { "custom": source(pcc, elem) },
+            "level": level,
+            "school": school,
+        }),
+        System::Pf1 => json!({
+            "description": description(elem),
+            "sources": [{ "id": source(pcc, elem) }],
+            "level": level,
+            "school": school,
+            "subschool": spell.subschools,
+            "descriptors": spell.descriptors,
+            "learnedAt": { "class": spell.classes, "domain": spell.domains },
+        }),
+    };
+    json!({
+        "_id": doc_id("SPELL", &spell.key),
+        "name": spell.name,
+        "type": "spell",
+        "system": data,
+    })
+}
+
+fn feat_doc(pcc: &Pcc, system: System, feat: &Ability, elem: &PccElem) -> Value {
+    let data = match system {
+        System::Dnd5e => json!({
+            "description": description(elem),
+            "source": { "custom": source(pcc, elem) },
+            "type": { "value": "feat" },
+        }),
+        System::Pf1 => json!({
+            "description": description(elem),
+            "sources": [{ "id": source(pcc, elem) }],
+            "subType": "feat",
+            "tags": feat.types,
+        }),
+    };
+    json!({
+        "_id": doc_id("ABILITY", &feat.key),
+        "name": elem.display_name(),
+        "type": "feat",
+        "system": data,
+    })
+}
+
+fn item_doc(pcc: &Pcc, system: System, item: &Equipment, elem: &PccElem) -> Value {
+    let doc_type = if item.has_type("Weapon") {
+        "weapon"
+    } else if item.has_type("Armor") || item.has_type("Shield") {
+        "equipment"
+    } else {
+        "loot"
+    };
+    let mut data = match system {
+        System::Dnd5e => json!({
+            "description": description(elem),
+            "source": { "custom": source(pcc, elem) },
+            "price": { "value": item.cost, "denomination": "gp" },
+            "weight": { "value": item.weight.unwrap_or(0.0), "units": "lb" },
+        }),
+        System::Pf1 => json!({
+            "description": description(elem),
+            "sources": [{ "id": source(pcc, elem) }],
+            "price": item.cost,
+            "weight": { "value": item.weight.unwrap_or(0.0) },
+            "tags": item.types,
+        }),
+    };
+    if let Some(damage) = &item.damage {
+        data["damage"] = match system {
+            System::Dnd5e => json!({ "parts": [[damage, ""]] }),
+            System::Pf1 => json!({ "parts": [{ "formula": damage }] }),
+        };
+    }
+    json!({
+        "_id": doc_id("EQUIPMENT", &item.name),
+        "name": elem.display_name(),
+        "type": doc_type,
+        "system": data,
+    })
+}
+
+fn class_doc(pcc: &Pcc, system: System, class: &Class, elem: &PccElem) -> Value {
+    let hit_die = class.hit_die.unwrap_or(8);
+    let data = match system {
+        System::Dnd5e => json!({
+            "description": description(elem),
+            "source": { "custom": source(pcc, elem) },
+            "levels": class.max_level.unwrap_or(20),
+            "hitDice": format!("d{}", hit_die),
+        }),
+        System::Pf1 => json!({
+            "description": description(elem),
+            "sources": [{ "id": source(pcc, elem) }],
+            "hd": hit_die,
+            "level": 1,
+            "tags": class.types,
+        }),
+    };
+    json!({
+        "_id": doc_id("CLASS", &class.name),
+        "name": class.name,
+        "type": "class",
+        "system": data,
+    })
+}
+
+// the changes a temporary bonus makes, in the form of the system;
+// targets the system has no change for are left out
+fn changes(system: System, temp: &TempBonus) -> Vec<Value> {
+    let bonus = &temp.bonus;
+    let mut changes = Vec::new();
+    for target in &bonus.targets {
+        let stat = STATS
+            .iter()
+            .find(|stat| bonus.category == "STAT" && target.eq_ignore_ascii_case(stat));
+        let (keys, pf1_target) = match stat {
+            Some(stat) => (
+                vec![format!("system.abilities.{}.value", stat)],
+                Some(*stat),
+            ),
+            None => match CHANGE_TARGETS.iter().find(|(category, name, _, _)| {
+                bonus.category == *category && target.eq_ignore_ascii_case(name)
+            }) {
+                Some((_, _, keys, pf1)) => (keys.iter().map(|k| k.to_string()).collect(), *pf1),
+                None => continue,
+            },
+        };
+        match system {
+            // mode 2: ADD
+            System::Dnd5e => changes.extend(
+                keys.into_iter()
+                    .map(|key| json!({ "key": key, "mode": 2, "value": bonus.formula })),
+            ),
+            System::Pf1 => changes.extend(pf1_target.map(|target| {
+                let bonus_type = bonus.bonus_type.as_deref().unwrap_or("untyped");
+                json!({
+                    "formula": bonus.formula,
+                    "target": target,
+                    "type": bonus_type.to_ascii_lowercase(),
+                })
+            })),
+        }
+    }
+    changes
+}
+
+// A temporary effect: a buff in pf1; in dnd5e, a feat carrying an
+// active effect, applied when enabled.  Its duration, as text, follows
+// its description.
+fn effect_doc(pcc: &Pcc, system: System, effect: &TempEffect, elem: &PccElem) -> Value {
+    let mut description = description(elem);
+    if let Some(duration) = &effect.duration {
+        let html = description["value"].as_str().unwrap_or("");
+        description["value"] = json!(format!("{}<p>Duration: {}</p>", html, escape(duration)));
+    }
+    let changes: Vec<Value> = effect
+        .bonuses
+        .iter()
+        .flat_map(|temp| changes(system, temp))
+        .collect();
+    let id = doc_id("TEMPBONUS", &format!("{}\0{}", effect.list, effect.key));
+
+    match system {
+        System::Dnd5e => json!({
+            "_id": id,
+            "name": effect.name,
+            "type": "feat",
+            "system": {
+                "description": description,
+                "source": { "custom": source(pcc, elem) },
+                "type": { "value": "feat" },
+            },
+            "effects": [{
+                "_id": id,
+                "name": effect.name,
+                "disabled": true,
+                "transfer": false,
+                "changes": changes,
+            }],
+        }),
+        System::Pf1 => json!({
+            "_id": id,
+            "name": effect.name,
+            "type": "buff",
+            "system": {
+                "description": description,
+                "sources": [{ "id": source(pcc, elem) }],
+                "subType": "temp",
+                "changes": changes,
+            },
+        }),
+    }
+}
+
+// the documents of one pack, in ident order
+fn pack_docs(pcc: &Pcc, system: System, list: &str) -> Vec<Value> {
+    let Some(lst) = pcc.list(list) else {
+        return Vec::new();
+    };
+    let mut elems: Vec<(&String, &PccElem)> = lst.props.iter().collect();
+    elems.sort_by(|a, b| a.0.cmp(b.0));
+
+    elems
+        .into_iter()
+        .filter_map(|(ident, elem)| match list {
+            "SPELL" => Some(spell_doc(pcc, system, &Spell::new(ident, elem), elem)),
+            "ABILITY" => {
+                let ability = Ability::new(ident, elem);
+                ability
+                    .category
+                    .eq_ignore_ascii_case("FEAT")
+                    .then(|| feat_doc(pcc, system, &ability, elem))
+            }
+            "EQUIPMENT" => Some(item_doc(pcc, system, &Equipment::new(ident, elem), elem)),
+            "CLASS" => Some(class_doc(pcc, system, &Class::new(ident, elem), elem)),
+            _ => None,
+        })
+        .collect()
+}
+
+// Write a compendium pack of documents to packs/, one per line, and
+// return its entry in module.json
+fn write_pack(
+    outdir: &Path,
+    system: System,
+    name: &str,
+    label: &str,
+    docs: &[Value],
+) -> io::Result<Value> {
+    let path = format!("packs/{}.db", name);
+    let mut w = BufWriter::new(File::create(outdir.join(&path))?);
+    for doc in docs {
+        serde_json::to_writer(&mut w, doc)?;
+        writeln!(w)?;
+    }
+    w.flush()?;
+
+    Ok(json!({
+        "name": name,
+        "label": label,
+        "path": path,
+        "type": "Item",
+        "system": system.to_string(),
+    }))
+}
+
+// Write a Foundry module to outdir: module.json, and one compendium
+// pack per kind of item in packs/, each a file of JSON documents, one
+// per line, plus a pack of the temporary effects, from TEMPBONUS, of
+// elements of any kind.  Empty packs are omitted.
+pub fn export(pcc: &Pcc, outdir: &str, system: System) -> io::Result<()> {
+    let outdir = Path::new(outdir);
+    fs::create_dir_all(outdir.join("packs"))?;
+
+    tracing::debug!("foundry::export({}, {})", outdir.display(), system);
+
+    let mut packs = Vec::new();
+    for (name, label, list) in PACKS {
+        let docs = pack_docs(pcc, system, list);
+        if !docs.is_empty() {
+            packs.push(write_pack(outdir, system, name, label, &docs)?);
+        }
+    }
+    let effects: Vec<Value> = tempbonus::effects(pcc)
+        .iter()
+        .map(|(elem, effect)| effect_doc(pcc, system, effect, elem))
+        .collect();
+    if !effects.is_empty() {
+        packs.push(write_pack(outdir, system, "effects", "Effects", &effects)?);
+    }
+
+    let title = match pcc.dict.get("CAMPAIGN") {
+        Some(PccDatum::Text(name)) => name.lines().collect::<Vec<&str>>().join(", "),
+        _ => String::from("Campaign data"),
+    };
+    let id: String = title
+        .to_ascii_lowercase()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+        .collect();
+    let manifest = json!({
+        "id": id,
+        "title": title,
+        "version": "1.0.0",
+        "relationships": { "systems": [{ "id": system.to_string(), "type": "system" }] },
+        "packs": packs,
+    });
+    let mut w = BufWriter::new(File::create(outdir.join("module.json"))?);
+    serde_json::to_writer_pretty(&mut w, &manifest)?;
+    writeln!(w)?;
+    w.flush()
+}
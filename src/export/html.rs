@@ -0,0 +1,225 @@
+//
+// export/html.rs -- static HTML browser export
+//
+// Copyright (c) 2024 Jeff Garzik
+//
+// This file is part of the pcgtoolssoftware project covered under
+// the MIT License.  For the full license text, please see the LICENSE
+// file in the root directory of this project.
+// SPDX-License-Identifier: MIT
+
+use crate::desc;
+use crate::pcc::{Pcc, PccDatum, PccElem, PccList};
+use std::{
+    collections::HashMap,
+    fs::{self, File},
+    io,
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+const STYLE: &str = "body { font-family: sans-serif; margin: 2em; }
+table { border-collapse: collapse; width: 100%; }
+th, td { border: 1px solid #ccc; padding: 4px 8px; text-align: left; vertical-align: top; }
+th { background: #eee; }
+tr:target { background: #ffd; }
+input { margin-bottom: 1em; padding: 4px; width: 30em; }";
+
+// filter table rows by the text typed into the search box
+const SCRIPT: &str = "function filterRows(q) {
+  q = q.toLowerCase();
+  for (const row of document.querySelectorAll('tbody tr')) {
+    row.style.display = row.textContent.toLowerCase().includes(q) ? '' : 'none';
+  }
+}";
+
+fn escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for ch in s.chars() {
+        match ch {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            _ => out.push(ch),
+        }
+    }
+    out
+}
+
+// HTML id for an element; ids may not contain whitespace
+fn anchor(ident: &str) -> String {
+    let mut out = String::from("e-");
+    for ch in ident.chars() {
+        if ch.is_ascii_alphanumeric() {
+            out.push(ch);
+        } else {
+            out.push_str(&format!("_{:x}", ch as u32));
+        }
+    }
+    out
+}
+
+fn page_name(list: &str) -> String {
+    format!("{}.html", list.to_lowercase())
+}
+
+// map of element ident => list name, for cross-linking references
+type RefIndex<'a> = HashMap<&'a str, &'a str>;
+
+// render a tag value, linking each '|' or ',' separated item that names
+// a known element (ignoring "=n" suffixes, as in "Wizard=3")
+fn linkify(value: &str, index: &RefIndex) -> String {
+    let mut out = String::new();
+    let mut item = String::new();
+    let flush = |item: &mut String, out: &mut String| {
+        let name = item.split('=').next().unwrap_or("");
+        match index.get(name) {
+            Some(list) if !name.is_empty() => {
+                out.push_str(&format!(
+                    "<a href=\"{}#{}\">{}</a>",
+                    page_name(list),
+                    anchor(name),
+                    escape(name)
+                ));
+                out.push_str(&escape(&item[name.len()..]));
+            }
+            _ => out.push_str(&escape(item)),
+        }
+        item.clear();
+    };
+
+    for ch in value.chars() {
+        if ch == '|' || ch == ',' {
+            flush(&mut item, &mut out);
+            out.push(ch);
+        } else {
+            item.push(ch);
+        }
+    }
+    flush(&mut item, &mut out);
+
+    out
+}
+
+fn write_header<W: Write>(w: &mut W, title: &str) -> io::Result<()> {
+    writeln!(w, "<!DOCTYPE html>")?;
+    writeln!(w, "<html><head><meta charset=\"utf-8\">")?;
+    writeln!(w, "<title>{}</title>", escape(title))?;
+    writeln!(w, "<style>{}</style>", STYLE)?;
+    writeln!(w, "<script>{}</script>", SCRIPT)?;
+    writeln!(w, "</head><body>")?;
+    writeln!(w, "<h1>{}</h1>", escape(title))
+}
+
+fn write_row<W: Write>(w: &mut W, ident: &str, elem: &PccElem, index: &RefIndex) -> io::Result<()> {
+    let get_all = |tag: &str| -> Vec<&str> {
+        elem.attribs
+            .iter()
+            .filter(|(k, _)| k == tag)
+            .map(|(_, v)| v.as_str())
+            .collect()
+    };
+
+    let other: Vec<String> = elem
+        .attribs
+        .iter()
+        .filter(|(k, _)| k != "TYPE" && k != "DESC")
+        .map(|(k, v)| format!("<b>{}</b>:{}", escape(k), linkify(v, index)))
+        .collect();
+
+    writeln!(w, "<tr id=\"{}\">", anchor(ident))?;
+    let name = elem.name.as_deref().unwrap_or(ident);
+    writeln!(
+        w,
+        "<td>{}</td>",
+        escape(elem.outputname.as_deref().unwrap_or(name))
+    )?;
+    writeln!(w, "<td>{}</td>", escape(&get_all("TYPE").join(".")))?;
+    writeln!(
+        w,
+        "<td>{}</td>",
+        escape(&desc::descriptions(elem).join(" "))
+    )?;
+    writeln!(w, "<td>{}</td>", other.join("<br>"))?;
+    writeln!(w, "</tr>")
+}
+
+fn write_list(path: &Path, name: &str, lst: &PccList, index: &RefIndex) -> io::Result<()> {
+    let mut w = BufWriter::new(File::create(path)?);
+
+    write_header(&mut w, name)?;
+    writeln!(w, "<p><a href=\"index.html\">Index</a></p>")?;
+    writeln!(
+        w,
+        "<input type=\"search\" placeholder=\"Search {}\" oninput=\"filterRows(this.value)\">",
+        escape(name)
+    )?;
+    writeln!(w, "<table>")?;
+    writeln!(
+        w,
+        "<thead><tr><th>Name</th><th>Type</th><th>Description</th><th>Tags</th></tr></thead>"
+    )?;
+    writeln!(w, "<tbody>")?;
+
+    let mut idents: Vec<&String> = lst.props.keys().collect();
+    idents.sort();
+    for ident in idents {
+        write_row(&mut w, ident, &lst.props[ident], index)?;
+    }
+
+    writeln!(w, "</tbody></table>")?;
+    writeln!(w, "</body></html>")?;
+    w.flush()
+}
+
+// generate a static site in outdir: index.html, plus one page per list
+pub fn export(pcc: &Pcc, outdir: &str) -> io::Result<()> {
+    let outdir = Path::new(outdir);
+    fs::create_dir_all(outdir)?;
+
+    tracing::debug!("html::export({})", outdir.display());
+
+    let mut lists: Vec<(&String, &PccList)> = pcc
+        .dict
+        .iter()
+        .filter_map(|(name, datum)| match datum {
+            PccDatum::List(lst) => Some((name, lst)),
+            _ => None,
+        })
+        .collect();
+    lists.sort_by(|a, b| a.0.cmp(b.0));
+
+    // index every element ident; where lists share an ident, the
+    // first list alphabetically wins
+    let mut index = RefIndex::new();
+    for (name, lst) in &lists {
+        for ident in lst.props.keys() {
+            index.entry(ident.as_str()).or_insert(name.as_str());
+        }
+    }
+
+    let mut w = BufWriter::new(File::create(outdir.join("index.html"))?);
+    let title = match pcc.dict.get("CAMPAIGN") {
+        Some(PccDatum::Text(name)) => name.lines().collect::<Vec<&str>>().join(", "),
+        _ => String::from("Campaign data"),
+    };
+    write_header(&mut w, &title)?;
+    writeln!(w, "<ul>")?;
+
+    for (name, lst) in &lists {
+        write_list(&outdir.join(page_name(name)), name, lst, &index)?;
+        writeln!(
+            w,
+            "<li><a href=\"{}\">{}</a> ({} entries)</li>",
+            page_name(name),
+            escape(name),
+            lst.props.len()
+        )?;
+    }
+
+    writeln!(w, "</ul>")?;
+    writeln!(w, "</body></html>")?;
+    w.flush()
+}
@@ -0,0 +1,38 @@
+//
+// export/lst.rs -- export of loaded lists as flattened LST files
+//
+// Copyright (c) 2024 Jeff Garzik
+//
+// This file is part of the pcgtoolssoftware project covered under
+// the MIT License.  For the full license text, please see the LICENSE
+// file in the root directory of this project.
+// SPDX-License-Identifier: MIT
+
+use crate::pcc::Pcc;
+use crate::writer::{lst_filename, write_lst_file, PccFile};
+use std::{fs, io, path::Path};
+
+// name of the PCC file referencing the LST files written
+const PCC_FILENAME: &str = "flattened.pcc";
+
+// Write each loaded list to an LST file in outdir, with every .MOD,
+// .COPY, .FORGET and .CLEAR applied, so that each element is defined
+// whole, in one place, as the data loaded.  A PCC file with the
+// campaign tags loaded references the LST files, so that the output
+// loads on its own.
+pub fn export(pcc: &Pcc, outdir: &str) -> io::Result<()> {
+    let outdir = Path::new(outdir);
+    fs::create_dir_all(outdir)?;
+
+    tracing::debug!("lst::export({})", outdir.display());
+
+    let mut lists = Vec::new();
+    for (list, lst) in pcc.lists() {
+        if lst.is_empty() {
+            continue;
+        }
+        write_lst_file(pcc, list, &outdir.join(lst_filename(list)))?;
+        lists.push(list);
+    }
+    PccFile::from_pcc(pcc, &lists).write_file(&outdir.join(PCC_FILENAME))
+}
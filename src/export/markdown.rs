@@ -0,0 +1,157 @@
+//
+// export/markdown.rs -- Markdown documentation generator
+//
+// Copyright (c) 2024 Jeff Garzik
+//
+// This file is part of the pcgtoolssoftware project covered under
+// the MIT License.  For the full license text, please see the LICENSE
+// file in the root directory of this project.
+// SPDX-License-Identifier: MIT
+
+use crate::desc;
+use crate::pcc::{Pcc, PccDatum, PccElem, PccList};
+use crate::prereq::Prereq;
+use std::{
+    fs::{self, File},
+    io,
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+// escape characters with special meaning in Markdown text
+pub(crate) fn escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for ch in s.chars() {
+        if matches!(
+            ch,
+            '\\' | '*' | '_' | '`' | '[' | ']' | '<' | '>' | '#' | '|'
+        ) {
+            out.push('\\');
+        }
+        out.push(ch);
+    }
+    out
+}
+
+// all values of a tag on an element
+fn values<'a>(elem: &'a PccElem, tag: &str) -> Vec<&'a str> {
+    elem.attribs
+        .iter()
+        .filter(|(k, _)| k == tag)
+        .map(|(_, v)| v.as_str())
+        .collect()
+}
+
+// readable source citation, e.g. "Core Rulebook, p. 42"
+fn source_text(elem: &PccElem) -> String {
+    let book = values(elem, "SOURCELONG")
+        .first()
+        .or(values(elem, "SOURCESHORT").first())
+        .map(|s| s.to_string())
+        .unwrap_or_else(|| {
+            Path::new(&elem.source)
+                .file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_default()
+        });
+
+    match values(elem, "SOURCEPAGE").first() {
+        Some(page) => format!("{}, {}", book, page),
+        None => book,
+    }
+}
+
+fn write_elem<W: Write>(w: &mut W, ident: &str, elem: &PccElem) -> io::Result<()> {
+    // elements are headed by name, though stored by KEY
+    let name = elem.name.as_deref().unwrap_or(ident);
+    writeln!(
+        w,
+        "## {}",
+        escape(elem.outputname.as_deref().unwrap_or(name))
+    )?;
+    writeln!(w)?;
+
+    for desc in desc::descriptions(elem) {
+        writeln!(w, "{}", escape(&desc))?;
+        writeln!(w)?;
+    }
+
+    let types = values(elem, "TYPE");
+    if !types.is_empty() {
+        writeln!(w, "- **Type:** {}", escape(&types.join(".")))?;
+    }
+
+    writeln!(w, "- **Source:** {}", escape(&source_text(elem)))?;
+
+    let prereqs: Vec<String> = elem
+        .attribs
+        .iter()
+        .filter_map(|(k, v)| Prereq::parse(&format!("{}:{}", k, v)))
+        .map(|p| p.describe())
+        .collect();
+    if !prereqs.is_empty() {
+        writeln!(w, "- **Prerequisites:**")?;
+        for p in prereqs {
+            writeln!(w, "  - {}", escape(&p))?;
+        }
+    }
+
+    writeln!(w)?;
+    Ok(())
+}
+
+fn write_list(path: &Path, name: &str, lst: &PccList) -> io::Result<()> {
+    let mut w = BufWriter::new(File::create(path)?);
+
+    writeln!(w, "# {}", escape(name))?;
+    writeln!(w)?;
+
+    let mut idents: Vec<&String> = lst.props.keys().collect();
+    idents.sort();
+
+    for ident in idents {
+        write_elem(&mut w, ident, &lst.props[ident])?;
+    }
+
+    w.flush()
+}
+
+// render each list into <outdir>/<list>.md, plus an index.md
+pub fn export(pcc: &Pcc, outdir: &str) -> io::Result<()> {
+    let outdir = Path::new(outdir);
+    fs::create_dir_all(outdir)?;
+
+    tracing::debug!("markdown::export({})", outdir.display());
+
+    let mut lists: Vec<(&String, &PccList)> = pcc
+        .dict
+        .iter()
+        .filter_map(|(name, datum)| match datum {
+            PccDatum::List(lst) => Some((name, lst)),
+            _ => None,
+        })
+        .collect();
+    lists.sort_by(|a, b| a.0.cmp(b.0));
+
+    let mut index = BufWriter::new(File::create(outdir.join("index.md"))?);
+    let title = match pcc.dict.get("CAMPAIGN") {
+        Some(PccDatum::Text(name)) => name.lines().collect::<Vec<&str>>().join(", "),
+        _ => String::from("Campaign data"),
+    };
+    writeln!(index, "# {}", escape(&title))?;
+    writeln!(index)?;
+
+    for (name, lst) in lists {
+        let fname = format!("{}.md", name.to_lowercase());
+        write_list(&outdir.join(&fname), name, lst)?;
+        writeln!(
+            index,
+            "- [{}]({}) ({} entries)",
+            escape(name),
+            fname,
+            lst.props.len()
+        )?;
+    }
+
+    index.flush()
+}
@@ -0,0 +1,18 @@
+//
+// export/mod.rs -- export loaded data to other formats
+//
+// Copyright (c) 2024 Jeff Garzik
+//
+// This file is part of the pcgtoolssoftware project covered under
+// the MIT License.  For the full license text, please see the LICENSE
+// file in the root directory of this project.
+// SPDX-License-Identifier: MIT
+
+pub mod fantasygrounds;
+pub mod fivetools;
+pub mod foundry;
+pub mod html;
+pub mod lst;
+pub mod markdown;
+#[cfg(feature = "native")]
+pub mod sqlite;
@@ -0,0 +1,115 @@
+//
+// export/sqlite.rs -- SQLite database export
+//
+// Copyright (c) 2024 Jeff Garzik
+//
+// This file is part of the pcgtoolssoftware project covered under
+// the MIT License.  For the full license text, please see the LICENSE
+// file in the root directory of this project.
+// SPDX-License-Identifier: MIT
+
+use crate::pcc::{Pcc, PccDatum};
+use rusqlite::{params, Connection};
+use std::{io, io::Error, path::Path};
+
+// quote an identifier for use as a table name
+fn quote_ident(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+fn export_conn(pcc: &Pcc, conn: &mut Connection) -> rusqlite::Result<()> {
+    let tx = conn.transaction()?;
+
+    // campaign-level text tags
+    tx.execute(
+        "CREATE TABLE campaign (tag TEXT NOT NULL, value TEXT NOT NULL)",
+        [],
+    )?;
+
+    // sort for deterministic table creation order
+    let mut names: Vec<&String> = pcc.dict.keys().collect();
+    names.sort();
+
+    for name in names {
+        match &pcc.dict[name] {
+            // one table of elements per list type, and one table of
+            // the attributes of those elements
+            PccDatum::List(lst) => {
+                let elem_table = quote_ident(name);
+                let attr_table = quote_ident(&format!("{}_attribs", name));
+
+                tx.execute(
+                    &format!(
+                        "CREATE TABLE {} (ident TEXT PRIMARY KEY, source_file TEXT NOT NULL)",
+                        elem_table
+                    ),
+                    [],
+                )?;
+                tx.execute(
+                    &format!(
+                        "CREATE TABLE {} (ident TEXT NOT NULL REFERENCES {}(ident), \
+                         seq INTEGER NOT NULL, tag TEXT NOT NULL, value TEXT NOT NULL, \
+                         source_file TEXT NOT NULL)",
+                        attr_table, elem_table
+                    ),
+                    [],
+                )?;
+                tx.execute(
+                    &format!(
+                        "CREATE INDEX {} ON {} (tag, value)",
+                        quote_ident(&format!("{}_attribs_tag", name)),
+                        attr_table
+                    ),
+                    [],
+                )?;
+
+                let mut elem_stmt = tx.prepare(&format!(
+                    "INSERT INTO {} (ident, source_file) VALUES (?1, ?2)",
+                    elem_table
+                ))?;
+                let mut attr_stmt = tx.prepare(&format!(
+                    "INSERT INTO {} (ident, seq, tag, value, source_file) \
+                     VALUES (?1, ?2, ?3, ?4, ?5)",
+                    attr_table
+                ))?;
+
+                for (ident, elem) in &lst.props {
+                    elem_stmt.execute(params![ident, elem.source])?;
+                    for (seq, (tag, value)) in elem.attribs.iter().enumerate() {
+                        attr_stmt.execute(params![
+                            ident,
+                            seq as i64,
+                            tag.as_str(),
+                            value.as_str(),
+                            elem.source
+                        ])?;
+                    }
+                }
+            }
+
+            datum => {
+                let val = datum.scalar_text().unwrap_or_default();
+                for line in val.lines() {
+                    tx.execute(
+                        "INSERT INTO campaign (tag, value) VALUES (?1, ?2)",
+                        params![name, line],
+                    )?;
+                }
+            }
+        }
+    }
+
+    tx.commit()
+}
+
+// write all loaded data to a new SQLite database
+pub fn export(pcc: &Pcc, path: &str) -> io::Result<()> {
+    if Path::new(path).exists() {
+        return Err(Error::other(format!("{} already exists", path)));
+    }
+
+    tracing::debug!("sqlite::export({})", path);
+
+    let mut conn = Connection::open(path).map_err(Error::other)?;
+    export_conn(pcc, &mut conn).map_err(Error::other)
+}
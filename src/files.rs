@@ -0,0 +1,349 @@
+//
+// files.rs -- access to data files, on disk, in ZIP archives, or
+// supplied by an embedder
+//
+// Copyright (c) 2024 Jeff Garzik
+//
+// This file is part of the pcgtoolssoftware project covered under
+// the MIT License.  For the full license text, please see the LICENSE
+// file in the root directory of this project.
+// SPDX-License-Identifier: MIT
+
+#[cfg(feature = "native")]
+use std::{
+    collections::HashMap,
+    io::{BufReader, Read},
+    sync::Mutex,
+};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    io::{self, Error, ErrorKind},
+    path::{Component, Path, PathBuf},
+    sync::{Arc, OnceLock, RwLock},
+};
+
+// Source of the PCC, LST and game mode files read by a load.  Reading
+// goes through the installed provider, rather than std::fs, so that
+// builds without a filesystem, such as wasm32-unknown-unknown, may
+// supply files themselves.
+pub trait FileProvider: Send + Sync {
+    // the contents of a file
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>>;
+
+    // the names of the files and directories in a directory
+    fn read_dir(&self, dir: &Path) -> io::Result<Vec<String>>;
+
+    fn is_file(&self, path: &Path) -> bool;
+
+    fn is_dir(&self, path: &Path) -> bool;
+
+    // the size of a file, in bytes; by default, that of its contents
+    fn size(&self, path: &Path) -> io::Result<u64> {
+        Ok(self.read(path)?.len() as u64)
+    }
+
+    // a path naming a file the same way however it is reached, so
+    // that a file is recognized when read twice; by default, the path
+    // as given
+    fn canonicalize(&self, path: &Path) -> io::Result<PathBuf> {
+        Ok(path.to_path_buf())
+    }
+}
+
+// files on disk, by std::fs
+#[cfg(feature = "native")]
+pub struct DiskFiles;
+
+#[cfg(feature = "native")]
+impl FileProvider for DiskFiles {
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        std::fs::read(path)
+    }
+
+    fn read_dir(&self, dir: &Path) -> io::Result<Vec<String>> {
+        std::fs::read_dir(dir)?
+            .map(|entry| Ok(entry?.file_name().to_string_lossy().to_string()))
+            .collect()
+    }
+
+    fn is_file(&self, path: &Path) -> bool {
+        path.is_file()
+    }
+
+    fn is_dir(&self, path: &Path) -> bool {
+        path.is_dir()
+    }
+
+    fn size(&self, path: &Path) -> io::Result<u64> {
+        Ok(std::fs::metadata(path)?.len())
+    }
+
+    fn canonicalize(&self, path: &Path) -> io::Result<PathBuf> {
+        std::fs::canonicalize(path)
+    }
+}
+
+// Files held in memory, e.g. as supplied by a web page.  Paths are
+// relative to the data directory, with "/" separators; "." and ".."
+// components are resolved, and a leading separator is ignored.
+#[derive(Clone, Debug, Default)]
+pub struct MemoryFiles {
+    files: BTreeMap<String, Vec<u8>>,
+}
+
+// the key of a path: its normal components, joined by "/"
+fn memory_key(path: &Path) -> String {
+    let mut parts: Vec<String> = Vec::new();
+    for comp in path.components() {
+        match comp {
+            Component::Normal(name) => parts.push(name.to_string_lossy().to_string()),
+            Component::ParentDir => {
+                parts.pop();
+            }
+            _ => {}
+        }
+    }
+    parts.join("/")
+}
+
+impl MemoryFiles {
+    pub fn new() -> MemoryFiles {
+        MemoryFiles::default()
+    }
+
+    pub fn insert(&mut self, path: &str, data: Vec<u8>) {
+        let path = path.replace('\\', "/");
+        self.files.insert(memory_key(Path::new(&path)), data);
+    }
+
+    // the paths of all files, in order
+    pub fn paths(&self) -> impl Iterator<Item = &str> {
+        self.files.keys().map(|k| k.as_str())
+    }
+
+    // the paths below a directory, less the directory
+    fn below<'a>(&'a self, dir: &Path) -> impl Iterator<Item = &'a str> {
+        let mut prefix = memory_key(dir);
+        if !prefix.is_empty() {
+            prefix.push('/');
+        }
+        self.files
+            .keys()
+            .filter_map(move |k| k.strip_prefix(prefix.as_str()))
+    }
+}
+
+impl FileProvider for MemoryFiles {
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        self.files.get(&memory_key(path)).cloned().ok_or_else(|| {
+            Error::new(
+                ErrorKind::NotFound,
+                format!("{}: not supplied", path.display()),
+            )
+        })
+    }
+
+    fn read_dir(&self, dir: &Path) -> io::Result<Vec<String>> {
+        if !self.is_dir(dir) {
+            return Err(Error::new(
+                ErrorKind::NotFound,
+                format!("{}: no such directory", dir.display()),
+            ));
+        }
+        let names: BTreeSet<&str> = self
+            .below(dir)
+            .map(|rest| rest.split('/').next().unwrap_or(rest))
+            .collect();
+        Ok(names.into_iter().map(String::from).collect())
+    }
+
+    fn is_file(&self, path: &Path) -> bool {
+        self.files.contains_key(&memory_key(path))
+    }
+
+    fn is_dir(&self, path: &Path) -> bool {
+        self.below(path).next().is_some()
+    }
+
+    fn canonicalize(&self, path: &Path) -> io::Result<PathBuf> {
+        Ok(PathBuf::from(memory_key(path)))
+    }
+}
+
+// Files on disk, and within ZIP archives on disk: a path through an
+// archive, e.g. "data.zip/mybook/mybook.pcc", names the file within
+// it, and the archive itself is a directory.  An archive is read
+// whole, into memory, when first reached.
+#[cfg(feature = "native")]
+#[derive(Default)]
+pub struct ZipFiles {
+    archives: Mutex<HashMap<PathBuf, Arc<MemoryFiles>>>,
+}
+
+#[cfg(feature = "native")]
+impl ZipFiles {
+    pub fn new() -> ZipFiles {
+        ZipFiles::default()
+    }
+
+    // the archive through which a path passes, if any, as (archive
+    // path, archive contents, path within the archive)
+    fn locate(&self, path: &Path) -> io::Result<Option<(PathBuf, Arc<MemoryFiles>, PathBuf)>> {
+        for archive in path.ancestors() {
+            let is_zip = archive
+                .extension()
+                .is_some_and(|ext| ext.eq_ignore_ascii_case("zip"));
+            if is_zip && archive.is_file() {
+                let inner = path.strip_prefix(archive).expect("path within archive");
+                let contents = self.open(archive)?;
+                return Ok(Some((archive.to_path_buf(), contents, inner.to_path_buf())));
+            }
+        }
+        Ok(None)
+    }
+
+    fn open(&self, archive: &Path) -> io::Result<Arc<MemoryFiles>> {
+        let mut archives = self.archives.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(contents) = archives.get(archive) {
+            return Ok(contents.clone());
+        }
+
+        let file = BufReader::new(std::fs::File::open(archive)?);
+        let mut zip = zip::ZipArchive::new(file).map_err(|e| {
+            Error::new(
+                ErrorKind::InvalidData,
+                format!("{}: {}", archive.display(), e),
+            )
+        })?;
+        let mut contents = MemoryFiles::new();
+        for i in 0..zip.len() {
+            let mut entry = zip.by_index(i).map_err(Error::other)?;
+            if entry.is_dir() {
+                continue;
+            }
+            let mut data = Vec::with_capacity(entry.size() as usize);
+            entry.read_to_end(&mut data)?;
+            contents.insert(entry.name(), data);
+        }
+        tracing::debug!("{}: {} files", archive.display(), zip.len());
+
+        let contents = Arc::new(contents);
+        archives.insert(archive.to_path_buf(), contents.clone());
+        Ok(contents)
+    }
+}
+
+#[cfg(feature = "native")]
+impl FileProvider for ZipFiles {
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        match self.locate(path)? {
+            Some((_, contents, inner)) => contents.read(&inner),
+            None => DiskFiles.read(path),
+        }
+    }
+
+    fn read_dir(&self, dir: &Path) -> io::Result<Vec<String>> {
+        match self.locate(dir)? {
+            Some((_, contents, inner)) => contents.read_dir(&inner),
+            None => DiskFiles.read_dir(dir),
+        }
+    }
+
+    fn is_file(&self, path: &Path) -> bool {
+        match self.locate(path) {
+            Ok(Some((_, contents, inner))) => contents.is_file(&inner),
+            Ok(None) => DiskFiles.is_file(path),
+            Err(_) => false,
+        }
+    }
+
+    fn is_dir(&self, path: &Path) -> bool {
+        match self.locate(path) {
+            Ok(Some((_, contents, inner))) => contents.is_dir(&inner),
+            Ok(None) => DiskFiles.is_dir(path),
+            Err(_) => false,
+        }
+    }
+
+    fn size(&self, path: &Path) -> io::Result<u64> {
+        match self.locate(path)? {
+            Some((_, contents, inner)) => contents.size(&inner),
+            None => DiskFiles.size(path),
+        }
+    }
+
+    fn canonicalize(&self, path: &Path) -> io::Result<PathBuf> {
+        match self.locate(path)? {
+            Some((archive, contents, inner)) => {
+                Ok(std::fs::canonicalize(archive)?.join(contents.canonicalize(&inner)?))
+            }
+            None => DiskFiles.canonicalize(path),
+        }
+    }
+}
+
+static PROVIDER: RwLock<Option<Arc<dyn FileProvider>>> = RwLock::new(None);
+
+// Install the provider of files for all loads that follow.  Until one
+// is installed, files are read from disk and ZIP archives, or, in
+// builds without the "native" feature, none exist.
+pub fn set_provider(provider: Arc<dyn FileProvider>) {
+    *PROVIDER.write().unwrap_or_else(|e| e.into_inner()) = Some(provider);
+}
+
+// the provider of files until one is installed, kept so that archives
+// are read once
+fn default_provider() -> Arc<dyn FileProvider> {
+    static DEFAULT: OnceLock<Arc<dyn FileProvider>> = OnceLock::new();
+    DEFAULT
+        .get_or_init(|| {
+            #[cfg(feature = "native")]
+            let provider = Arc::new(ZipFiles::new());
+            #[cfg(not(feature = "native"))]
+            let provider = Arc::new(MemoryFiles::new());
+            provider
+        })
+        .clone()
+}
+
+// the installed provider of files
+pub fn provider() -> Arc<dyn FileProvider> {
+    let installed = PROVIDER.read().unwrap_or_else(|e| e.into_inner());
+    match &*installed {
+        Some(provider) => provider.clone(),
+        None => default_provider(),
+    }
+}
+
+pub fn read<P: AsRef<Path>>(path: P) -> io::Result<Vec<u8>> {
+    provider().read(path.as_ref())
+}
+
+pub fn read_to_string<P: AsRef<Path>>(path: P) -> io::Result<String> {
+    String::from_utf8(read(path)?).map_err(|e| Error::new(ErrorKind::InvalidData, e))
+}
+
+pub fn read_dir<P: AsRef<Path>>(dir: P) -> io::Result<Vec<String>> {
+    provider().read_dir(dir.as_ref())
+}
+
+pub fn is_file<P: AsRef<Path>>(path: P) -> bool {
+    provider().is_file(path.as_ref())
+}
+
+pub fn is_dir<P: AsRef<Path>>(path: P) -> bool {
+    provider().is_dir(path.as_ref())
+}
+
+pub fn exists<P: AsRef<Path>>(path: P) -> bool {
+    let provider = provider();
+    provider.is_file(path.as_ref()) || provider.is_dir(path.as_ref())
+}
+
+pub fn size<P: AsRef<Path>>(path: P) -> io::Result<u64> {
+    provider().size(path.as_ref())
+}
+
+pub fn canonicalize<P: AsRef<Path>>(path: P) -> io::Result<PathBuf> {
+    provider().canonicalize(path.as_ref())
+}
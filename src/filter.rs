@@ -0,0 +1,62 @@
+//
+// filter.rs -- selection of elements by tag value, e.g. TYPE=Wondrous
+//
+// Copyright (c) 2024 Jeff Garzik
+//
+// This file is part of the pcgtoolssoftware project covered under
+// the MIT License.  For the full license text, please see the LICENSE
+// file in the root directory of this project.
+// SPDX-License-Identifier: MIT
+
+use crate::pcc::PccElem;
+use std::str::FromStr;
+
+// "TAG=value": elements with a value of TAG equal to value, ignoring
+// case.  Values of multi-valued tags are compared individually, so
+// TYPE=Wondrous selects TYPE:Magic.Wondrous.
+#[derive(Clone, Debug)]
+pub struct ElemFilter {
+    // None for any SOURCExxx tag
+    tag: Option<String>,
+    value: String,
+}
+
+impl ElemFilter {
+    // a source filter: "SOURCESHORT=CRB", or "CRB" for any SOURCExxx tag
+    pub fn source(s: &str) -> Result<ElemFilter, String> {
+        match s.split_once('=') {
+            Some((tag, _)) if !tag.to_ascii_uppercase().starts_with("SOURCE") => {
+                Err(format!("{} is not a SOURCE tag", tag))
+            }
+            Some(_) => s.parse(),
+            None => Ok(ElemFilter {
+                tag: None,
+                value: s.to_string(),
+            }),
+        }
+    }
+
+    pub fn matches(&self, elem: &PccElem) -> bool {
+        let eq = |v: &str| v.trim().eq_ignore_ascii_case(&self.value);
+        match &self.tag {
+            Some(tag) => elem.values(tag).into_iter().any(eq),
+            None => elem
+                .attribs()
+                .any(|(k, v)| k.starts_with("SOURCE") && eq(v)),
+        }
+    }
+}
+
+impl FromStr for ElemFilter {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<ElemFilter, String> {
+        match s.split_once('=') {
+            Some((tag, value)) if !tag.is_empty() => Ok(ElemFilter {
+                tag: Some(tag.trim().to_ascii_uppercase()),
+                value: value.trim().to_string(),
+            }),
+            _ => Err(format!("filter {} is not TAG=value", s)),
+        }
+    }
+}
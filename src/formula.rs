@@ -0,0 +1,289 @@
+//
+// formula.rs -- evaluation of PCGen formulas, e.g. "(PLUS*PLUS)*2000"
+//
+// Copyright (c) 2024 Jeff Garzik
+//
+// This file is part of the pcgtoolssoftware project covered under
+// the MIT License.  For the full license text, please see the LICENSE
+// file in the root directory of this project.
+// SPDX-License-Identifier: MIT
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Num(f64),
+    Ident(String),
+    Op(&'static str),
+    LParen,
+    RParen,
+    Comma,
+}
+
+// operators, longest first so that "<=" is not read as "<"
+const OPERATORS: &[&str] = &[
+    "<=", ">=", "==", "!=", "&&", "||", "+", "-", "*", "/", "^", "<", ">", "!",
+];
+
+fn is_ident_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_' || c == '.'
+}
+
+fn tokenize(formula: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut rest = formula.trim_start();
+
+    while let Some(c) = rest.chars().next() {
+        if c.is_ascii_digit() || (c == '.' && rest[1..].starts_with(|d: char| d.is_ascii_digit())) {
+            let len = rest
+                .find(|c: char| !c.is_ascii_digit() && c != '.')
+                .unwrap_or(rest.len());
+            let num = rest[..len]
+                .parse()
+                .map_err(|_| format!("invalid number {}", &rest[..len]))?;
+            tokens.push(Token::Num(num));
+            rest = &rest[len..];
+        } else if c.is_ascii_alphabetic() || c == '_' {
+            let mut len = rest.find(|c| !is_ident_char(c)).unwrap_or(rest.len());
+
+            // old-style variables name their subject, e.g. "CL=Monk"
+            if rest[len..].starts_with('=') && !rest[len..].starts_with("==") {
+                len += 1;
+                len += rest[len..]
+                    .find(|c| !is_ident_char(c))
+                    .unwrap_or(rest.len() - len);
+            }
+            tokens.push(Token::Ident(rest[..len].to_string()));
+            rest = &rest[len..];
+        } else if c == '(' {
+            tokens.push(Token::LParen);
+            rest = &rest[1..];
+        } else if c == ')' {
+            tokens.push(Token::RParen);
+            rest = &rest[1..];
+        } else if c == ',' {
+            tokens.push(Token::Comma);
+            rest = &rest[1..];
+        } else if let Some(op) = OPERATORS.iter().find(|op| rest.starts_with(*op)) {
+            tokens.push(Token::Op(op));
+            rest = &rest[op.len()..];
+        } else {
+            return Err(format!("unexpected '{}' in formula", c));
+        }
+        rest = rest.trim_start();
+    }
+
+    Ok(tokens)
+}
+
+fn truth(v: bool) -> f64 {
+    if v {
+        1.0
+    } else {
+        0.0
+    }
+}
+
+// recursive descent evaluator, lowest precedence first:
+// || && comparison +- */ unary ^ primary
+struct Parser<'a, F: Fn(&str) -> Option<f64>> {
+    tokens: Vec<Token>,
+    pos: usize,
+    vars: &'a F,
+}
+
+impl<F: Fn(&str) -> Option<f64>> Parser<'_, F> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let tok = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        tok
+    }
+
+    fn eat_op(&mut self, ops: &[&str]) -> Option<&'static str> {
+        match self.peek() {
+            Some(Token::Op(op)) if ops.contains(op) => {
+                let op = *op;
+                self.pos += 1;
+                Some(op)
+            }
+            _ => None,
+        }
+    }
+
+    fn or(&mut self) -> Result<f64, String> {
+        let mut val = self.and()?;
+        while self.eat_op(&["||"]).is_some() {
+            let rhs = self.and()?;
+            val = truth(val != 0.0 || rhs != 0.0);
+        }
+        Ok(val)
+    }
+
+    fn and(&mut self) -> Result<f64, String> {
+        let mut val = self.comparison()?;
+        while self.eat_op(&["&&"]).is_some() {
+            let rhs = self.comparison()?;
+            val = truth(val != 0.0 && rhs != 0.0);
+        }
+        Ok(val)
+    }
+
+    fn comparison(&mut self) -> Result<f64, String> {
+        let lhs = self.additive()?;
+        let Some(op) = self.eat_op(&["<", ">", "<=", ">=", "==", "!="]) else {
+            return Ok(lhs);
+        };
+        let rhs = self.additive()?;
+        Ok(truth(match op {
+            "<" => lhs < rhs,
+            ">" => lhs > rhs,
+            "<=" => lhs <= rhs,
+            ">=" => lhs >= rhs,
+            "==" => lhs == rhs,
+            _ => lhs != rhs,
+        }))
+    }
+
+    fn additive(&mut self) -> Result<f64, String> {
+        let mut val = self.multiplicative()?;
+        while let Some(op) = self.eat_op(&["+", "-"]) {
+            let rhs = self.multiplicative()?;
+            val = if op == "+" { val + rhs } else { val - rhs };
+        }
+        Ok(val)
+    }
+
+    fn multiplicative(&mut self) -> Result<f64, String> {
+        let mut val = self.unary()?;
+        while let Some(op) = self.eat_op(&["*", "/"]) {
+            let rhs = self.unary()?;
+            val = if op == "*" {
+                val * rhs
+            } else if rhs == 0.0 {
+                return Err(String::from("division by zero"));
+            } else {
+                val / rhs
+            };
+        }
+        Ok(val)
+    }
+
+    fn unary(&mut self) -> Result<f64, String> {
+        match self.eat_op(&["-", "+", "!"]) {
+            Some("-") => Ok(-self.unary()?),
+            Some("!") => Ok(truth(self.unary()? == 0.0)),
+            Some(_) => self.unary(),
+            None => self.power(),
+        }
+    }
+
+    fn power(&mut self) -> Result<f64, String> {
+        let base = self.primary()?;
+        if self.eat_op(&["^"]).is_some() {
+            return Ok(base.powf(self.unary()?));
+        }
+        Ok(base)
+    }
+
+    fn args(&mut self) -> Result<Vec<f64>, String> {
+        let mut args = Vec::new();
+        if self.peek() == Some(&Token::RParen) {
+            self.pos += 1;
+            return Ok(args);
+        }
+        loop {
+            args.push(self.or()?);
+            match self.next() {
+                Some(Token::Comma) => continue,
+                Some(Token::RParen) => return Ok(args),
+                _ => return Err(String::from("expected ',' or ')'")),
+            }
+        }
+    }
+
+    fn call(&mut self, name: &str) -> Result<f64, String> {
+        let args = self.args()?;
+        let arg = |i: usize| {
+            args.get(i)
+                .copied()
+                .ok_or_else(|| format!("{}: missing argument", name))
+        };
+        match name.to_ascii_lowercase().as_str() {
+            "floor" => Ok(arg(0)?.floor()),
+            "ceil" => Ok(arg(0)?.ceil()),
+            "round" => Ok(arg(0)?.round()),
+            "abs" => Ok(arg(0)?.abs()),
+            "min" => args
+                .iter()
+                .copied()
+                .reduce(f64::min)
+                .ok_or_else(|| String::from("min: missing argument")),
+            "max" => args
+                .iter()
+                .copied()
+                .reduce(f64::max)
+                .ok_or_else(|| String::from("max: missing argument")),
+            "if" => Ok(if arg(0)? != 0.0 { arg(1)? } else { arg(2)? }),
+            _ => Err(format!("unknown function {}", name)),
+        }
+    }
+
+    fn primary(&mut self) -> Result<f64, String> {
+        match self.next() {
+            Some(Token::Num(n)) => Ok(n),
+            Some(Token::Ident(name)) => {
+                if self.peek() == Some(&Token::LParen) {
+                    self.pos += 1;
+                    return self.call(&name);
+                }
+                (self.vars)(&name).ok_or_else(|| format!("unknown variable {}", name))
+            }
+            Some(Token::LParen) => {
+                let val = self.or()?;
+                match self.next() {
+                    Some(Token::RParen) => Ok(val),
+                    _ => Err(String::from("expected ')'")),
+                }
+            }
+            Some(tok) => Err(format!("unexpected {:?} in formula", tok)),
+            None => Err(String::from("unexpected end of formula")),
+        }
+    }
+}
+
+// Evaluate a formula.  vars supplies the value of each variable named
+// by the formula; an unknown variable is an error.  Comparisons and
+// logical operators yield 1 or 0, and if(cond,a,b), min, max, floor,
+// ceil, round and abs are supported.
+pub fn eval<F: Fn(&str) -> Option<f64>>(formula: &str, vars: &F) -> Result<f64, String> {
+    let mut parser = Parser {
+        tokens: tokenize(formula)?,
+        pos: 0,
+        vars,
+    };
+    let val = parser.or()?;
+    if parser.pos < parser.tokens.len() {
+        return Err(format!(
+            "unexpected {:?} in formula",
+            parser.tokens[parser.pos]
+        ));
+    }
+    Ok(val)
+}
+
+// names of the variables a formula refers to
+pub fn variables(formula: &str) -> Result<Vec<String>, String> {
+    let tokens = tokenize(formula)?;
+    let mut names = Vec::new();
+    for (i, tok) in tokens.iter().enumerate() {
+        if let Token::Ident(name) = tok {
+            let is_call = tokens.get(i + 1) == Some(&Token::LParen);
+            if !is_call && !names.contains(name) {
+                names.push(name.clone());
+            }
+        }
+    }
+    Ok(names)
+}
@@ -0,0 +1,189 @@
+//
+// gamemode.rs -- PCgen game mode system files
+//
+// Copyright (c) 2024 Jeff Garzik
+//
+// This file is part of the pcgtoolssoftware project covered under
+// the MIT License.  For the full license text, please see the LICENSE
+// file in the root directory of this project.
+// SPDX-License-Identifier: MIT
+
+use crate::files;
+use crate::text::{self, Encoding};
+use serde::{Deserialize, Serialize};
+use std::{
+    io,
+    io::Error,
+    path::{Path, PathBuf},
+};
+
+// a single game mode record, such as a stat, check or alignment
+#[derive(Clone, Serialize, Deserialize)]
+pub struct GameModeEntry {
+    pub name: String,
+    pub attribs: Vec<(String, String)>,
+}
+
+impl GameModeEntry {
+    pub fn get(&self, tag: &str) -> Option<&str> {
+        self.attribs
+            .iter()
+            .find(|(k, _)| k == tag)
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+// game mode definition, from system/gameModes/<mode>/*.lst
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct GameMode {
+    pub name: String,
+    pub dir: String,
+    pub misc: Vec<(String, String)>,
+    pub stats: Vec<GameModeEntry>,
+    pub checks: Vec<GameModeEntry>,
+    pub alignments: Vec<GameModeEntry>,
+    pub load: Vec<(String, String)>,
+    pub other: Vec<(String, GameModeEntry)>,
+}
+
+// split a tab-separated line into tag:value pairs
+fn line_attribs(line: &str) -> Vec<(String, String)> {
+    line.split('\t')
+        .filter(|token| !token.trim().is_empty())
+        .map(|token| match token.split_once(':') {
+            None => (token.to_string(), String::new()),
+            Some((k, v)) => (k.to_string(), v.to_string()),
+        })
+        .collect()
+}
+
+// read the non-comment, non-empty lines of a system file.  a missing
+// file is not an error; not every game mode provides every file.
+fn read_lines(path: &Path) -> io::Result<Vec<String>> {
+    if !files::is_file(path) {
+        tracing::debug!("GameMode: {} not present", path.display());
+        return Ok(Vec::new());
+    }
+
+    let mut lines = Vec::new();
+    for line in text::read_lines(path, Encoding::Auto)? {
+        // comments and empty lines
+        let ch = line.chars().next();
+        if ch.is_none() || ch == Some('#') {
+            continue;
+        }
+
+        lines.push(line);
+    }
+
+    Ok(lines)
+}
+
+impl GameMode {
+    // locate the directory for a game mode, by directory name or by
+    // the GAMEMODE tag in its miscinfo.lst
+    pub fn find(systemdir: &str, mode: &str) -> io::Result<PathBuf> {
+        let modes_dir = Path::new(systemdir).join("gameModes");
+        for entry in files::read_dir(&modes_dir)? {
+            let path = modes_dir.join(entry);
+            if !files::is_dir(&path) {
+                continue;
+            }
+
+            let dir_matches = path
+                .file_name()
+                .and_then(|n| n.to_str())
+                .is_some_and(|n| n.eq_ignore_ascii_case(mode));
+            if dir_matches {
+                return Ok(path);
+            }
+
+            let tag_matches = read_lines(&path.join("miscinfo.lst"))?
+                .iter()
+                .flat_map(|line| line_attribs(line))
+                .any(|(k, v)| k == "GAMEMODE" && v.eq_ignore_ascii_case(mode));
+            if tag_matches {
+                return Ok(path);
+            }
+        }
+
+        Err(Error::other(format!(
+            "Game mode {} not found in {}",
+            mode,
+            modes_dir.display()
+        )))
+    }
+
+    // locate and parse a game mode's system files
+    pub fn load(systemdir: &str, mode: &str) -> io::Result<GameMode> {
+        let dir = GameMode::find(systemdir, mode)?;
+
+        tracing::debug!("GameMode.load({})", dir.display());
+
+        let mut gm = GameMode {
+            name: String::from(mode),
+            dir: dir.to_string_lossy().to_string(),
+            ..Default::default()
+        };
+
+        // miscinfo: one or more global tags per line
+        for line in read_lines(&dir.join("miscinfo.lst"))? {
+            for (k, v) in line_attribs(&line) {
+                if k == "GAMEMODE" {
+                    gm.name = v.clone();
+                }
+                gm.misc.push((k, v));
+            }
+        }
+
+        // statsandchecks: one record per line, keyed by its first tag
+        for line in read_lines(&dir.join("statsandchecks.lst"))? {
+            let mut attribs = line_attribs(&line);
+            if attribs.is_empty() {
+                continue;
+            }
+            let (kind, name) = attribs.remove(0);
+            let entry = GameModeEntry { name, attribs };
+            match kind.as_str() {
+                "STATNAME" => gm.stats.push(entry),
+                "CHECKNAME" => gm.checks.push(entry),
+                "ALIGNMENTNAME" => gm.alignments.push(entry),
+                _ => gm.other.push((kind, entry)),
+            }
+        }
+
+        // load: encumbrance and carrying capacity constants
+        for line in read_lines(&dir.join("load.lst"))? {
+            gm.load.extend(line_attribs(&line));
+        }
+
+        Ok(gm)
+    }
+
+    // first value of a miscinfo tag
+    pub fn misc(&self, tag: &str) -> Option<&str> {
+        self.misc
+            .iter()
+            .find(|(k, _)| k == tag)
+            .map(|(_, v)| v.as_str())
+    }
+
+    // currency unit abbreviation, e.g. "gp"
+    pub fn currency(&self) -> Option<&str> {
+        self.misc("CURRENCYUNITABBREV")
+    }
+
+    // lookup a stat by name or abbreviation
+    pub fn stat(&self, name: &str) -> Option<&GameModeEntry> {
+        self.stats.iter().find(|s| {
+            s.name.eq_ignore_ascii_case(name) || s.get("ABB").is_some_and(|abb| abb == name)
+        })
+    }
+
+    // lookup a save/check by name
+    pub fn check(&self, name: &str) -> Option<&GameModeEntry> {
+        self.checks
+            .iter()
+            .find(|c| c.name.eq_ignore_ascii_case(name))
+    }
+}
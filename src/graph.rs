@@ -0,0 +1,496 @@
+//
+// graph.rs -- graphs of the files of loaded data, and of campaigns
+//
+// Copyright (c) 2024 Jeff Garzik
+//
+// This file is part of the pcgtoolssoftware project covered under
+// the MIT License.  For the full license text, please see the LICENSE
+// file in the root directory of this project.
+// SPDX-License-Identifier: MIT
+
+use crate::campaign::CampaignInfo;
+use crate::diag::Diagnostic;
+use crate::files;
+use crate::paths;
+use crate::pcc::Pcc;
+use std::{
+    collections::{BTreeSet, HashMap},
+    fmt::Write,
+};
+
+// a PCC or LST file read by a load
+pub struct FileNode {
+    pub path: String,
+    // "PCC", or the list tag of an LST file
+    pub kind: String,
+    // none if the file could not be examined
+    pub bytes: Option<u64>,
+    // elements first defined in the file
+    pub elements: usize,
+}
+
+// The files of a load, and the references between them: each PCC
+// file to the PCC and LST files it references.  A file referenced by
+// several PCC files is one node, with an edge from each.
+pub struct IncludeGraph {
+    pub nodes: Vec<FileNode>,
+    // (from, to), as indexes of nodes, in load order
+    pub edges: Vec<(usize, usize)>,
+    // nodes loaded directly, rather than referenced
+    pub roots: Vec<usize>,
+    datadirs: Vec<String>,
+}
+
+impl IncludeGraph {
+    pub fn new(pcc: &Pcc) -> IncludeGraph {
+        let mut elements: HashMap<&str, usize> = HashMap::new();
+        for (_, lst) in pcc.lists() {
+            for (_, elem) in lst.iter() {
+                *elements.entry(elem.source()).or_default() += 1;
+            }
+        }
+
+        let mut graph = IncludeGraph {
+            nodes: Vec::new(),
+            edges: Vec::new(),
+            roots: Vec::new(),
+            datadirs: pcc.config().datadirs.clone(),
+        };
+        let mut index: HashMap<&str, usize> = HashMap::new();
+        for file in pcc.files() {
+            let node = *index.entry(file.path.as_str()).or_insert_with(|| {
+                graph.nodes.push(FileNode {
+                    path: file.path.clone(),
+                    kind: file.kind.clone(),
+                    bytes: files::size(&file.path).ok(),
+                    elements: elements.get(file.path.as_str()).copied().unwrap_or(0),
+                });
+                graph.nodes.len() - 1
+            });
+
+            // a PCC file is always read before the files it references
+            let edge = file.from.as_deref().and_then(|from| index.get(from));
+            match edge {
+                Some(from) if !graph.edges.contains(&(*from, node)) => {
+                    graph.edges.push((*from, node));
+                }
+                Some(_) => {}
+                None if !graph.roots.contains(&node) => graph.roots.push(node),
+                None => {}
+            }
+        }
+        graph
+    }
+
+    // a path relative to the data directory, if within one
+    fn display_path<'a>(&self, path: &'a str) -> &'a str {
+        paths::within_datadirs(&self.datadirs, path)
+    }
+
+    // the size and element count of a node, e.g. "3.4 KiB, 12 elements"
+    fn annotation(&self, node: &FileNode) -> String {
+        let mut notes = Vec::new();
+        if let Some(bytes) = node.bytes {
+            notes.push(human_size(bytes));
+        }
+        if node.kind != "PCC" {
+            notes.push(match node.elements {
+                1 => String::from("1 element"),
+                n => format!("{} elements", n),
+            });
+        }
+        notes.join(", ")
+    }
+
+    // nodes referenced by a node: LST files, then PCC files
+    fn children(&self, node: usize) -> Vec<usize> {
+        let mut children: Vec<usize> = self
+            .edges
+            .iter()
+            .filter(|(from, _)| *from == node)
+            .map(|(_, to)| *to)
+            .collect();
+        children.sort_by_key(|child| self.nodes[*child].kind == "PCC");
+        children
+    }
+
+    // Lay out the graph as an indented tree, each file under those
+    // referencing it, e.g.
+    //
+    //   mybook/mybook.pcc (312 B)
+    //     SPELL mybook/spells.lst (1.2 KiB, 12 elements)
+    pub fn to_text(&self) -> String {
+        let mut out = String::new();
+        for root in &self.roots {
+            self.write_tree(&mut out, *root, 0, &mut Vec::new());
+        }
+        out
+    }
+
+    fn write_tree(&self, out: &mut String, node: usize, depth: usize, path: &mut Vec<usize>) {
+        let file = &self.nodes[node];
+        let kind = match file.kind.as_str() {
+            "PCC" => String::new(),
+            kind => format!("{} ", kind),
+        };
+        let _ = writeln!(
+            out,
+            "{}{}{} ({})",
+            "  ".repeat(depth),
+            kind,
+            self.display_path(&file.path),
+            self.annotation(file)
+        );
+
+        path.push(node);
+        for child in self.children(node) {
+            if !path.contains(&child) {
+                self.write_tree(out, child, depth + 1, path);
+            }
+        }
+        path.pop();
+    }
+
+    // Write the graph in the Graphviz DOT language: PCC files as
+    // folders, LST files as notes labeled with their list, size and
+    // element count.
+    pub fn to_dot(&self) -> String {
+        let mut out = String::from("digraph includes {\n");
+        out.push_str("  rankdir=LR;\n");
+        out.push_str("  node [fontname=\"Helvetica\", fontsize=10];\n");
+        for (i, node) in self.nodes.iter().enumerate() {
+            let (shape, label) = match node.kind.as_str() {
+                "PCC" => ("folder", self.display_path(&node.path).to_string()),
+                kind => (
+                    "note",
+                    format!("{}: {}", kind, self.display_path(&node.path)),
+                ),
+            };
+            let _ = writeln!(
+                out,
+                "  n{} [shape={}, label=\"{}\\n{}\", tooltip=\"{}\"];",
+                i,
+                shape,
+                dot_escape(&label),
+                dot_escape(&self.annotation(node)),
+                dot_escape(&node.path)
+            );
+        }
+        for (from, to) in &self.edges {
+            let _ = writeln!(out, "  n{} -> n{};", from, to);
+        }
+        out.push_str("}\n");
+        out
+    }
+}
+
+// a campaign required by another, by PRECAMPAIGN
+pub struct CampaignEdge {
+    pub from: usize,
+    // the campaign named, or none if no campaign has the name
+    pub to: Option<usize>,
+    pub name: String,
+    // the line of the PRECAMPAIGN in the requiring PCC file
+    pub line: usize,
+    // whether the requirement is circular, or out of RANK order
+    pub flagged: bool,
+}
+
+// The campaigns of a data directory, and the campaigns each requires
+// by PRECAMPAIGN, with the problems of those requirements: campaigns
+// not found, circular requirements, and required campaigns of higher
+// RANK than those requiring them, which load after them.
+pub struct CampaignGraph {
+    // in load order, by RANK, then path
+    pub campaigns: Vec<CampaignInfo>,
+    pub edges: Vec<CampaignEdge>,
+    pub problems: Vec<Diagnostic>,
+    datadirs: Vec<String>,
+}
+
+impl CampaignGraph {
+    // Build the graph of the campaigns given, as found in the data
+    // directories, or, if roots are given, as indexes of campaigns, of
+    // those campaigns and all they require, directly or not.
+    pub fn new(
+        datadirs: &[String],
+        campaigns: Vec<CampaignInfo>,
+        roots: &[usize],
+    ) -> CampaignGraph {
+        // the requirements of each campaign, as (line, name, campaign)
+        let requires: Vec<Vec<(usize, &str, Option<usize>)>> = campaigns
+            .iter()
+            .map(|c| {
+                let mut reqs = Vec::new();
+                for req in &c.requires {
+                    for name in &req.names {
+                        let mut found = campaigns
+                            .iter()
+                            .enumerate()
+                            .filter(|(_, c)| c.is_named(name));
+                        match found.next() {
+                            None => reqs.push((req.line, name.as_str(), None)),
+                            Some((first, _)) => {
+                                reqs.push((req.line, name.as_str(), Some(first)));
+                                reqs.extend(found.map(|(i, _)| (req.line, name.as_str(), Some(i))));
+                            }
+                        }
+                    }
+                }
+                reqs
+            })
+            .collect();
+
+        let mut scope: BTreeSet<usize> = BTreeSet::new();
+        match roots.is_empty() {
+            true => scope.extend(0..campaigns.len()),
+            false => {
+                let mut pending = roots.to_vec();
+                while let Some(i) = pending.pop() {
+                    if scope.insert(i) {
+                        pending.extend(requires[i].iter().filter_map(|(_, _, to)| *to));
+                    }
+                }
+            }
+        }
+
+        // campaigns load in RANK order, unranked last, then path order
+        let mut order: Vec<usize> = scope.into_iter().collect();
+        order.sort_by_key(|i| (campaigns[*i].rank.is_none(), campaigns[*i].rank));
+        let index: HashMap<usize, usize> = order.iter().enumerate().map(|(n, i)| (*i, n)).collect();
+
+        let mut edges = Vec::new();
+        for (from, i) in order.iter().enumerate() {
+            for (line, name, to) in &requires[*i] {
+                edges.push(CampaignEdge {
+                    from,
+                    to: to.map(|to| index[&to]),
+                    name: name.to_string(),
+                    line: *line,
+                    flagged: false,
+                });
+            }
+        }
+
+        let mut slots: Vec<Option<CampaignInfo>> = campaigns.into_iter().map(Some).collect();
+        let campaigns = order
+            .iter()
+            .map(|i| slots[*i].take().expect("campaign"))
+            .collect();
+        let mut graph = CampaignGraph {
+            campaigns,
+            edges,
+            problems: Vec::new(),
+            datadirs: datadirs.to_vec(),
+        };
+        graph.check_missing();
+        graph.check_cycles();
+        graph.check_ranks();
+        graph
+            .problems
+            .sort_by(|a, b| (&a.file, a.line).cmp(&(&b.file, b.line)));
+        graph
+    }
+
+    // a requirement is missing if fewer of the campaigns it names are
+    // found than it requires
+    fn check_missing(&mut self) {
+        for (i, campaign) in self.campaigns.iter().enumerate() {
+            for req in &campaign.requires {
+                let named = |name: &String| {
+                    self.edges
+                        .iter()
+                        .filter(|e| e.from == i && e.line == req.line && e.name == *name)
+                        .any(|e| e.to.is_some())
+                };
+                let missing: Vec<&str> = req
+                    .names
+                    .iter()
+                    .filter(|name| !named(name))
+                    .map(|name| name.as_str())
+                    .collect();
+                if req.names.len() - missing.len() < req.count {
+                    self.problems.push(Diagnostic::error(
+                        &campaign.path,
+                        req.line,
+                        format!("required campaign not found: {}", missing.join(", ")),
+                    ));
+                }
+            }
+        }
+    }
+
+    // report each cycle of requirements once, at the requirement
+    // closing it
+    fn check_cycles(&mut self) {
+        let mut done = vec![false; self.campaigns.len()];
+        for start in 0..self.campaigns.len() {
+            let mut path = Vec::new();
+            self.visit(start, &mut path, &mut done);
+        }
+    }
+
+    fn visit(&mut self, node: usize, path: &mut Vec<usize>, done: &mut [bool]) {
+        if done[node] {
+            return;
+        }
+        path.push(node);
+        for e in 0..self.edges.len() {
+            let edge = &self.edges[e];
+            let Some(to) = edge.to.filter(|_| edge.from == node) else {
+                continue;
+            };
+            match path.iter().position(|n| *n == to) {
+                Some(pos) => {
+                    let chain: Vec<&str> = path[pos..]
+                        .iter()
+                        .chain(std::iter::once(&to))
+                        .map(|n| self.campaigns[*n].name.as_str())
+                        .collect();
+                    self.problems.push(Diagnostic::error(
+                        &self.campaigns[node].path,
+                        edge.line,
+                        format!("circular campaign requirement: {}", chain.join(" -> ")),
+                    ));
+                    self.edges[e].flagged = true;
+                }
+                None => self.visit(to, path, done),
+            }
+        }
+        path.pop();
+        done[node] = true;
+    }
+
+    fn check_ranks(&mut self) {
+        for edge in &mut self.edges {
+            let Some(to) = edge.to else {
+                continue;
+            };
+            let (from, to) = (&self.campaigns[edge.from], &self.campaigns[to]);
+            if let (Some(rank), Some(required)) = (from.rank, to.rank) {
+                if required > rank {
+                    self.problems.push(Diagnostic::warning(
+                        &from.path,
+                        edge.line,
+                        format!(
+                            "required campaign {} has RANK {}, above this campaign's {}, so loads after it",
+                            to.name, required, rank
+                        ),
+                    ));
+                    edge.flagged = true;
+                }
+            }
+        }
+    }
+
+    fn display_path<'a>(&self, path: &'a str) -> &'a str {
+        paths::within_datadirs(&self.datadirs, path)
+    }
+
+    fn describe(&self, campaign: &CampaignInfo) -> String {
+        let rank = match campaign.rank {
+            Some(rank) => format!("RANK {}", rank),
+            None => String::from("unranked"),
+        };
+        format!(
+            "{} ({}, {})",
+            campaign.name,
+            self.display_path(&campaign.path),
+            rank
+        )
+    }
+
+    // List the campaigns in load order, each with those it requires,
+    // e.g.
+    //
+    //   Addon (addon/addon.pcc, RANK 3)
+    //     requires Core Rules (core/core.pcc, RANK 1)
+    //     requires Missing Book: not found
+    pub fn to_text(&self) -> String {
+        let mut out = String::new();
+        for (i, campaign) in self.campaigns.iter().enumerate() {
+            let _ = writeln!(out, "{}", self.describe(campaign));
+            for edge in self.edges.iter().filter(|e| e.from == i) {
+                let _ = match edge.to {
+                    Some(to) => writeln!(out, "  requires {}", self.describe(&self.campaigns[to])),
+                    None => writeln!(out, "  requires {}: not found", edge.name),
+                };
+            }
+        }
+        out
+    }
+
+    // Write the graph in the Graphviz DOT language: campaigns as boxes
+    // labeled with their RANK, campaigns not found as dashed boxes, and
+    // circular or out of order requirements in red.
+    pub fn to_dot(&self) -> String {
+        let mut out = String::from("digraph campaigns {\n");
+        out.push_str("  node [shape=box, fontname=\"Helvetica\", fontsize=10];\n");
+        for (i, campaign) in self.campaigns.iter().enumerate() {
+            let rank = match campaign.rank {
+                Some(rank) => format!("RANK {}", rank),
+                None => String::from("unranked"),
+            };
+            let _ = writeln!(
+                out,
+                "  c{} [label=\"{}\\n{}\", tooltip=\"{}\"];",
+                i,
+                dot_escape(&campaign.name),
+                rank,
+                dot_escape(&campaign.path)
+            );
+        }
+
+        let missing: BTreeSet<&str> = self
+            .edges
+            .iter()
+            .filter(|e| e.to.is_none())
+            .map(|e| e.name.as_str())
+            .collect();
+        for (i, name) in missing.iter().enumerate() {
+            let _ = writeln!(
+                out,
+                "  m{} [label=\"{}\\nnot found\", style=dashed, color=red];",
+                i,
+                dot_escape(name)
+            );
+        }
+
+        for edge in &self.edges {
+            let to = match edge.to {
+                Some(to) => format!("c{}", to),
+                None => {
+                    let m = missing.iter().position(|name| *name == edge.name);
+                    format!("m{}", m.expect("missing campaign"))
+                }
+            };
+            let style = match edge.flagged {
+                true => " [color=red]",
+                false => "",
+            };
+            let _ = writeln!(out, "  c{} -> {}{};", edge.from, to, style);
+        }
+        out.push_str("}\n");
+        out
+    }
+}
+
+// escape text for a quoted DOT string
+pub(crate) fn dot_escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+// a size in bytes, for people, e.g. "3.4 KiB"
+fn human_size(bytes: u64) -> String {
+    const UNITS: [&str; 3] = ["KiB", "MiB", "GiB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit + 1 < UNITS.len() {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", size, UNITS[unit])
+}
@@ -0,0 +1,113 @@
+//
+// install.rs -- locating an installed copy of PCGen, and its data
+//
+// Copyright (c) 2024 Jeff Garzik
+//
+// This file is part of the pcgtoolssoftware project covered under
+// the MIT License.  For the full license text, please see the LICENSE
+// file in the root directory of this project.
+// SPDX-License-Identifier: MIT
+
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+// directories in which PCGen is commonly installed, by platform,
+// beneath the environment variable named, if any, or else absolute
+fn install_dirs() -> Vec<PathBuf> {
+    let under = |var: &str, dirs: &[&str]| -> Vec<PathBuf> {
+        match env::var_os(var) {
+            Some(base) if !base.is_empty() => {
+                dirs.iter().map(|d| Path::new(&base).join(d)).collect()
+            }
+            _ => Vec::new(),
+        }
+    };
+
+    let mut dirs = Vec::new();
+    if cfg!(windows) {
+        dirs.extend(under("ProgramFiles", &["PCGen"]));
+        dirs.extend(under("ProgramFiles(x86)", &["PCGen"]));
+        dirs.extend(under("LOCALAPPDATA", &["PCGen", "Programs\\PCGen"]));
+        dirs.extend(under("USERPROFILE", &["PCGen"]));
+    } else if cfg!(target_os = "macos") {
+        dirs.extend(under(
+            "HOME",
+            &["Applications/PCGen.app/Contents/Resources", "PCGen"],
+        ));
+        dirs.push(PathBuf::from("/Applications/PCGen.app/Contents/Resources"));
+        dirs.push(PathBuf::from("/Applications/PCGen"));
+    } else {
+        dirs.extend(under("HOME", &["pcgen", "PCGen", ".local/share/pcgen"]));
+        dirs.push(PathBuf::from("/opt/pcgen"));
+        dirs.push(PathBuf::from("/usr/local/share/pcgen"));
+        dirs.push(PathBuf::from("/usr/share/pcgen"));
+    }
+    dirs
+}
+
+// The data directory of a PCGen installation: its "data" directory,
+// or, where releases are installed side by side in versioned
+// directories, e.g. PCGen/6.08.00, that of the latest.
+fn data_within(dir: &Path) -> Option<PathBuf> {
+    let data = dir.join("data");
+    if data.is_dir() {
+        return Some(data);
+    }
+
+    let mut versions: Vec<PathBuf> = fs::read_dir(dir)
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.join("data").is_dir())
+        .collect();
+    versions.sort_by_key(|path| version_key(path));
+    versions.pop().map(|path| path.join("data"))
+}
+
+// a directory name's runs of digits, for ordering "6.10.0" after
+// "6.9.1"
+fn version_key(path: &Path) -> Vec<u64> {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    name.split(|c: char| !c.is_ascii_digit())
+        .filter_map(|run| run.parse().ok())
+        .collect()
+}
+
+// Find the data directory of an installed PCGen: that of PCGEN_HOME,
+// if set, or else of the first standard install location having one.
+pub fn pcgen_datadir() -> Option<PathBuf> {
+    if let Some(home) = env::var_os("PCGEN_HOME").filter(|home| !home.is_empty()) {
+        return data_within(Path::new(&home));
+    }
+    install_dirs().iter().find_map(|dir| data_within(dir))
+}
+
+// Does a directory look like a data directory: are there PCC files
+// in it, or in its subdirectories?  Only two levels are examined, so
+// that a large tree such as a home directory is not searched.
+pub fn has_campaigns(dir: &Path) -> bool {
+    let is_pcc = |path: &Path| {
+        path.extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("pcc"))
+    };
+    let Ok(entries) = fs::read_dir(dir) else {
+        return false;
+    };
+    for entry in entries.filter_map(|entry| entry.ok()) {
+        let path = entry.path();
+        if is_pcc(&path) {
+            return true;
+        }
+        if path.is_dir() {
+            let found = fs::read_dir(&path)
+                .map(|sub| sub.filter_map(|e| e.ok()).any(|e| is_pcc(&e.path())))
+                .unwrap_or(false);
+            if found {
+                return true;
+            }
+        }
+    }
+    false
+}
@@ -0,0 +1,280 @@
+//
+// jsonschema.rs -- JSON Schema of the dump format
+//
+// Copyright (c) 2024 Jeff Garzik
+//
+// This file is part of the pcgtoolssoftware project covered under
+// the MIT License.  For the full license text, please see the LICENSE
+// file in the root directory of this project.
+// SPDX-License-Identifier: MIT
+
+use crate::compat::DataCompat;
+use crate::diag::Severity;
+use crate::pcc::{DuplicatePolicy, PccTag};
+use crate::text::Encoding;
+use serde::Serialize;
+use serde_json::{json, Value};
+
+const SCHEMA_DIALECT: &str = "https://json-schema.org/draft/2020-12/schema";
+
+// an enum of the names of unit variants, as serialized
+fn names<T: Serialize>(variants: &[T]) -> Value {
+    let names: Vec<Value> = variants
+        .iter()
+        .map(|v| serde_json::to_value(v).expect("variant name"))
+        .collect();
+    json!({ "type": "string", "enum": names })
+}
+
+fn nullable(kind: &str) -> Value {
+    json!({ "type": [kind, "null"] })
+}
+
+// an array of fixed length, of the given item schemas in order
+fn tuple(items: &[Value]) -> Value {
+    json!({
+        "type": "array",
+        "prefixItems": items,
+        "minItems": items.len(),
+        "maxItems": items.len(),
+    })
+}
+
+// an object of the given required properties, and no others
+fn object(properties: Value) -> Value {
+    let required: Vec<&String> = properties.as_object().expect("properties").keys().collect();
+    json!({
+        "type": "object",
+        "properties": properties,
+        "required": required,
+        "additionalProperties": false,
+    })
+}
+
+fn config() -> Value {
+    object(json!({
+        "datadirs": { "type": "array", "items": { "type": "string" } },
+        "gamemode": nullable("string"),
+        "systemdir": nullable("string"),
+        "vendordir": nullable("string"),
+        "homebrewdir": nullable("string"),
+        "cachedir": nullable("string"),
+        "encoding": names(&[Encoding::Auto, Encoding::Utf8, Encoding::Windows1252]),
+        "provenance": { "type": "boolean" },
+        "duplicates": names(&[
+            DuplicatePolicy::Warn,
+            DuplicatePolicy::Error,
+            DuplicatePolicy::KeepFirst,
+            DuplicatePolicy::KeepLast,
+        ]),
+        "allow_unknown_tags": { "type": "boolean" },
+        "schema": nullable("string"),
+        "migrate": { "type": "boolean" },
+        "migrations": nullable("string"),
+        "compat": names(&[DataCompat::V5_16, DataCompat::V6_06, DataCompat::V6_08]),
+    }))
+}
+
+fn provenance() -> Value {
+    object(json!({
+        "file": { "type": "string" },
+        "line": { "type": "integer", "minimum": 0 },
+    }))
+}
+
+// an element, with its attribs as [tag, value] pairs in load order.
+// name is present only for elements whose KEY differs from their name,
+// outputname only for those having an OUTPUTNAME, provenance only when
+// recorded, and split_values only for elements
+// having multi-valued tags, e.g. TYPE.
+fn elem() -> Value {
+    let provenances = json!({ "type": "array", "items": { "$ref": "#/$defs/provenance" } });
+    json!({
+        "type": "object",
+        "properties": {
+            "_ident": { "type": "string" },
+            "name": { "type": "string" },
+            "outputname": { "type": "string" },
+            "source": { "type": "string" },
+            "line": { "type": "integer", "minimum": 0 },
+            "attribs": {
+                "type": "array",
+                "items": tuple(&[json!({ "type": "string" }), json!({ "type": "string" })]),
+            },
+            "provenance": object(json!({
+                "mods": provenances,
+                "attribs": provenances,
+            })),
+            "split_values": {
+                "type": "object",
+                "additionalProperties": { "type": "array", "items": { "type": "string" } },
+            },
+        },
+        "required": ["_ident", "source", "line", "attribs"],
+        "additionalProperties": false,
+    })
+}
+
+// a list, its elements keyed by ident
+fn list() -> Value {
+    object(json!({
+        "_ident": { "type": "string" },
+        "props": {
+            "type": "object",
+            "additionalProperties": { "$ref": "#/$defs/elem" },
+        },
+    }))
+}
+
+// a value of the dictionary, tagged by its kind
+fn datum() -> Value {
+    let variant = |kind: &str, value: Value| object(json!({ kind: value }));
+    json!({
+        "oneOf": [
+            variant("Text", json!({ "type": "string" })),
+            variant("Bool", json!({ "type": "boolean" })),
+            variant("Number", json!({ "type": "integer" })),
+            variant(
+                "Date",
+                json!({ "type": "string", "pattern": "^[0-9]{4}-[0-9]{2}(-[0-9]{2})?$" })
+            ),
+            variant("List", json!({ "$ref": "#/$defs/list" })),
+        ],
+    })
+}
+
+fn gamemode() -> Value {
+    let pair = tuple(&[json!({ "type": "string" }), json!({ "type": "string" })]);
+    let pairs = json!({ "type": "array", "items": pair });
+    let entries = json!({ "type": "array", "items": { "$ref": "#/$defs/gamemode_entry" } });
+    json!({
+        "oneOf": [
+            { "type": "null" },
+            object(json!({
+                "name": { "type": "string" },
+                "dir": { "type": "string" },
+                "misc": pairs,
+                "stats": entries,
+                "checks": entries,
+                "alignments": entries,
+                "load": pairs,
+                "other": {
+                    "type": "array",
+                    "items": tuple(&[
+                        json!({ "type": "string" }),
+                        json!({ "$ref": "#/$defs/gamemode_entry" }),
+                    ]),
+                },
+            })),
+        ],
+    })
+}
+
+fn gamemode_entry() -> Value {
+    object(json!({
+        "name": { "type": "string" },
+        "attribs": {
+            "type": "array",
+            "items": tuple(&[json!({ "type": "string" }), json!({ "type": "string" })]),
+        },
+    }))
+}
+
+fn diagnostic() -> Value {
+    object(json!({
+        "severity": names(&[Severity::Warning, Severity::Error]),
+        "file": { "type": "string" },
+        "line": { "type": "integer", "minimum": 0 },
+        "message": { "type": "string" },
+    }))
+}
+
+// Return a JSON Schema of the output of `pcgtools dump`, in JSON.
+// Elements of all lists share one shape, of tag and value pairs; the
+// schema of a list's elements is at "#/$defs/elem".
+pub fn dump_schema() -> Value {
+    let pcc_tags = names(&[
+        PccTag::BioSet,
+        PccTag::Bool,
+        PccTag::Class,
+        PccTag::DataTable,
+        PccTag::Date,
+        PccTag::Dynamic,
+        PccTag::ForwardRef,
+        PccTag::Kit,
+        PccTag::LstFile,
+        PccTag::Number,
+        PccTag::Text,
+        PccTag::PccFile,
+    ]);
+    let diagnostics = json!({ "type": "array", "items": { "$ref": "#/$defs/diagnostic" } });
+
+    let mut schema = object(json!({
+        "config": { "$ref": "#/$defs/config" },
+        // campaign tags, and lists, by tag
+        "dict": {
+            "type": "object",
+            "additionalProperties": { "$ref": "#/$defs/datum" },
+        },
+        "pcc_schema": { "type": "object", "additionalProperties": pcc_tags },
+        // ABB aliases, by alias
+        "aliases": {
+            "type": "object",
+            "additionalProperties": object(json!({
+                "list": { "type": "string" },
+                "ident": { "type": "string" },
+                "file": { "type": "string" },
+                "line": { "type": "integer", "minimum": 0 },
+            })),
+        },
+        // the names of elements identified by KEY, by list, then KEY
+        "key_names": {
+            "type": "object",
+            "additionalProperties": {
+                "type": "object",
+                "additionalProperties": object(json!({
+                    "name": { "type": "string" },
+                    "file": { "type": "string" },
+                    "line": { "type": "integer", "minimum": 0 },
+                })),
+            },
+        },
+        "gamemode": { "$ref": "#/$defs/gamemode" },
+        "files": {
+            "type": "array",
+            "items": object(json!({
+                "kind": { "type": "string" },
+                "path": { "type": "string" },
+                "from": nullable("string"),
+            })),
+        },
+        "unknown_tags": {
+            "type": "array",
+            "items": object(json!({
+                "tag": { "type": "string" },
+                "file": { "type": "string" },
+                "line": { "type": "integer", "minimum": 0 },
+            })),
+        },
+        "diagnostics": diagnostics,
+        "migrations": diagnostics,
+    }));
+
+    let top = schema.as_object_mut().expect("schema object");
+    top.insert("$schema".into(), json!(SCHEMA_DIALECT));
+    top.insert("title".into(), json!("pcgtools dump"));
+    top.insert(
+        "$defs".into(),
+        json!({
+            "config": config(),
+            "datum": datum(),
+            "list": list(),
+            "elem": elem(),
+            "provenance": provenance(),
+            "gamemode": gamemode(),
+            "gamemode_entry": gamemode_entry(),
+            "diagnostic": diagnostic(),
+        }),
+    );
+    schema
+}
@@ -0,0 +1,120 @@
+//
+// kit.rs -- typed model of KIT data
+//
+// Copyright (c) 2024 Jeff Garzik
+//
+// This file is part of the pcgtoolssoftware project covered under
+// the MIT License.  For the full license text, please see the LICENSE
+// file in the root directory of this project.
+// SPDX-License-Identifier: MIT
+
+use crate::pcc::{Pcc, PccElem};
+use serde::Serialize;
+
+// lines which may follow a STARTPACK line, each granting something
+pub(crate) const KIT_COMPONENTS: &[&str] = &[
+    "ABILITY",
+    "AGE",
+    "ALIGN",
+    "CLASS",
+    "DEITY",
+    "FEAT",
+    "FUNDS",
+    "GEAR",
+    "GENDER",
+    "KIT",
+    "LANGBONUS",
+    "LEVELABILITY",
+    "NAME",
+    "PROF",
+    "RACE",
+    "SELECT",
+    "SKILL",
+    "SPELLS",
+    "STAT",
+    "TABLE",
+    "TEMPLATE",
+];
+
+// a component line of a kit, e.g. "GEAR:Dagger<tab>QTY:2"
+#[derive(Clone, Debug, Serialize)]
+pub struct KitComponent {
+    // GEAR, SKILL, CLASS, ...
+    pub kind: String,
+    pub value: String,
+    // remaining tags of the line, e.g. QTY:2
+    pub options: Vec<(String, String)>,
+}
+
+impl KitComponent {
+    fn new(kind: &str, stored: &str) -> KitComponent {
+        let mut tokens = stored.split('\t');
+        let value = tokens.next().unwrap_or("").to_string();
+        let options = tokens
+            .map(|t| {
+                let (k, v) = t.split_once(':').unwrap_or((t, ""));
+                (k.to_string(), v.to_string())
+            })
+            .collect();
+        KitComponent {
+            kind: kind.to_string(),
+            value,
+            options,
+        }
+    }
+
+    // value of an option tag, e.g. QTY
+    pub fn option(&self, tag: &str) -> Option<&str> {
+        self.options
+            .iter()
+            .find(|(k, _)| k == tag)
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+// a STARTPACK kit, and the components it grants
+#[derive(Clone, Debug, Serialize)]
+pub struct Kit {
+    pub name: String,
+    pub types: Vec<String>,
+    // tags of the STARTPACK line, e.g. EQUIPBUY, VISIBLE, PRExxx
+    pub attribs: Vec<(String, String)>,
+    pub components: Vec<KitComponent>,
+    pub source: String,
+    pub line: usize,
+}
+
+impl Kit {
+    pub fn new(name: &str, elem: &PccElem) -> Kit {
+        let mut kit = Kit {
+            name: name.to_string(),
+            types: elem.values("TYPE").into_iter().map(String::from).collect(),
+            attribs: Vec::new(),
+            components: Vec::new(),
+            source: elem.source().to_string(),
+            line: elem.line(),
+        };
+
+        for (tag, value) in &elem.attribs {
+            if KIT_COMPONENTS.contains(&tag.as_str()) {
+                kit.components.push(KitComponent::new(tag, value));
+            } else {
+                kit.attribs.push((tag.to_string(), value.to_string()));
+            }
+        }
+
+        kit
+    }
+
+    // components of one kind, e.g. all GEAR
+    pub fn components_of<'a>(&'a self, kind: &'a str) -> impl Iterator<Item = &'a KitComponent> {
+        self.components.iter().filter(move |c| c.kind == kind)
+    }
+}
+
+// all loaded kits, in name order
+pub fn kits(pcc: &Pcc) -> Vec<Kit> {
+    let mut kits: Vec<Kit> = pcc.typed().collect();
+    kits.sort_by(|a, b| a.name.cmp(&b.name));
+    kits
+}
@@ -0,0 +1,175 @@
+//
+// language.rs -- typed model of LANGUAGE data, and the languages races
+// and templates grant
+//
+// Copyright (c) 2024 Jeff Garzik
+//
+// This file is part of the pcgtoolssoftware project covered under
+// the MIT License.  For the full license text, please see the LICENSE
+// file in the root directory of this project.
+// SPDX-License-Identifier: MIT
+
+use crate::auto::{AutoGrant, AutoKind};
+use crate::pcc::{Pcc, PccElem};
+use crate::token;
+use serde::Serialize;
+use std::collections::BTreeSet;
+
+#[derive(Clone, Debug, Serialize)]
+pub struct Language {
+    pub name: String,
+    pub key: String,
+    // e.g. Spoken, Written
+    pub types: Vec<String>,
+    pub source: String,
+    pub line: usize,
+}
+
+impl Language {
+    pub fn new(ident: &str, elem: &PccElem) -> Language {
+        Language {
+            name: elem.name().to_string(),
+            key: ident.to_string(),
+            types: elem.values("TYPE").into_iter().map(String::from).collect(),
+            source: elem.source().to_string(),
+            line: elem.line(),
+        }
+    }
+
+    fn has_type(&self, t: &str) -> bool {
+        self.types.iter().any(|have| have.eq_ignore_ascii_case(t))
+    }
+}
+
+// all loaded languages, in name order
+pub fn languages(pcc: &Pcc) -> Vec<Language> {
+    let mut languages: Vec<Language> = pcc.languages().collect();
+    languages.sort_by(|a, b| a.name.cmp(&b.name));
+    languages
+}
+
+// The language references of a LANGBONUS or LANGAUTO value, e.g.
+// "Draconic,Gnoll,TYPE=Spoken": names, TYPE=x or ALL.  .CLEAR and
+// conditions are left out.
+pub fn references(value: &str) -> Vec<&str> {
+    token::items(value)
+        .into_iter()
+        .filter(|item| !token::is_prereq(item))
+        .flat_map(token::sublist)
+        .filter(|name| !name.is_empty() && !name.starts_with('.'))
+        .collect()
+}
+
+// The languages an element grants: those known automatically, by
+// AUTO:LANG or the older LANGAUTO, and those it allows to be learned
+// as bonus languages, by LANGBONUS.  References are as written.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct LanguageGrants {
+    pub automatic: Vec<String>,
+    pub bonus: Vec<String>,
+}
+
+impl LanguageGrants {
+    pub fn new(elem: &PccElem) -> LanguageGrants {
+        let mut grants = LanguageGrants::default();
+        let names = |refs: Vec<&str>| refs.into_iter().map(String::from).collect::<Vec<_>>();
+        for (tag, value) in elem.attribs() {
+            match tag {
+                "AUTO" => {
+                    if let Some(grant) = AutoGrant::parse(value) {
+                        if grant.kind == AutoKind::Lang {
+                            grants.automatic.extend(grant.names().map(String::from));
+                        }
+                    }
+                }
+                "LANGAUTO" => grants.automatic.extend(names(references(value))),
+                "LANGBONUS" => grants.bonus.extend(names(references(value))),
+                _ => {}
+            }
+        }
+        grants
+    }
+}
+
+// Resolve language references against the LANGUAGE list: a name or
+// KEY names one language, TYPE=Spoken those of a type, and ALL every
+// one.  Returns the names of the languages, in order, and the
+// references which name none.
+pub fn resolve<S: AsRef<str>>(pcc: &Pcc, refs: &[S]) -> (Vec<String>, Vec<String>) {
+    let all = languages(pcc);
+    let mut found = BTreeSet::new();
+    let mut unresolved = Vec::new();
+    for r in refs.iter().map(|r| r.as_ref().trim()) {
+        let upper = r.to_ascii_uppercase();
+        let matched: Vec<&Language> = if upper == "ALL" {
+            all.iter().collect()
+        } else if let Some(types) = upper.strip_prefix("TYPE=").or(upper.strip_prefix("TYPE.")) {
+            let types = token::dotted(types);
+            all.iter()
+                .filter(|lang| types.iter().all(|t| lang.has_type(t)))
+                .collect()
+        } else {
+            all.iter()
+                .filter(|lang| {
+                    lang.name.eq_ignore_ascii_case(r) || lang.key.eq_ignore_ascii_case(r)
+                })
+                .collect()
+        };
+        if matched.is_empty() {
+            unresolved.push(r.to_string());
+        }
+        found.extend(matched.into_iter().map(|lang| lang.name.clone()));
+    }
+    (found.into_iter().collect(), unresolved)
+}
+
+// the languages available to a character of a race: those known
+// automatically, and those which may be learned as bonus languages,
+// granted by the race and the templates it applies
+#[derive(Clone, Debug, Serialize)]
+pub struct RaceLanguages {
+    pub race: String,
+    pub templates: Vec<String>,
+    pub automatic: Vec<String>,
+    // bonus languages not already known
+    pub bonus: Vec<String>,
+    // references naming no loaded language
+    pub unresolved: Vec<String>,
+}
+
+// the languages available to a race, by name, KEY or alias; None if no
+// such race is loaded
+pub fn race_languages(pcc: &Pcc, race: &str) -> Option<RaceLanguages> {
+    let elem = pcc.lookup_in("RACE", race)?;
+
+    // templates applied outright; those offered by TEMPLATE:CHOOSE are
+    // a choice of the player
+    let templates: Vec<&PccElem> = elem
+        .get_all("TEMPLATE")
+        .into_iter()
+        .filter(|value| !value.starts_with("CHOOSE:"))
+        .flat_map(token::items)
+        .filter_map(|name| pcc.lookup_in("TEMPLATE", name))
+        .collect();
+
+    let mut grants = LanguageGrants::new(elem);
+    for template in &templates {
+        let more = LanguageGrants::new(template);
+        grants.automatic.extend(more.automatic);
+        grants.bonus.extend(more.bonus);
+    }
+
+    let (automatic, mut unresolved) = resolve(pcc, &grants.automatic);
+    let (bonus, unresolved_bonus) = resolve(pcc, &grants.bonus);
+    unresolved.extend(unresolved_bonus);
+    Some(RaceLanguages {
+        race: elem.name().to_string(),
+        templates: templates.iter().map(|t| t.name().to_string()).collect(),
+        bonus: bonus
+            .into_iter()
+            .filter(|lang| !automatic.contains(lang))
+            .collect(),
+        automatic,
+        unresolved,
+    })
+}
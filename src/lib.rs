@@ -0,0 +1,81 @@
+//
+// lib.rs -- pcgtools library
+//
+// Copyright (c) 2024 Jeff Garzik
+//
+// This file is part of the pcgtoolssoftware project covered under
+// the MIT License.  For the full license text, please see the LICENSE
+// file in the root directory of this project.
+// SPDX-License-Identifier: MIT
+
+pub mod ability;
+pub mod auto;
+pub mod bioset;
+pub mod bonus;
+#[cfg(feature = "native")]
+pub mod cache;
+pub mod campaign;
+pub mod choose;
+pub mod class;
+pub mod compat;
+pub mod datacontrol;
+pub mod datatable;
+pub mod define;
+pub mod deity;
+pub mod desc;
+pub mod deserialize;
+pub mod diag;
+pub mod dice;
+pub mod diff;
+pub mod equipment;
+pub mod export;
+pub mod files;
+pub mod filter;
+pub mod formula;
+pub mod gamemode;
+pub mod graph;
+#[cfg(feature = "native")]
+pub mod install;
+pub mod intern;
+pub mod jsonschema;
+pub mod kit;
+pub mod language;
+pub mod lint;
+pub mod lstfmt;
+pub mod manifest;
+pub mod migrate;
+pub mod model;
+pub mod npc;
+pub mod output;
+pub mod paths;
+pub mod pcc;
+pub mod pcg;
+pub mod prereq;
+pub mod qualify;
+pub mod race;
+pub mod reader;
+pub mod region;
+#[cfg(feature = "native")]
+pub mod remote;
+pub mod repl;
+pub mod search;
+pub mod spell;
+pub mod stats;
+pub mod suggest;
+pub mod table;
+pub mod tempbonus;
+pub mod text;
+pub mod token;
+pub mod validate;
+pub mod variable;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+#[cfg(feature = "native")]
+pub mod watch;
+pub mod writer;
+
+pub use output::OutputFormat;
+pub use pcc::{
+    DuplicatePolicy, ElemProvenance, Pcc, PccConfig, PccDate, PccDatum, PccElem, PccList,
+    Provenance,
+};
@@ -0,0 +1,261 @@
+//
+// lint.rs -- style and consistency checks of LST files
+//
+// Copyright (c) 2024 Jeff Garzik
+//
+// This file is part of the pcgtoolssoftware project covered under
+// the MIT License.  For the full license text, please see the LICENSE
+// file in the root directory of this project.
+// SPDX-License-Identifier: MIT
+
+use crate::diag::Diagnostic;
+use crate::pcc::{self, Pcc};
+use crate::text;
+use crate::token;
+use std::{
+    collections::{HashMap, HashSet},
+    io,
+};
+
+// file location of a TYPE spelling, for capitalization checks
+struct TypeUse {
+    file: String,
+    line: usize,
+}
+
+// an element definition (not a .MOD), for name collision checks
+struct Definition {
+    file: String,
+    line: usize,
+}
+
+struct Linter<'a> {
+    pcc: &'a Pcc,
+    diags: Vec<Diagnostic>,
+    // list tag => element name => first definition
+    definitions: HashMap<String, HashMap<String, Definition>>,
+    // lowercased TYPE => spelling => uses
+    types: HashMap<String, HashMap<String, Vec<TypeUse>>>,
+}
+
+impl Linter<'_> {
+    fn lint_line(&mut self, kind: &str, file: &str, lineno: usize, line: &str, has_header: bool) {
+        if line.ends_with([' ', '\t']) {
+            self.diags.push(Diagnostic::warning(
+                file,
+                lineno,
+                String::from("trailing whitespace"),
+            ));
+        }
+
+        // comments and empty lines
+        let ch = line.chars().next();
+        if ch.is_none() || ch == Some('#') {
+            return;
+        }
+
+        let mut tokens = line.split('\t').map(|t| t.trim()).filter(|t| !t.is_empty());
+        let is_header = pcc::is_source_header(line);
+        let ident = if is_header {
+            ""
+        } else {
+            tokens.next().unwrap_or("")
+        };
+
+        let mut seen = HashSet::new();
+        let mut has_source = is_header;
+        let mut key = None;
+        for token in tokens {
+            let (tag, val) = token.split_once(':').unwrap_or((token, ""));
+
+            if tag.starts_with("SOURCE") {
+                has_source = true;
+            }
+            if tag == "KEY" {
+                key = Some(val);
+            }
+
+            if !seen.insert(tag) && self.pcc.is_single_valued(tag) {
+                self.diags.push(Diagnostic::warning(
+                    file,
+                    lineno,
+                    format!("{}: duplicate {} tag", ident, tag),
+                ));
+            }
+
+            // deprecated tags, as of the release the data is for.  FEAT
+            // and SPELL are deprecated as grants, but remain valid in
+            // their own contexts.
+            let compat = self.pcc.config().compat;
+            let exempt =
+                (tag == "SPELL" && kind == "SPELL") || (tag == "FEAT" && kind == "ABILITY");
+            if let Some(dep) = compat.deprecation(tag).filter(|_| !exempt) {
+                if compat.removed(dep) {
+                    self.diags.push(Diagnostic::error(
+                        file,
+                        lineno,
+                        format!(
+                            "{}: {} tag not read by PCGen {}; use {}",
+                            ident, tag, compat, dep.replacement
+                        ),
+                    ));
+                } else {
+                    self.diags.push(Diagnostic::warning(
+                        file,
+                        lineno,
+                        format!("{}: deprecated {} tag; use {}", ident, tag, dep.replacement),
+                    ));
+                }
+            }
+
+            if tag == "TYPE" {
+                for t in token::dotted(val) {
+                    self.types
+                        .entry(t.to_lowercase())
+                        .or_default()
+                        .entry(t.to_string())
+                        .or_default()
+                        .push(TypeUse {
+                            file: file.to_string(),
+                            line: lineno,
+                        });
+                }
+            }
+        }
+
+        if is_header {
+            return;
+        }
+
+        // remaining checks apply to new element definitions only
+        let is_directive =
+            ident.ends_with(".MOD") || ident.ends_with(".FORGET") || ident.contains(".COPY=");
+        if is_directive {
+            return;
+        }
+
+        // kit component lines belong to the preceding STARTPACK.
+        // BIOSET AGESET blocks are shared across files, and their
+        // RACENAME lines repeat by design.  DATATABLE lines are table
+        // rows, not elements.
+        if (kind == "KIT" && !ident.starts_with("STARTPACK:"))
+            || kind == "BIOSET"
+            || kind == "DATATABLE"
+        {
+            return;
+        }
+
+        // CLASS level lines, and subclasses, belong to the preceding
+        // class line
+        let is_class_part = ident.parse::<u32>().is_ok()
+            || ident.starts_with("SUBCLASS")
+            || ident.starts_with("SUBSTITUTIONCLASS");
+        if kind == "CLASS" && is_class_part {
+            return;
+        }
+
+        if !has_source && !has_header {
+            self.diags.push(Diagnostic::warning(
+                file,
+                lineno,
+                format!("{}: missing SOURCE tags", ident),
+            ));
+        }
+
+        let name = key.unwrap_or(ident).to_lowercase();
+        let defs = self.definitions.entry(kind.to_string()).or_default();
+        match defs.get(&name) {
+            // a class is commonly defined over several lines
+            Some(first) if kind == "CLASS" && first.file == file => {}
+            Some(first) => {
+                let msg = match key {
+                    Some(k) => format!(
+                        "{}: duplicate {} KEY {}, first defined at {}:{}",
+                        ident, kind, k, first.file, first.line
+                    ),
+                    None => format!(
+                        "{}: {} name collides with definition at {}:{}; add a unique KEY",
+                        ident, kind, first.file, first.line
+                    ),
+                };
+                self.diags.push(Diagnostic::warning(file, lineno, msg));
+            }
+            None => {
+                defs.insert(
+                    name,
+                    Definition {
+                        file: file.to_string(),
+                        line: lineno,
+                    },
+                );
+            }
+        }
+    }
+
+    fn lint_file(&mut self, kind: &str, file: &str) -> io::Result<()> {
+        let lines = text::read_lines(file, self.pcc.config().encoding)?;
+        let mut has_header = false;
+        for (idx, line) in lines.into_iter().enumerate() {
+            if pcc::is_source_header(&line) {
+                has_header = true;
+            }
+            self.lint_line(kind, file, idx + 1, &line, has_header);
+        }
+        Ok(())
+    }
+
+    // flag TYPE values spelled with differing capitalization, reporting
+    // the less common spellings
+    fn lint_types(&mut self) {
+        let mut lowers: Vec<&String> = self.types.keys().collect();
+        lowers.sort();
+        for lower in lowers {
+            let spellings = &self.types[lower];
+            if spellings.len() < 2 {
+                continue;
+            }
+
+            let (common, _) = spellings
+                .iter()
+                .max_by(|a, b| a.1.len().cmp(&b.1.len()).then(b.0.cmp(a.0)))
+                .unwrap();
+            for (spelling, uses) in spellings {
+                if spelling == common {
+                    continue;
+                }
+                for u in uses {
+                    self.diags.push(Diagnostic::warning(
+                        &u.file,
+                        u.line,
+                        format!(
+                            "TYPE {} capitalized inconsistently; elsewhere {}",
+                            spelling, common
+                        ),
+                    ));
+                }
+            }
+        }
+    }
+}
+
+// check all loaded LST files for style and consistency problems
+pub fn lint(pcc: &Pcc) -> io::Result<Vec<Diagnostic>> {
+    let mut linter = Linter {
+        pcc,
+        diags: Vec::new(),
+        definitions: HashMap::new(),
+        types: HashMap::new(),
+    };
+
+    // a file may be loaded more than once, via different PCC files
+    let mut linted = HashSet::new();
+    for f in pcc.files() {
+        if f.kind != "PCC" && linted.insert(f.path.as_str()) {
+            linter.lint_file(&f.kind, &f.path)?;
+        }
+    }
+
+    linter.lint_types();
+
+    Ok(linter.diags)
+}
@@ -0,0 +1,367 @@
+//
+// lstfmt.rs -- canonical formatting of LST files, after Prettylst
+//
+// Copyright (c) 2024 Jeff Garzik
+//
+// This file is part of the pcgtoolssoftware project covered under
+// the MIT License.  For the full license text, please see the LICENSE
+// file in the root directory of this project.
+// SPDX-License-Identifier: MIT
+
+use crate::diag::Diagnostic;
+use crate::migrate::Migrator;
+use crate::pcc::{is_source_header, region_line, LstForm, Pcc};
+use crate::text;
+use crate::writer::tag_rank;
+use std::{collections::HashSet, fs, io};
+
+// columns are aligned to tab stops this far apart, as by Prettylst
+const TAB_WIDTH: usize = 6;
+
+// the column after text begun at a column, with tabs expanded
+fn advance(mut col: usize, text: &str) -> usize {
+    for c in text.chars() {
+        col = match c {
+            '\t' => (col / TAB_WIDTH + 1) * TAB_WIDTH,
+            _ => col + 1,
+        };
+    }
+    col
+}
+
+// the tag of a token, e.g. "TYPE" of "TYPE:Magic"; a token without a
+// tag is its own
+fn token_tag(token: &str) -> &str {
+    token.split_once(':').map_or(token, |(tag, _)| tag)
+}
+
+// an element line, or a line following the first line of a block
+struct Row {
+    ident: String,
+    // in canonical order
+    tokens: Vec<String>,
+}
+
+// Lay out rows in columns: the ident, then one column per tag, holding
+// all tokens of that tag.  Each column starts at a tab stop past the
+// widest entry of the column before it, so that a tag lines up across
+// all rows.
+fn align(list: &str, rows: &[Row]) -> Vec<String> {
+    let mut tags: Vec<&str> = Vec::new();
+    for row in rows {
+        for token in &row.tokens {
+            let tag = token_tag(token);
+            if !tags.contains(&tag) {
+                tags.push(tag);
+            }
+        }
+    }
+    tags.sort_by_key(|tag| tag_rank(list, tag));
+
+    // the cells of each row, column 0 being the ident
+    let cells: Vec<Vec<Option<String>>> = rows
+        .iter()
+        .map(|row| {
+            let mut cells = vec![None; tags.len() + 1];
+            cells[0] = Some(row.ident.clone());
+            for (i, tag) in tags.iter().enumerate() {
+                let tokens: Vec<&str> = row
+                    .tokens
+                    .iter()
+                    .filter(|t| token_tag(t) == *tag)
+                    .map(|t| t.as_str())
+                    .collect();
+                if !tokens.is_empty() {
+                    cells[i + 1] = Some(tokens.join("\t"));
+                }
+            }
+            cells
+        })
+        .collect();
+
+    let mut starts = vec![0; tags.len() + 1];
+    for col in 1..starts.len() {
+        let width = cells
+            .iter()
+            .filter_map(|row| row[col - 1].as_deref())
+            .map(|cell| advance(0, cell))
+            .max()
+            .unwrap_or(0);
+        starts[col] = starts[col - 1] + (width / TAB_WIDTH + 1) * TAB_WIDTH;
+    }
+
+    cells
+        .iter()
+        .map(|row| {
+            let mut line = String::new();
+            let mut at = 0;
+            for (cell, start) in row.iter().zip(&starts) {
+                let Some(cell) = cell else {
+                    continue;
+                };
+                while at < *start {
+                    line.push('\t');
+                    at = advance(at, "\t");
+                }
+                line.push_str(cell);
+                at = advance(at, cell);
+            }
+            line
+        })
+        .collect()
+}
+
+// a line of the formatted file
+enum Piece {
+    // a comment, blank line or source header, kept as is
+    Text(String),
+    // index of an element row, or of a block row
+    Elem(usize),
+    Block(usize),
+}
+
+// an element line, or a block, with the comments preceding it
+struct Record {
+    pieces: Vec<Piece>,
+    // lowercased SORTKEY or ident; none for .MOD, .COPY and .FORGET
+    // lines, which keep their order, after all definitions
+    key: Option<String>,
+    // a REGION line, which applies to the blocks following it, so that
+    // blocks are sorted only between REGION lines
+    barrier: bool,
+}
+
+// Sort records by key, .MOD, .COPY and .FORGET lines following the
+// definitions, within each run of records between barriers.
+fn sort_records(records: Vec<Record>) -> Vec<Record> {
+    let mut sorted = Vec::with_capacity(records.len());
+    let mut run: Vec<Record> = Vec::new();
+    let flush = |run: &mut Vec<Record>, sorted: &mut Vec<Record>| {
+        let (mut defs, mods): (Vec<_>, Vec<_>) = run.drain(..).partition(|r| r.key.is_some());
+        defs.sort_by(|a, b| a.key.cmp(&b.key));
+        sorted.extend(defs);
+        sorted.extend(mods);
+    };
+    for record in records {
+        if record.barrier {
+            flush(&mut run, &mut sorted);
+            sorted.push(record);
+        } else {
+            run.push(record);
+        }
+    }
+    flush(&mut run, &mut sorted);
+    sorted
+}
+
+// the sort key of an element line, by its ident and tokens
+fn sort_key(ident: &str, tokens: &[String]) -> Option<String> {
+    if ident.ends_with(".MOD") || ident.ends_with(".FORGET") || ident.contains(".COPY=") {
+        return None;
+    }
+    let key = tokens
+        .iter()
+        .find_map(|t| t.strip_prefix("SORTKEY:"))
+        .unwrap_or(ident);
+    Some(key.to_lowercase())
+}
+
+// Format the lines of an LST file of a list.  Tags of each line are
+// put in the canonical order of the list, and aligned in columns
+// across lines.  Elements are sorted by SORTKEY, or by name, with
+// comments preceding an element kept with it; .MOD, .COPY and .FORGET
+// lines follow, in their original order.  Blocks are sorted only
+// between the REGION lines of a block file.  Elements of a file with
+// more than one source header line, or of a BIOSET list, are not
+// sorted, as their order matters.  DATATABLE files are returned
+// unchanged.
+pub fn format_lst(pcc: &Pcc, list: &str, lines: &[String]) -> Vec<String> {
+    let form = pcc.lst_form(list);
+    let block_tag = match form {
+        LstForm::Tables => return lines.to_vec(),
+        LstForm::Blocks(tag) => Some(format!("{}:", tag)),
+        LstForm::Lines => None,
+    };
+    let mut sortable = form != LstForm::Blocks("AGESET");
+
+    let mut elems: Vec<Row> = Vec::new();
+    let mut blocks: Vec<Row> = Vec::new();
+    let mut head: Vec<Piece> = Vec::new();
+    let mut records: Vec<Record> = Vec::new();
+    let mut pending: Vec<Piece> = Vec::new();
+
+    for line in lines {
+        if line.trim().is_empty() || line.starts_with('#') {
+            pending.push(Piece::Text(line.clone()));
+            continue;
+        }
+        if is_source_header(line) {
+            pending.push(Piece::Text(line.clone()));
+            match records.last_mut() {
+                None => head.append(&mut pending),
+                Some(record) => {
+                    sortable = false;
+                    record.pieces.append(&mut pending);
+                }
+            }
+            continue;
+        }
+
+        let mut tokens = line.split('\t');
+        let ident = tokens.next().unwrap_or("").to_string();
+        if block_tag.is_some() && region_line(&ident).is_some() {
+            pending.push(Piece::Text(line.clone()));
+            records.push(Record {
+                pieces: std::mem::take(&mut pending),
+                key: None,
+                barrier: true,
+            });
+            continue;
+        }
+        let mut tokens: Vec<String> = tokens
+            .filter(|t| !t.trim().is_empty())
+            .map(String::from)
+            .collect();
+        tokens.sort_by_key(|t| tag_rank(list, token_tag(t)));
+
+        let in_block = block_tag
+            .as_ref()
+            .is_some_and(|tag| !ident.starts_with(tag.as_str()));
+        if in_block {
+            // lines before the first block stay at the top
+            let pieces = match records.last_mut() {
+                Some(record) => &mut record.pieces,
+                None => &mut head,
+            };
+            pieces.append(&mut pending);
+            pieces.push(Piece::Block(blocks.len()));
+            blocks.push(Row { ident, tokens });
+            continue;
+        }
+
+        let name = match &block_tag {
+            Some(tag) => ident.strip_prefix(tag.as_str()).unwrap_or(&ident),
+            None => &ident,
+        };
+        let key = sort_key(name, &tokens);
+        pending.push(Piece::Elem(elems.len()));
+        records.push(Record {
+            pieces: std::mem::take(&mut pending),
+            key,
+            barrier: false,
+        });
+        elems.push(Row { ident, tokens });
+    }
+
+    if sortable {
+        records = sort_records(records);
+    }
+
+    let elem_lines = align(list, &elems);
+    let block_lines = align(list, &blocks);
+    let pieces = head
+        .into_iter()
+        .chain(records.into_iter().flat_map(|r| r.pieces))
+        .chain(pending);
+    pieces
+        .map(|piece| match piece {
+            Piece::Text(line) => line,
+            Piece::Elem(i) => elem_lines[i].clone(),
+            Piece::Block(i) => block_lines[i].clone(),
+        })
+        .collect()
+}
+
+// Format every LST file loaded, rewriting those whose formatting
+// differs, as UTF-8 with LF line endings.  With a migrator, deprecated
+// tags are rewritten first.  With check, no file is written.  Returns
+// the paths of the files which differ, and the tags rewritten.
+pub fn format_files(
+    pcc: &Pcc,
+    check: bool,
+    fix: Option<&Migrator>,
+) -> io::Result<(Vec<String>, Vec<Diagnostic>)> {
+    let mut seen = HashSet::new();
+    let mut changed = Vec::new();
+    let mut migrations = Vec::new();
+    for file in pcc.files() {
+        if file.kind == "PCC" || !seen.insert(file.path.as_str()) {
+            continue;
+        }
+
+        let mut lines = text::read_lines(&file.path, pcc.config().encoding)?;
+        if let Some(migrator) = fix {
+            for (i, line) in lines.iter_mut().enumerate() {
+                let (fixed, diags) = migrator.migrate_line(&file.kind, &file.path, i + 1, line);
+                *line = fixed;
+                migrations.extend(diags);
+            }
+        }
+        let mut text = format_lst(pcc, &file.kind, &lines).join("\n");
+        if !text.is_empty() {
+            text.push('\n');
+        }
+        if fs::read(&file.path)? == text.as_bytes() {
+            continue;
+        }
+
+        tracing::debug!("format_files: {} differs", file.path);
+        if !check {
+            fs::write(&file.path, text)?;
+        }
+        changed.push(file.path.clone());
+    }
+    Ok((changed, migrations))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pcc::tests::test_config;
+
+    fn format(list: &str, text: &str) -> Vec<String> {
+        let pcc = Pcc::new(&test_config(&std::env::temp_dir()));
+        let lines: Vec<String> = text.lines().map(String::from).collect();
+        format_lst(&pcc, list, &lines)
+    }
+
+    #[test]
+    fn elements_sorted_with_comments_and_mods_last() {
+        let lines = format(
+            "SPELL",
+            "# wishes\nWish\tSCHOOL:Universal\nAid.MOD\tLEVEL:Cleric=2\nAid\tSCHOOL:Enchantment\n",
+        );
+        assert!(lines[0].starts_with("Aid\t"));
+        assert_eq!(lines[1], "# wishes");
+        assert!(lines[2].starts_with("Wish\t"));
+        assert!(lines[3].starts_with("Aid.MOD\t"));
+    }
+
+    #[test]
+    fn blocks_sorted_only_between_regions() {
+        let lines = format(
+            "KIT",
+            "REGION:Faerun\n\
+             STARTPACK:Zeta\n\
+             GEAR:Rope\n\
+             STARTPACK:Beta\n\
+             REGION:Always\n\
+             STARTPACK:Alpha\n",
+        );
+        let idents: Vec<&str> = lines
+            .iter()
+            .map(|l| l.split('\t').next().unwrap().trim_end())
+            .collect();
+        assert_eq!(
+            idents,
+            [
+                "REGION:Faerun",
+                "STARTPACK:Beta",
+                "STARTPACK:Zeta",
+                "GEAR:Rope",
+                "REGION:Always",
+                "STARTPACK:Alpha"
+            ]
+        );
+    }
+}
@@ -1,5 +1,5 @@
 //
-// main.rs -- pcgtools core code
+// main.rs -- pcgtools command line tool
 //
 // Copyright (c) 2024 Jeff Garzik
 //
@@ -9,478 +9,1883 @@
 // SPDX-License-Identifier: MIT
 
 extern crate clap;
-extern crate log;
-
-use clap::Parser;
-use serde::{Deserialize, Serialize};
+use clap::{Parser, Subcommand};
+use indicatif::{HumanBytes, ProgressBar, ProgressStyle};
+use pcgtools::{
+    ability::Abilities,
+    bioset::BioSet,
+    bonus, cache, campaign,
+    choose::Choose,
+    compat::DataCompat,
+    datatable,
+    diag::Diagnostic,
+    diff,
+    equipment::Armory,
+    export, files,
+    filter::ElemFilter,
+    graph::{CampaignGraph, IncludeGraph},
+    install, jsonschema, language, lint, lstfmt,
+    manifest::{Manifest, Mismatch},
+    migrate::Migrator,
+    npc, output, paths,
+    pcc::{Alias, KeyName, LoadProgress},
+    pcg::Character,
+    qualify::{self, Profile},
+    region::Regions,
+    remote::{self, HttpFiles},
+    repl, search, spell, stats, suggest,
+    table::{self, ColorChoice},
+    text::Encoding,
+    validate,
+    variable::{Definition, Modifier, Variable, Variables},
+    watch, writer, DuplicatePolicy, OutputFormat, Pcc, PccConfig, PccElem,
+};
+use serde::Serialize;
 use std::{
-    collections::HashMap,
-    fs::File,
-    io,
-    io::{prelude::*, BufReader, Error, ErrorKind},
-    path::Path,
+    collections::BTreeMap,
+    env, fs,
+    io::{self, IsTerminal},
+    path::{Path, PathBuf},
+    process, slice,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
 };
+use tracing_subscriber::EnvFilter;
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 struct Args {
-    /// Pathname of PCC file to input
-    pccfile: String,
+    /// Pathnames of PCC files to dump (same as "dump <PCCFILES>...")
+    pccfiles: Vec<String>,
+
+    /// Base directory where PCC and LST files are found, or a ZIP
+    /// archive of it, e.g. data.zip or data.zip/data.  Repeat to search
+    /// several in order, followed by those of PCGTOOLS_DATA_PATH
+    /// [default: ., or, if it holds no PCC files, the data directory of
+    /// PCGEN_HOME or of an installed PCGen]
+    #[arg(short, long, global = true)]
+    datadir: Vec<String>,
+
+    /// Only load campaigns for this game mode (e.g. 35e, Pathfinder)
+    #[arg(short, long, global = true)]
+    gamemode: Option<String>,
+
+    /// Directory of game mode system files [default: <first
+    /// datadir>/../system]
+    #[arg(long, global = true)]
+    systemdir: Option<String>,
+
+    /// Directory of vendor data, searched first for "*" paths
+    /// [default: <first datadir>/../vendordata]
+    #[arg(long, global = true)]
+    vendordir: Option<String>,
+
+    /// Directory of homebrew data, searched after vendor data for "*"
+    /// paths [default: <first datadir>/../homebrewdata]
+    #[arg(long, global = true)]
+    homebrewdir: Option<String>,
+
+    /// Output format: json, json-compact, yaml, toml, or table, for
+    /// query show only
+    #[arg(short, long, default_value = "json", global = true)]
+    format: OutputFormat,
+
+    /// Character encoding of PCC and LST files: auto, utf-8, windows-1252
+    #[arg(long, default_value = "auto", global = true)]
+    encoding: Encoding,
+
+    /// Record the file and line of each attribute and .MOD, and
+    /// include them in output
+    #[arg(long, global = true)]
+    with_provenance: bool,
+
+    /// Handling of elements defined twice in a list: warn, error,
+    /// keep-first, keep-last
+    #[arg(long, default_value = "warn", global = true)]
+    duplicates: DuplicatePolicy,
+
+    /// Warn of unknown PCC tags, rather than failing the load
+    #[arg(long, global = true)]
+    allow_unknown_tags: bool,
+
+    /// TOML file of PCC tags to add to the built-in schema
+    #[arg(long, global = true)]
+    schema: Option<String>,
+
+    /// Rewrite deprecated LST tags to their modern forms while loading,
+    /// reporting each rewrite
+    #[arg(long, global = true)]
+    migrate: bool,
+
+    /// TOML file of migration rules, tried before the built-in rules
+    #[arg(long, global = true)]
+    migrations: Option<String>,
+
+    /// PCGen release the data was written for: 5.16, 6.06 or 6.08.
+    /// Decides which PCC tags are read, which LST tags are deprecated,
+    /// and whether deprecated tags are rewritten while loading.
+    #[arg(long, default_value = "6.08", global = true)]
+    data_compat: DataCompat,
+
+    /// Treat PCC file arguments as campaign names or KEYs, and load the
+    /// matching PCC files found in datadir
+    #[arg(long, global = true, overrides_with = "campaign")]
+    campaign: bool,
+
+    /// Cache parsed data in this directory, re-using it while no input
+    /// file has changed
+    #[arg(long, global = true)]
+    cache: Option<String>,
+
+    /// Dump or export only elements with a tag value, e.g.
+    /// TYPE=Wondrous; repeat to require several
+    #[arg(long, global = true, value_name = "TAG=VALUE")]
+    filter: Vec<ElemFilter>,
+
+    /// Dump or export only elements from a source, e.g.
+    /// SOURCESHORT=CRB, or CRB for any SOURCE tag; repeat to require
+    /// several
+    #[arg(long, global = true, value_name = "SOURCE", value_parser = ElemFilter::source)]
+    source: Vec<ElemFilter>,
+
+    /// Re-run lint, stats or validate whenever a loaded file changes
+    #[arg(short, long, global = true)]
+    watch: bool,
+
+    /// Read PCC and LST files of http(s) URLs only from the download
+    /// cache, rather than fetching them
+    #[arg(long, global = true)]
+    offline: bool,
+
+    /// Directory in which files fetched from URLs are kept
+    /// [default: ~/.cache/pcgtools/downloads]
+    #[arg(long, global = true)]
+    download_cache: Option<String>,
+
+    /// Show no progress bar while loading
+    #[arg(short, long, global = true)]
+    quiet: bool,
+
+    /// Format of log messages on stderr: text, or json, one object
+    /// per line, with the files being read
+    #[arg(long, default_value = "text", global = true, value_parser = ["text", "json"])]
+    log_format: String,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
 
-    /// Base directory where PCC and LST files are found
-    #[arg(short, long, default_value = ".")]
-    datadir: String,
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Check a saved PCGen character against loaded data
+    Audit {
+        /// Pathname of the .pcg character file
+        pcgfile: String,
+
+        /// Pathnames of PCC files to input, loaded in order; by
+        /// default, the campaigns the character names
+        pccfiles: Vec<String>,
+    },
+
+    /// Compare two campaign trees, reporting changed elements
+    Diff {
+        /// Pathname of the older PCC file, relative to datadir
+        oldpcc: String,
+
+        /// Pathname of the newer PCC file, relative to datadir
+        newpcc: String,
+    },
+
+    /// Generate Markdown documentation pages, one per list
+    Docgen {
+        /// Pathnames of PCC files to input, loaded in order
+        #[arg(required = true)]
+        pccfiles: Vec<String>,
+
+        /// Directory in which to write Markdown files
+        outdir: String,
+    },
+
+    /// Display all loaded data
+    Dump {
+        /// Pathnames of PCC files to input, loaded in order
+        #[arg(required = true)]
+        pccfiles: Vec<String>,
+    },
+
+    /// Export loaded data to other formats
+    #[command(subcommand)]
+    Export(ExportCommand),
+
+    /// Rewrite loaded LST files in canonical form: tags ordered and
+    /// aligned in columns, and elements sorted by SORTKEY or name
+    Fmt {
+        /// Pathnames of PCC files to input, loaded in order
+        #[arg(required = true)]
+        pccfiles: Vec<String>,
+
+        /// Write no files; list those not in canonical form, and exit
+        /// with status 1 if there are any
+        #[arg(long)]
+        check: bool,
+
+        /// Also rewrite deprecated tags to their modern forms,
+        /// reporting each rewrite
+        #[arg(long)]
+        fix: bool,
+    },
+
+    /// Show the PCC include tree of loaded files, with the LST files
+    /// each PCC file references, their sizes and element counts
+    Graph {
+        /// Pathnames of PCC files to input, loaded in order
+        #[arg(required_unless_present = "campaigns")]
+        pccfiles: Vec<String>,
+
+        /// Write a Graphviz DOT graph, rather than a text tree
+        #[arg(long)]
+        dot: bool,
+
+        /// Graph the campaigns of the data directory, or those of the
+        /// PCC files given and all they require, by PRECAMPAIGN, in
+        /// RANK order, flagging missing and circular requirements
+        #[arg(long)]
+        campaigns: bool,
+    },
+
+    /// Generate random characters and character details from loaded
+    /// data
+    #[command(subcommand)]
+    Generate(GenerateCommand),
+
+    /// List the campaigns found in the data directory
+    ListCampaigns,
+
+    /// Check LST files for style and consistency problems
+    Lint {
+        /// Pathnames of PCC files to input, loaded in order
+        #[arg(required = true)]
+        pccfiles: Vec<String>,
+    },
+
+    /// Record, or check, the sizes and SHA-256 hashes of the files of
+    /// a load
+    #[command(subcommand)]
+    Manifest(ManifestCommand),
+
+    /// Query loaded data
+    #[command(subcommand)]
+    Query(QueryCommand),
+
+    /// Create a skeleton campaign: a PCC file, and an empty LST file
+    /// per list
+    NewCampaign {
+        /// Directory in which to create the campaign
+        outdir: String,
+
+        /// Campaign name, e.g. "My Book"
+        #[arg(long)]
+        name: String,
+
+        /// Game mode of the campaign, e.g. 35e
+        #[arg(long)]
+        gamemode: String,
+
+        /// Short source name, e.g. MB
+        #[arg(long)]
+        source_short: String,
+
+        /// Source web site
+        #[arg(long)]
+        source_web: Option<String>,
+
+        /// List to create an LST file for; repeat for several.  By
+        /// default, ABILITY, CLASS, DEITY, EQUIPMENT, RACE, SKILL,
+        /// SPELL and TEMPLATE
+        #[arg(long = "list", value_name = "LIST")]
+        lists: Vec<String>,
+    },
+
+    /// Explore loaded data interactively
+    Repl {
+        /// Pathnames of PCC files to input, loaded in order
+        #[arg(required = true)]
+        pccfiles: Vec<String>,
+    },
+
+    /// Write all loaded lists as LST files, read them back, and
+    /// report any element whose data changed
+    Roundtrip {
+        /// Pathnames of PCC files to input, loaded in order
+        #[arg(required = true)]
+        pccfiles: Vec<String>,
+
+        /// Directory in which to keep the LST files written; by
+        /// default, a temporary directory, removed afterward
+        #[arg(long)]
+        outdir: Option<String>,
+    },
+
+    /// Print a JSON Schema of the dump format
+    Schema,
+
+    /// Search element names, DESC and SPROP text of all lists
+    Search {
+        /// Pathname of PCC file to input
+        pccfile: String,
+
+        /// Text to find, ignoring case, e.g. "fire damage"
+        pattern: String,
+
+        /// Treat the pattern as a regular expression
+        #[arg(long)]
+        regex: bool,
+    },
+
+    /// Print summary statistics of the loaded data
+    Stats {
+        /// Pathnames of PCC files to input, loaded in order
+        #[arg(required = true)]
+        pccfiles: Vec<String>,
+    },
+
+    /// Check cross-references between lists, reporting unresolved names
+    Validate {
+        /// Pathnames of PCC files to input, loaded in order
+        #[arg(required = true)]
+        pccfiles: Vec<String>,
+    },
 }
 
-#[derive(Serialize, Deserialize)]
-enum PccTag {
-    Bool,
-    Date,
-    LstFile,
-    Number,
-    Text,
-    PccFile,
+#[derive(Subcommand, Debug)]
+enum ExportCommand {
+    /// Export spells and equipment as a 5eTools JSON document,
+    /// reporting data which has no 5eTools field
+    #[command(name = "5etools")]
+    FiveTools {
+        /// Pathnames of PCC files to input, loaded in order
+        #[arg(required = true)]
+        pccfiles: Vec<String>,
+
+        /// Pathname of JSON file to create
+        outfile: String,
+    },
+
+    /// Export spells, items, races, classes and temporary effects as
+    /// the files of a Fantasy Grounds module
+    FantasyGrounds {
+        /// Pathnames of PCC files to input, loaded in order
+        #[arg(required = true)]
+        pccfiles: Vec<String>,
+
+        /// Directory in which to write definition.xml and db.xml
+        outdir: String,
+
+        /// Fantasy Grounds ruleset of the module, e.g. 3.5E or PFRPG
+        #[arg(long, default_value = "3.5E")]
+        ruleset: String,
+    },
+
+    /// Export spells, feats, equipment, classes and temporary effects as
+    /// a Foundry VTT module of compendium packs
+    Foundry {
+        /// Pathnames of PCC files to input, loaded in order
+        #[arg(required = true)]
+        pccfiles: Vec<String>,
+
+        /// Directory in which to write the module
+        outdir: String,
+
+        /// Foundry game system whose schema to write: dnd5e or pf1
+        #[arg(long, default_value = "dnd5e")]
+        system: export::foundry::System,
+    },
+
+    /// Export loaded lists as LST files with every .MOD, .COPY and
+    /// .CLEAR applied, plus a PCC file referencing them
+    FlattenedLst {
+        /// Pathnames of PCC files to input, loaded in order
+        #[arg(required = true)]
+        pccfiles: Vec<String>,
+
+        /// Directory in which to write the PCC and LST files
+        outdir: String,
+    },
+
+    /// Export loaded data as a static HTML site
+    Html {
+        /// Pathnames of PCC files to input, loaded in order
+        #[arg(required = true)]
+        pccfiles: Vec<String>,
+
+        /// Directory in which to write HTML files
+        outdir: String,
+    },
+
+    /// Export loaded data to a new SQLite database
+    Sqlite {
+        /// Pathnames of PCC files to input, loaded in order
+        #[arg(required = true)]
+        pccfiles: Vec<String>,
+
+        /// Pathname of database file to create
+        dbfile: String,
+    },
 }
 
-#[derive(Serialize, Deserialize)]
-pub struct PccElem {
-    _ident: String,
-    attribs: Vec<(String, String)>,
+#[derive(Subcommand, Debug)]
+enum ManifestCommand {
+    /// Write a manifest of every PCC and LST file loaded, as JSON
+    Create {
+        /// Pathnames of PCC files to input, loaded in order
+        #[arg(required = true)]
+        pccfiles: Vec<String>,
+
+        /// Pathname of manifest file to create
+        #[arg(short, long, default_value = "manifest.json")]
+        output: String,
+    },
+
+    /// Report files of the data directory that are missing, or differ
+    /// from a manifest
+    Verify {
+        /// Pathname of manifest file to check
+        #[arg(default_value = "manifest.json")]
+        manifest: String,
+    },
 }
 
-impl PccElem {
-    fn new(ident: &str) -> PccElem {
-        PccElem {
-            _ident: String::from(ident),
-            attribs: Vec::new(),
-        }
-    }
+#[derive(Subcommand, Debug)]
+enum GenerateCommand {
+    /// Roll the age, height and weight of a character from BIOSET
+    /// data
+    Bio {
+        /// Pathnames of PCC files to input, loaded in order
+        #[arg(required = true)]
+        pccfiles: Vec<String>,
+
+        /// Race of the character, e.g. Elf
+        #[arg(long)]
+        race: String,
+
+        /// Class of the character, for its starting age
+        #[arg(long)]
+        class: Option<String>,
+
+        /// Sex of the character, e.g. Female; random by default
+        #[arg(long)]
+        sex: Option<String>,
+
+        /// Seed of the dice rolls, to repeat a result
+        #[arg(long)]
+        seed: Option<u64>,
+    },
+
+    /// Generate a random character, with feats, spells and equipment
+    /// whose prerequisites it meets
+    Npc {
+        /// Pathnames of PCC files to input, loaded in order
+        #[arg(required = true)]
+        pccfiles: Vec<String>,
+
+        /// Race of the character, e.g. Elf
+        #[arg(long)]
+        race: String,
+
+        /// Class of the character, e.g. Wizard
+        #[arg(long)]
+        class: String,
+
+        /// Class level of the character
+        #[arg(long, default_value_t = 1)]
+        level: u32,
+
+        /// Seed of the dice rolls, to repeat a result
+        #[arg(long)]
+        seed: Option<u64>,
+
+        /// Write a Markdown character sheet, rather than --format
+        #[arg(long)]
+        markdown: bool,
+    },
 }
 
-#[derive(Serialize, Deserialize)]
-pub struct PccList {
-    _ident: String,
-    props: HashMap<String, PccElem>,
+#[derive(Subcommand, Debug)]
+enum QueryCommand {
+    /// Display ABB aliases, with the file and line defining each, and
+    /// the names of elements known by a KEY
+    Aliases {
+        /// Pathname of PCC file to input
+        pccfile: String,
+
+        /// Display those of this list only, e.g. EQUIPMENT
+        #[arg(long)]
+        list: Option<String>,
+    },
+
+    /// Display the abilities of an ability category, or a single one
+    Abilities {
+        /// Pathname of PCC file to input
+        pccfile: String,
+
+        /// Ability category, e.g. FEAT
+        category: String,
+
+        /// Ability name or KEY
+        ident: Option<String>,
+    },
+
+    /// Display the CHOOSE of an element, and the selections it offers
+    Choices {
+        /// Pathname of PCC file to input
+        pccfile: String,
+
+        /// Element name or KEY
+        name: String,
+    },
+
+    /// Display a character's stats, armor class, saves and skills,
+    /// with the bonuses making them up
+    Bonuses {
+        /// Pathname of PCC file to input
+        pccfile: String,
+
+        /// Pathname of the character: a TOML profile, or a .pcg file
+        character: String,
+    },
+
+    /// Display the values of a character's DEFINE variables, or a
+    /// single one
+    Defines {
+        /// Pathname of PCC file to input
+        pccfile: String,
+
+        /// Pathname of the character: a TOML profile, or a .pcg file
+        character: String,
+
+        /// Variable name
+        name: Option<String>,
+    },
+
+    /// Display an equipment item with equipment modifiers applied
+    Item {
+        /// Pathname of PCC file to input
+        pccfile: String,
+
+        /// Base equipment name, e.g. Longsword
+        base: String,
+
+        /// EQMOD keys or names to apply, in order
+        eqmods: Vec<String>,
+    },
+
+    /// Display the loaded languages, or those available to a race:
+    /// known automatically, and which may be learned as bonus languages
+    Languages {
+        /// Pathname of PCC file to input
+        pccfile: String,
+
+        /// Race name, e.g. Elf
+        race: Option<String>,
+    },
+
+    /// Find elements of any list by KEY, name or alias
+    Lookup {
+        /// Pathname of PCC file to input
+        pccfile: String,
+
+        /// Element name, KEY or alias, e.g. "Power Attack"
+        name: String,
+    },
+
+    /// Check whether a character meets the prerequisites of an
+    /// element, e.g. a feat or class
+    Prereqs {
+        /// Pathname of PCC file to input
+        pccfile: String,
+
+        /// Pathname of the character: a TOML profile, or a .pcg file
+        character: String,
+
+        /// Element name, KEY or alias, e.g. "Power Attack"
+        name: String,
+    },
+
+    /// Display the regions of the loaded data, with their subregions
+    /// and elements, or a single region
+    Regions {
+        /// Pathname of PCC file to input
+        pccfile: String,
+
+        /// Region name
+        region: Option<String>,
+    },
+
+    /// Display a list, or a single element of a list
+    Show {
+        /// Pathname of PCC file to input
+        pccfile: String,
+
+        /// List type, e.g. SPELL
+        list: String,
+
+        /// Element name or KEY
+        ident: Option<String>,
+
+        /// Columns of --format table: "name", or tags, e.g.
+        /// name,SCHOOL,CLASSES [default: name, TYPE and the list's
+        /// common tags]
+        #[arg(long, value_delimiter = ',')]
+        columns: Vec<String>,
+
+        /// Color table output: auto, always, never
+        #[arg(long, default_value = "auto")]
+        color: ColorChoice,
+    },
+
+    /// Display the spells of a class or domain, by spell level
+    Spelllist {
+        /// Pathname of PCC file to input
+        pccfile: String,
+
+        /// Class or domain name, e.g. Wizard
+        class: String,
+
+        /// Spell level; all levels if omitted
+        level: Option<u32>,
+    },
+
+    /// Display a DATATABLE, a row of it, or a single cell
+    Table {
+        /// Pathname of PCC file to input
+        pccfile: String,
+
+        /// Table name
+        table: String,
+
+        /// Row key, the value of the row's first column
+        row: Option<String>,
+
+        /// Column name
+        column: Option<String>,
+    },
+
+    /// Display the values of global variables, or a single one
+    Variables {
+        /// Pathname of PCC file to input
+        pccfile: String,
+
+        /// Variable name
+        name: Option<String>,
+
+        /// List every variable and stat the data defines, by VARIABLE,
+        /// DEFINE or DEFINESTAT, and where, rather than values
+        #[arg(long)]
+        defined: bool,
+    },
 }
 
-impl PccList {
-    fn new(ident: &str) -> PccList {
-        PccList {
-            _ident: String::from(ident),
-            props: HashMap::new(),
-        }
+// show no progress bar while loading; set by --quiet
+static QUIET: AtomicBool = AtomicBool::new(false);
+
+// Show the files read by a load on a progress bar, drawn on stderr
+// when it is a terminal: files read of those known so far, bytes read,
+// and the file last read.
+fn progress_bar(pcc: &mut Pcc) -> Option<ProgressBar> {
+    if QUIET.load(Ordering::Relaxed) {
+        return None;
     }
-}
 
-#[derive(Serialize, Deserialize)]
-pub enum PccDatum {
-    Text(String),
-    List(PccList),
+    let style = ProgressStyle::with_template("[{elapsed}] {bar:30} {pos}/{len} files, {msg}")
+        .expect("progress template")
+        .progress_chars("=> ");
+    let bar = ProgressBar::new(0).with_style(style);
+    let shown = bar.clone();
+    let bytes = AtomicUsize::new(0);
+    pcc.on_progress(move |step| match step {
+        LoadProgress::LstQueued(count) => shown.inc_length(*count as u64),
+        LoadProgress::FileRead { path, bytes: size } => {
+            // PCC files are not known until read
+            if shown.position() >= shown.length().unwrap_or(0) {
+                shown.inc_length(1);
+            }
+            shown.inc(1);
+            let total = bytes.fetch_add(*size, Ordering::Relaxed) + size;
+            let name = Path::new(path).file_name().unwrap_or_default();
+            shown.set_message(format!("{} {}", HumanBytes(total as u64), name.display()));
+        }
+    });
+    Some(bar)
 }
 
-impl PccDatum {
-    pub fn as_mut_list(&mut self) -> Option<&mut PccList> {
-        match self {
-            PccDatum::List(l) => Some(l),
-            _ => None,
+// recursively read all PCC and LST data, starting at toplevel files,
+// plus the game mode system files
+fn try_load(cfg: &PccConfig, pccfiles: &[String]) -> io::Result<(Pcc, Duration)> {
+    let start = Instant::now();
+    if let Some(pcc) = cfg
+        .cachedir
+        .as_ref()
+        .and_then(|dir| cache::load(dir, cfg, pccfiles))
+    {
+        return Ok((pcc, start.elapsed()));
+    }
+
+    let mut pcc = Pcc::new(cfg);
+    let bar = progress_bar(&mut pcc);
+    let res = pcc.read_all(pccfiles, true);
+    if let Some(bar) = bar {
+        bar.finish_and_clear();
+    }
+    res?;
+    let load_time = start.elapsed();
+
+    if let Some(gamemode) = &cfg.gamemode {
+        if !pcc.provides_gamemode(gamemode) {
+            tracing::warn!("Game mode {} not provided by any loaded campaign", gamemode);
+        }
+    }
+
+    // load game mode system files, for requested or first loaded game mode
+    let gamemode = match &cfg.gamemode {
+        Some(mode) => Some(mode.clone()),
+        None => pcc.gamemodes().first().map(|m| m.to_string()),
+    };
+    if let Some(mode) = gamemode {
+        if let Err(e) = pcc.load_gamemode(&mode) {
+            // system files are often not beside the data; only say so
+            // if they were asked for
+            if cfg.systemdir.is_some() || cfg.gamemode.is_some() {
+                tracing::warn!("Game mode {} system files not loaded: {}", mode, e);
+            } else {
+                tracing::debug!("Game mode {} system files not loaded: {}", mode, e);
+            }
         }
     }
+
+    if let Some(dir) = &cfg.cachedir {
+        if let Err(e) = cache::store(dir, cfg, pccfiles, &pcc) {
+            tracing::warn!("Cache not written to {}: {}", dir, e);
+        }
+    }
+
+    Ok((pcc, load_time))
 }
 
-#[derive(Clone, Serialize, Deserialize)]
-pub struct PccConfig {
-    datadir: String,
+fn load(cfg: &PccConfig, pccfiles: &[String]) -> (Pcc, Duration) {
+    let (pcc, load_time) = try_load(cfg, pccfiles).unwrap_or_else(|e| {
+        eprintln!("PCC read error: {}", e);
+        process::exit(1);
+    });
+    if cfg.migrate {
+        report_migrations(pcc.migrations());
+    }
+    (pcc, load_time)
 }
 
-#[derive(Serialize, Deserialize)]
-pub struct Pcc {
-    config: PccConfig,
-    dict: HashMap<String, PccDatum>,
-    pcc_schema: HashMap<String, PccTag>,
-    aliases: HashMap<String, String>,
+// print each deprecated tag rewritten
+fn report_migrations(migrations: &[Diagnostic]) {
+    for diag in migrations {
+        eprintln!("{}", diag);
+    }
+    if !migrations.is_empty() {
+        eprintln!("{} tags migrated", migrations.len());
+    }
 }
 
-fn dir_from_path(full_path: &str) -> Option<String> {
-    let path = Path::new(full_path);
-    path.parent() // Get the parent directory as Option<&Path>
-        .and_then(|p| p.to_str()) // Convert &Path to Option<&str>
-        .map(|s| s.to_string()) // Convert &str to String
+// print diagnostics, returning true if there were any
+fn report(diags: &[Diagnostic], what: &str) -> bool {
+    for diag in diags {
+        println!("{}", diag);
+    }
+    if !diags.is_empty() {
+        eprintln!("{} {}", diags.len(), what);
+    }
+    !diags.is_empty()
 }
 
-fn new_pcc_schema() -> HashMap<String, PccTag> {
-    HashMap::from([
-        (String::from("PRECAMPAIGN"), PccTag::Text),
-        (String::from("BOOKTYPE"), PccTag::Text),
-        (String::from("CAMPAIGN"), PccTag::Text),
-        (String::from("COMPANIONLIST"), PccTag::Text),
-        (String::from("COPYRIGHT"), PccTag::Text),
-        (String::from("COVER"), PccTag::Text),
-        (String::from("DESC"), PccTag::Text),
-        (String::from("DYNAMIC"), PccTag::Text),
-        (String::from("FORWARDREF"), PccTag::Text),
-        (String::from("GAMEMODE"), PccTag::Text),
-        (String::from("GENRE"), PccTag::Text),
-        (String::from("HELP"), PccTag::Text),
-        (String::from("HIDETYPE"), PccTag::Text),
-        (String::from("INFOTEXT"), PccTag::Bool),
-        (String::from("ISOGL"), PccTag::Bool),
-        (String::from("ISLICENSED"), PccTag::Bool),
-        (String::from("KEY"), PccTag::Text),
-        (String::from("LOGO"), PccTag::Text),
-        (String::from("PCC"), PccTag::PccFile),
-        (String::from("PUBNAMELONG"), PccTag::Text),
-        (String::from("PUBNAMESHORT"), PccTag::Text),
-        (String::from("PUBNAMEWEB"), PccTag::Text),
-        (String::from("RANK"), PccTag::Number),
-        (String::from("SETTING"), PccTag::Text),
-        (String::from("SHOWINMENU"), PccTag::Text),
-        (String::from("SOURCEDATE"), PccTag::Date),
-        (String::from("SOURCELONG"), PccTag::Text),
-        (String::from("SOURCESHORT"), PccTag::Text),
-        (String::from("SOURCEWEB"), PccTag::Text),
-        (String::from("STATUS"), PccTag::Text),
-        (String::from("TYPE"), PccTag::Text),
-        (String::from("URL"), PccTag::Text),
-        (String::from("ABILITY"), PccTag::LstFile),
-        (String::from("ABILITYCATEGORY"), PccTag::LstFile),
-        (String::from("ALIGNMENT"), PccTag::LstFile),
-        (String::from("ARMORPROF"), PccTag::LstFile),
-        (String::from("BIOSET"), PccTag::LstFile),
-        (String::from("CLASS"), PccTag::LstFile),
-        (String::from("COMPANIONMOD"), PccTag::LstFile),
-        (String::from("DATATABLE"), PccTag::LstFile),
-        (String::from("DATACONTROL"), PccTag::LstFile), // includes wildcards?
-        (String::from("DEITY"), PccTag::LstFile),
-        (String::from("DOMAIN"), PccTag::LstFile),
-        (String::from("EQUIPMENT"), PccTag::LstFile),
-        (String::from("EQUIPMOD"), PccTag::LstFile),
-        (String::from("GLOBALMODIFIER"), PccTag::LstFile),
-        (String::from("KIT"), PccTag::LstFile),
-        (String::from("LANGUAGE"), PccTag::LstFile),
-        (String::from("RACE"), PccTag::LstFile),
-        (String::from("SAVE"), PccTag::LstFile),
-        (String::from("SHIELDPROF"), PccTag::LstFile),
-        (String::from("SIZE"), PccTag::LstFile),
-        (String::from("SKILL"), PccTag::LstFile),
-        (String::from("SPELL"), PccTag::LstFile),
-        (String::from("STAT"), PccTag::LstFile),
-        (String::from("TEMPLATE"), PccTag::LstFile),
-        (String::from("VARIABLE"), PccTag::LstFile),
-        (String::from("WEAPONPROF"), PccTag::LstFile),
-    ])
+// run a diagnostic command over the loaded data, returning true if
+// problems were found
+fn check(command: &Command, pcc: &Pcc, load_time: Duration) -> bool {
+    match command {
+        Command::Lint { .. } => report(&lint::lint(pcc).expect("Lint I/O error"), "lint warnings"),
+        Command::Stats { .. } => {
+            print!("{}", stats::stats(pcc, Some(load_time)));
+            false
+        }
+        Command::Validate { .. } => report(&validate::validate(pcc), "unresolved references"),
+        _ => unreachable!(),
+    }
 }
 
-impl Pcc {
-    // create a new Pcc object
-    pub fn new(config: &PccConfig) -> Pcc {
-        Pcc {
-            config: config.clone(),
-            dict: HashMap::new(),
-            pcc_schema: new_pcc_schema(),
-            aliases: HashMap::new(),
+// run a diagnostic command, then re-load and re-run each time a loaded
+// file changes
+fn watch_loop(cfg: &PccConfig, pccfiles: &[String], command: &Command) -> ! {
+    let (pcc, load_time) = load(cfg, pccfiles);
+    check(command, &pcc, load_time);
+    let mut files = pcc.files().to_vec();
+
+    loop {
+        eprintln!("Watching {} files for changes...", files.len());
+        let changed = watch::wait_for_change(&files).expect("Watch error");
+        eprintln!("{} changed, reloading", changed.display());
+
+        match try_load(cfg, pccfiles) {
+            Ok((pcc, load_time)) => {
+                check(command, &pcc, load_time);
+                files = pcc.files().to_vec();
+            }
+
+            // keep watching the previous file set, awaiting a fix
+            Err(e) => eprintln!("Load failed: {}", e),
         }
     }
+}
 
-    // Read a single LST record
-    fn read_lst_line(&mut self, datum: &mut PccDatum, line: &str) -> io::Result<()> {
-        // split input by <tab> into tokens
-        let mut tokens: Vec<&str> = line.split('\t').collect();
+// load, keeping only elements matching every filter
+fn load_filtered(cfg: &PccConfig, pccfiles: &[String], filters: &[ElemFilter]) -> Pcc {
+    let (mut pcc, _) = load(cfg, pccfiles);
+    if !filters.is_empty() {
+        pcc.retain_elems(|_, elem| filters.iter().all(|f| f.matches(elem)));
+    }
+    pcc
+}
 
-        // the first token is our symbol.  the remainder are attribs.
-        let raw_ident = tokens.remove(0);
+fn dump(cfg: &PccConfig, pccfiles: &[String], filters: &[ElemFilter], format: OutputFormat) {
+    let pcc = load_filtered(cfg, pccfiles, filters);
+    pcc.to_writer(format, &mut io::stdout().lock())
+        .expect("Output I/O error");
+}
 
-        // the ".MOD" suffix triggers update of existing elem
-        let is_mod = raw_ident.ends_with(".MOD");
-        let mut ident;
-        if is_mod {
-            ident = String::from(&raw_ident[0..(raw_ident.len() - 4)]);
-        } else {
-            ident = String::from(raw_ident);
-        }
+// the PCC file arguments of a command
+fn pccfiles_mut(command: &mut Command) -> Vec<&mut String> {
+    match command {
+        Command::Diff { oldpcc, newpcc } => vec![oldpcc, newpcc],
+        Command::Search { pccfile, .. } => vec![pccfile],
+        Command::Query(
+            QueryCommand::Abilities { pccfile, .. }
+            | QueryCommand::Aliases { pccfile, .. }
+            | QueryCommand::Bonuses { pccfile, .. }
+            | QueryCommand::Choices { pccfile, .. }
+            | QueryCommand::Defines { pccfile, .. }
+            | QueryCommand::Item { pccfile, .. }
+            | QueryCommand::Languages { pccfile, .. }
+            | QueryCommand::Lookup { pccfile, .. }
+            | QueryCommand::Prereqs { pccfile, .. }
+            | QueryCommand::Regions { pccfile, .. }
+            | QueryCommand::Show { pccfile, .. }
+            | QueryCommand::Spelllist { pccfile, .. }
+            | QueryCommand::Table { pccfile, .. }
+            | QueryCommand::Variables { pccfile, .. },
+        ) => vec![pccfile],
+        Command::Audit { pccfiles, .. }
+        | Command::Docgen { pccfiles, .. }
+        | Command::Dump { pccfiles }
+        | Command::Export(ExportCommand::FantasyGrounds { pccfiles, .. })
+        | Command::Export(ExportCommand::FiveTools { pccfiles, .. })
+        | Command::Export(ExportCommand::FlattenedLst { pccfiles, .. })
+        | Command::Export(ExportCommand::Foundry { pccfiles, .. })
+        | Command::Export(ExportCommand::Html { pccfiles, .. })
+        | Command::Export(ExportCommand::Sqlite { pccfiles, .. })
+        | Command::Fmt { pccfiles, .. }
+        | Command::Graph { pccfiles, .. }
+        | Command::Generate(GenerateCommand::Bio { pccfiles, .. })
+        | Command::Generate(GenerateCommand::Npc { pccfiles, .. })
+        | Command::Lint { pccfiles }
+        | Command::Manifest(ManifestCommand::Create { pccfiles, .. })
+        | Command::Repl { pccfiles }
+        | Command::Roundtrip { pccfiles, .. }
+        | Command::Stats { pccfiles }
+        | Command::Validate { pccfiles } => pccfiles.iter_mut().collect(),
+        Command::ListCampaigns
+        | Command::Manifest(ManifestCommand::Verify { .. })
+        | Command::NewCampaign { .. }
+        | Command::Schema => vec![],
+    }
+}
 
-        // if ident is an alias, lookup true ident
-        match self.aliases.get(&ident) {
-            None => {}
-            Some(alias) => {
-                log::debug!("ALIAS MATCH: {} => {}", ident, alias);
-                ident = alias.clone();
+// locate the PCC file of a campaign by name or KEY, returning its path
+// relative to datadir.  a name shared across game modes may be narrowed
+// by --gamemode.
+fn find_campaign(cfg: &PccConfig, name: &str) -> String {
+    let campaigns = campaign::discover(&cfg.datadirs, cfg.encoding).expect("Campaign scan error");
+    let matches: Vec<_> = campaigns
+        .iter()
+        .filter(|c| c.is_named(name))
+        .filter(|c| match &cfg.gamemode {
+            Some(mode) => c.gamemodes.iter().any(|m| m.eq_ignore_ascii_case(mode)),
+            None => true,
+        })
+        .collect();
+
+    match matches.as_slice() {
+        [] => {
+            eprintln!(
+                "Campaign {} not found in {}; see list-campaigns",
+                name,
+                cfg.datadirs.join(", ")
+            );
+            process::exit(1);
+        }
+        [found] => paths::within_datadirs(&cfg.datadirs, &found.path).to_string(),
+        _ => {
+            eprintln!(
+                "Campaign {} is ambiguous; select with --gamemode, or by path:",
+                name
+            );
+            for c in matches {
+                eprintln!("  {} ({})", c.path, c.gamemodes.join("|"));
             }
+            process::exit(2);
         }
+    }
+}
 
-        log::debug!("ID={}, is_mod={}", ident, is_mod);
-
-        // gather key=value attribs into a list
-        let mut attribs: Vec<(String, String)> = Vec::new();
-        for token in &tokens {
-            match token.split_once(':') {
-                None => {
-                    if !token.trim().is_empty() {
-                        log::debug!("\t{}", token);
-                        attribs.push((token.to_string(), String::from("")));
-                    }
-                }
-                Some((akey, aval)) => {
-                    log::debug!("\t{}={}", akey, aval);
-                    attribs.push((akey.to_string(), aval.to_string()));
-                }
+// Print the graph of campaign requirements, of all campaigns in
+// datadir, or of those of the given PCC files.  Exits 1 if problems
+// are found.
+fn graph_campaigns(cfg: &PccConfig, pccfiles: &[String], dot: bool) {
+    let campaigns = campaign::discover(&cfg.datadirs, cfg.encoding).expect("Campaign scan error");
+    let canonical: Vec<Option<PathBuf>> = campaigns
+        .iter()
+        .map(|c| files::canonicalize(&c.path).ok())
+        .collect();
+
+    let mut roots = Vec::new();
+    for pccfile in pccfiles {
+        let path = paths::in_datadirs(&cfg.datadirs, &paths::native(pccfile));
+        let path = files::canonicalize(path).ok();
+        match canonical.iter().position(|c| c.is_some() && *c == path) {
+            Some(i) => roots.push(i),
+            None => {
+                eprintln!("{}: not a campaign in {}", pccfile, cfg.datadirs.join(", "));
+                process::exit(1);
             }
         }
+    }
 
-        // pre-processing
-        for (key, val) in &attribs {
-            match key.as_str() {
-                "ABB" => {
-                    log::debug!("ALIAS: {}={}", val, ident);
-                    self.aliases.insert(val.to_string(), ident.clone());
-                }
+    let graph = CampaignGraph::new(&cfg.datadirs, campaigns, &roots);
+    let problems = match dot {
+        true => {
+            print!("{}", graph.to_dot());
+            for diag in &graph.problems {
+                eprintln!("{}", diag);
+            }
+            !graph.problems.is_empty()
+        }
+        false => {
+            print!("{}", graph.to_text());
+            report(&graph.problems, "campaign requirement problems")
+        }
+    };
+    if problems {
+        process::exit(1);
+    }
+}
 
-                "KEY" => {
-                    log::debug!("KEY: {}={}", val, ident);
-                    ident = val.to_string();
-                }
+// print a table of the campaigns in datadir
+fn list_campaigns(cfg: &PccConfig) {
+    let campaigns = campaign::discover(&cfg.datadirs, cfg.encoding).expect("Campaign scan error");
+
+    let mut rows = vec![[
+        String::from("CAMPAIGN"),
+        String::from("GAMEMODE"),
+        String::from("RANK"),
+        String::from("STATUS"),
+        String::from("PUBLISHER"),
+        String::from("PATH"),
+    ]];
+    for c in &campaigns {
+        let path = paths::within_datadirs(&cfg.datadirs, &c.path);
+        rows.push([
+            c.name.clone(),
+            c.gamemodes.join("|"),
+            c.rank.map(|r| r.to_string()).unwrap_or_default(),
+            c.status.clone().unwrap_or_default(),
+            c.pubnameshort.clone().unwrap_or_default(),
+            path.to_string(),
+        ]);
+    }
 
-                _ => {}
-            }
+    let mut widths = [0; 6];
+    for row in &rows {
+        for (w, cell) in widths.iter_mut().zip(row) {
+            *w = (*w).max(cell.chars().count());
         }
+    }
+    for row in &rows {
+        let cells: Vec<String> = row
+            .iter()
+            .zip(widths)
+            .map(|(cell, w)| format!("{:w$}", cell, w = w))
+            .collect();
+        println!("{}", cells.join("  ").trim_end());
+    }
+}
 
-        // grab ref to list inside datum, for update
-        let lst = datum.as_mut_list().unwrap();
+// dice rolls repeat for a given seed
+fn rng(seed: Option<u64>) -> fastrand::Rng {
+    match seed {
+        Some(seed) => fastrand::Rng::with_seed(seed),
+        None => fastrand::Rng::new(),
+    }
+}
 
-        // remove Elem for update, or create new if nonexistent
-        let mut obj;
-        if lst.props.contains_key(&ident) {
-            obj = lst.props.remove(&ident).unwrap();
-        } else {
-            obj = PccElem::new(&ident);
+fn generate(cfg: &PccConfig, cmd: &GenerateCommand, format: OutputFormat) {
+    match cmd {
+        GenerateCommand::Bio {
+            pccfiles,
+            race,
+            class,
+            sex,
+            seed,
+        } => {
+            let (pcc, _) = load(cfg, pccfiles);
+            let bio = BioSet::new(&pcc)
+                .generate(race, class.as_deref(), sex.as_deref(), &mut rng(*seed))
+                .unwrap_or_else(|e| {
+                    eprintln!("{}", e);
+                    process::exit(1);
+                });
+            output::write_value(format, &mut io::stdout().lock(), &bio).expect("Output I/O error");
         }
 
-        // merge new attribs into master attrib list
-        for attrib in attribs {
-            obj.attribs.push(attrib);
+        GenerateCommand::Npc {
+            pccfiles,
+            race,
+            class,
+            level,
+            seed,
+            markdown,
+        } => {
+            let (pcc, _) = load(cfg, pccfiles);
+            let npc =
+                npc::generate(&pcc, race, class, *level, &mut rng(*seed)).unwrap_or_else(|e| {
+                    eprintln!("{}", e);
+                    process::exit(1);
+                });
+
+            let mut out = io::stdout().lock();
+            let res = match markdown {
+                true => npc.write_markdown(&mut out),
+                false => output::write_value(format, &mut out, &npc),
+            };
+            res.expect("Output I/O error");
         }
+    }
+}
 
-        // push Elem with new attribs back into List
-        lst.props.insert(ident.to_string(), obj);
+// ABB aliases, by alias, and the names of KEYed elements, by list
+// then KEY
+#[derive(Serialize)]
+struct AliasReport<'a> {
+    aliases: BTreeMap<&'a str, &'a Alias>,
+    keys: BTreeMap<&'a str, &'a BTreeMap<String, KeyName>>,
+}
 
-        Ok(())
+// a CHOOSE of an element, and the selections it offers, if known
+#[derive(Serialize)]
+struct ChoiceReport<'a> {
+    list: &'a str,
+    name: &'a str,
+    choose: Choose,
+    options: Option<Vec<String>>,
+}
+
+// a variable of the data: its VARIABLE line, if any, the DEFINE tags
+// creating it, and the GLOBALMODIFIER modifiers upon it
+#[derive(Serialize)]
+struct VariableReport<'a> {
+    variable: Option<&'a Variable>,
+    defines: &'a [Definition],
+    modifiers: Vec<&'a Modifier>,
+}
+
+fn variable_report<'a>(vars: &'a Variables, name: &'a str) -> VariableReport<'a> {
+    VariableReport {
+        variable: vars.variable(name),
+        defines: vars
+            .defines
+            .get(name)
+            .map(Vec::as_slice)
+            .unwrap_or_default(),
+        modifiers: vars.modifiers_of(name).collect(),
     }
+}
 
-    // Read LST file into data dictionary
-    pub fn read_lst(
-        &mut self,
-        pcc_tag: &str,
-        basedir: &str,
-        lstpath: &str,
-        lstopts: &str,
-    ) -> io::Result<()> {
-        let mut fpath = String::new();
+// the variables and stats defined by the data
+#[derive(Serialize)]
+struct DefinedReport<'a> {
+    variables: BTreeMap<&'a str, VariableReport<'a>>,
+    stats: &'a BTreeMap<String, Vec<Definition>>,
+}
 
-        // parse path prefixes
-        let prefix = lstpath.chars().next().expect("Empty LST path");
-        match prefix {
-            // absolute path
-            '/' => {
-                fpath.push_str(lstpath);
-            }
+fn query(cfg: &PccConfig, cmd: &QueryCommand, format: OutputFormat) {
+    match cmd {
+        QueryCommand::Abilities {
+            pccfile,
+            category,
+            ident,
+        } => {
+            let (pcc, _) = load(cfg, slice::from_ref(pccfile));
+            let abilities = Abilities::new(&pcc);
+
+            let mut out = io::stdout().lock();
+            let res = match ident {
+                None => output::write_value(format, &mut out, &abilities.in_category(category)),
+                Some(ident) => match abilities.get(category, ident) {
+                    Some(ability) => output::write_value(format, &mut out, ability),
+                    None => {
+                        let names = abilities
+                            .in_category(category)
+                            .into_iter()
+                            .map(|a| a.name.as_str());
+                        let hint = suggest::did_you_mean(ident, names);
+                        eprintln!("{} ability {} not found{}", category, ident, hint);
+                        process::exit(1);
+                    }
+                },
+            };
+            res.expect("Output I/O error");
+        }
 
-            // base directory is toplevel data dir
-            '@' | '*' => {
-                let relpath = &lstpath[1..];
-                fpath.push_str(&self.config.datadir);
-                fpath.push_str(relpath);
-            }
+        QueryCommand::Aliases { pccfile, list } => {
+            let (pcc, _) = load(cfg, slice::from_ref(pccfile));
+            let wanted = |name: &str| list.as_ref().is_none_or(|l| l.eq_ignore_ascii_case(name));
+            let report = AliasReport {
+                aliases: pcc
+                    .aliases()
+                    .iter()
+                    .filter(|(_, alias)| wanted(&alias.list))
+                    .map(|(abb, alias)| (abb.as_str(), alias))
+                    .collect(),
+                keys: pcc
+                    .key_names()
+                    .iter()
+                    .filter(|(name, _)| wanted(name))
+                    .map(|(name, keys)| (name.as_str(), keys))
+                    .collect(),
+            };
+            output::write_value(format, &mut io::stdout().lock(), &report)
+                .expect("Output I/O error");
+        }
 
-            // "local file", in the same directory as PCC file
-            _ => {
-                fpath.push_str(basedir);
-                fpath.push_str("/");
-                fpath.push_str(lstpath);
-            }
+        QueryCommand::Bonuses { pccfile, character } => {
+            let profile = Profile::read(character, cfg.encoding).unwrap_or_else(|e| {
+                eprintln!("{}: {}", character, e);
+                process::exit(1);
+            });
+            let (pcc, _) = load(cfg, slice::from_ref(pccfile));
+            let summary = bonus::Engine::new(&pcc, &profile).summary();
+            output::write_value(format, &mut io::stdout().lock(), &summary)
+                .expect("Output I/O error");
         }
 
-        log::debug!("Pcc.read_lst({}, {}, \"{}\")", pcc_tag, fpath, lstopts);
+        QueryCommand::Choices { pccfile, name } => {
+            let (pcc, _) = load(cfg, slice::from_ref(pccfile));
+            let found = pcc.lookup(name);
+            if found.is_empty() {
+                eprintln!("{} not found{}", name, pcc.did_you_mean(None, name));
+                process::exit(1);
+            }
 
-        let mut datum;
+            let mut choices = Vec::new();
+            for (list, elem) in found {
+                for value in elem.get_all("CHOOSE") {
+                    let Some(choose) = Choose::parse(value) else {
+                        tracing::warn!("{} {}: CHOOSE:{} names no chooser", list, name, value);
+                        continue;
+                    };
+                    let options = choose.options(&pcc);
+                    choices.push(ChoiceReport {
+                        list,
+                        name: elem.name(),
+                        choose,
+                        options,
+                    });
+                }
+            }
+            if choices.is_empty() {
+                eprintln!("{} has no CHOOSE", name);
+                process::exit(1);
+            }
+            output::write_value(format, &mut io::stdout().lock(), &choices)
+                .expect("Output I/O error");
+        }
 
-        // Does the List record already exist?  if not, create a new one.
-        // Due to "second mutable borrow" issue, we must remove from
-        // HashMap, and then insert back into HashMap when we're done.
-        if !self.dict.contains_key(pcc_tag) {
-            datum = PccDatum::List(PccList::new(pcc_tag));
-        } else {
-            datum = self.dict.remove(pcc_tag).unwrap();
+        QueryCommand::Defines {
+            pccfile,
+            character,
+            name,
+        } => {
+            let profile = Profile::read(character, cfg.encoding).unwrap_or_else(|e| {
+                eprintln!("{}: {}", character, e);
+                process::exit(1);
+            });
+            let (pcc, _) = load(cfg, slice::from_ref(pccfile));
+            let engine = bonus::Engine::new(&pcc, &profile);
+            let vars = engine.vars();
+
+            let mut out = io::stdout().lock();
+            let res = match name {
+                None => {
+                    let mut values = BTreeMap::new();
+                    for (name, value) in vars.values() {
+                        match value {
+                            Ok(value) => {
+                                values.insert(name, value);
+                            }
+                            Err(e) => tracing::warn!("{}: {}", name, e),
+                        }
+                    }
+                    output::write_value(format, &mut out, &values)
+                }
+                Some(name) => match vars.value(name) {
+                    Ok(value) => output::write_value(format, &mut out, &value),
+                    Err(e) => {
+                        eprintln!("{}", e);
+                        process::exit(1);
+                    }
+                },
+            };
+            res.expect("Output I/O error");
         }
 
-        // record type check
-        match &datum {
-            PccDatum::List(_val) => {}
-            _ => {
-                // todo: technically an error, not a panic
-                panic!("key is not a list");
-            }
+        QueryCommand::Item {
+            pccfile,
+            base,
+            eqmods,
+        } => {
+            let (pcc, _) = load(cfg, slice::from_ref(pccfile));
+            let eqmods: Vec<&str> = eqmods.iter().map(|m| m.as_str()).collect();
+            let item = Armory::new(&pcc)
+                .materialize(base, &eqmods)
+                .unwrap_or_else(|e| {
+                    eprintln!("{}", e);
+                    process::exit(1);
+                });
+            output::write_value(format, &mut io::stdout().lock(), &item).expect("Output I/O error");
         }
 
-        // open and buffer list file input data
-        let file = File::open(fpath)?;
-        let rdr = BufReader::new(file);
+        QueryCommand::Languages { pccfile, race } => {
+            let (pcc, _) = load(cfg, slice::from_ref(pccfile));
 
-        // iterate through each text file line
-        for line_res in rdr.lines() {
-            let line = line_res.expect("BufReader.lst parse failed");
+            let mut out = io::stdout().lock();
+            let res = match race {
+                None => output::write_value(format, &mut out, &language::languages(&pcc)),
+                Some(race) => match language::race_languages(&pcc, race) {
+                    Some(languages) => output::write_value(format, &mut out, &languages),
+                    None => {
+                        eprintln!(
+                            "race {} not found{}",
+                            race,
+                            pcc.did_you_mean(Some("RACE"), race)
+                        );
+                        process::exit(1);
+                    }
+                },
+            };
+            res.expect("Output I/O error");
+        }
 
-            // comments and empty lines
-            let ch = line.chars().next();
-            if ch.is_none() || ch == Some('#') {
-                continue;
+        QueryCommand::Lookup { pccfile, name } => {
+            let (pcc, _) = load(cfg, slice::from_ref(pccfile));
+            let found = pcc.lookup(name);
+            if found.is_empty() {
+                eprintln!("{} not found{}", name, pcc.did_you_mean(None, name));
+                process::exit(1);
             }
 
-            // parse line
-            self.read_lst_line(&mut datum, &line)?;
+            let mut by_list: BTreeMap<&str, Vec<&PccElem>> = BTreeMap::new();
+            for (list, elem) in found {
+                by_list.entry(list).or_default().push(elem);
+            }
+            output::write_value(format, &mut io::stdout().lock(), &by_list)
+                .expect("Output I/O error");
         }
 
-        // finally, replace updated datum in dictionary
-        self.dict.insert(pcc_tag.to_string(), datum);
+        QueryCommand::Prereqs {
+            pccfile,
+            character,
+            name,
+        } => {
+            let profile = Profile::read(character, cfg.encoding).unwrap_or_else(|e| {
+                eprintln!("{}: {}", character, e);
+                process::exit(1);
+            });
+            let (pcc, _) = load(cfg, slice::from_ref(pccfile));
+            let found = pcc.lookup(name);
+            if found.is_empty() {
+                eprintln!("{} not found{}", name, pcc.did_you_mean(None, name));
+                process::exit(1);
+            }
 
-        Ok(())
-    }
+            let mut by_list: BTreeMap<&str, Vec<qualify::Outcome>> = BTreeMap::new();
+            for (list, elem) in found {
+                by_list
+                    .entry(list)
+                    .or_default()
+                    .extend(qualify::check(&pcc, &profile, elem));
+            }
+            output::write_value(format, &mut io::stdout().lock(), &by_list)
+                .expect("Output I/O error");
 
-    fn read_pcc_line(&mut self, basedir: &str, line: &str) -> io::Result<()> {
-        // split on ':'
-        let sor = line.split_once(':');
-        if sor.is_none() {
-            return Err(Error::new(ErrorKind::Other, "PCC invalid line:colon"));
+            let failed = by_list.values().flatten().any(|o| o.passed == Some(false));
+            if failed {
+                process::exit(1);
+            }
         }
 
-        let mut lhs;
-        let rhs;
-        (lhs, rhs) = sor.unwrap();
-        let _tag_negate;
+        QueryCommand::Regions { pccfile, region } => {
+            let (pcc, _) = load(cfg, slice::from_ref(pccfile));
+            let regions = Regions::new(&pcc);
 
-        if lhs.chars().next() == Some('!') {
-            lhs = &lhs[1..];
-            _tag_negate = true;
-        } else {
-            _tag_negate = false;
+            let mut out = io::stdout().lock();
+            let res = match region {
+                None => output::write_value(format, &mut out, &regions),
+                Some(name) => match regions.region(name) {
+                    Some(region) => output::write_value(format, &mut out, region),
+                    None => {
+                        eprintln!("region {} not found", name);
+                        process::exit(1);
+                    }
+                },
+            };
+            res.expect("Output I/O error");
         }
 
-        // is this tag in the known schema?
-        let tagtype_res = self.pcc_schema.get(lhs);
-        if tagtype_res.is_none() {
-            return Err(Error::new(
-                ErrorKind::Other,
-                format!("PCC invalid key {}", lhs),
-            ));
+        QueryCommand::Show {
+            pccfile,
+            list,
+            ident,
+            columns,
+            color,
+        } => {
+            let (pcc, _) = load(cfg, slice::from_ref(pccfile));
+            let Some(lst) = pcc.list(list) else {
+                eprintln!("List {} not loaded", list);
+                process::exit(1);
+            };
+
+            let elem = ident.as_ref().map(|ident| {
+                match lst.get(ident).or_else(|| pcc.lookup_in(list, ident)) {
+                    Some(elem) => (elem.ident(), elem),
+                    None => {
+                        let hint = pcc.did_you_mean(Some(list), ident);
+                        eprintln!("{} {} not found{}", list, ident, hint);
+                        process::exit(1);
+                    }
+                }
+            });
+
+            if format == OutputFormat::Table {
+                let elems: Vec<(&str, &PccElem)> = match elem {
+                    Some(elem) => vec![elem],
+                    None => lst.sorted(),
+                };
+                let columns = match columns.is_empty() {
+                    true => table::default_columns(list, &elems),
+                    false => columns.clone(),
+                };
+                for line in table::render(&elems, &columns, color.enabled()) {
+                    println!("{}", line);
+                }
+                return;
+            }
+
+            let mut out = io::stdout().lock();
+            let res = match elem {
+                None => output::write_value(format, &mut out, lst),
+                Some((_, elem)) => output::write_value(format, &mut out, elem),
+            };
+            res.expect("Output I/O error");
         }
 
-        let tagtype = tagtype_res.unwrap();
-        match tagtype {
-            // input included PCC file
-            PccTag::PccFile => {
-                // relative path indicated by leading '@'
-                let (is_rel, fpath);
-                if rhs.chars().nth(0) == Some('@') {
-                    is_rel = true;
-                    fpath = &rhs[1..];
-                } else {
-                    is_rel = false;
-                    fpath = &rhs;
+        QueryCommand::Spelllist {
+            pccfile,
+            class,
+            level,
+        } => {
+            let (pcc, _) = load(cfg, slice::from_ref(pccfile));
+            let mut list = spell::spell_list(&pcc, class);
+
+            let mut out = io::stdout().lock();
+            let res = match level {
+                None => output::write_value(format, &mut out, &list),
+                Some(level) => {
+                    let spells = list.remove(level).unwrap_or_default();
+                    output::write_value(format, &mut out, &spells)
                 }
+            };
+            res.expect("Output I/O error");
+        }
 
-                self.read(fpath, is_rel)?;
-            }
-
-            // read LST file
-            PccTag::LstFile => match rhs.split_once('|') {
-                None => self.read_lst(lhs, &basedir, rhs, String::from("").as_str())?,
-                Some((lstpath, lstopts)) => self.read_lst(lhs, &basedir, lstpath, lstopts)?,
-            },
-
-            // handle other data types
-            PccTag::Bool | PccTag::Date | PccTag::Number | PccTag::Text => {
-                // store in global data dictionary
-                let tag = self.dict.get_mut(lhs);
-                match tag {
-                    // new key; store in hashmap
+        QueryCommand::Table {
+            pccfile,
+            table,
+            row,
+            column,
+        } => {
+            let (pcc, _) = load(cfg, slice::from_ref(pccfile));
+            let Some(tbl) = datatable::table(&pcc, table) else {
+                let hint = pcc.did_you_mean(Some("DATATABLE"), table);
+                eprintln!("DATATABLE {} not found{}", table, hint);
+                process::exit(1);
+            };
+
+            let mut out = io::stdout().lock();
+            let res = match (row, column) {
+                (None, _) => output::write_value(format, &mut out, &tbl),
+                (Some(row), None) => match tbl.row(row) {
+                    Some(cells) => output::write_value(format, &mut out, &cells),
+                    None => {
+                        let keys = tbl.rows.iter().filter_map(|r| match r.first() {
+                            Some(datatable::Value::Text(key)) => Some(key.as_str()),
+                            _ => None,
+                        });
+                        let hint = suggest::did_you_mean(row, keys);
+                        eprintln!("{}: row {} not found{}", table, row, hint);
+                        process::exit(1);
+                    }
+                },
+                (Some(row), Some(column)) => match tbl.lookup(row, column) {
+                    Some(value) => output::write_value(format, &mut out, value),
                     None => {
-                        self.dict
-                            .insert(lhs.to_string(), PccDatum::Text(rhs.to_string()));
+                        eprintln!("{}: no {} in row {}", table, column, row);
+                        process::exit(1);
                     }
+                },
+            };
+            res.expect("Output I/O error");
+        }
 
-                    // existing key; append to string value
-                    Some(datum) => match datum {
-                        PccDatum::Text(val) => {
-                            val.push_str("\n");
-                            val.push_str(rhs);
+        QueryCommand::Variables {
+            pccfile,
+            name,
+            defined,
+        } => {
+            let (pcc, _) = load(cfg, slice::from_ref(pccfile));
+            let vars = Variables::new(&pcc);
+
+            let mut out = io::stdout().lock();
+            let res = match (name, defined) {
+                (None, true) => {
+                    let report = DefinedReport {
+                        variables: vars
+                            .names()
+                            .map(|name| (name, variable_report(&vars, name)))
+                            .collect(),
+                        stats: &vars.stat_defines,
+                    };
+                    output::write_value(format, &mut out, &report)
+                }
+                (Some(name), true) => {
+                    let report = variable_report(&vars, name);
+                    if report.variable.is_none() && report.defines.is_empty() {
+                        eprintln!("variable {} not defined", name);
+                        process::exit(1);
+                    }
+                    output::write_value(format, &mut out, &report)
+                }
+                (None, false) => {
+                    let mut values = BTreeMap::new();
+                    for (name, value) in vars.values() {
+                        match value {
+                            Ok(value) => {
+                                values.insert(name, value);
+                            }
+                            Err(e) => tracing::warn!("{}: {}", name, e),
                         }
-                        _ => {}
-                    },
+                    }
+                    output::write_value(format, &mut out, &values)
                 }
+                (Some(name), false) => match vars.value(name) {
+                    Ok(value) => output::write_value(format, &mut out, &value),
+                    Err(e) => {
+                        eprintln!("{}", e);
+                        process::exit(1);
+                    }
+                },
+            };
+            res.expect("Output I/O error");
+        }
+    }
+}
+
+// The data directories: those given, then those of
+// PCGTOOLS_DATA_PATH, a list like PATH.  If none, the current
+// directory, unless it holds no PCC files and PCGen is installed, in
+// which case PCGen's data directory.
+fn datadirs(given: &[String]) -> Vec<String> {
+    let mut dirs: Vec<String> = given.iter().map(|dir| paths::native(dir)).collect();
+    if let Some(path) = env::var_os("PCGTOOLS_DATA_PATH") {
+        dirs.extend(
+            env::split_paths(&path)
+                .filter(|dir| !dir.as_os_str().is_empty())
+                .map(|dir| dir.to_string_lossy().to_string()),
+        );
+    }
+    if dirs.is_empty() {
+        let installed = match install::has_campaigns(Path::new(".")) {
+            true => None,
+            false => install::pcgen_datadir(),
+        };
+        match installed {
+            Some(dir) => {
+                tracing::info!("Using PCGen data directory {}", dir.display());
+                dirs.push(dir.to_string_lossy().to_string());
             }
+            None => dirs.push(String::from(".")),
+        }
+    }
+    dirs
+}
+
+// Log to stderr, showing warnings by default; RUST_LOG overrides.
+// Messages logged while a file is read name the file, and those
+// including it.
+fn init_logging(format: &str) {
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("warn"));
+    let builder = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(io::stderr)
+        .with_ansi(io::stderr().is_terminal());
+    match format {
+        "json" => builder.json().with_span_list(true).init(),
+        _ => builder.without_time().init(),
+    }
+}
+
+fn main() {
+    // parse command line options
+    let args = Args::parse();
+    init_logging(&args.log_format);
+    QUIET.store(args.quiet, Ordering::Relaxed);
+
+    // PCC files may be given, or included, by URL
+    let downloads = match &args.download_cache {
+        Some(dir) => PathBuf::from(dir),
+        None => remote::default_download_dir(),
+    };
+    files::set_provider(Arc::new(HttpFiles::new(
+        files::provider(),
+        &downloads,
+        args.offline,
+    )));
+
+    let cfg = PccConfig {
+        datadirs: datadirs(&args.datadir),
+        gamemode: args.gamemode.clone(),
+        systemdir: args.systemdir.clone(),
+        vendordir: args.vendordir.clone(),
+        homebrewdir: args.homebrewdir.clone(),
+        cachedir: args.cache.clone(),
+        encoding: args.encoding,
+        provenance: args.with_provenance,
+        duplicates: args.duplicates,
+        allow_unknown_tags: args.allow_unknown_tags,
+        schema: args.schema.clone(),
+        migrate: args.migrate,
+        migrations: args.migrations.clone(),
+        compat: args.data_compat,
+    };
+
+    let mut command = match (args.command, args.pccfiles) {
+        (Some(_), pccfiles) if !pccfiles.is_empty() => {
+            eprintln!("Unexpected {} before subcommand; see --help", pccfiles[0]);
+            process::exit(2);
+        }
+        (Some(command), _) => command,
+        (None, pccfiles) if !pccfiles.is_empty() => Command::Dump { pccfiles },
+        (None, _) => {
+            eprintln!("A PCC file or subcommand is required; see --help");
+            process::exit(2);
         }
+    };
+
+    if args.campaign {
+        for pccfile in pccfiles_mut(&mut command) {
+            *pccfile = find_campaign(&cfg, pccfile);
+        }
+    }
+
+    let watchable = matches!(
+        command,
+        Command::Lint { .. } | Command::Stats { .. } | Command::Validate { .. }
+    );
+    if args.watch && !watchable {
+        eprintln!("--watch is supported by lint, stats and validate only");
+        process::exit(2);
+    }
 
-        Ok(())
+    let filters: Vec<ElemFilter> = args.filter.into_iter().chain(args.source).collect();
+    let filterable = matches!(
+        command,
+        Command::Docgen { .. } | Command::Dump { .. } | Command::Export(_)
+    );
+    let tabular = matches!(command, Command::Query(QueryCommand::Show { .. }));
+    if args.format == OutputFormat::Table && !tabular {
+        eprintln!("--format table is supported by query show only");
+        process::exit(2);
     }
+    if !filters.is_empty() && !filterable {
+        eprintln!("--filter and --source are supported by dump, docgen and export only");
+        process::exit(2);
+    }
+
+    match &command {
+        Command::Audit { pcgfile, pccfiles } => {
+            let character = Character::read(pcgfile, cfg.encoding).unwrap_or_else(|e| {
+                eprintln!("{}: {}", pcgfile, e);
+                process::exit(1);
+            });
+            let pccfiles = match pccfiles.is_empty() {
+                true => character
+                    .campaigns
+                    .iter()
+                    .map(|c| find_campaign(&cfg, c))
+                    .collect(),
+                false => pccfiles.clone(),
+            };
+            let (pcc, _) = load(&cfg, &pccfiles);
+            if report(&character.audit(&pcc), "character problems") {
+                process::exit(1);
+            }
+        }
 
-    // recursively read PCC file data into Pcc object
-    pub fn read(&mut self, pccpath: &str, is_relative: bool) -> io::Result<()> {
-        let mut fpath = String::new();
+        Command::Diff { oldpcc, newpcc } => {
+            let (older, _) = load(&cfg, slice::from_ref(oldpcc));
+            let (newer, _) = load(&cfg, slice::from_ref(newpcc));
+            for ld in diff::diff(&older, &newer) {
+                print!("{}", ld);
+            }
+        }
 
-        if is_relative {
-            fpath.push_str(&self.config.datadir);
+        Command::Docgen { pccfiles, outdir } => {
+            let pcc = load_filtered(&cfg, pccfiles, &filters);
+            export::markdown::export(&pcc, outdir).expect("Markdown export error")
         }
 
-        fpath.push_str(pccpath);
+        Command::Dump { pccfiles } => dump(&cfg, pccfiles, &filters, args.format),
 
-        if fpath.contains("\\") {
-            fpath = fpath.replace("\\", "/");
+        Command::Export(ExportCommand::FantasyGrounds {
+            pccfiles,
+            outdir,
+            ruleset,
+        }) => {
+            let pcc = load_filtered(&cfg, pccfiles, &filters);
+            export::fantasygrounds::export(&pcc, outdir, ruleset)
+                .expect("Fantasy Grounds export error")
         }
 
-        let basedir = dir_from_path(&fpath).unwrap();
+        Command::Export(ExportCommand::FiveTools { pccfiles, outfile }) => {
+            let pcc = load_filtered(&cfg, pccfiles, &filters);
+            let report = export::fivetools::export(&pcc, outfile).expect("5eTools export error");
+            eprint!("{}", report);
+        }
+
+        Command::Export(ExportCommand::Foundry {
+            pccfiles,
+            outdir,
+            system,
+        }) => {
+            let pcc = load_filtered(&cfg, pccfiles, &filters);
+            export::foundry::export(&pcc, outdir, *system).expect("Foundry export error")
+        }
 
-        log::debug!("Pcc.read({})", fpath);
+        Command::Export(ExportCommand::FlattenedLst { pccfiles, outdir }) => {
+            let pcc = load_filtered(&cfg, pccfiles, &filters);
+            export::lst::export(&pcc, outdir).expect("LST export error")
+        }
 
-        let file = File::open(fpath)?;
-        let rdr = BufReader::new(file);
+        Command::Export(ExportCommand::Html { pccfiles, outdir }) => {
+            let pcc = load_filtered(&cfg, pccfiles, &filters);
+            export::html::export(&pcc, outdir).expect("HTML export error")
+        }
 
-        for line_res in rdr.lines() {
-            let line = line_res.expect("BufReader parse failed");
+        Command::Export(ExportCommand::Sqlite { pccfiles, dbfile }) => {
+            let pcc = load_filtered(&cfg, pccfiles, &filters);
+            export::sqlite::export(&pcc, dbfile).expect("SQLite export error")
+        }
 
-            // comments and empty lines
-            let ch = line.chars().next();
-            if ch.is_none() || ch == Some('#') {
-                continue;
+        Command::Generate(cmd) => generate(&cfg, cmd, args.format),
+
+        Command::ListCampaigns => list_campaigns(&cfg),
+
+        Command::Manifest(ManifestCommand::Create { pccfiles, output }) => {
+            let (pcc, _) = load(&cfg, pccfiles);
+            let manifest = Manifest::new(&pcc, pccfiles).expect("Manifest I/O error");
+            if let Err(e) = manifest.write(output) {
+                eprintln!("{}: {}", output, e);
+                process::exit(1);
             }
+            eprintln!("{} files recorded in {}", manifest.files.len(), output);
+        }
 
-            self.read_pcc_line(&basedir, &line)?;
+        Command::Manifest(ManifestCommand::Verify { manifest }) => {
+            let manifest = match Manifest::read(manifest) {
+                Ok(m) => m,
+                Err(e) => {
+                    eprintln!("{}: {}", manifest, e);
+                    process::exit(1);
+                }
+            };
+            let mismatches = manifest.verify(&cfg.datadirs);
+            for (entry, mismatch) in &mismatches {
+                match mismatch {
+                    Mismatch::Missing => println!("{}: missing", entry.path),
+                    Mismatch::Unreadable(e) => println!("{}: unreadable: {}", entry.path, e),
+                    Mismatch::Modified { size, sha256 } => println!(
+                        "{}: modified: {} bytes, SHA-256 {}; expected {} bytes, SHA-256 {}",
+                        entry.path, size, sha256, entry.size, entry.sha256
+                    ),
+                }
+            }
+            if !mismatches.is_empty() {
+                eprintln!(
+                    "{} of {} files differ",
+                    mismatches.len(),
+                    manifest.files.len()
+                );
+                process::exit(1);
+            }
+            eprintln!("{} files verified", manifest.files.len());
         }
 
-        Ok(())
-    }
+        Command::Lint { pccfiles }
+        | Command::Stats { pccfiles }
+        | Command::Validate { pccfiles } => {
+            if args.watch {
+                watch_loop(&cfg, pccfiles, &command);
+            }
 
-    // display all data in data dictionary
-    pub fn display(&self) {
-        println!("{}", serde_json::to_string_pretty(self).unwrap());
-    }
-}
+            let (pcc, load_time) = load(&cfg, pccfiles);
+            if check(&command, &pcc, load_time) {
+                process::exit(1);
+            }
+        }
 
-fn main() {
-    env_logger::builder().format_timestamp(None).init();
+        Command::NewCampaign {
+            outdir,
+            name,
+            gamemode,
+            source_short,
+            source_web,
+            lists,
+        } => {
+            let scaffold = writer::Scaffold {
+                name: name.clone(),
+                gamemode: gamemode.clone(),
+                source_short: source_short.clone(),
+                source_web: source_web.clone(),
+                lists: match lists.is_empty() {
+                    true => writer::SCAFFOLD_LISTS.map(String::from).to_vec(),
+                    false => lists.clone(),
+                },
+            };
+            match writer::new_campaign(Path::new(outdir), &scaffold) {
+                Ok(path) => println!("{}", path.display()),
+                Err(e) => {
+                    eprintln!("{}", e);
+                    process::exit(1);
+                }
+            }
+        }
 
-    // parse command line options
-    let args = Args::parse();
+        Command::Query(cmd) => query(&cfg, cmd, args.format),
+
+        Command::Schema => output::write_value(
+            args.format,
+            &mut io::stdout().lock(),
+            &jsonschema::dump_schema(),
+        )
+        .expect("Output I/O error"),
+
+        Command::Search {
+            pccfile,
+            pattern,
+            regex,
+        } => {
+            let pattern = match regex {
+                false => search::Pattern::text(pattern),
+                true => search::Pattern::regex(pattern).unwrap_or_else(|e| {
+                    eprintln!("Invalid pattern: {}", e);
+                    process::exit(2);
+                }),
+            };
+            let (pcc, _) = load(&cfg, slice::from_ref(pccfile));
+            let matches = search::search(&pcc, &pattern);
+            for m in &matches {
+                println!("{}", m);
+            }
+            if matches.is_empty() {
+                process::exit(1);
+            }
+        }
 
-    let mut datadir = args.datadir.clone();
-    if datadir.chars().last() != Some('/') {
-        datadir.push_str("/"); // todo: windows
-    }
+        Command::Fmt {
+            pccfiles,
+            check,
+            fix,
+        } => {
+            let (pcc, _) = load(&cfg, pccfiles);
+            let migrator = match fix {
+                true => Some(
+                    Migrator::load(cfg.migrations.as_deref()).unwrap_or_else(|e| {
+                        eprintln!("Migration rules error: {}", e);
+                        process::exit(1);
+                    }),
+                ),
+                false => None,
+            };
+            let (changed, migrations) = lstfmt::format_files(&pcc, *check, migrator.as_ref())
+                .expect("Formatting I/O error");
+            report_migrations(&migrations);
+            for path in &changed {
+                println!("{}", path);
+            }
+            if *check && !changed.is_empty() {
+                process::exit(1);
+            }
+        }
 
-    // create new Pcc object
-    let pcc_cfg = PccConfig { datadir };
-    let mut pcc = Pcc::new(&pcc_cfg);
+        Command::Graph {
+            pccfiles,
+            dot,
+            campaigns: true,
+        } => graph_campaigns(&cfg, pccfiles, *dot),
+
+        Command::Graph { pccfiles, dot, .. } => {
+            let (pcc, _) = load(&cfg, pccfiles);
+            let graph = IncludeGraph::new(&pcc);
+            match dot {
+                true => print!("{}", graph.to_dot()),
+                false => print!("{}", graph.to_text()),
+            }
+        }
 
-    // recursively read all PCC and LST data, starting at toplevel file
-    pcc.read(&args.pccfile, true).expect("PCC.read I/O error");
+        Command::Roundtrip { pccfiles, outdir } => {
+            let (pcc, _) = load(&cfg, pccfiles);
+            let dir = match outdir {
+                Some(dir) => PathBuf::from(dir),
+                None => env::temp_dir().join(format!("pcgtools-roundtrip-{}", process::id())),
+            };
+            let res = writer::roundtrip(&pcc, &dir);
+            if outdir.is_none() {
+                let _ = fs::remove_dir_all(&dir);
+            }
+            let diags = res.expect("Round trip I/O error");
+            if report(&diags, "round trip differences") {
+                process::exit(1);
+            }
+        }
 
-    // debug: display data dictionary
-    pcc.display();
+        Command::Repl { pccfiles } => {
+            let (mut pcc, _) = load(&cfg, pccfiles);
+            let reload = || try_load(&cfg, pccfiles).map(|(pcc, _)| pcc);
+            repl::run(&mut pcc, reload, io::stdin().lock(), io::stdout()).expect("REPL I/O error");
+        }
+    }
 }
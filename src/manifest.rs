@@ -0,0 +1,105 @@
+//
+// manifest.rs -- manifests of loaded files, for verifying data snapshots
+//
+// Copyright (c) 2024 Jeff Garzik
+//
+// This file is part of the pcgtoolssoftware project covered under
+// the MIT License.  For the full license text, please see the LICENSE
+// file in the root directory of this project.
+// SPDX-License-Identifier: MIT
+
+use crate::files;
+use crate::paths;
+use crate::pcc::Pcc;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::BTreeSet,
+    fs::File,
+    io::{self, BufWriter, Error, ErrorKind, Write},
+};
+
+// a file of a manifest; path is relative to the data directory
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ManifestEntry {
+    pub path: String,
+    pub size: u64,
+    pub sha256: String,
+}
+
+// The PCC and LST files read by a load, with the size and SHA-256 hash
+// of each, so that a copy of the data may be checked against them.
+// pccfiles are the PCC files loaded, as given.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Manifest {
+    pub pccfiles: Vec<String>,
+    pub files: Vec<ManifestEntry>,
+}
+
+// a file differing from its manifest entry
+#[derive(Clone, Debug)]
+pub enum Mismatch {
+    Missing,
+    Unreadable(String),
+    Modified { size: u64, sha256: String },
+}
+
+// the size and SHA-256 hash, in hex, of a file, as read by a load
+fn file_hash(path: &str) -> io::Result<(u64, String)> {
+    let data = files::read(path)?;
+    let hash = Sha256::digest(&data);
+    Ok((data.len() as u64, format!("{:x}", hash)))
+}
+
+impl Manifest {
+    // a manifest of the files of a load, in path order
+    pub fn new(pcc: &Pcc, pccfiles: &[String]) -> io::Result<Manifest> {
+        let datadirs = &pcc.config().datadirs;
+        let loaded: BTreeSet<&str> = pcc.files().iter().map(|f| f.path.as_str()).collect();
+
+        let mut files = Vec::new();
+        for path in loaded {
+            let (size, sha256) = file_hash(path)?;
+            files.push(ManifestEntry {
+                path: paths::within_datadirs(datadirs, path).replace('\\', "/"),
+                size,
+                sha256,
+            });
+        }
+
+        Ok(Manifest {
+            pccfiles: pccfiles.to_vec(),
+            files,
+        })
+    }
+
+    pub fn read(path: &str) -> io::Result<Manifest> {
+        serde_json::from_slice(&files::read(path)?).map_err(Error::other)
+    }
+
+    pub fn write(&self, path: &str) -> io::Result<()> {
+        let mut w = BufWriter::new(File::create(path)?);
+        serde_json::to_writer_pretty(&mut w, self)?;
+        writeln!(w)?;
+        w.flush()
+    }
+
+    // Check the files of the manifest, as found in the data
+    // directories, returning those missing or modified, in manifest
+    // order.
+    pub fn verify(&self, datadirs: &[String]) -> Vec<(&ManifestEntry, Mismatch)> {
+        let mut mismatches = Vec::new();
+        for entry in &self.files {
+            let path = paths::in_datadirs(datadirs, &paths::native(&entry.path));
+            match file_hash(&path) {
+                Ok((size, sha256)) if size == entry.size && sha256 == entry.sha256 => {}
+                Ok((size, sha256)) => mismatches.push((entry, Mismatch::Modified { size, sha256 })),
+                Err(e) if e.kind() == ErrorKind::NotFound => {
+                    mismatches.push((entry, Mismatch::Missing))
+                }
+                Err(e) => mismatches.push((entry, Mismatch::Unreadable(e.to_string()))),
+            }
+        }
+        mismatches
+    }
+}
@@ -0,0 +1,207 @@
+//
+// migrate.rs -- rewriting deprecated LST tags to their modern forms
+//
+// Copyright (c) 2024 Jeff Garzik
+//
+// This file is part of the pcgtoolssoftware project covered under
+// the MIT License.  For the full license text, please see the LICENSE
+// file in the root directory of this project.
+// SPDX-License-Identifier: MIT
+
+use crate::diag::Diagnostic;
+use crate::files;
+use regex::Regex;
+use serde::Deserialize;
+use std::io::{self, Error};
+
+// migration rules built in to pcgtools; see migrate_rules.toml
+const DEFAULT_RULES: &str = include_str!("migrate_rules.toml");
+
+// a rewrite of a deprecated tag; see migrate_rules.toml
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Rule {
+    pub tag: String,
+    #[serde(default)]
+    pub lists: Vec<String>,
+    #[serde(default)]
+    pub except: Vec<String>,
+    #[serde(rename = "match")]
+    pub pattern: Option<String>,
+    pub split: Option<(String, String)>,
+    pub replace: Vec<String>,
+    #[serde(default)]
+    pub note: String,
+}
+
+#[derive(Deserialize)]
+struct RuleFile {
+    #[serde(default)]
+    rule: Vec<Rule>,
+}
+
+// a rule, with its pattern compiled
+#[derive(Clone, Debug)]
+struct Compiled {
+    rule: Rule,
+    pattern: Regex,
+}
+
+fn parse_rules(text: &str) -> Result<Vec<Compiled>, String> {
+    let file: RuleFile = toml::from_str(text).map_err(|e| e.to_string())?;
+    file.rule
+        .into_iter()
+        .map(|rule| {
+            let pattern = rule.pattern.as_deref().unwrap_or("(?s)^.*$");
+            let pattern =
+                Regex::new(pattern).map_err(|e| format!("rule for {}: {}", rule.tag, e))?;
+            Ok(Compiled { rule, pattern })
+        })
+        .collect()
+}
+
+// Rewrites deprecated tags by rules: those of a user's file, if any,
+// then those built in.  The first rule matching a tag applies; its
+// replacements are not themselves rewritten.
+#[derive(Clone, Debug)]
+pub struct Migrator {
+    rules: Vec<Compiled>,
+}
+
+impl Migrator {
+    pub fn builtin() -> Migrator {
+        Migrator {
+            rules: parse_rules(DEFAULT_RULES).expect("built-in migration rules"),
+        }
+    }
+
+    // the built-in rules, after the rules of a TOML file
+    fn with_rules(path: &str) -> io::Result<Migrator> {
+        let text = files::read_to_string(path)?;
+        let mut rules = parse_rules(&text).map_err(|e| Error::other(format!("{}: {}", path, e)))?;
+        tracing::debug!("{}: {} migration rules", path, rules.len());
+        rules.extend(Migrator::builtin().rules);
+        Ok(Migrator { rules })
+    }
+
+    // the built-in rules, plus those of a file if given
+    pub fn load(path: Option<&str>) -> io::Result<Migrator> {
+        match path {
+            Some(path) => Migrator::with_rules(path),
+            None => Ok(Migrator::builtin()),
+        }
+    }
+
+    // The replacement tokens of a tag of an element of a list, as
+    // (tag, value), and the rule's note; none if no rule applies.
+    pub fn migrate(
+        &self,
+        list: &str,
+        tag: &str,
+        value: &str,
+    ) -> Option<(Vec<(String, String)>, &str)> {
+        for c in &self.rules {
+            let rule = &c.rule;
+            if rule.tag != tag
+                || (!rule.lists.is_empty() && !rule.lists.iter().any(|l| l == list))
+                || rule.except.iter().any(|l| l == list)
+            {
+                continue;
+            }
+            let value = match &rule.split {
+                Some((from, to)) => value.replace(from.as_str(), to),
+                None => value.to_string(),
+            };
+            let Some(caps) = c.pattern.captures(&value) else {
+                continue;
+            };
+
+            let tokens = rule
+                .replace
+                .iter()
+                .map(|template| {
+                    let mut token = String::new();
+                    caps.expand(template, &mut token);
+                    match token.split_once(':') {
+                        Some((tag, value)) => (tag.to_string(), value.to_string()),
+                        None => (token, String::new()),
+                    }
+                })
+                .collect();
+            return Some((tokens, &rule.note));
+        }
+        None
+    }
+
+    // Rewrite the tagged tokens of attribs of an element of a list,
+    // reporting each rewrite against a file and line.
+    pub fn migrate_attribs(
+        &self,
+        list: &str,
+        file: &str,
+        line: usize,
+        attribs: &mut Vec<(String, String)>,
+    ) -> Vec<Diagnostic> {
+        let mut diags = Vec::new();
+        let mut out = Vec::with_capacity(attribs.len());
+        for (tag, value) in attribs.drain(..) {
+            match self.migrate(list, &tag, &value) {
+                Some((tokens, note)) => {
+                    diags.push(rewritten(file, line, &tag, &value, &tokens, note));
+                    out.extend(tokens);
+                }
+                None => out.push((tag, value)),
+            }
+        }
+        *attribs = out;
+        diags
+    }
+
+    // Rewrite the tokens of an LST line of a list, after its first,
+    // reporting each rewrite.  Other tokens are kept as they are.
+    pub fn migrate_line(
+        &self,
+        list: &str,
+        file: &str,
+        lineno: usize,
+        line: &str,
+    ) -> (String, Vec<Diagnostic>) {
+        if line.trim().is_empty() || line.starts_with('#') {
+            return (line.to_string(), Vec::new());
+        }
+
+        let mut diags = Vec::new();
+        let mut tokens = line.split('\t');
+        let mut out = vec![tokens.next().unwrap_or("").to_string()];
+        for token in tokens {
+            let migrated = token
+                .split_once(':')
+                .and_then(|(tag, value)| Some((tag, value, self.migrate(list, tag, value)?)));
+            match migrated {
+                Some((tag, value, (replacements, note))) => {
+                    diags.push(rewritten(file, lineno, tag, value, &replacements, note));
+                    out.extend(replacements.iter().map(|(t, v)| format!("{}:{}", t, v)));
+                }
+                None => out.push(token.to_string()),
+            }
+        }
+        (out.join("\t"), diags)
+    }
+}
+
+// the report of a rewrite, e.g. "SA:x => SAB:x (note)"
+fn rewritten(
+    file: &str,
+    line: usize,
+    tag: &str,
+    value: &str,
+    tokens: &[(String, String)],
+    note: &str,
+) -> Diagnostic {
+    let to: Vec<String> = tokens.iter().map(|(t, v)| format!("{}:{}", t, v)).collect();
+    let mut message = format!("{}:{} => {}", tag, value, to.join(" "));
+    if !note.is_empty() {
+        message.push_str(&format!(" ({})", note));
+    }
+    Diagnostic::warning(file, line, message)
+}
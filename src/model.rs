@@ -0,0 +1,146 @@
+//
+// model.rs -- typed access to the elements of loaded lists
+//
+// Copyright (c) 2024 Jeff Garzik
+//
+// This file is part of the pcgtoolssoftware project covered under
+// the MIT License.  For the full license text, please see the LICENSE
+// file in the root directory of this project.
+// SPDX-License-Identifier: MIT
+
+use crate::ability::Ability;
+use crate::class::Class;
+use crate::deity::Deity;
+use crate::equipment::{EquipMod, Equipment};
+use crate::kit::Kit;
+use crate::language::Language;
+use crate::pcc::{Pcc, PccElem};
+use crate::race::Race;
+use crate::spell::Spell;
+
+// a typed model of the elements of one list
+pub trait Model: Sized {
+    // list type, e.g. SPELL
+    const LIST: &'static str;
+
+    // build from an element, stored under ident
+    fn from_elem(ident: &str, elem: &PccElem) -> Self;
+}
+
+impl Model for Ability {
+    const LIST: &'static str = "ABILITY";
+
+    fn from_elem(ident: &str, elem: &PccElem) -> Ability {
+        Ability::new(ident, elem)
+    }
+}
+
+impl Model for Class {
+    const LIST: &'static str = "CLASS";
+
+    fn from_elem(ident: &str, elem: &PccElem) -> Class {
+        Class::new(ident, elem)
+    }
+}
+
+impl Model for Deity {
+    const LIST: &'static str = "DEITY";
+
+    fn from_elem(ident: &str, elem: &PccElem) -> Deity {
+        Deity::new(ident, elem)
+    }
+}
+
+impl Model for EquipMod {
+    const LIST: &'static str = "EQUIPMOD";
+
+    fn from_elem(ident: &str, elem: &PccElem) -> EquipMod {
+        EquipMod::new(ident, elem)
+    }
+}
+
+impl Model for Equipment {
+    const LIST: &'static str = "EQUIPMENT";
+
+    fn from_elem(ident: &str, elem: &PccElem) -> Equipment {
+        Equipment::new(ident, elem)
+    }
+}
+
+impl Model for Kit {
+    const LIST: &'static str = "KIT";
+
+    fn from_elem(ident: &str, elem: &PccElem) -> Kit {
+        Kit::new(ident, elem)
+    }
+}
+
+impl Model for Language {
+    const LIST: &'static str = "LANGUAGE";
+
+    fn from_elem(ident: &str, elem: &PccElem) -> Language {
+        Language::new(ident, elem)
+    }
+}
+
+impl Model for Race {
+    const LIST: &'static str = "RACE";
+
+    fn from_elem(ident: &str, elem: &PccElem) -> Race {
+        Race::new(ident, elem)
+    }
+}
+
+impl Model for Spell {
+    const LIST: &'static str = "SPELL";
+
+    fn from_elem(ident: &str, elem: &PccElem) -> Spell {
+        Spell::new(ident, elem)
+    }
+}
+
+impl Pcc {
+    // every element of a model's list, in definition order, e.g.
+    // pcc.typed::<Spell>()
+    pub fn typed<T: Model>(&self) -> impl Iterator<Item = T> + '_ {
+        self.list(T::LIST)
+            .into_iter()
+            .flat_map(|lst| lst.props.iter())
+            .map(|(ident, elem)| T::from_elem(ident, elem))
+    }
+
+    // an element of a model's list by KEY, name or alias, ignoring
+    // case, e.g. pcc.get::<Spell>("Fireball")
+    pub fn get<T: Model>(&self, name: &str) -> Option<T> {
+        self.lookup_idents(name)
+            .into_iter()
+            .find(|(list, _, _)| *list == T::LIST)
+            .map(|(_, ident, elem)| T::from_elem(ident, elem))
+    }
+
+    pub fn spells(&self) -> impl Iterator<Item = Spell> + '_ {
+        self.typed()
+    }
+
+    pub fn languages(&self) -> impl Iterator<Item = Language> + '_ {
+        self.typed()
+    }
+
+    pub fn races(&self) -> impl Iterator<Item = Race> + '_ {
+        self.typed()
+    }
+
+    pub fn classes(&self) -> impl Iterator<Item = Class> + '_ {
+        self.typed()
+    }
+
+    // abilities of the FEAT category
+    pub fn feats(&self) -> impl Iterator<Item = Ability> + '_ {
+        self.typed::<Ability>()
+            .filter(|a| a.category.eq_ignore_ascii_case("FEAT"))
+    }
+
+    pub fn equipment(&self) -> impl Iterator<Item = Equipment> + '_ {
+        self.typed()
+    }
+}
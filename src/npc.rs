@@ -0,0 +1,341 @@
+//
+// npc.rs -- random generation of a character legal under loaded data
+//
+// Copyright (c) 2024 Jeff Garzik
+//
+// This file is part of the pcgtoolssoftware project covered under
+// the MIT License.  For the full license text, please see the LICENSE
+// file in the root directory of this project.
+// SPDX-License-Identifier: MIT
+
+use crate::ability::Ability;
+use crate::bioset::{Bio, BioSet};
+use crate::bonus::{self, stat_mod, Summary};
+use crate::class::Class;
+use crate::dice::Dice;
+use crate::equipment::Equipment;
+use crate::export::markdown::escape;
+use crate::pcc::{Pcc, PccElem};
+use crate::qualify::{self, Profile};
+use crate::race::Race;
+use crate::spell::Spell;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+
+// stats rolled when no game mode is loaded
+const DEFAULT_STATS: [&str; 6] = ["STR", "DEX", "CON", "INT", "WIS", "CHA"];
+
+// spells picked per spell level, for a class without CAST tags
+const SPELLS_PER_LEVEL: usize = 2;
+
+// a generated character
+#[derive(Clone, Debug, Serialize)]
+pub struct Npc {
+    pub race: String,
+    pub class: String,
+    pub level: u32,
+    pub bio: Option<Bio>,
+    // stat abbreviation => rolled score, before bonuses
+    pub stats: BTreeMap<String, i64>,
+    pub hit_points: i64,
+    pub feats: Vec<String>,
+    // spell level => spells
+    pub spells: BTreeMap<u32, Vec<String>>,
+    pub equipment: Vec<String>,
+    // stats, armor class, saves and skills, with bonuses applied
+    pub summary: Summary,
+}
+
+// 4d6, dropping the lowest die
+fn roll_stat(rng: &mut fastrand::Rng) -> i64 {
+    let mut dice: Vec<i64> = (0..4).map(|_| rng.i64(1..=6)).collect();
+    dice.sort();
+    dice[1..].iter().sum()
+}
+
+// the elements of a list, in ident order so that a seed repeats
+fn sorted<'a>(pcc: &'a Pcc, list: &str) -> Vec<(&'a String, &'a PccElem)> {
+    let mut elems: Vec<(&String, &PccElem)> = pcc
+        .list(list)
+        .map_or(Vec::new(), |lst| lst.props.iter().collect());
+    elems.sort_by(|a, b| a.0.cmp(b.0));
+    elems
+}
+
+// does a character meet every prerequisite of an element?  Unknown
+// prerequisites do not disqualify.
+fn qualifies(pcc: &Pcc, profile: &Profile, elem: &PccElem) -> bool {
+    qualify::check(pcc, profile, elem)
+        .iter()
+        .all(|o| o.passed != Some(false))
+}
+
+// the number of spells castable per day of each spell level, from the
+// latest CAST tag of the class up to a level, e.g. CAST:3,1
+fn cast_counts(class: &Class, level: u32) -> Option<Vec<usize>> {
+    let cast = class
+        .levels_to(level)
+        .filter_map(|l| l.values("CAST").last().copied())
+        .last()?;
+    Some(
+        cast.split(',')
+            .map(|c| c.trim().parse().unwrap_or(0))
+            .collect(),
+    )
+}
+
+// Generate a character of a race and class at a level: rolled stats,
+// hit points and biography, plus feats, spells and equipment picked at
+// random among those whose prerequisites it meets.  Feats are picked
+// one at a time, so that a feat may qualify the character for another.
+pub fn generate(
+    pcc: &Pcc,
+    race: &str,
+    class: &str,
+    level: u32,
+    rng: &mut fastrand::Rng,
+) -> Result<Npc, String> {
+    let race = pcc.get::<Race>(race).ok_or_else(|| {
+        format!(
+            "RACE {} not found{}",
+            race,
+            pcc.did_you_mean(Some("RACE"), race)
+        )
+    })?;
+    let class = pcc.get::<Class>(class).ok_or_else(|| {
+        format!(
+            "CLASS {} not found{}",
+            class,
+            pcc.did_you_mean(Some("CLASS"), class)
+        )
+    })?;
+    if level == 0 || class.max_level.is_some_and(|max| level > max) {
+        return Err(format!(
+            "CLASS {} cannot be taken to level {}",
+            class.name, level
+        ));
+    }
+
+    let stat_names: Vec<String> = match pcc.gamemode() {
+        Some(gm) if !gm.stats.is_empty() => gm
+            .stats
+            .iter()
+            .map(|s| s.get("ABB").unwrap_or(&s.name).to_string())
+            .collect(),
+        _ => DEFAULT_STATS.iter().map(|s| s.to_string()).collect(),
+    };
+    let mut profile = Profile {
+        race: Some(race.name.clone()),
+        stats: stat_names
+            .iter()
+            .map(|s| (s.clone(), roll_stat(rng)))
+            .collect(),
+        classes: BTreeMap::from([(class.name.clone(), level)]),
+        ..Profile::default()
+    };
+    profile.bab = Some(
+        bonus::Engine::new(pcc, &profile)
+            .total(&["COMBAT"], "BASEAB")
+            .value as i64,
+    );
+
+    // the hit die is maximized at first level
+    let con = profile
+        .stats
+        .get("CON")
+        .map_or(0.0, |s| stat_mod(*s as f64)) as i64;
+    let hit_die = Dice {
+        count: 1,
+        sides: class.hit_die.unwrap_or(8),
+        modifier: 0,
+    };
+    let mut hit_points = hit_die.sides as i64 + con;
+    for _ in 1..level {
+        hit_points += (hit_die.roll(rng) + con).max(1);
+    }
+
+    // one feat at first level, and another every third level
+    let mut feats = Vec::new();
+    let mut candidates: Vec<(Ability, &PccElem)> = sorted(pcc, "ABILITY")
+        .into_iter()
+        .map(|(ident, elem)| (Ability::new(ident, elem), elem))
+        .filter(|(a, _)| a.category.eq_ignore_ascii_case("FEAT"))
+        .collect();
+    rng.shuffle(&mut candidates);
+    for _ in 0..1 + level / 3 {
+        let Some(i) = candidates
+            .iter()
+            .position(|(_, elem)| qualifies(pcc, &profile, elem))
+        else {
+            break;
+        };
+        let (feat, _) = candidates.remove(i);
+        profile.abilities.push(feat.key.clone());
+        feats.push(feat.name);
+    }
+
+    let mut spells: BTreeMap<u32, Vec<String>> = BTreeMap::new();
+    let counts = cast_counts(&class, level);
+    for (ident, elem) in sorted(pcc, "SPELL") {
+        let spell = Spell::new(ident, elem);
+        if let Some(spell_level) = spell.level_for(&class.name) {
+            spells.entry(spell_level).or_default().push(spell.key);
+        }
+    }
+    let max_spell_level = match &counts {
+        Some(counts) => counts.iter().rposition(|c| *c > 0).map(|l| l as u32),
+        None => Some(level.div_ceil(2).min(9)),
+    };
+    spells.retain(|l, _| max_spell_level.is_some_and(|max| *l <= max));
+    for (spell_level, names) in spells.iter_mut() {
+        let wanted = counts
+            .as_ref()
+            .and_then(|c| c.get(*spell_level as usize).copied())
+            .unwrap_or(SPELLS_PER_LEVEL);
+        rng.shuffle(names);
+        names.retain(|name| {
+            pcc.lookup_in("SPELL", name)
+                .is_some_and(|elem| qualifies(pcc, &profile, elem))
+        });
+        names.truncate(wanted);
+        profile.spells.extend(names.iter().cloned());
+
+        // shown by name, the keys having served for lookups
+        for name in names.iter_mut() {
+            if let Some(elem) = pcc.lookup_in("SPELL", name) {
+                *name = elem.display_name().to_string();
+            }
+        }
+        names.sort();
+    }
+    spells.retain(|_, names| !names.is_empty());
+
+    // a weapon, a suit of armor, and an item of mundane gear
+    let items: Vec<(Equipment, &PccElem)> = sorted(pcc, "EQUIPMENT")
+        .into_iter()
+        .map(|(ident, elem)| (Equipment::new(ident, elem), elem))
+        .filter(|(_, elem)| qualifies(pcc, &profile, elem))
+        .collect();
+    let kinds: [fn(&Equipment) -> bool; 3] = [
+        |e| e.has_type("Weapon"),
+        |e| e.has_type("Armor") && !e.has_type("Shield"),
+        |e| {
+            !["Weapon", "Armor", "Shield", "Magic"]
+                .iter()
+                .any(|t| e.has_type(t))
+        },
+    ];
+    let mut equipment = Vec::new();
+    for kind in kinds {
+        let choices: Vec<&Equipment> = items.iter().map(|(e, _)| e).filter(|e| kind(e)).collect();
+        if let Some(item) = rng.choice(choices) {
+            equipment.push(item.name.clone());
+        }
+    }
+    profile.equipment = equipment.clone();
+
+    let bio = match BioSet::new(pcc).generate(&race.name, Some(&class.name), None, rng) {
+        Ok(bio) => Some(bio),
+        Err(e) => {
+            tracing::debug!("{}", e);
+            None
+        }
+    };
+
+    Ok(Npc {
+        race: race.name,
+        class: class.name,
+        level,
+        bio,
+        stats: profile.stats.clone(),
+        hit_points,
+        feats,
+        spells,
+        equipment,
+        summary: bonus::Engine::new(pcc, &profile).summary(),
+    })
+}
+
+impl Npc {
+    // write the character as a Markdown character sheet
+    pub fn write_markdown<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        writeln!(
+            w,
+            "# {} {} {}",
+            escape(&self.race),
+            escape(&self.class),
+            self.level
+        )?;
+        writeln!(w)?;
+        if let Some(bio) = &self.bio {
+            writeln!(w, "- **Sex:** {}", escape(&bio.sex))?;
+            writeln!(w, "- **Age:** {} ({})", bio.age, escape(&bio.ageset))?;
+            writeln!(
+                w,
+                "- **Height:** {}' {}\"",
+                bio.height / 12,
+                bio.height % 12
+            )?;
+            writeln!(w, "- **Weight:** {} lb.", bio.weight)?;
+        }
+        writeln!(w, "- **Hit points:** {}", self.hit_points)?;
+        writeln!(w, "- **Armor class:** {}", self.summary.ac.value)?;
+        writeln!(w)?;
+
+        writeln!(w, "## Stats")?;
+        writeln!(w)?;
+        writeln!(w, "| Stat | Score | Modifier |")?;
+        writeln!(w, "|---|---|---|")?;
+        for (stat, total) in &self.summary.stats {
+            writeln!(
+                w,
+                "| {} | {} | {:+} |",
+                escape(stat),
+                total.value,
+                stat_mod(total.value)
+            )?;
+        }
+        writeln!(w)?;
+
+        writeln!(w, "## Saves")?;
+        writeln!(w)?;
+        for (save, total) in &self.summary.saves {
+            writeln!(w, "- **{}:** {:+}", escape(save), total.value)?;
+        }
+        writeln!(w)?;
+
+        let mut section = |title: &str, items: &[String]| -> io::Result<()> {
+            if items.is_empty() {
+                return Ok(());
+            }
+            writeln!(w, "## {}", title)?;
+            writeln!(w)?;
+            for item in items {
+                writeln!(w, "- {}", escape(item))?;
+            }
+            writeln!(w)
+        };
+        section("Feats", &self.feats)?;
+        section("Equipment", &self.equipment)?;
+
+        if !self.spells.is_empty() {
+            writeln!(w, "## Spells")?;
+            writeln!(w)?;
+            for (level, names) in &self.spells {
+                let names: Vec<String> = names.iter().map(|n| escape(n)).collect();
+                writeln!(w, "- **Level {}:** {}", level, names.join(", "))?;
+            }
+            writeln!(w)?;
+        }
+
+        if !self.summary.skills.is_empty() {
+            writeln!(w, "## Skills")?;
+            writeln!(w)?;
+            for (skill, total) in &self.summary.skills {
+                writeln!(w, "- **{}:** {:+}", escape(skill), total.value)?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,85 @@
+//
+// output.rs -- serialized output formats
+//
+// Copyright (c) 2024 Jeff Garzik
+//
+// This file is part of the pcgtoolssoftware project covered under
+// the MIT License.  For the full license text, please see the LICENSE
+// file in the root directory of this project.
+// SPDX-License-Identifier: MIT
+
+use serde::Serialize;
+use std::{
+    fmt, io,
+    io::{Error, Write},
+    str::FromStr,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OutputFormat {
+    Json,
+    JsonCompact,
+    Yaml,
+    Toml,
+    // an aligned table of list elements; see table.rs
+    Table,
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<OutputFormat, String> {
+        match s.to_ascii_lowercase().as_str() {
+            "json" => Ok(OutputFormat::Json),
+            "json-compact" => Ok(OutputFormat::JsonCompact),
+            "yaml" => Ok(OutputFormat::Yaml),
+            "toml" => Ok(OutputFormat::Toml),
+            "table" => Ok(OutputFormat::Table),
+            _ => Err(format!("unknown output format {}", s)),
+        }
+    }
+}
+
+impl fmt::Display for OutputFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match self {
+            OutputFormat::Json => "json",
+            OutputFormat::JsonCompact => "json-compact",
+            OutputFormat::Yaml => "yaml",
+            OutputFormat::Toml => "toml",
+            OutputFormat::Table => "table",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+// serialize a value to the writer, in the given format.  Tables are
+// laid out from list elements instead; see table.rs
+pub fn write_value<T: Serialize, W: Write>(
+    format: OutputFormat,
+    w: &mut W,
+    value: &T,
+) -> io::Result<()> {
+    match format {
+        OutputFormat::Json => {
+            serde_json::to_writer_pretty(&mut *w, value)?;
+            writeln!(w)?;
+        }
+        OutputFormat::JsonCompact => {
+            serde_json::to_writer(&mut *w, value)?;
+            writeln!(w)?;
+        }
+        OutputFormat::Yaml => {
+            serde_yaml::to_writer(&mut *w, value).map_err(Error::other)?;
+        }
+        OutputFormat::Toml => {
+            let s = toml::to_string_pretty(value).map_err(Error::other)?;
+            w.write_all(s.as_bytes())?;
+        }
+        OutputFormat::Table => {
+            return Err(Error::other("table output is only of list elements"));
+        }
+    }
+
+    Ok(())
+}
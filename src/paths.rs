@@ -0,0 +1,177 @@
+//
+// paths.rs -- data file path resolution
+//
+// Copyright (c) 2024 Jeff Garzik
+//
+// This file is part of the pcgtoolssoftware project covered under
+// the MIT License.  For the full license text, please see the LICENSE
+// file in the root directory of this project.
+// SPDX-License-Identifier: MIT
+
+use crate::files;
+use std::path::{Component, Path, PathBuf, MAIN_SEPARATOR};
+
+// is a path the URL of a file to fetch over HTTP(S)?  either "//"
+// or "/" may follow the scheme, as paths joined by Path lose one
+pub fn is_url(path: &str) -> bool {
+    let scheme = path.get(..7).unwrap_or(path).to_ascii_lowercase();
+    scheme.starts_with("http:/") || scheme.starts_with("https:/")
+}
+
+// convert a path as written in a data file, with '/' or '\\'
+// separators, to the native form; URLs are left as they are
+pub fn native(path: &str) -> String {
+    if MAIN_SEPARATOR == '/' || is_url(path) {
+        path.replace('\\', "/")
+    } else {
+        path.replace('/', "\\")
+    }
+}
+
+// is a (native) path absolute?  a leading separator counts, even on
+// Windows, where such a path is relative to the current drive, as does
+// a URL
+pub fn is_absolute(path: &str) -> bool {
+    path.starts_with(['/', '\\']) || Path::new(path).is_absolute() || is_url(path)
+}
+
+// strip leading separators, so that the path may be joined to a base
+// directory; "@/foo/bar.lst" and "@foo/bar.lst" are equivalent
+pub fn relative(path: &str) -> &str {
+    path.trim_start_matches(['/', '\\'])
+}
+
+// Resolve a path relative to the data directories: the path within
+// the first of them having the file, or, if none has it, within the
+// first, so that errors name it.
+pub fn in_datadirs(datadirs: &[String], relpath: &str) -> String {
+    let relpath = relative(relpath);
+    let mut candidates = datadirs
+        .iter()
+        .map(|dir| resolve(&Path::new(dir).join(relpath).to_string_lossy()));
+    let first = candidates.next().unwrap_or_else(|| resolve(relpath));
+    if datadirs.len() <= 1 || files::exists(&first) {
+        return first;
+    }
+    candidates.find(|path| files::exists(path)).unwrap_or(first)
+}
+
+// a path relative to the data directory containing it, or the path
+// itself if within none
+pub fn within_datadirs<'a>(datadirs: &[String], path: &'a str) -> &'a str {
+    datadirs
+        .iter()
+        .find_map(|dir| Path::new(path).strip_prefix(dir).ok())
+        .and_then(|p| p.to_str())
+        .unwrap_or(path)
+}
+
+// find the entry in dir whose name matches name, ignoring case
+fn find_ignore_case(dir: &Path, name: &str) -> Option<PathBuf> {
+    let dir = if dir.as_os_str().is_empty() {
+        Path::new(".")
+    } else {
+        dir
+    };
+
+    files::read_dir(dir)
+        .ok()?
+        .into_iter()
+        .find(|entry| entry.eq_ignore_ascii_case(name))
+        .map(|entry| dir.join(entry))
+}
+
+// does the file name of a path contain a '*' wildcard?
+pub fn has_wildcard(path: &str) -> bool {
+    Path::new(path)
+        .file_name()
+        .is_some_and(|name| name.to_string_lossy().contains('*'))
+}
+
+// match a file name against a pattern of literal text and '*'
+// wildcards, ignoring case
+fn wildcard_match(pattern: &str, name: &str) -> bool {
+    let pattern = pattern.to_lowercase();
+    let name = name.to_lowercase();
+    let Some((first, rest)) = pattern.split_once('*') else {
+        return pattern == name;
+    };
+    let Some(mut name) = name.strip_prefix(first) else {
+        return false;
+    };
+
+    // each part between wildcards matches at its earliest position,
+    // and the final part at the very end
+    let mut parts: Vec<&str> = rest.split('*').collect();
+    let last = parts.pop().unwrap_or("");
+    for part in parts {
+        match name.find(part) {
+            Some(pos) => name = &name[pos + part.len()..],
+            None => return false,
+        }
+    }
+    name.ends_with(last)
+}
+
+// expand a '*' wildcard in the file name of a path to the matching
+// files, in name order
+pub fn expand_wildcard(path: &str) -> Vec<String> {
+    let path = Path::new(path);
+    let Some(pattern) = path.file_name() else {
+        return Vec::new();
+    };
+    let pattern = pattern.to_string_lossy();
+    let dir = resolve(&path.parent().unwrap_or(Path::new("")).to_string_lossy());
+    let dir = if dir.is_empty() {
+        String::from(".")
+    } else {
+        dir
+    };
+
+    let Ok(entries) = files::read_dir(&dir) else {
+        return Vec::new();
+    };
+    let mut found: Vec<String> = entries
+        .into_iter()
+        .filter(|entry| wildcard_match(&pattern, entry))
+        .map(|entry| Path::new(&dir).join(entry))
+        .filter(|path| files::is_file(path))
+        .map(|path| path.to_string_lossy().to_string())
+        .collect();
+    found.sort();
+    found
+}
+
+// Resolve a data file path.  Data authored on Windows often references
+// files with case differing from the files on disk; where the exact
+// path does not exist, each missing component is matched against its
+// directory ignoring case.  Returns the path unchanged if no match,
+// or if a URL.
+pub fn resolve(path: &str) -> String {
+    if is_url(path) || files::exists(path) {
+        return String::from(path);
+    }
+
+    let mut resolved = PathBuf::new();
+    for comp in Path::new(path).components() {
+        let Component::Normal(name) = comp else {
+            resolved.push(comp);
+            continue;
+        };
+
+        let exact = resolved.join(name);
+        if files::exists(&exact) {
+            resolved = exact;
+            continue;
+        }
+
+        match find_ignore_case(&resolved, &name.to_string_lossy()) {
+            Some(found) => resolved = found,
+            None => return String::from(path),
+        }
+    }
+
+    let resolved = resolved.to_string_lossy().to_string();
+    tracing::warn!("{} not found, using {}", path, resolved);
+    resolved
+}
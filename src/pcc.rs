@@ -0,0 +1,2735 @@
+//
+// pcc.rs -- PCC and LST file reader
+//
+// Copyright (c) 2024 Jeff Garzik
+//
+// This file is part of the pcgtoolssoftware project covered under
+// the MIT License.  For the full license text, please see the LICENSE
+// file in the root directory of this project.
+// SPDX-License-Identifier: MIT
+
+use crate::campaign::{self, CampaignInfo};
+use crate::compat::DataCompat;
+use crate::diag::{Diagnostic, Severity};
+use crate::files;
+use crate::gamemode::GameMode;
+use crate::intern::{Interner, Sym};
+use crate::migrate::Migrator;
+use crate::output::{self, OutputFormat};
+use crate::paths;
+use crate::prereq::{self, Prereq};
+use crate::suggest;
+use crate::text::{self, Encoding};
+use crate::token;
+use indexmap::IndexMap;
+use rayon::prelude::*;
+use serde::{de, ser::SerializeStruct, Deserialize, Deserializer, Serialize, Serializer};
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fmt, io,
+    io::{Error, Write},
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::{Arc, OnceLock},
+};
+
+#[derive(Serialize, Deserialize)]
+pub(crate) enum PccTag {
+    // a BIOSET file, of AGESET lines each followed by RACENAME lines
+    BioSet,
+    Bool,
+    // a CLASS file, of class lines each followed by numbered level lines
+    Class,
+    // a DATATABLE file, of comma-separated tables
+    DataTable,
+    Date,
+    // an LST file of "SCOPE:Name" elements, each merged into the list
+    // named by its scope
+    Dynamic,
+    // "LIST|name,name": elements expected to be defined by LST data
+    ForwardRef,
+    // a KIT file, of STARTPACK lines each followed by component lines
+    Kit,
+    LstFile,
+    Number,
+    Text,
+    PccFile,
+}
+
+impl FromStr for PccTag {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<PccTag, String> {
+        match s.to_ascii_lowercase().as_str() {
+            "bioset" => Ok(PccTag::BioSet),
+            "bool" => Ok(PccTag::Bool),
+            "class" => Ok(PccTag::Class),
+            "datatable" => Ok(PccTag::DataTable),
+            "date" => Ok(PccTag::Date),
+            "dynamic" => Ok(PccTag::Dynamic),
+            "forwardref" => Ok(PccTag::ForwardRef),
+            "kit" => Ok(PccTag::Kit),
+            "lstfile" => Ok(PccTag::LstFile),
+            "number" => Ok(PccTag::Number),
+            "text" => Ok(PccTag::Text),
+            "pccfile" => Ok(PccTag::PccFile),
+            _ => Err(format!("unknown PCC tag kind {}", s)),
+        }
+    }
+}
+
+// the form of the LST files of a list: element lines, blocks of lines
+// begun by a block tag, e.g. CLASS, or comma-separated tables
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum LstForm {
+    Lines,
+    Blocks(&'static str),
+    Tables,
+}
+
+// how a repeated LST tag merges with existing values of that tag
+enum LstMerge {
+    Append,
+    Replace,
+}
+
+// a file and line at which data was defined
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Provenance {
+    pub file: Sym,
+    pub line: usize,
+}
+
+// where an element's data came from; recorded only when requested
+// with PccConfig.provenance
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct ElemProvenance {
+    // .MOD and .COPY lines applied to the element, in load order
+    pub mods: Vec<Provenance>,
+    // the origin of each attrib, in the same order as the attribs
+    pub attribs: Vec<Provenance>,
+}
+
+#[derive(Deserialize)]
+pub struct PccElem {
+    pub(crate) _ident: String,
+    // the name of an element identified by a KEY, where it differs
+    #[serde(default)]
+    pub(crate) name: Option<String>,
+    // OUTPUTNAME, resolved, if given
+    #[serde(default)]
+    pub(crate) outputname: Option<String>,
+    pub(crate) source: String,
+    pub(crate) line: usize,
+    pub(crate) attribs: Vec<(Sym, Sym)>,
+    #[serde(default)]
+    pub(crate) provenance: Option<ElemProvenance>,
+}
+
+// multi-valued tags, and the separator between their values
+const MULTI_VALUED_TAGS: &[(&str, char)] = &[
+    ("TYPE", '.'),
+    ("CLASSES", '|'),
+    ("DESCRIPTOR", '|'),
+    ("SUBSCHOOL", '|'),
+    ("RACESUBTYPE", '|'),
+];
+
+fn value_separator(tag: &str) -> Option<char> {
+    MULTI_VALUED_TAGS
+        .iter()
+        .find(|(t, _)| *t == tag)
+        .map(|(_, sep)| *sep)
+}
+
+// provenance, the name of an element known by its KEY, and its
+// resolved OUTPUTNAME are omitted from human-readable output unless
+// recorded.
+// human-readable output also includes multi-valued tags split into
+// their individual values.  binary formats, as used by the cache,
+// require exactly the stored fields.
+impl Serialize for PccElem {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let readable = serializer.is_human_readable();
+        let with_prov = self.provenance.is_some() || !readable;
+        let split = if readable {
+            self.split_values()
+        } else {
+            BTreeMap::new()
+        };
+
+        let with_name = self.name.is_some() || !readable;
+        let with_outputname = self.outputname.is_some() || !readable;
+        let len = 4
+            + usize::from(with_name)
+            + usize::from(with_outputname)
+            + usize::from(with_prov)
+            + usize::from(!split.is_empty());
+        let mut st = serializer.serialize_struct("PccElem", len)?;
+        st.serialize_field("_ident", &self._ident)?;
+        if with_name {
+            st.serialize_field("name", &self.name)?;
+        } else {
+            st.skip_field("name")?;
+        }
+        if with_outputname {
+            st.serialize_field("outputname", &self.outputname)?;
+        } else {
+            st.skip_field("outputname")?;
+        }
+        st.serialize_field("source", &self.source)?;
+        st.serialize_field("line", &self.line)?;
+        st.serialize_field("attribs", &self.attribs)?;
+        if with_prov {
+            st.serialize_field("provenance", &self.provenance)?;
+        } else {
+            st.skip_field("provenance")?;
+        }
+        if !split.is_empty() {
+            st.serialize_field("split_values", &split)?;
+        }
+        st.end()
+    }
+}
+
+impl PccElem {
+    fn new(ident: &str, source: &str, line: usize) -> PccElem {
+        PccElem {
+            _ident: String::from(ident),
+            name: None,
+            outputname: None,
+            source: String::from(source),
+            line,
+            attribs: Vec::new(),
+            provenance: None,
+        }
+    }
+
+    // the ident under which the element is stored: its KEY, if given,
+    // or else its name
+    pub fn ident(&self) -> &str {
+        &self._ident
+    }
+
+    // the name of the element as displayed: that on its defining line,
+    // even where a KEY replaced it as the ident
+    pub fn name(&self) -> &str {
+        self.name.as_deref().unwrap_or(&self._ident)
+    }
+
+    // the name of the element as output, e.g. on a character sheet: its
+    // OUTPUTNAME, resolved, or else its name
+    pub fn display_name(&self) -> &str {
+        self.outputname.as_deref().unwrap_or(self.name())
+    }
+
+    // all attribs, as (tag, value), in load order
+    pub fn attribs(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.attribs.iter().map(|(k, v)| (k.as_str(), v.as_str()))
+    }
+
+    // first value of a tag, as written
+    pub fn get(&self, tag: &str) -> Option<&str> {
+        self.attribs
+            .iter()
+            .find(|(k, _)| *k == tag)
+            .map(|(_, v)| v.as_str())
+    }
+
+    // every value of a tag, as written, for tags that may repeat
+    pub fn get_all(&self, tag: &str) -> Vec<&str> {
+        self.attribs
+            .iter()
+            .filter(|(k, _)| *k == tag)
+            .map(|(_, v)| v.as_str())
+            .collect()
+    }
+
+    // file in which the element was first defined
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    // line at which the element was first defined
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    // per-attrib and .MOD provenance, if recorded
+    pub fn provenance(&self) -> Option<&ElemProvenance> {
+        self.provenance.as_ref()
+    }
+
+    // individual values of a tag, across all its attribs.  values of
+    // multi-valued tags, such as TYPE:Weapon.Martial, are split apart.
+    pub fn values(&self, tag: &str) -> Vec<&str> {
+        let sep = value_separator(tag);
+        self.attribs
+            .iter()
+            .filter(|(k, _)| *k == tag)
+            .flat_map(|(_, v)| match sep {
+                Some(sep) => token::split_top(v, sep),
+                None => vec![v.as_str()],
+            })
+            .filter(|v| !v.is_empty())
+            .collect()
+    }
+
+    // individual values of each multi-valued tag present
+    pub fn split_values(&self) -> BTreeMap<&str, Vec<&str>> {
+        let mut split = BTreeMap::new();
+        for (k, _) in &self.attribs {
+            if value_separator(k).is_some() && !split.contains_key(k.as_str()) {
+                split.insert(k.as_str(), self.values(k));
+            }
+        }
+        split
+    }
+
+    fn push_attrib(&mut self, akey: Sym, aval: Sym, at: &Provenance) {
+        self.attribs.push((akey, aval));
+        if let Some(prov) = &mut self.provenance {
+            prov.attribs.push(at.clone());
+        }
+    }
+
+    // retain attribs for which keep() returns true, with their provenance
+    fn retain_attribs<F: Fn(&Sym, &Sym) -> bool>(&mut self, keep: F) {
+        let Some(prov) = &mut self.provenance else {
+            self.attribs.retain(|(k, v)| keep(k, v));
+            return;
+        };
+
+        let kept: Vec<bool> = self.attribs.iter().map(|(k, v)| keep(k, v)).collect();
+        let mut flags = kept.iter();
+        self.attribs.retain(|_| *flags.next().unwrap());
+        let mut flags = kept.iter();
+        prov.attribs.retain(|_| *flags.next().unwrap());
+    }
+
+    // merge a single attrib into the attrib list, honoring the
+    // ".CLEAR" and ".CLEAR.<value>" directives, and the tag's merge policy
+    fn merge_attrib(
+        &mut self,
+        akey: Sym,
+        aval: Sym,
+        policy: &LstMerge,
+        pool: &mut Interner,
+        at: &Provenance,
+    ) {
+        if akey == "TYPE" {
+            self.merge_type(aval, pool, at);
+        } else if aval == ".CLEAR" {
+            self.retain_attribs(|k, _| *k != akey);
+        } else if let Some(targets) = aval.strip_prefix(".CLEAR.") {
+            for target in targets.split(',') {
+                self.remove_value(&akey, target, pool);
+            }
+        } else {
+            if let LstMerge::Replace = policy {
+                self.retain_attribs(|k, _| *k != akey);
+            }
+            self.push_attrib(akey, aval, at);
+        }
+    }
+
+    // remove a single value from all attribs named akey.  the value may
+    // be the entire attrib value, or one item of a comma-separated list.
+    fn remove_value(&mut self, akey: &str, target: &str, pool: &mut Interner) {
+        for (k, v) in self.attribs.iter_mut() {
+            if *k == akey && *v != target && v.contains(',') {
+                *v = pool.intern(
+                    &v.split(',')
+                        .filter(|item| *item != target)
+                        .collect::<Vec<&str>>()
+                        .join(","),
+                );
+            }
+        }
+        self.retain_attribs(|k, v| !(*k == akey && (*v == target || v.is_empty())));
+    }
+
+    // TYPE values are dot-separated, with embedded CLEAR, ADD and
+    // REMOVE keywords, e.g. "TYPE:.CLEAR.Magic" or "TYPE:Foo.REMOVE.Bar"
+    fn merge_type(&mut self, aval: Sym, pool: &mut Interner, at: &Provenance) {
+        let is_directive = aval
+            .split('.')
+            .any(|tok| tok == "CLEAR" || tok == "ADD" || tok == "REMOVE");
+        if !is_directive {
+            self.push_attrib(pool.intern("TYPE"), aval, at);
+            return;
+        }
+
+        let mut adds: Vec<&str> = Vec::new();
+        let mut removing = false;
+        for tok in aval.split('.') {
+            match tok {
+                "" => {}
+                "CLEAR" => {
+                    self.retain_attribs(|k, _| *k != "TYPE");
+                    adds.clear();
+                }
+                "ADD" => removing = false,
+                "REMOVE" => removing = true,
+                _ if removing => self.remove_type(tok, pool),
+                _ => adds.push(tok),
+            }
+        }
+
+        if !adds.is_empty() {
+            self.push_attrib(pool.intern("TYPE"), pool.intern(&adds.join(".")), at);
+        }
+    }
+
+    // remove a single type from all TYPE attribs (case-insensitive,
+    // as PCGen types are)
+    fn remove_type(&mut self, target: &str, pool: &mut Interner) {
+        for (k, v) in self.attribs.iter_mut() {
+            if *k == "TYPE" {
+                *v = pool.intern(
+                    &v.split('.')
+                        .filter(|t| !t.eq_ignore_ascii_case(target))
+                        .collect::<Vec<&str>>()
+                        .join("."),
+                );
+            }
+        }
+        self.retain_attribs(|k, v| !(*k == "TYPE" && v.is_empty()));
+    }
+}
+
+// elements are kept in the order first defined
+#[derive(Deserialize)]
+pub struct PccList {
+    pub(crate) _ident: String,
+    pub(crate) props: IndexMap<String, PccElem>,
+}
+
+// elements of human-readable output are sorted, so that output is the
+// same for the same data however loaded.  binary formats, as used by
+// the cache, keep the order of definition.
+impl Serialize for PccList {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let readable = serializer.is_human_readable();
+        let mut st = serializer.serialize_struct("PccList", 2)?;
+        st.serialize_field("_ident", &self._ident)?;
+        if readable {
+            st.serialize_field("props", &SortedElems(self.sorted()))?;
+        } else {
+            st.serialize_field("props", &self.props)?;
+        }
+        st.end()
+    }
+}
+
+struct SortedElems<'a>(Vec<(&'a str, &'a PccElem)>);
+
+impl Serialize for SortedElems<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_map(self.0.iter().copied())
+    }
+}
+
+impl PccList {
+    fn new(ident: &str) -> PccList {
+        PccList {
+            _ident: String::from(ident),
+            props: IndexMap::new(),
+        }
+    }
+
+    // list type, e.g. SPELL
+    pub fn name(&self) -> &str {
+        &self._ident
+    }
+
+    // lookup element by ident
+    pub fn get(&self, ident: &str) -> Option<&PccElem> {
+        self.props.get(ident)
+    }
+
+    // all elements, as (ident, element), in the order first defined
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &PccElem)> {
+        self.props
+            .iter()
+            .map(|(ident, elem)| (ident.as_str(), elem))
+    }
+
+    // all elements, as (ident, element), sorted by SORTKEY, or else by
+    // name, ignoring case, then by ident
+    pub fn sorted(&self) -> Vec<(&str, &PccElem)> {
+        let mut elems: Vec<(String, &str, &PccElem)> = self
+            .props
+            .iter()
+            .map(|(ident, elem)| {
+                let key = elem.get("SORTKEY").unwrap_or(elem.name());
+                (key.to_lowercase(), ident.as_str(), elem)
+            })
+            .collect();
+        elems.sort_by(|a, b| (&a.0, a.1).cmp(&(&b.0, b.1)));
+        elems
+            .into_iter()
+            .map(|(_, ident, elem)| (ident, elem))
+            .collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.props.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.props.is_empty()
+    }
+
+    fn insert_table(&mut self, table: PccElem) {
+        self.props.insert(table._ident.clone(), table);
+    }
+}
+
+// a SOURCEDATE value, e.g. "2004-05" or "2004-05-24"
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct PccDate {
+    pub year: u16,
+    pub month: u8,
+    pub day: Option<u8>,
+}
+
+impl FromStr for PccDate {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<PccDate, String> {
+        let err = || format!("invalid date {}, expected YYYY-MM or YYYY-MM-DD", s);
+        let mut parts = s.trim().split('-');
+        let year = parts.next().ok_or_else(err)?;
+        let month = parts.next().ok_or_else(err)?;
+        let day = parts.next();
+        if year.len() != 4 || parts.next().is_some() {
+            return Err(err());
+        }
+
+        let year = year.parse::<u16>().map_err(|_| err())?;
+        let month = month.parse::<u8>().map_err(|_| err())?;
+        let day = match day {
+            None => None,
+            Some(d) => Some(d.parse::<u8>().map_err(|_| err())?),
+        };
+        if !(1..=12).contains(&month) || day.is_some_and(|d| !(1..=31).contains(&d)) {
+            return Err(err());
+        }
+
+        Ok(PccDate { year, month, day })
+    }
+}
+
+impl fmt::Display for PccDate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:04}-{:02}", self.year, self.month)?;
+        if let Some(day) = self.day {
+            write!(f, "-{:02}", day)?;
+        }
+        Ok(())
+    }
+}
+
+impl Serialize for PccDate {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for PccDate {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<PccDate, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(de::Error::custom)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub enum PccDatum {
+    Text(String),
+    Bool(bool),
+    Number(i64),
+    Date(PccDate),
+    List(PccList),
+}
+
+impl PccDatum {
+    // the value of a campaign tag, as text; None for lists
+    pub fn scalar_text(&self) -> Option<String> {
+        match self {
+            PccDatum::Text(val) => Some(val.clone()),
+            PccDatum::Bool(val) => Some(String::from(if *val { "YES" } else { "NO" })),
+            PccDatum::Number(val) => Some(val.to_string()),
+            PccDatum::Date(val) => Some(val.to_string()),
+            PccDatum::List(_) => None,
+        }
+    }
+
+    pub fn as_mut_list(&mut self) -> Option<&mut PccList> {
+        match self {
+            PccDatum::List(l) => Some(l),
+            _ => None,
+        }
+    }
+}
+
+// element selection options trailing an LST file reference in a PCC file
+pub(crate) enum LstFilter {
+    All,
+    Include(Vec<String>),
+    Exclude(Vec<String>),
+}
+
+impl LstFilter {
+    pub(crate) fn parse(lstopts: &str) -> LstFilter {
+        let opts = lstopts.trim();
+        let opts = opts.strip_prefix('(').unwrap_or(opts);
+        let opts = opts.strip_suffix(')').unwrap_or(opts);
+
+        // ability includes may be scoped by "CATEGORY=x|", which is
+        // not itself an element name
+        let names = |list: &str| -> Vec<String> {
+            list.split('|')
+                .filter(|name| !name.is_empty() && !name.starts_with("CATEGORY="))
+                .map(String::from)
+                .collect()
+        };
+
+        if let Some(list) = opts.strip_prefix("INCLUDE:") {
+            LstFilter::Include(names(list))
+        } else if let Some(list) = opts.strip_prefix("EXCLUDE:") {
+            LstFilter::Exclude(names(list))
+        } else {
+            if !opts.is_empty() {
+                tracing::warn!("Unknown LST options \"{}\" ignored", lstopts);
+            }
+            LstFilter::All
+        }
+    }
+
+    // is the element on this LST line selected?  matches against the
+    // element name (sans .MOD/.COPY/.FORGET directive) or its KEY.
+    pub(crate) fn allows(&self, line: &str) -> bool {
+        let names = match self {
+            LstFilter::All => return true,
+            LstFilter::Include(names) | LstFilter::Exclude(names) => names,
+        };
+
+        let mut tokens = line.split('\t');
+        let raw_ident = tokens.next().unwrap_or("");
+        let ident = match raw_ident.split_once(".COPY=") {
+            Some((_, newname)) => newname,
+            None => raw_ident
+                .strip_suffix(".MOD")
+                .or_else(|| raw_ident.strip_suffix(".FORGET"))
+                .unwrap_or(raw_ident),
+        };
+        let key = tokens.find_map(|token| token.strip_prefix("KEY:"));
+
+        let listed = names.iter().any(|name| {
+            name.eq_ignore_ascii_case(ident) || key.is_some_and(|k| name.eq_ignore_ascii_case(k))
+        });
+
+        match self {
+            LstFilter::Include(_) => listed,
+            _ => !listed,
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct PccConfig {
+    // data directories, searched in order for the files of "@" and "*"
+    // paths, and for PCC files named relative to the data directory
+    pub datadirs: Vec<String>,
+    pub gamemode: Option<String>,
+    pub systemdir: Option<String>,
+    // directories of purchased and of homebrew data, searched before
+    // the data directories for the files of "*" paths
+    pub vendordir: Option<String>,
+    pub homebrewdir: Option<String>,
+    // directory of cached parsed data; see cache.rs
+    pub cachedir: Option<String>,
+    pub encoding: Encoding,
+    // record per-attrib provenance; see ElemProvenance
+    pub provenance: bool,
+    pub duplicates: DuplicatePolicy,
+    // warn of, rather than reject, unknown PCC tags
+    pub allow_unknown_tags: bool,
+    // file of PCC tags extending the built-in schema
+    pub schema: Option<String>,
+    // rewrite deprecated LST tags while loading; see migrate.rs
+    pub migrate: bool,
+    // file of migration rules tried before the built-in rules
+    pub migrations: Option<String>,
+    // the PCGen release the data was written for
+    pub compat: DataCompat,
+}
+
+impl PccConfig {
+    // the first data directory
+    pub fn datadir(&self) -> &str {
+        self.datadirs.first().map_or(".", |dir| dir.as_str())
+    }
+
+    // game mode system files directory; by default, PCGen's "system"
+    // directory alongside its (first) "data" directory
+    pub fn systemdir(&self) -> String {
+        match &self.systemdir {
+            Some(dir) => dir.clone(),
+            None => Path::new(self.datadir())
+                .join("..")
+                .join("system")
+                .to_string_lossy()
+                .to_string(),
+        }
+    }
+
+    // a directory beside the (first) data directory, as PCGen
+    // installs its vendordata and homebrewdata directories
+    fn beside_datadir(&self, dir: &Option<String>, name: &str) -> String {
+        match dir {
+            Some(dir) => dir.clone(),
+            None => Path::new(self.datadir())
+                .join("..")
+                .join(name)
+                .to_string_lossy()
+                .to_string(),
+        }
+    }
+
+    // vendor data directory; by default, PCGen's "vendordata"
+    pub fn vendordir(&self) -> String {
+        self.beside_datadir(&self.vendordir, "vendordata")
+    }
+
+    // homebrew data directory; by default, PCGen's "homebrewdata"
+    pub fn homebrewdir(&self) -> String {
+        self.beside_datadir(&self.homebrewdir, "homebrewdata")
+    }
+
+    // Resolve the path of a file named with a prefix, as PCGen does:
+    // "@" paths are of the data directories, and "*" paths of the
+    // vendor data directory, the homebrew data directory, or else
+    // the data directories, the first having the file.  None for a
+    // path of neither prefix.
+    pub fn prefixed_path(&self, path: &str) -> Option<String> {
+        if let Some(relpath) = path.strip_prefix('@') {
+            return Some(paths::in_datadirs(&self.datadirs, relpath));
+        }
+        let relpath = path.strip_prefix('*')?;
+        for dir in [self.vendordir(), self.homebrewdir()] {
+            let found = paths::resolve(
+                &Path::new(&dir)
+                    .join(paths::relative(relpath))
+                    .to_string_lossy(),
+            );
+            if files::exists(&found) {
+                return Some(found);
+            }
+        }
+        Some(paths::in_datadirs(&self.datadirs, relpath))
+    }
+}
+
+// how a second definition of an existing element, in the same list,
+// is handled
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum DuplicatePolicy {
+    // merge the second definition into the first, with a warning
+    #[default]
+    Warn,
+    // fail the load
+    Error,
+    // ignore the second definition, with a warning
+    KeepFirst,
+    // replace the first definition with the second, with a warning
+    KeepLast,
+}
+
+impl FromStr for DuplicatePolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<DuplicatePolicy, String> {
+        match s.to_ascii_lowercase().as_str() {
+            "warn" => Ok(DuplicatePolicy::Warn),
+            "error" => Ok(DuplicatePolicy::Error),
+            "keep-first" => Ok(DuplicatePolicy::KeepFirst),
+            "keep-last" => Ok(DuplicatePolicy::KeepLast),
+            _ => Err(format!("unknown duplicate policy {}", s)),
+        }
+    }
+}
+
+impl fmt::Display for DuplicatePolicy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match self {
+            DuplicatePolicy::Warn => "warn",
+            DuplicatePolicy::Error => "error",
+            DuplicatePolicy::KeepFirst => "keep-first",
+            DuplicatePolicy::KeepLast => "keep-last",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+// a PCC or LST file read during load; kind is "PCC" or the list tag
+#[derive(Clone, Serialize, Deserialize)]
+pub struct LoadedFile {
+    pub kind: String,
+    pub path: String,
+    // the PCC file which first referenced it; none for a file loaded
+    // directly
+    pub from: Option<String>,
+}
+
+// a tag that could not be interpreted, and where it was found
+#[derive(Clone, Serialize, Deserialize)]
+pub struct UnknownTag {
+    pub tag: String,
+    pub file: String,
+    pub line: usize,
+}
+
+// an ABB alias of an element, and where it was defined
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Alias {
+    pub list: String,
+    pub ident: String,
+    pub file: String,
+    pub line: usize,
+}
+
+// the name of an element whose KEY replaced it as the element's ident,
+// and where the KEY was given
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct KeyName {
+    pub name: String,
+    pub file: String,
+    pub line: usize,
+}
+
+// a step of a load, reported to the callback of Pcc::on_progress
+pub enum LoadProgress<'a> {
+    // the PCC files have been read, and this many LST files will be
+    // read next
+    LstQueued(usize),
+    // a PCC or LST file has been read, of this many bytes of text
+    FileRead { path: &'a str, bytes: usize },
+}
+
+pub type ProgressFn = dyn Fn(&LoadProgress) + Send + Sync;
+
+// the size of the text of lines read from a file
+fn text_bytes(lines: &[String]) -> usize {
+    lines.iter().map(|line| line.len() + 1).sum()
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Pcc {
+    config: PccConfig,
+    pub(crate) dict: BTreeMap<String, PccDatum>,
+    pcc_schema: BTreeMap<String, PccTag>,
+    // ABB aliases, by alias
+    pub(crate) aliases: BTreeMap<String, Alias>,
+    // the names of elements identified by KEY, by list, then KEY
+    key_names: BTreeMap<String, BTreeMap<String, KeyName>>,
+    gamemode: Option<GameMode>,
+    files: Vec<LoadedFile>,
+    unknown_tags: Vec<UnknownTag>,
+    diagnostics: Vec<Diagnostic>,
+    // deprecated tags rewritten while loading
+    migrations: Vec<Diagnostic>,
+    #[serde(skip)]
+    migrator: Option<Migrator>,
+    #[serde(skip)]
+    pending: Vec<LstJob>,
+    // campaigns in the data directories, discovered on first use
+    #[serde(skip)]
+    campaigns: Option<Vec<CampaignInfo>>,
+    // PCC files read, and the chain of PCC files now being read
+    #[serde(skip)]
+    visited: HashSet<PathBuf>,
+    #[serde(skip)]
+    includes: Vec<PathBuf>,
+    // file and line of each PCC reference leading to the file being read
+    #[serde(skip)]
+    include_refs: Vec<(String, usize)>,
+    #[serde(skip)]
+    forward_refs: Vec<ForwardRef>,
+    #[serde(skip, default = "new_lst_merge_policy")]
+    merge_policy: HashMap<String, LstMerge>,
+    #[serde(skip)]
+    pool: Interner,
+    // lowercased KEY or name => (list type, ident) of each element,
+    // built on first lookup
+    #[serde(skip)]
+    index: OnceLock<HashMap<String, Vec<(String, String)>>>,
+    #[serde(skip)]
+    progress: Option<Arc<ProgressFn>>,
+}
+
+// an LST file referenced by a PCC file, queued for reading
+struct LstJob {
+    pcc_tag: String,
+    fpath: String,
+    lstopts: String,
+    encoding: Encoding,
+    // where the file was referenced, for error messages
+    origin: String,
+    // the PCC file referencing it
+    from: Option<String>,
+}
+
+// an element declared by FORWARDREF, checked once all data is loaded
+struct ForwardRef {
+    list: String,
+    name: String,
+    file: String,
+    line: usize,
+}
+
+// a single tokenized LST element line
+#[derive(Clone)]
+struct LstRecord {
+    line: usize,
+    raw_ident: String,
+    attribs: Vec<(String, String)>,
+    // tokens lacking a "TAG:" prefix
+    untagged: Vec<String>,
+}
+
+enum LstLine {
+    Header(usize, Vec<(String, String)>),
+    Record(LstRecord),
+}
+
+// an LST file, read and tokenized but not yet merged into the dictionary
+struct ParsedLst {
+    job: LstJob,
+    lines: Vec<LstLine>,
+    bytes: usize,
+}
+
+// Read and tokenize an LST file.  Independent of any other file, so
+// that many files may be parsed in parallel.
+fn parse_lst_file(job: LstJob) -> io::Result<ParsedLst> {
+    let _span = tracing::warn_span!("lst", list = %job.pcc_tag, path = %job.fpath).entered();
+    tracing::debug!(
+        "parse_lst_file({}, {}, \"{}\")",
+        job.pcc_tag,
+        job.fpath,
+        job.lstopts
+    );
+
+    // parse "(INCLUDE:a|b)" or "(EXCLUDE:a|b)" options
+    let filter = LstFilter::parse(&job.lstopts);
+
+    let mut lines = Vec::new();
+
+    // iterate through each text file line
+    let text_lines = text::read_lines(&job.fpath, job.encoding)
+        .map_err(|e| Error::new(e.kind(), format!("{}: {}{}", job.fpath, e, job.origin)))?;
+    let bytes = text_bytes(&text_lines);
+    for (idx, line) in text_lines.into_iter().enumerate() {
+        // comments and empty lines
+        let ch = line.chars().next();
+        if ch.is_none() || ch == Some('#') {
+            continue;
+        }
+
+        // source header line, e.g. "SOURCELONG:x<tab>SOURCESHORT:y"
+        if is_source_header(&line) {
+            let source = line
+                .split('\t')
+                .filter_map(|token| token.split_once(':'))
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect();
+            lines.push(LstLine::Header(idx + 1, source));
+            continue;
+        }
+
+        // skip elements not selected by INCLUDE/EXCLUDE options
+        if !filter.allows(&line) {
+            continue;
+        }
+
+        // split input by <tab> into tokens.  the first token is our
+        // symbol.  the remainder are attribs.
+        let mut tokens = line.split('\t');
+        let raw_ident = tokens.next().unwrap_or("").to_string();
+
+        // gather key=value attribs into a list
+        let mut attribs = Vec::new();
+        let mut untagged = Vec::new();
+        for token in tokens {
+            match token.split_once(':') {
+                None => {
+                    if !token.trim().is_empty() {
+                        attribs.push((token.to_string(), String::from("")));
+                        untagged.push(token.to_string());
+                    }
+                }
+                Some((akey, aval)) => {
+                    attribs.push((akey.to_string(), aval.to_string()));
+                }
+            }
+        }
+
+        lines.push(LstLine::Record(LstRecord {
+            line: idx + 1,
+            raw_ident,
+            attribs,
+            untagged,
+        }));
+    }
+
+    Ok(ParsedLst { job, lines, bytes })
+}
+
+// per-file state while merging an LST file
+struct LstContext {
+    fpath: String,
+    // SOURCExxx tags from the file's source header line, applied to
+    // each element the file defines
+    source: Vec<(Sym, Sym)>,
+    source_line: usize,
+}
+
+// limit on PCC files including PCC files
+const MAX_INCLUDE_DEPTH: usize = 64;
+
+// PRECAMPAIGN:count,name[,name...], where each name may be written as
+// INCLUDES=name
+pub(crate) fn precampaign_args<'a>(args: &[&'a str]) -> (usize, Vec<&'a str>) {
+    let (count, names) = match args.split_first() {
+        Some((n, names)) => (n.parse::<usize>().unwrap_or(1), names),
+        None => (1, args),
+    };
+    let names = names
+        .iter()
+        .map(|name| name.strip_prefix("INCLUDES=").unwrap_or(name))
+        .collect();
+    (count, names)
+}
+
+// Is a PREGAMEMODE or PRECAMPAIGN condition of a PCC line met, by the
+// values of the GAMEMODE, CAMPAIGN and KEY tags of the PCC files read
+// so far?  None for other conditions, which depend on a character and
+// cannot be evaluated at load time.
+pub(crate) fn pcc_prereq_met<'a, F>(prereq: &Prereq, values: F) -> Option<bool>
+where
+    F: Fn(&str) -> Vec<&'a str>,
+{
+    let named = |tag: &str, name: &str| values(tag).iter().any(|v| v.eq_ignore_ascii_case(name));
+    let args = prereq.arg_list();
+    let passed = match prereq.kind.as_str() {
+        // PREGAMEMODE:mode[,mode...]
+        "GAMEMODE" => args.iter().any(|mode| named("GAMEMODE", mode)),
+
+        // PRECAMPAIGN:count,campaign[,campaign...]
+        "CAMPAIGN" => {
+            let (count, names) = precampaign_args(&args);
+            let found = names
+                .iter()
+                .filter(|name| named("CAMPAIGN", name) || named("KEY", name))
+                .count();
+            found >= count
+        }
+
+        _ => return None,
+    };
+    Some(prereq.apply(passed))
+}
+
+// The GAMEMODE of a PCC file, by its lines, if it is not the game mode
+// a load is limited to; the file is then skipped whole.  None if the
+// load is not limited, or the file names no GAMEMODE.
+pub(crate) fn other_gamemode<'a, I>(config: &PccConfig, lines: I) -> Option<&'a str>
+where
+    I: IntoIterator<Item = &'a str>,
+{
+    let wanted = config.gamemode.as_ref()?;
+    let modes = lines
+        .into_iter()
+        .find_map(|line| line.strip_prefix("GAMEMODE:"))?;
+    match modes.split('|').any(|m| m.eq_ignore_ascii_case(wanted)) {
+        true => None,
+        false => Some(modes),
+    }
+}
+
+// PCC boolean values are YES or NO
+fn parse_bool(s: &str) -> Option<bool> {
+    match s.trim().to_ascii_uppercase().as_str() {
+        "YES" | "TRUE" => Some(true),
+        "NO" | "FALSE" => Some(false),
+        _ => None,
+    }
+}
+
+// LST source header lines begin with a SOURCExxx tag, not an element name
+pub(crate) fn is_source_header(line: &str) -> bool {
+    let first = line.split('\t').next().unwrap_or("");
+    first.starts_with("SOURCE") && first.contains(':')
+}
+
+// The region set by a REGION line of a block file, by its ident, e.g.
+// "REGION:Faerun": Some(None) for REGION:Always, which lifts the
+// restriction, and None if the line is not a REGION line.
+pub(crate) fn region_line(ident: &str) -> Option<Option<&str>> {
+    let name = ident.strip_prefix("REGION:")?.trim();
+    if name.is_empty() || name.eq_ignore_ascii_case("Always") {
+        Some(None)
+    } else {
+        Some(Some(name))
+    }
+}
+
+// The name of the block a line of a block file begins, by its ident,
+// e.g. "Fighter Kit" of "STARTPACK:Fighter Kit"; a bare name, as of a
+// class, begins a block too.  None for a line within a block.
+pub(crate) fn block_name<'a>(block_tag: &str, ident: &'a str) -> Option<&'a str> {
+    let block = ident
+        .strip_prefix(block_tag)
+        .and_then(|r| r.strip_prefix(':'));
+    match block {
+        None if ident.parse::<u32>().is_err() && !ident.contains(':') => Some(ident),
+        _ => block,
+    }
+}
+
+// the tag of a line within a block, by its ident: LEVEL for a class
+// level line, e.g. "1", else the tag it begins with, e.g. GEAR of
+// "GEAR:Dagger"
+pub(crate) fn block_line_tag(ident: &str) -> &str {
+    if ident.parse::<u32>().is_ok() {
+        "LEVEL"
+    } else {
+        ident.split_once(':').map_or(ident, |(tag, _)| tag)
+    }
+}
+
+// the path of an LST file referenced by a PCC file in basedir
+pub(crate) fn lst_path(config: &PccConfig, basedir: &str, lstpath: &str) -> String {
+    let lstpath = paths::native(lstpath);
+
+    // parse path prefixes
+    let fpath = if paths::is_absolute(&lstpath) {
+        PathBuf::from(lstpath)
+    } else if let Some(found) = config.prefixed_path(&lstpath) {
+        return found;
+    } else {
+        // "local file", in the same directory as PCC file
+        Path::new(basedir).join(&lstpath)
+    };
+
+    paths::resolve(&fpath.to_string_lossy())
+}
+
+pub(crate) fn dir_from_path(full_path: &str) -> Option<String> {
+    let path = Path::new(full_path);
+    path.parent() // Get the parent directory as Option<&Path>
+        .and_then(|p| p.to_str()) // Convert &Path to Option<&str>
+        .map(|s| s.to_string()) // Convert &str to String
+}
+
+// PCC tags built in to pcgtools; see pcc_schema.toml
+const DEFAULT_PCC_SCHEMA: &str = include_str!("pcc_schema.toml");
+
+#[derive(Deserialize)]
+struct PccSchemaFile {
+    #[serde(default)]
+    tags: HashMap<String, String>,
+}
+
+// parse a PCC schema file, mapping tag names to tag kinds
+pub(crate) fn parse_pcc_schema(text: &str) -> Result<BTreeMap<String, PccTag>, String> {
+    let file: PccSchemaFile = toml::from_str(text).map_err(|e| e.to_string())?;
+    file.tags
+        .into_iter()
+        .map(|(tag, kind)| Ok((tag, kind.parse()?)))
+        .collect()
+}
+
+fn new_pcc_schema() -> BTreeMap<String, PccTag> {
+    parse_pcc_schema(DEFAULT_PCC_SCHEMA).expect("built-in PCC schema")
+}
+
+// the built-in PCC schema, less the tags added after the release the
+// data was written for, which are unknown to it
+pub(crate) fn compat_pcc_schema(compat: DataCompat) -> BTreeMap<String, PccTag> {
+    let mut schema = new_pcc_schema();
+    for tag in compat.unread_pcc_tags() {
+        schema.remove(tag);
+    }
+    schema
+}
+
+// single-valued LST tags, whose values are replaced rather than
+// appended when repeated (typically by a .MOD line).  tags not
+// listed here are multi-valued.
+fn new_lst_merge_policy() -> HashMap<String, LstMerge> {
+    HashMap::from([
+        (String::from("ACCHECK"), LstMerge::Replace),
+        (String::from("ALIGN"), LstMerge::Replace),
+        (String::from("BASEQTY"), LstMerge::Replace),
+        (String::from("CASTTIME"), LstMerge::Replace),
+        (String::from("CATEGORY"), LstMerge::Replace),
+        (String::from("COMPS"), LstMerge::Replace),
+        (String::from("COST"), LstMerge::Replace),
+        (String::from("CR"), LstMerge::Replace),
+        (String::from("CRITMULT"), LstMerge::Replace),
+        (String::from("CRITRANGE"), LstMerge::Replace),
+        (String::from("DAMAGE"), LstMerge::Replace),
+        (String::from("DESC"), LstMerge::Replace),
+        (String::from("DESCISPI"), LstMerge::Replace),
+        (String::from("DURATION"), LstMerge::Replace),
+        (String::from("FACE"), LstMerge::Replace),
+        (String::from("HANDS"), LstMerge::Replace),
+        (String::from("HD"), LstMerge::Replace),
+        (String::from("LEGS"), LstMerge::Replace),
+        (String::from("LEVELADJUSTMENT"), LstMerge::Replace),
+        (String::from("MAXDEX"), LstMerge::Replace),
+        (String::from("MAXLEVEL"), LstMerge::Replace),
+        (String::from("NAMEISPI"), LstMerge::Replace),
+        (String::from("OUTPUTNAME"), LstMerge::Replace),
+        (String::from("RACETYPE"), LstMerge::Replace),
+        (String::from("RANGE"), LstMerge::Replace),
+        (String::from("RATEOFFIRE"), LstMerge::Replace),
+        (String::from("REACH"), LstMerge::Replace),
+        (String::from("SAVEINFO"), LstMerge::Replace),
+        (String::from("SIZE"), LstMerge::Replace),
+        (String::from("SORTKEY"), LstMerge::Replace),
+        (String::from("SOURCELONG"), LstMerge::Replace),
+        (String::from("SOURCEPAGE"), LstMerge::Replace),
+        (String::from("SOURCESHORT"), LstMerge::Replace),
+        (String::from("SOURCEWEB"), LstMerge::Replace),
+        (String::from("SPELLFAILURE"), LstMerge::Replace),
+        (String::from("SPELLRES"), LstMerge::Replace),
+        (String::from("STACK"), LstMerge::Replace),
+        (String::from("TARGETAREA"), LstMerge::Replace),
+        (String::from("VISIBLE"), LstMerge::Replace),
+        (String::from("WT"), LstMerge::Replace),
+    ])
+}
+
+impl Pcc {
+    // create a new Pcc object
+    pub fn new(config: &PccConfig) -> Pcc {
+        Pcc {
+            config: config.clone(),
+            dict: BTreeMap::new(),
+            pcc_schema: compat_pcc_schema(config.compat),
+            aliases: BTreeMap::new(),
+            key_names: BTreeMap::new(),
+            gamemode: None,
+            files: Vec::new(),
+            unknown_tags: Vec::new(),
+            diagnostics: Vec::new(),
+            migrations: Vec::new(),
+            migrator: None,
+            pending: Vec::new(),
+            campaigns: None,
+            visited: HashSet::new(),
+            includes: Vec::new(),
+            include_refs: Vec::new(),
+            forward_refs: Vec::new(),
+            merge_policy: new_lst_merge_policy(),
+            pool: Interner::new(),
+            index: OnceLock::new(),
+            progress: None,
+        }
+    }
+
+    // Report each file read by the loads that follow to a callback,
+    // e.g. to show progress.  LST files are read in parallel, so the
+    // callback may be called from several threads at once.
+    pub fn on_progress<F: Fn(&LoadProgress) + Send + Sync + 'static>(&mut self, callback: F) {
+        self.progress = Some(Arc::new(callback));
+    }
+
+    fn report_progress(&self, step: LoadProgress) {
+        if let Some(callback) = &self.progress {
+            callback(&step);
+        }
+    }
+
+    // re-share pooled strings, after deserialization has given each
+    // string its own allocation
+    #[cfg(feature = "native")]
+    pub(crate) fn repool(&mut self) {
+        let pool = &mut self.pool;
+        for datum in self.dict.values_mut() {
+            let PccDatum::List(lst) = datum else {
+                continue;
+            };
+            for elem in lst.props.values_mut() {
+                for (k, v) in elem.attribs.iter_mut() {
+                    *k = pool.intern(k);
+                    if *k == "TYPE" || k.starts_with("SOURCE") {
+                        *v = pool.intern(v);
+                    }
+                }
+            }
+        }
+    }
+
+    // report a problem found during load
+    fn diagnose(&mut self, diag: Diagnostic) {
+        match diag.severity {
+            Severity::Warning => tracing::warn!("{}", diag),
+            Severity::Error => tracing::error!("{}", diag),
+        }
+        self.diagnostics.push(diag);
+    }
+
+    // if ident is an alias, return the true ident
+    fn resolve_alias(&self, ident: &str) -> String {
+        match self.aliases.get(ident) {
+            None => String::from(ident),
+            Some(alias) => {
+                tracing::debug!("ALIAS MATCH: {} => {}", ident, alias.ident);
+                alias.ident.clone()
+            }
+        }
+    }
+
+    // create an element defined at the given line, optionally with the
+    // file's source header tags
+    fn new_elem(
+        &mut self,
+        ident: &str,
+        ctx: &LstContext,
+        line: usize,
+        with_source: bool,
+    ) -> PccElem {
+        let mut obj = PccElem::new(ident, &ctx.fpath, line);
+        if self.config.provenance {
+            obj.provenance = Some(ElemProvenance::default());
+        }
+
+        if with_source {
+            let header = Provenance {
+                file: self.pool.intern(&ctx.fpath),
+                line: ctx.source_line,
+            };
+            for (akey, aval) in &ctx.source {
+                obj.push_attrib(akey.clone(), aval.clone(), &header);
+            }
+        }
+
+        obj
+    }
+
+    // Merge a single parsed LST record into the list
+    fn merge_lst_record(
+        &mut self,
+        datum: &mut PccDatum,
+        ctx: &LstContext,
+        rec: &LstRecord,
+    ) -> io::Result<()> {
+        let raw_ident = rec.raw_ident.as_str();
+
+        // the ".FORGET" suffix removes an existing elem, possibly one
+        // defined by an earlier-loaded file
+        if let Some(forget_ident) = raw_ident.strip_suffix(".FORGET") {
+            let ident = self.resolve_alias(forget_ident);
+            tracing::debug!("FORGET: {}", ident);
+
+            let lst = datum.as_mut_list().unwrap();
+            if lst.props.shift_remove(&ident).is_none() {
+                tracing::warn!("FORGET target {} not found", ident);
+            }
+            return Ok(());
+        }
+
+        // the ".MOD" suffix triggers update of existing elem.
+        // "Src.COPY=New" creates a new elem, cloned from an existing one.
+        let is_mod = raw_ident.ends_with(".MOD");
+        let mut copy_src = None;
+        let mut ident;
+        if is_mod {
+            ident = String::from(&raw_ident[0..(raw_ident.len() - 4)]);
+        } else if let Some((src, newname)) = raw_ident.split_once(".COPY=") {
+            copy_src = Some(self.resolve_alias(src));
+            ident = String::from(newname);
+        } else {
+            ident = String::from(raw_ident);
+        }
+
+        // if ident is an alias, lookup true ident
+        ident = self.resolve_alias(&ident);
+
+        tracing::debug!("ID={}, is_mod={}, copy={:?}", ident, is_mod, copy_src);
+
+        // tokens lacking a "TAG:" prefix are kept, but noted
+        for tag in &rec.untagged {
+            self.unknown_tags.push(UnknownTag {
+                tag: tag.clone(),
+                file: ctx.fpath.clone(),
+                line: rec.line,
+            });
+        }
+
+        // tag names, and TYPE values, are pooled
+        let mut attribs: Vec<(Sym, Sym)> = Vec::new();
+        for (akey, aval) in &rec.attribs {
+            tracing::debug!("\t{}={}", akey, aval);
+            let aval = match akey.as_str() {
+                "TYPE" => self.pool.intern(aval),
+                _ => Sym::from(aval.as_str()),
+            };
+            attribs.push((self.pool.intern(akey), aval));
+        }
+
+        // pre-processing
+        let list = datum.as_mut_list().unwrap()._ident.clone();
+        let mut name = None;
+        for (key, val) in &attribs {
+            match key.as_ref() {
+                "ABB" => {
+                    tracing::debug!("ALIAS: {}={}", val, ident);
+                    let alias = Alias {
+                        list: list.clone(),
+                        ident: ident.clone(),
+                        file: ctx.fpath.clone(),
+                        line: rec.line,
+                    };
+                    self.aliases.insert(val.to_string(), alias);
+                }
+
+                "KEY" => {
+                    tracing::debug!("KEY: {}={}", val, ident);
+                    if **val != ident {
+                        let key_name = KeyName {
+                            name: ident.clone(),
+                            file: ctx.fpath.clone(),
+                            line: rec.line,
+                        };
+                        let keys = self.key_names.entry(list.clone()).or_default();
+                        keys.insert(val.to_string(), key_name);
+                    }
+                    name = Some(ident).filter(|name| **val != *name);
+                    ident = val.to_string();
+                }
+
+                _ => {}
+            }
+        }
+
+        // grab ref to list inside datum, for update
+        let lst = datum.as_mut_list().unwrap();
+
+        let at = Provenance {
+            file: self.pool.intern(&ctx.fpath),
+            line: rec.line,
+        };
+        let is_copy = copy_src.is_some();
+
+        // remove Elem for update, clone Elem for copy, or create new
+        // if nonexistent
+        let mut obj;
+        if let Some(src) = copy_src {
+            obj = self.new_elem(&ident, ctx, rec.line, false);
+            match lst.props.get(&src) {
+                None => {
+                    tracing::warn!("COPY source {} not found, creating {}", src, ident);
+                }
+                Some(src_obj) => {
+                    // the copy is known by its new name, not the source KEY
+                    obj.attribs = src_obj.attribs.clone();
+                    obj.provenance = src_obj.provenance.clone();
+                    obj.retain_attribs(|akey, _| *akey != "KEY");
+                }
+            }
+        } else if let Some(existing) = lst.props.get_mut(&ident) {
+            // taken for update, and put back in its place below
+            obj = std::mem::replace(existing, PccElem::new(&ident, "", 0));
+
+            // a second definition, rather than a .MOD of the first
+            if !is_mod {
+                let msg = format!(
+                    "{}: duplicate {} definition, first defined at {}:{}",
+                    ident, lst._ident, obj.source, obj.line
+                );
+                match self.config.duplicates {
+                    DuplicatePolicy::Warn => {
+                        self.diagnose(Diagnostic::warning(&ctx.fpath, rec.line, msg));
+                    }
+                    DuplicatePolicy::Error => {
+                        let diag = Diagnostic::error(&ctx.fpath, rec.line, msg);
+                        lst.props.insert(ident, obj);
+                        return Err(Error::other(diag.to_string()));
+                    }
+                    DuplicatePolicy::KeepFirst => {
+                        let msg = format!("{}; ignored", msg);
+                        self.diagnose(Diagnostic::warning(&ctx.fpath, rec.line, msg));
+                        lst.props.insert(ident, obj);
+                        return Ok(());
+                    }
+                    DuplicatePolicy::KeepLast => {
+                        let msg = format!("{}; replaced", msg);
+                        self.diagnose(Diagnostic::warning(&ctx.fpath, rec.line, msg));
+                        obj = self.new_elem(&ident, ctx, rec.line, true);
+                    }
+                }
+            }
+        } else {
+            obj = self.new_elem(&ident, ctx, rec.line, true);
+        }
+
+        if is_mod || is_copy {
+            if let Some(prov) = &mut obj.provenance {
+                prov.mods.push(at.clone());
+            }
+        }
+
+        if name.is_some() {
+            obj.name = name;
+        }
+
+        // merge new attribs into master attrib list
+        for (akey, aval) in attribs {
+            let policy = self
+                .merge_policy
+                .get(akey.as_str())
+                .unwrap_or(&LstMerge::Append);
+            obj.merge_attrib(akey, aval, policy, &mut self.pool, &at);
+        }
+
+        // push Elem with new attribs back into List
+        lst.props.insert(ident.to_string(), obj);
+
+        Ok(())
+    }
+
+    // resolve an LST file reference from a PCC file into a pathname
+    fn lst_path(&self, basedir: &str, lstpath: &str) -> String {
+        lst_path(&self.config, basedir, lstpath)
+    }
+
+    // merge a parsed DYNAMIC file into data dictionary.  Each element
+    // is named "SCOPE:Name", and is merged into the list named by its
+    // scope, creating that list if need be.
+    fn merge_dynamic_lst(&mut self, parsed: ParsedLst) -> io::Result<()> {
+        self.files.push(LoadedFile {
+            kind: parsed.job.pcc_tag.clone(),
+            path: parsed.job.fpath.clone(),
+            from: parsed.job.from.clone(),
+        });
+
+        let mut ctx = LstContext {
+            fpath: parsed.job.fpath.clone(),
+            source: Vec::new(),
+            source_line: 0,
+        };
+
+        for lstline in &parsed.lines {
+            let rec = match lstline {
+                LstLine::Header(line, source) => {
+                    ctx.source_line = *line;
+                    ctx.source = source
+                        .iter()
+                        .map(|(k, v)| (self.pool.intern(k), self.pool.intern(v)))
+                        .collect();
+                    continue;
+                }
+                LstLine::Record(rec) => rec,
+            };
+
+            let Some((scope, name)) = rec.raw_ident.split_once(':') else {
+                let msg = format!("{}: DYNAMIC element lacks a SCOPE: prefix", rec.raw_ident);
+                self.diagnose(Diagnostic::warning(&ctx.fpath, rec.line, msg));
+                continue;
+            };
+            let scope = scope.to_ascii_uppercase();
+
+            let mut datum = match self.dict.remove(&scope) {
+                Some(datum) => datum,
+                None => PccDatum::List(PccList::new(&scope)),
+            };
+            if datum.as_mut_list().is_none() {
+                let msg = format!("{}: DYNAMIC scope {} is not a list", rec.raw_ident, scope);
+                self.diagnose(Diagnostic::warning(&ctx.fpath, rec.line, msg));
+                self.dict.insert(scope, datum);
+                continue;
+            }
+
+            let rec = LstRecord {
+                raw_ident: name.to_string(),
+                ..rec.clone()
+            };
+            let res = self.merge_lst_record(&mut datum, &ctx, &rec);
+            self.dict.insert(scope, datum);
+            res?;
+        }
+
+        Ok(())
+    }
+
+    // merge a parsed file of blocks into data dictionary, as for KIT,
+    // BIOSET and CLASS files.  A line "START:Name", START being the
+    // given block tag, or an ordinary element line, defines an element;
+    // each following line, until the next block, belongs to that
+    // element.  A KIT line such as "GEAR:Dagger<tab>QTY:2" is stored as
+    // an attrib GEAR="Dagger<tab>QTY:2", and a CLASS level line such as
+    // "3<tab>BONUS:x" as LEVEL="3<tab>BONUS:x".  A block repeated within
+    // a file, as a CLASS line often is, extends the earlier one; where
+    // blocks are shared by many files, as BIOSET AGESET blocks are, so
+    // does a block repeated in another file.
+    fn merge_block_lst(
+        &mut self,
+        parsed: ParsedLst,
+        block_tag: &str,
+        shared: bool,
+    ) -> io::Result<()> {
+        let pcc_tag = parsed.job.pcc_tag.clone();
+        let mut datum = match self.dict.remove(&pcc_tag) {
+            Some(datum) => datum,
+            None => PccDatum::List(PccList::new(&pcc_tag)),
+        };
+        if datum.as_mut_list().is_none() {
+            self.dict.insert(pcc_tag.clone(), datum);
+            return Err(Error::other(format!("{} is not a list", pcc_tag)));
+        }
+
+        self.files.push(LoadedFile {
+            kind: pcc_tag.clone(),
+            path: parsed.job.fpath.clone(),
+            from: parsed.job.from.clone(),
+        });
+
+        let mut ctx = LstContext {
+            fpath: parsed.job.fpath.clone(),
+            source: Vec::new(),
+            source_line: 0,
+        };
+
+        let mut current: Option<String> = None;
+        let mut region: Option<String> = None;
+        let mut in_file = HashSet::new();
+        for lstline in &parsed.lines {
+            let rec = match lstline {
+                LstLine::Header(line, source) => {
+                    ctx.source_line = *line;
+                    ctx.source = source
+                        .iter()
+                        .map(|(k, v)| (self.pool.intern(k), self.pool.intern(v)))
+                        .collect();
+                    continue;
+                }
+                LstLine::Record(rec) => rec,
+            };
+
+            if let Some(name) = block_name(block_tag, &rec.raw_ident) {
+                let key = rec.attribs.iter().find(|(k, _)| k == "KEY");
+                let ident = match key {
+                    Some((_, key)) => key.clone(),
+                    None => match name.split_once(".COPY=") {
+                        Some((_, newname)) => newname.to_string(),
+                        None => name.strip_suffix(".MOD").unwrap_or(name).to_string(),
+                    },
+                };
+                let repeated = !in_file.insert(ident.clone())
+                    || (shared && datum.as_mut_list().unwrap().props.contains_key(&ident));
+                let raw_ident = if repeated && !name.ends_with(".MOD") && !name.contains(".COPY=") {
+                    format!("{}.MOD", name)
+                } else {
+                    name.to_string()
+                };
+                let mut rec = LstRecord {
+                    raw_ident,
+                    ..rec.clone()
+                };
+                if let Some(region) = &region {
+                    rec.attribs.push((String::from("REGION"), region.clone()));
+                }
+                self.merge_lst_record(&mut datum, &ctx, &rec)?;
+                current = Some(self.resolve_alias(&ident));
+                continue;
+            }
+
+            // a REGION line restricts the blocks following it to a
+            // region, recorded as their REGION tag; REGION:Always lifts
+            // the restriction
+            if let Some(name) = region_line(&rec.raw_ident) {
+                region = name.map(String::from);
+                continue;
+            }
+
+            let lst = datum.as_mut_list().unwrap();
+            let Some(elem) = current.as_ref().and_then(|k| lst.props.get_mut(k)) else {
+                let msg = format!("{}: line outside any {} block", rec.raw_ident, block_tag);
+                self.diagnose(Diagnostic::warning(&ctx.fpath, rec.line, msg));
+                continue;
+            };
+
+            let kind = block_line_tag(&rec.raw_ident);
+            let mut value = match rec.raw_ident.split_once(':') {
+                Some((_, value)) => value.to_string(),
+                // a class level line, stored whole
+                None => rec.raw_ident.clone(),
+            };
+            for (k, v) in &rec.attribs {
+                value.push('\t');
+                value.push_str(k);
+                if !v.is_empty() || !rec.untagged.contains(k) {
+                    value.push(':');
+                    value.push_str(v);
+                }
+            }
+            let at = Provenance {
+                file: self.pool.intern(&ctx.fpath),
+                line: rec.line,
+            };
+            elem.push_attrib(self.pool.intern(kind), self.pool.intern(&value), &at);
+        }
+
+        self.dict.insert(pcc_tag, datum);
+        Ok(())
+    }
+
+    // merge a parsed DATATABLE file into data dictionary.  A table is
+    // written as comma-separated lines:
+    //
+    //   STARTTABLE:Name
+    //   column names
+    //   column formats
+    //   rows...
+    //   ENDTABLE:Name
+    //
+    // and is stored as an element Name, with attribs COLUMNS, FORMATS
+    // and one ROW per row, each holding its line as written.
+    fn merge_datatable_lst(&mut self, parsed: ParsedLst) -> io::Result<()> {
+        let pcc_tag = parsed.job.pcc_tag.clone();
+        let mut datum = match self.dict.remove(&pcc_tag) {
+            Some(datum) => datum,
+            None => PccDatum::List(PccList::new(&pcc_tag)),
+        };
+        if datum.as_mut_list().is_none() {
+            self.dict.insert(pcc_tag.clone(), datum);
+            return Err(Error::other(format!("{} is not a list", pcc_tag)));
+        }
+
+        self.files.push(LoadedFile {
+            kind: pcc_tag.clone(),
+            path: parsed.job.fpath.clone(),
+            from: parsed.job.from.clone(),
+        });
+
+        let ctx = LstContext {
+            fpath: parsed.job.fpath.clone(),
+            source: Vec::new(),
+            source_line: 0,
+        };
+
+        // spreadsheet exports pad lines with empty cells
+        let first_cell = |line: &str| line.split(',').next().unwrap_or("").trim().to_string();
+
+        let mut current: Option<PccElem> = None;
+        for lstline in &parsed.lines {
+            let LstLine::Record(rec) = lstline else {
+                continue;
+            };
+            let line = rec.raw_ident.as_str();
+            if line
+                .trim_matches(|c: char| c == ',' || c.is_whitespace())
+                .is_empty()
+            {
+                continue;
+            }
+
+            if let Some(name) = line.strip_prefix("STARTTABLE:") {
+                if let Some(table) = current.take() {
+                    let msg = format!("DATATABLE {} lacks ENDTABLE", table._ident);
+                    self.diagnose(Diagnostic::warning(&ctx.fpath, table.line, msg));
+                    datum.as_mut_list().unwrap().insert_table(table);
+                }
+                current = Some(self.new_elem(&first_cell(name), &ctx, rec.line, false));
+                continue;
+            }
+
+            let Some(table) = current.as_mut() else {
+                let msg = format!("{}: line outside any DATATABLE", first_cell(line));
+                self.diagnose(Diagnostic::warning(&ctx.fpath, rec.line, msg));
+                continue;
+            };
+
+            if line.starts_with("ENDTABLE:") {
+                let table = current.take().unwrap();
+                let lst = datum.as_mut_list().unwrap();
+                if let Some(first) = lst.props.get(&table._ident) {
+                    let msg = format!(
+                        "DATATABLE {} redefined, first defined at {}:{}",
+                        table._ident, first.source, first.line
+                    );
+                    self.diagnose(Diagnostic::warning(&ctx.fpath, table.line, msg));
+                }
+                datum.as_mut_list().unwrap().insert_table(table);
+                continue;
+            }
+
+            let kind = match table.attribs.len() {
+                0 => "COLUMNS",
+                1 => "FORMATS",
+                _ => "ROW",
+            };
+            let at = Provenance {
+                file: self.pool.intern(&ctx.fpath),
+                line: rec.line,
+            };
+            table.push_attrib(self.pool.intern(kind), self.pool.intern(line), &at);
+        }
+
+        if let Some(table) = current {
+            let msg = format!("DATATABLE {} lacks ENDTABLE", table._ident);
+            self.diagnose(Diagnostic::warning(&ctx.fpath, table.line, msg));
+            datum.as_mut_list().unwrap().insert_table(table);
+        }
+
+        self.dict.insert(pcc_tag, datum);
+        Ok(())
+    }
+
+    // rewrite the deprecated tags of a file's records, if migrating
+    fn migrate_lst(&mut self, parsed: &mut ParsedLst) {
+        let Some(migrator) = &self.migrator else {
+            return;
+        };
+        for lstline in parsed.lines.iter_mut() {
+            if let LstLine::Record(rec) = lstline {
+                self.migrations.extend(migrator.migrate_attribs(
+                    &parsed.job.pcc_tag,
+                    &parsed.job.fpath,
+                    rec.line,
+                    &mut rec.attribs,
+                ));
+            }
+        }
+    }
+
+    // merge a parsed LST file into data dictionary
+    fn merge_lst(&mut self, mut parsed: ParsedLst) -> io::Result<()> {
+        let job = &parsed.job;
+        let _span = tracing::warn_span!("lst", list = %job.pcc_tag, path = %job.fpath).entered();
+        self.index = OnceLock::new();
+        self.migrate_lst(&mut parsed);
+
+        let pcc_tag = parsed.job.pcc_tag.as_str();
+        match self.pcc_schema.get(pcc_tag) {
+            Some(PccTag::Dynamic) => return self.merge_dynamic_lst(parsed),
+            Some(PccTag::BioSet) => return self.merge_block_lst(parsed, "AGESET", true),
+            Some(PccTag::Class) => return self.merge_block_lst(parsed, "CLASS", false),
+            Some(PccTag::DataTable) => return self.merge_datatable_lst(parsed),
+            Some(PccTag::Kit) => return self.merge_block_lst(parsed, "STARTPACK", false),
+            _ => {}
+        }
+
+        let mut datum;
+
+        // Does the List record already exist?  if not, create a new one.
+        // Due to "second mutable borrow" issue, we must remove from
+        // the map, and then insert back into the map when we're done.
+        if !self.dict.contains_key(pcc_tag) {
+            datum = PccDatum::List(PccList::new(pcc_tag));
+        } else {
+            datum = self.dict.remove(pcc_tag).unwrap();
+        }
+
+        // record type check
+        if datum.as_mut_list().is_none() {
+            self.dict.insert(pcc_tag.to_string(), datum);
+            return Err(Error::other(format!(
+                "{}: {} is not a list",
+                parsed.job.fpath, pcc_tag
+            )));
+        }
+
+        self.files.push(LoadedFile {
+            kind: pcc_tag.to_string(),
+            path: parsed.job.fpath.clone(),
+            from: parsed.job.from.clone(),
+        });
+
+        let mut ctx = LstContext {
+            fpath: parsed.job.fpath.clone(),
+            source: Vec::new(),
+            source_line: 0,
+        };
+
+        for lstline in &parsed.lines {
+            match lstline {
+                LstLine::Header(line, source) => {
+                    ctx.source_line = *line;
+                    ctx.source = source
+                        .iter()
+                        .map(|(k, v)| (self.pool.intern(k), self.pool.intern(v)))
+                        .collect()
+                }
+                LstLine::Record(rec) => self.merge_lst_record(&mut datum, &ctx, rec)?,
+            }
+        }
+
+        // finally, replace updated datum in dictionary
+        self.dict.insert(pcc_tag.to_string(), datum);
+
+        Ok(())
+    }
+
+    // Read LST file into data dictionary
+    pub fn read_lst(
+        &mut self,
+        pcc_tag: &str,
+        basedir: &str,
+        lstpath: &str,
+        lstopts: &str,
+    ) -> io::Result<()> {
+        let job = LstJob {
+            pcc_tag: pcc_tag.to_string(),
+            fpath: self.lst_path(basedir, lstpath),
+            lstopts: lstopts.to_string(),
+            encoding: self.config.encoding,
+            origin: String::new(),
+            from: None,
+        };
+
+        let parsed = parse_lst_file(job)?;
+        self.merge_lst(parsed)
+    }
+
+    // Read all LST files referenced by PCC files read so far.  Files
+    // are parsed in parallel, then merged serially in reference order,
+    // as .MOD, .COPY and .FORGET depend on that order.
+    fn read_pending_lst(&mut self) -> io::Result<()> {
+        let jobs = std::mem::take(&mut self.pending);
+        self.report_progress(LoadProgress::LstQueued(jobs.len()));
+        let parsed: Vec<io::Result<ParsedLst>> = jobs
+            .into_par_iter()
+            .map(|job| {
+                let parsed = parse_lst_file(job)?;
+                self.report_progress(LoadProgress::FileRead {
+                    path: &parsed.job.fpath,
+                    bytes: parsed.bytes,
+                });
+                Ok(parsed)
+            })
+            .collect();
+
+        for res in parsed {
+            self.merge_lst(res?)?;
+        }
+
+        self.check_forward_refs();
+        self.resolve_output_names();
+        Ok(())
+    }
+
+    // Resolve the OUTPUTNAME of each element having one: "[BASE]" is
+    // replaced by the name of the element's BASEITEM, or else its own
+    // name, and "%" by its own name, e.g. "OUTPUTNAME:[BASE] +1" of a
+    // magic longsword gives "Longsword +1".
+    fn resolve_output_names(&mut self) {
+        let mut resolved = Vec::new();
+        for (list, datum) in &self.dict {
+            let PccDatum::List(lst) = datum else {
+                continue;
+            };
+            for (ident, elem) in &lst.props {
+                let outputname = elem.get("OUTPUTNAME").map(|pattern| {
+                    let base = match elem.get("BASEITEM") {
+                        Some(base) => lst
+                            .get(base)
+                            .or_else(|| self.lookup_in(list, base))
+                            .map_or(base, |e| e.name()),
+                        None => elem.name(),
+                    };
+                    pattern.replace("[BASE]", base).replace('%', elem.name())
+                });
+                if outputname != elem.outputname {
+                    resolved.push((list.clone(), ident.clone(), outputname));
+                }
+            }
+        }
+
+        for (list, ident, outputname) in resolved {
+            if let Some(PccDatum::List(lst)) = self.dict.get_mut(&list) {
+                if let Some(elem) = lst.props.get_mut(&ident) {
+                    elem.outputname = outputname;
+                }
+            }
+        }
+    }
+
+    // diagnose FORWARDREF declarations of elements the loaded data
+    // never defined
+    fn check_forward_refs(&mut self) {
+        for fref in std::mem::take(&mut self.forward_refs) {
+            let ident = self.resolve_alias(&fref.name);
+            let found = self.list(&fref.list).is_some_and(|lst| {
+                lst.props.contains_key(&ident)
+                    || lst.props.keys().any(|k| k.eq_ignore_ascii_case(&ident))
+            });
+            if !found {
+                let msg = format!("FORWARDREF: {} {} never defined", fref.list, fref.name);
+                self.diagnose(Diagnostic::warning(&fref.file, fref.line, msg));
+            }
+        }
+    }
+
+    // values of a (possibly repeated) PCC text tag
+    fn text_values(&self, tag: &str) -> Vec<&str> {
+        match self.dict.get(tag) {
+            Some(PccDatum::Text(val)) => val.lines().flat_map(|v| v.split('|')).collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    // evaluate a PRExxx condition against the campaign state loaded so far
+    fn check_prereq(&self, prereq: &Prereq) -> bool {
+        pcc_prereq_met(prereq, |tag| self.text_values(tag)).unwrap_or_else(|| {
+            tracing::debug!("PRE{} not evaluated at load time", prereq.kind);
+            true
+        })
+    }
+
+    // locate and load the campaigns a PRECAMPAIGN line requires, that
+    // are not yet loaded
+    fn load_required_campaigns(
+        &mut self,
+        prereq: &Prereq,
+        fpath: &str,
+        lineno: usize,
+    ) -> io::Result<()> {
+        if self.campaigns.is_none() {
+            let found = campaign::discover(&self.config.datadirs, self.config.encoding)?;
+            self.campaigns = Some(found);
+        }
+
+        let args = prereq.arg_list();
+        let (_, names) = precampaign_args(&args);
+        for name in names {
+            let loaded = self
+                .text_values("CAMPAIGN")
+                .iter()
+                .chain(self.text_values("KEY").iter())
+                .any(|c| c.eq_ignore_ascii_case(name));
+            if loaded {
+                continue;
+            }
+
+            let campaigns = self.campaigns.as_deref().unwrap_or_default();
+            let Some(info) = campaigns.iter().find(|c| c.is_named(name)) else {
+                let msg = format!(
+                    "PRECAMPAIGN: required campaign {} not found in {}",
+                    name,
+                    self.config.datadirs.join(", ")
+                );
+                self.diagnose(Diagnostic::error(fpath, lineno, msg));
+                continue;
+            };
+
+            let already_read = self
+                .files
+                .iter()
+                .any(|f| f.kind == "PCC" && f.path == info.path);
+            if !already_read {
+                tracing::info!("PRECAMPAIGN: loading {} from {}", name, info.path);
+                let path = info.path.clone();
+                self.read_pcc(&path, false)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn read_pcc_line(
+        &mut self,
+        basedir: &str,
+        fpath: &str,
+        lineno: usize,
+        line: &str,
+    ) -> io::Result<()> {
+        // split on ':'
+        let sor = line.split_once(':');
+        if sor.is_none() {
+            return Err(Error::other("PCC invalid line:colon"));
+        }
+
+        let (lhs, rhs) = sor.unwrap();
+
+        // a PRExxx line is a prerequisite of the campaign itself
+        if let Some(prereq) = Prereq::parse(line) {
+            if prereq.kind == "CAMPAIGN" && !prereq.negate && !self.check_prereq(&prereq) {
+                self.include_refs.push((fpath.to_string(), lineno));
+                let res = self.load_required_campaigns(&prereq, fpath, lineno);
+                self.include_refs.pop();
+                res?;
+            }
+            if !self.check_prereq(&prereq) {
+                tracing::warn!("Campaign prerequisite not met: {}", line);
+            }
+            if !self.pcc_schema.contains_key(lhs) {
+                return Ok(());
+            }
+        }
+
+        // PCGen reads a leading '!' only as the negation of a PRExxx
+        // tag, handled above; on any other tag it names no token PCGen
+        // knows, and the line is reported and ignored, as here
+        if lhs.starts_with('!') {
+            let msg = format!("{}: negated PCC tag, ignored", lhs);
+            self.diagnose(Diagnostic::warning(fpath, lineno, msg));
+            return Ok(());
+        }
+
+        // strip trailing conditions, and skip line if conditions not met
+        let (rhs, prereqs) = prereq::split_prereqs(rhs);
+        let rhs = rhs.as_str();
+        if let Some(failed) = prereqs.iter().find(|p| !self.check_prereq(p)) {
+            tracing::debug!("PCC line skipped, {:?} not met: {}", failed, line);
+            return Ok(());
+        }
+
+        // is this tag in the known schema?
+        let tagtype_res = self.pcc_schema.get(lhs);
+        if tagtype_res.is_none() {
+            if !self.config.allow_unknown_tags {
+                return Err(Error::other(format!("PCC invalid key {}", lhs)));
+            }
+
+            let msg = format!("{}: unknown PCC tag, ignored", lhs);
+            self.diagnose(Diagnostic::warning(fpath, lineno, msg));
+            self.unknown_tags.push(UnknownTag {
+                tag: line.to_string(),
+                file: fpath.to_string(),
+                line: lineno,
+            });
+            return Ok(());
+        }
+
+        let tagtype = tagtype_res.unwrap();
+        match tagtype {
+            // input included PCC file
+            PccTag::PccFile => {
+                // relative path indicated by leading '@'; one of
+                // leading '*' may also be of vendor or homebrew data
+                let (is_rel, path) = match rhs.strip_prefix('@') {
+                    Some(relpath) => (true, relpath.to_string()),
+                    None if rhs.starts_with('*') => {
+                        let path = self.config.prefixed_path(&paths::native(rhs));
+                        (false, path.expect("prefixed path"))
+                    }
+                    // a remote PCC file's own paths are of files
+                    // beside it, on its server
+                    None if paths::is_url(fpath) && !paths::is_absolute(rhs) => {
+                        let path = Path::new(basedir).join(paths::native(rhs));
+                        (false, path.to_string_lossy().to_string())
+                    }
+                    None => (false, rhs.to_string()),
+                };
+
+                self.include_refs.push((fpath.to_string(), lineno));
+                let res = self.read_pcc(&path, is_rel);
+                self.include_refs.pop();
+                res?;
+            }
+
+            // queue LST file for reading
+            PccTag::BioSet
+            | PccTag::Class
+            | PccTag::DataTable
+            | PccTag::Dynamic
+            | PccTag::Kit
+            | PccTag::LstFile => {
+                let (lstpath, lstopts) = rhs.split_once('|').unwrap_or((rhs, ""));
+                let lstpath = self.lst_path(basedir, lstpath);
+
+                // "DATACONTROL:@/data/*.lst" names every matching file
+                let lstpaths = if paths::has_wildcard(&lstpath) {
+                    let found = paths::expand_wildcard(&lstpath);
+                    if found.is_empty() {
+                        let msg = format!("{}: no files match {}", lhs, lstpath);
+                        self.diagnose(Diagnostic::warning(fpath, lineno, msg));
+                    }
+                    found
+                } else {
+                    vec![lstpath]
+                };
+
+                for lstpath in lstpaths {
+                    self.pending.push(LstJob {
+                        pcc_tag: lhs.to_string(),
+                        fpath: lstpath,
+                        lstopts: lstopts.to_string(),
+                        encoding: self.config.encoding,
+                        origin: self.include_chain(Some((fpath, lineno))),
+                        from: Some(fpath.to_string()),
+                    });
+                }
+            }
+
+            // typed values replace any earlier value.  a value not of
+            // the declared type is kept as text.
+            PccTag::Bool | PccTag::Date | PccTag::Number => {
+                let (typename, value) = match tagtype {
+                    PccTag::Bool => ("YES or NO", parse_bool(rhs).map(PccDatum::Bool)),
+                    PccTag::Date => ("a date", rhs.parse().ok().map(PccDatum::Date)),
+                    _ => ("a number", rhs.trim().parse().ok().map(PccDatum::Number)),
+                };
+                let value = value.unwrap_or_else(|| {
+                    let msg = format!("{}: expected {}, found \"{}\"", lhs, typename, rhs);
+                    self.diagnose(Diagnostic::warning(fpath, lineno, msg));
+                    PccDatum::Text(rhs.to_string())
+                });
+                self.dict.insert(lhs.to_string(), value);
+            }
+
+            // FORWARDREF:ABILITY=FEAT|Power Attack,Cleave
+            PccTag::ForwardRef => {
+                let (kind, names) = rhs.split_once('|').unwrap_or((rhs, ""));
+                let list = kind.split('=').next().unwrap_or(kind).to_ascii_uppercase();
+                for name in names.split(',').map(|n| n.trim()).filter(|n| !n.is_empty()) {
+                    self.forward_refs.push(ForwardRef {
+                        list: list.clone(),
+                        name: name.to_string(),
+                        file: fpath.to_string(),
+                        line: lineno,
+                    });
+                }
+            }
+
+            // handle text data
+            PccTag::Text => {
+                // store in global data dictionary
+                let tag = self.dict.get_mut(lhs);
+                match tag {
+                    // new key; store in hashmap
+                    None => {
+                        self.dict
+                            .insert(lhs.to_string(), PccDatum::Text(rhs.to_string()));
+                    }
+
+                    // existing key; append to string value
+                    Some(datum) => {
+                        if let PccDatum::Text(val) = datum {
+                            val.push('\n');
+                            val.push_str(rhs);
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    // add the PCC tags of a schema file to those known; see
+    // pcc_schema.toml for its form
+    pub fn load_schema(&mut self, path: &str) -> io::Result<()> {
+        let text = files::read_to_string(path)?;
+        let tags = parse_pcc_schema(&text).map_err(|e| Error::other(format!("{}: {}", path, e)))?;
+        tracing::debug!("{}: {} PCC schema tags", path, tags.len());
+        self.pcc_schema.extend(tags);
+        Ok(())
+    }
+
+    fn load_config_schema(&mut self) -> io::Result<()> {
+        match self.config.schema.clone() {
+            Some(path) => self.load_schema(&path),
+            None => Ok(()),
+        }
+    }
+
+    // deprecated tags are rewritten on request, or for data of a
+    // release reading them
+    fn load_config_migrator(&mut self) -> io::Result<()> {
+        let migrate = self.config.migrate || self.config.compat.reads_legacy_tags();
+        if migrate && self.migrator.is_none() {
+            self.migrator = Some(Migrator::load(self.config.migrations.as_deref())?);
+        }
+        Ok(())
+    }
+
+    // recursively read PCC file data, and the LST files it references,
+    // into Pcc object
+    pub fn read(&mut self, pccpath: &str, is_relative: bool) -> io::Result<()> {
+        self.load_config_schema()?;
+        self.load_config_migrator()?;
+        self.read_pcc(pccpath, is_relative)?;
+        self.read_pending_lst()
+    }
+
+    // read several toplevel PCC files, in order, into one data set.
+    // LST files from all of them are read together.
+    pub fn read_all<S: AsRef<str>>(&mut self, pccpaths: &[S], is_relative: bool) -> io::Result<()> {
+        self.load_config_schema()?;
+        self.load_config_migrator()?;
+        for pccpath in pccpaths {
+            self.read_pcc(pccpath.as_ref(), is_relative)?;
+        }
+        self.read_pending_lst()
+    }
+
+    // describe the chain of PCC references leading to a file, starting
+    // with the reference at, if any, for error messages
+    fn include_chain(&self, at: Option<(&str, usize)>) -> String {
+        let outer = self.include_refs.iter().rev();
+        let mut refs = at
+            .into_iter()
+            .chain(outer.map(|(file, line)| (file.as_str(), *line)));
+        let mut chain = String::new();
+        if let Some((file, line)) = refs.next() {
+            chain.push_str(&format!("\n  referenced at {}:{}", file, line));
+        }
+        for (file, line) in refs {
+            chain.push_str(&format!("\n  included from {}:{}", file, line));
+        }
+        chain
+    }
+
+    // recursively read PCC file data, queueing LST files for reading
+    fn read_pcc(&mut self, pccpath: &str, is_relative: bool) -> io::Result<()> {
+        let pccpath = paths::native(pccpath);
+        let fpath = if is_relative && !paths::is_url(&pccpath) {
+            paths::in_datadirs(&self.config.datadirs, &pccpath)
+        } else {
+            paths::resolve(&pccpath)
+        };
+
+        let basedir = dir_from_path(&fpath).unwrap();
+
+        // messages, and PCC files read from this one, are logged within
+        // its span; spans are at warn level, so that the warnings shown
+        // by default name the file
+        let _span = tracing::warn_span!("pcc", path = %fpath).entered();
+        tracing::debug!("Pcc.read({})", fpath);
+
+        // a file including itself, directly or through other files, is
+        // an error.  a file included again by an unrelated path, as by
+        // two campaigns sharing a core file, is read only once.
+        let canon = files::canonicalize(&fpath).unwrap_or_else(|_| PathBuf::from(&fpath));
+        if self.includes.contains(&canon) {
+            let chain: Vec<String> = self
+                .includes
+                .iter()
+                .chain(std::iter::once(&canon))
+                .map(|p| p.display().to_string())
+                .collect();
+            return Err(Error::other(format!(
+                "PCC include cycle: {}",
+                chain.join(" -> ")
+            )));
+        }
+        if self.includes.len() >= MAX_INCLUDE_DEPTH {
+            return Err(Error::other(format!(
+                "PCC includes nested deeper than {} at {}",
+                MAX_INCLUDE_DEPTH, fpath
+            )));
+        }
+        if !self.visited.insert(canon.clone()) {
+            tracing::info!("Skipping {}: already read", fpath);
+            return Ok(());
+        }
+
+        let text_lines = text::read_lines(&fpath, self.config.encoding).map_err(|e| {
+            Error::new(
+                e.kind(),
+                format!("{}: {}{}", fpath, e, self.include_chain(None)),
+            )
+        })?;
+        self.files.push(LoadedFile {
+            kind: String::from("PCC"),
+            path: fpath.clone(),
+            from: self.include_refs.last().map(|(file, _)| file.clone()),
+        });
+        self.report_progress(LoadProgress::FileRead {
+            path: &fpath,
+            bytes: text_bytes(&text_lines),
+        });
+
+        let mut lines = Vec::new();
+        for (idx, line) in text_lines.into_iter().enumerate() {
+            // comments and empty lines
+            let ch = line.chars().next();
+            if ch.is_none() || ch == Some('#') {
+                continue;
+            }
+
+            lines.push((idx + 1, line));
+        }
+
+        // skip entire campaign, if not for the requested game mode
+        let texts = lines.iter().map(|(_, line)| line.as_str());
+        if let Some(modes) = other_gamemode(&self.config, texts) {
+            let wanted = self.config.gamemode.as_deref().unwrap_or_default();
+            tracing::info!("Skipping {}: GAMEMODE {} not {}", fpath, modes, wanted);
+            return Ok(());
+        }
+
+        self.includes.push(canon);
+        for (lineno, line) in &lines {
+            self.read_pcc_line(&basedir, &fpath, *lineno, line)?;
+        }
+        self.includes.pop();
+
+        Ok(())
+    }
+
+    // load game mode system files
+    pub fn load_gamemode(&mut self, mode: &str) -> io::Result<()> {
+        let _span = tracing::warn_span!("gamemode", mode).entered();
+        let gm = GameMode::load(&self.config.systemdir(), mode)?;
+        self.gamemode = Some(gm);
+        Ok(())
+    }
+
+    pub fn config(&self) -> &PccConfig {
+        &self.config
+    }
+
+    pub fn gamemode(&self) -> Option<&GameMode> {
+        self.gamemode.as_ref()
+    }
+
+    // game modes provided by loaded PCC files
+    pub fn gamemodes(&self) -> Vec<&str> {
+        self.text_values("GAMEMODE")
+    }
+
+    // all loaded lists, as (list type, list), in list type order
+    pub fn lists(&self) -> impl Iterator<Item = (&str, &PccList)> {
+        self.dict.iter().filter_map(|(name, datum)| match datum {
+            PccDatum::List(lst) => Some((name.as_str(), lst)),
+            _ => None,
+        })
+    }
+
+    // keep only the list elements for which keep(list type, element)
+    // returns true
+    pub fn retain_elems<F: FnMut(&str, &PccElem) -> bool>(&mut self, mut keep: F) {
+        for (name, datum) in self.dict.iter_mut() {
+            if let PccDatum::List(lst) = datum {
+                lst.props.retain(|_, elem| keep(name, elem));
+            }
+        }
+        self.index = OnceLock::new();
+    }
+
+    // lookup a list by its list type, e.g. "SPELL"
+    pub fn list(&self, name: &str) -> Option<&PccList> {
+        match self.dict.get(name) {
+            Some(PccDatum::List(lst)) => Some(lst),
+            _ => None,
+        }
+    }
+
+    fn index(&self) -> &HashMap<String, Vec<(String, String)>> {
+        self.index.get_or_init(|| {
+            let mut index: HashMap<String, Vec<(String, String)>> = HashMap::new();
+            for (name, datum) in &self.dict {
+                let PccDatum::List(lst) = datum else {
+                    continue;
+                };
+                for (ident, elem) in &lst.props {
+                    let entry = (name.clone(), ident.clone());
+                    let key = ident.to_lowercase();
+                    let mut names = vec![elem._ident.to_lowercase(), elem.name().to_lowercase()];
+                    names.dedup();
+                    for elem_name in names.into_iter().filter(|n| *n != key) {
+                        index.entry(elem_name).or_default().push(entry.clone());
+                    }
+                    index.entry(key).or_default().push(entry);
+                }
+            }
+            for entries in index.values_mut() {
+                entries.sort();
+            }
+            index
+        })
+    }
+
+    // find elements of any list by KEY, name or alias, ignoring case,
+    // e.g. lookup("Power Attack") => [("ABILITY", elem)]
+    pub fn lookup(&self, name: &str) -> Vec<(&str, &PccElem)> {
+        self.lookup_idents(name)
+            .into_iter()
+            .map(|(list, _, elem)| (list, elem))
+            .collect()
+    }
+
+    // as lookup, with the ident under which each element is stored
+    pub(crate) fn lookup_idents(&self, name: &str) -> Vec<(&str, &str, &PccElem)> {
+        let index = self.index();
+        let entries = index
+            .get(&name.to_lowercase())
+            .or_else(|| index.get(&self.aliases.get(name)?.ident.to_lowercase()));
+        entries
+            .into_iter()
+            .flatten()
+            .filter_map(|(list, ident)| {
+                Some((list.as_str(), ident.as_str(), self.list(list)?.get(ident)?))
+            })
+            .collect()
+    }
+
+    // find an element of one list by KEY, name or alias, ignoring case
+    pub fn lookup_in(&self, list: &str, name: &str) -> Option<&PccElem> {
+        self.lookup(name)
+            .into_iter()
+            .find(|(l, _)| *l == list)
+            .map(|(_, elem)| elem)
+    }
+
+    // the loaded KEY or name nearest to a misspelt one, considering
+    // only elements of list, if given
+    pub fn suggest(&self, list: Option<&str>, name: &str) -> Option<&str> {
+        let candidates = self.index().values().flatten().filter_map(|(l, ident)| {
+            let elem = self.list(l)?.get(ident)?;
+            list.is_none_or(|list| list == l)
+                .then_some([ident.as_str(), elem.name()])
+        });
+        suggest::nearest(name, candidates.flatten())
+    }
+
+    // "; did you mean "X"?" for a misspelt name, or nothing
+    pub fn did_you_mean(&self, list: Option<&str>, name: &str) -> String {
+        suggest::did_you_mean(name, self.suggest(list, name))
+    }
+
+    // ABB aliases, and the elements they name
+    pub fn aliases(&self) -> &BTreeMap<String, Alias> {
+        &self.aliases
+    }
+
+    // the names of elements identified by a KEY instead, by list, then
+    // KEY
+    pub fn key_names(&self) -> &BTreeMap<String, BTreeMap<String, KeyName>> {
+        &self.key_names
+    }
+
+    // all PCC and LST files read, in load order
+    pub fn files(&self) -> &[LoadedFile] {
+        &self.files
+    }
+
+    // tags encountered during load that could not be interpreted
+    pub fn unknown_tags(&self) -> &[UnknownTag] {
+        &self.unknown_tags
+    }
+
+    // problems found in the data during load
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        &self.diagnostics
+    }
+
+    // deprecated tags rewritten during load, with --migrate
+    pub fn migrations(&self) -> &[Diagnostic] {
+        &self.migrations
+    }
+
+    // the form of a list's LST files, by the kind of its PCC tag
+    pub(crate) fn lst_form(&self, list: &str) -> LstForm {
+        match self.pcc_schema.get(list) {
+            Some(PccTag::BioSet) => LstForm::Blocks("AGESET"),
+            Some(PccTag::Class) => LstForm::Blocks("CLASS"),
+            Some(PccTag::Kit) => LstForm::Blocks("STARTPACK"),
+            Some(PccTag::DataTable) => LstForm::Tables,
+            _ => LstForm::Lines,
+        }
+    }
+
+    // is this a single-valued LST tag, replaced rather than appended?
+    pub fn is_single_valued(&self, tag: &str) -> bool {
+        matches!(self.merge_policy.get(tag), Some(LstMerge::Replace))
+    }
+
+    // was the game mode provided by any loaded PCC file?
+    pub fn provides_gamemode(&self, gamemode: &str) -> bool {
+        self.text_values("GAMEMODE")
+            .iter()
+            .any(|m| m.eq_ignore_ascii_case(gamemode))
+    }
+
+    // write all data in data dictionary, in the given format
+    pub fn to_writer<W: Write>(&self, format: OutputFormat, w: &mut W) -> io::Result<()> {
+        output::write_value(format, w, self)
+    }
+
+    // display all data in data dictionary
+    pub fn display(&self) {
+        self.to_writer(OutputFormat::Json, &mut io::stdout().lock())
+            .unwrap();
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::reader::{PccReader, PccVisitor};
+    use std::fs;
+
+    // a directory of its own for the files of a test
+    pub(crate) fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("pcgtools-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    pub(crate) fn test_config(dir: &Path) -> PccConfig {
+        PccConfig {
+            datadirs: vec![dir.to_string_lossy().to_string()],
+            gamemode: None,
+            systemdir: None,
+            vendordir: None,
+            homebrewdir: None,
+            cachedir: None,
+            encoding: Encoding::Utf8,
+            provenance: false,
+            duplicates: DuplicatePolicy::Warn,
+            allow_unknown_tags: false,
+            schema: None,
+            migrate: false,
+            migrations: None,
+            compat: DataCompat::default(),
+        }
+    }
+
+    // Write files, as (path, text), to the directory of a test, and
+    // load its t.pcc.
+    pub(crate) fn load(name: &str, files: &[(&str, &str)]) -> Pcc {
+        let dir = test_dir(name);
+        for (path, text) in files {
+            fs::write(dir.join(path), text).unwrap();
+        }
+        let mut pcc = Pcc::new(&test_config(&dir));
+        pcc.read_all(&["t.pcc"], true).unwrap();
+        pcc
+    }
+
+    fn spell<'a>(pcc: &'a Pcc, ident: &str) -> Option<&'a PccElem> {
+        pcc.list("SPELL")?.get(ident)
+    }
+
+    const SPELLS: &str = "Fireball\tSCHOOL:Evocation\tDURATION:Instantaneous\tDESCRIPTOR:Fire\n";
+
+    #[test]
+    fn mod_replaces_single_valued_and_appends_others() {
+        let lst = format!(
+            "{}Fireball.MOD\tDURATION:1 round\tDESCRIPTOR:Evil\n",
+            SPELLS
+        );
+        let pcc = load(
+            "mod",
+            &[("t.pcc", "CAMPAIGN:T\nSPELL:s.lst\n"), ("s.lst", &lst)],
+        );
+        let elem = spell(&pcc, "Fireball").unwrap();
+        assert_eq!(elem.get_all("DURATION"), ["1 round"]);
+        assert_eq!(elem.get_all("DESCRIPTOR"), ["Fire", "Evil"]);
+        assert_eq!(elem.line(), 1);
+    }
+
+    #[test]
+    fn mod_clears_values() {
+        let lst = "Fireball\tDESCRIPTOR:Fire,Evil\tCOMPS:V\tTYPE:Arcane.Divine\n\
+                   Fireball.MOD\tDESCRIPTOR:.CLEAR.Evil\tCOMPS:.CLEAR\tTYPE:.REMOVE.divine.ADD.Psionic\n";
+        let pcc = load(
+            "clear",
+            &[("t.pcc", "CAMPAIGN:T\nSPELL:s.lst\n"), ("s.lst", lst)],
+        );
+        let elem = spell(&pcc, "Fireball").unwrap();
+        assert_eq!(elem.get_all("DESCRIPTOR"), ["Fire"]);
+        assert!(elem.get("COMPS").is_none());
+        assert_eq!(elem.values("TYPE"), ["Arcane", "Psionic"]);
+    }
+
+    #[test]
+    fn copy_and_forget() {
+        let lst = format!(
+            "{}Fireball.COPY=Ice Storm\tDESCRIPTOR:.CLEAR\tDESCRIPTOR:Cold\n\
+             Magic Missile\tSCHOOL:Evocation\nMagic Missile.FORGET\n",
+            SPELLS
+        );
+        let pcc = load(
+            "copy",
+            &[("t.pcc", "CAMPAIGN:T\nSPELL:s.lst\n"), ("s.lst", &lst)],
+        );
+        let copy = spell(&pcc, "Ice Storm").unwrap();
+        assert_eq!(copy.get("SCHOOL"), Some("Evocation"));
+        assert_eq!(copy.get_all("DESCRIPTOR"), ["Cold"]);
+        assert_eq!(
+            spell(&pcc, "Fireball").unwrap().get_all("DESCRIPTOR"),
+            ["Fire"]
+        );
+        assert!(spell(&pcc, "Magic Missile").is_none());
+    }
+
+    const CONDITIONAL_PCC: &str = "CAMPAIGN:T\nGAMEMODE:35e\n\
+                                   SPELL:a.lst|PREGAMEMODE:35e\n\
+                                   SPELL:b.lst|PREGAMEMODE:Pathfinder\n\
+                                   SPELL:c.lst|!PREGAMEMODE:Pathfinder\n\
+                                   !SPELL:d.lst\n";
+
+    fn conditional_files() -> Vec<(&'static str, &'static str)> {
+        vec![
+            ("t.pcc", CONDITIONAL_PCC),
+            ("a.lst", "A\n"),
+            ("b.lst", "B\n"),
+            ("c.lst", "C\n"),
+            ("d.lst", "D\n"),
+        ]
+    }
+
+    #[test]
+    fn pcc_line_conditions() {
+        let pcc = load("pre", &conditional_files());
+        let idents: Vec<&str> = pcc.list("SPELL").unwrap().iter().map(|(i, _)| i).collect();
+        assert_eq!(idents, ["A", "C"]);
+    }
+
+    // the LST files a streaming parse reports
+    #[derive(Default)]
+    struct LstFiles(Vec<String>);
+
+    impl PccVisitor for LstFiles {
+        fn lst_file(&mut self, _list: &str, path: &str) {
+            let name = Path::new(path).file_name().unwrap();
+            self.0.push(name.to_string_lossy().to_string());
+        }
+    }
+
+    #[test]
+    fn reader_agrees_on_pcc_line_conditions() {
+        let dir = test_dir("reader");
+        for (path, text) in conditional_files() {
+            fs::write(dir.join(path), text).unwrap();
+        }
+        let mut config = test_config(&dir);
+        let mut files = LstFiles::default();
+        PccReader::open(&config, &["t.pcc"])
+            .unwrap()
+            .parse_with(&mut files)
+            .unwrap();
+        assert_eq!(files.0, ["a.lst", "c.lst"]);
+
+        // campaigns of other game modes are skipped whole
+        config.gamemode = Some(String::from("Pathfinder"));
+        let mut files = LstFiles::default();
+        PccReader::open(&config, &["t.pcc"])
+            .unwrap()
+            .parse_with(&mut files)
+            .unwrap();
+        assert!(files.0.is_empty());
+        let mut pcc = Pcc::new(&config);
+        pcc.read_all(&["t.pcc"], true).unwrap();
+        assert!(pcc.list("SPELL").is_none());
+    }
+
+    #[test]
+    fn lst_of_a_text_tag_is_an_error() {
+        let mut pcc = load("text", &[("t.pcc", "CAMPAIGN:T\n"), ("x.lst", "X\n")]);
+        let dir = pcc.config().datadir().to_string();
+        assert!(pcc.read_lst("CAMPAIGN", &dir, "x.lst", "").is_err());
+    }
+}
@@ -0,0 +1,348 @@
+//
+// pcg.rs -- reader of saved PCGen character (.pcg) files, and audit of
+// a character against loaded data
+//
+// Copyright (c) 2024 Jeff Garzik
+//
+// This file is part of the pcgtoolssoftware project covered under
+// the MIT License.  For the full license text, please see the LICENSE
+// file in the root directory of this project.
+// SPDX-License-Identifier: MIT
+
+use crate::class::Class;
+use crate::diag::Diagnostic;
+use crate::pcc::Pcc;
+use crate::spell::Spell;
+use crate::text::{self, Encoding};
+use serde::Serialize;
+use std::{collections::BTreeMap, io, path::Path};
+
+// PCGen's escapes for characters with meaning in .pcg lines
+const ENTITIES: [(&str, &str); 8] = [
+    ("&colon;", ":"),
+    ("&pipe;", "|"),
+    ("&lbracket;", "["),
+    ("&rbracket;", "]"),
+    ("&nl;", "\n"),
+    ("&cr;", "\r"),
+    ("&lf;", "\n"),
+    ("&amp;", "&"),
+];
+
+fn decode(s: &str) -> String {
+    let mut out = s.to_string();
+    for (entity, ch) in ENTITIES {
+        out = out.replace(entity, ch);
+    }
+    out
+}
+
+// Split a .pcg line, or the inside of a [...] value, into its TAG:value
+// fields.  Fields are separated by '|', except within brackets, which
+// hold nested fields, e.g.
+// "SKILL:Climb|CLASSBOUGHT:[CLASS:Fighter|RANKS:3.0]".
+fn fields(line: &str) -> Vec<(&str, &str)> {
+    let mut out = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+    for (i, c) in line.char_indices() {
+        match c {
+            '[' => depth += 1,
+            ']' => depth -= 1,
+            '|' if depth == 0 => {
+                out.push(&line[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    out.push(&line[start..]);
+
+    out.into_iter()
+        .filter(|f| !f.is_empty())
+        .map(|f| f.split_once(':').unwrap_or((f, "")))
+        .collect()
+}
+
+// the fields within a [...] value
+fn nested(value: &str) -> Vec<(&str, &str)> {
+    fields(value.trim_start_matches('[').trim_end_matches(']'))
+}
+
+fn field<'a>(fields: &[(&str, &'a str)], tag: &str) -> Option<&'a str> {
+    fields.iter().find(|(k, _)| *k == tag).map(|(_, v)| *v)
+}
+
+// a reference to data by name, and the line of the .pcg file making it
+#[derive(Clone, Debug, Serialize)]
+pub struct Ref {
+    pub name: String,
+    pub line: usize,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct CharClass {
+    pub name: String,
+    pub level: u32,
+    pub line: usize,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct CharAbility {
+    pub category: String,
+    // NORMAL, AUTOMATIC or VIRTUAL
+    pub nature: String,
+    pub key: String,
+    pub line: usize,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct CharSkill {
+    pub name: String,
+    // ranks bought, across all classes
+    pub ranks: f64,
+    pub line: usize,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct CharItem {
+    pub name: String,
+    // the base item of a customized item, e.g. Longsword for
+    // "Longsword +1"
+    pub base: Option<String>,
+    pub quantity: f64,
+    pub line: usize,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct CharSpell {
+    pub name: String,
+    pub class: String,
+    pub level: Option<u32>,
+    pub line: usize,
+}
+
+// a saved character
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct Character {
+    // the .pcg file read
+    pub file: String,
+    pub name: Option<String>,
+    pub player: Option<String>,
+    pub alignment: Option<String>,
+    pub gender: Option<String>,
+    pub age: Option<u32>,
+    // CAMPAIGN names, the data the character was made with
+    pub campaigns: Vec<String>,
+    pub race: Option<Ref>,
+    pub deity: Option<Ref>,
+    // stat abbreviation => score
+    pub stats: BTreeMap<String, i64>,
+    pub classes: Vec<CharClass>,
+    pub abilities: Vec<CharAbility>,
+    pub skills: Vec<CharSkill>,
+    pub equipment: Vec<CharItem>,
+    pub languages: Vec<Ref>,
+    pub spells: Vec<CharSpell>,
+}
+
+impl Character {
+    pub fn read<P: AsRef<Path>>(path: P, encoding: Encoding) -> io::Result<Character> {
+        let path = path.as_ref();
+        let lines = text::read_lines(path, encoding)?;
+        Ok(Character::parse(&path.to_string_lossy(), &lines))
+    }
+
+    pub fn parse<S: AsRef<str>>(file: &str, lines: &[S]) -> Character {
+        let mut ch = Character {
+            file: file.to_string(),
+            ..Character::default()
+        };
+
+        for (n, line) in lines.iter().enumerate() {
+            let line_num = n + 1;
+            let line = line.as_ref().trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let fields = fields(line);
+            let Some(&(tag, value)) = fields.first() else {
+                continue;
+            };
+            let value = decode(value);
+            let r = |name: String| Ref {
+                name,
+                line: line_num,
+            };
+
+            match tag {
+                "CHARACTERNAME" => ch.name = Some(value),
+                "PLAYERNAME" => ch.player = Some(value),
+                "ALIGNMENT" | "ALIGN" => ch.alignment = Some(value),
+                "GENDER" => ch.gender = Some(value),
+                "AGE" => ch.age = value.trim().parse().ok(),
+                "CAMPAIGN" => ch.campaigns.extend(
+                    fields
+                        .iter()
+                        .filter(|(k, _)| *k == "CAMPAIGN")
+                        .map(|(_, v)| decode(v)),
+                ),
+                "RACE" => ch.race = Some(r(value)),
+                "DEITY" => ch.deity = Some(r(value)),
+                "STAT" => {
+                    if let Some(score) = field(&fields, "SCORE").and_then(|s| s.parse().ok()) {
+                        ch.stats.insert(value, score);
+                    }
+                }
+                "CLASS" => ch.classes.push(CharClass {
+                    name: value,
+                    level: field(&fields, "LEVEL")
+                        .and_then(|l| l.parse().ok())
+                        .unwrap_or(0),
+                    line: line_num,
+                }),
+
+                // ABILITY:FEAT|TYPE:NORMAL|CATEGORY:FEAT|MAPKEY:Power
+                // Attack, or in older files, FEAT:Power Attack
+                "ABILITY" => ch.abilities.push(CharAbility {
+                    category: field(&fields, "CATEGORY").map_or(value.clone(), decode),
+                    nature: field(&fields, "TYPE").unwrap_or("NORMAL").to_string(),
+                    key: field(&fields, "MAPKEY").map_or(String::new(), decode),
+                    line: line_num,
+                }),
+                "FEAT" | "VFEAT" => ch.abilities.push(CharAbility {
+                    category: String::from("FEAT"),
+                    nature: String::from(if tag == "VFEAT" { "VIRTUAL" } else { "NORMAL" }),
+                    key: value,
+                    line: line_num,
+                }),
+
+                "SKILL" => ch.skills.push(CharSkill {
+                    name: value,
+                    ranks: fields
+                        .iter()
+                        .filter(|(k, _)| *k == "CLASSBOUGHT")
+                        .filter_map(|(_, v)| field(&nested(v), "RANKS")?.parse::<f64>().ok())
+                        .sum(),
+                    line: line_num,
+                }),
+                "EQUIPNAME" => ch.equipment.push(CharItem {
+                    name: value,
+                    base: field(&fields, "CUSTOMIZATION")
+                        .and_then(|c| field(&nested(c), "BASEITEM").map(decode)),
+                    quantity: field(&fields, "QUANTITY")
+                        .and_then(|q| q.parse().ok())
+                        .unwrap_or(1.0),
+                    line: line_num,
+                }),
+                "LANGUAGE" => ch.languages.extend(
+                    fields
+                        .iter()
+                        .filter(|(k, _)| *k == "LANGUAGE")
+                        .map(|(_, v)| r(decode(v))),
+                ),
+                "SPELLNAME" => ch.spells.push(CharSpell {
+                    name: value,
+                    class: field(&fields, "CLASS").map_or(String::new(), decode),
+                    level: field(&fields, "SPELLLEVEL").and_then(|l| l.parse().ok()),
+                    line: line_num,
+                }),
+                _ => {}
+            }
+        }
+
+        ch
+    }
+
+    // total character level
+    pub fn level(&self) -> u32 {
+        self.classes.iter().map(|c| c.level).sum()
+    }
+
+    // Check the character against loaded data: every race, class,
+    // ability, skill, item, language, spell and deity it names must be
+    // defined, and class levels and spell levels must agree with the
+    // data.
+    pub fn audit(&self, pcc: &Pcc) -> Vec<Diagnostic> {
+        let mut diags = Vec::new();
+        let mut resolve = |list: &str, name: &str, line: usize| {
+            if name.is_empty() || pcc.lookup_in(list, name).is_some() {
+                return;
+            }
+            let hint = pcc.did_you_mean(Some(list), name);
+            diags.push(Diagnostic::error(
+                &self.file,
+                line,
+                format!("{} {} not found in loaded data{}", list, name, hint),
+            ));
+        };
+
+        if let Some(race) = &self.race {
+            resolve("RACE", &race.name, race.line);
+        }
+        if let Some(deity) = &self.deity {
+            resolve("DEITY", &deity.name, deity.line);
+        }
+        for class in &self.classes {
+            resolve("CLASS", &class.name, class.line);
+        }
+        for ability in &self.abilities {
+            resolve("ABILITY", &ability.key, ability.line);
+        }
+        for skill in &self.skills {
+            resolve("SKILL", &skill.name, skill.line);
+        }
+        for item in &self.equipment {
+            resolve(
+                "EQUIPMENT",
+                item.base.as_ref().unwrap_or(&item.name),
+                item.line,
+            );
+        }
+        for lang in &self.languages {
+            resolve("LANGUAGE", &lang.name, lang.line);
+        }
+        for spell in &self.spells {
+            resolve("SPELL", &spell.name, spell.line);
+        }
+
+        for class in &self.classes {
+            let max = pcc.get::<Class>(&class.name).and_then(|c| c.max_level);
+            if let Some(max) = max.filter(|max| class.level > *max) {
+                diags.push(Diagnostic::error(
+                    &self.file,
+                    class.line,
+                    format!(
+                        "CLASS {} level {} exceeds MAXLEVEL {}",
+                        class.name, class.level, max
+                    ),
+                ));
+            }
+        }
+
+        for spell in &self.spells {
+            let (Some(level), Some(data)) = (spell.level, pcc.get::<Spell>(&spell.name)) else {
+                continue;
+            };
+            match data.level_for(&spell.class) {
+                Some(l) if l == level => {}
+                Some(l) => diags.push(Diagnostic::warning(
+                    &self.file,
+                    spell.line,
+                    format!(
+                        "SPELL {} is level {} for {}, not {}",
+                        spell.name, l, spell.class, level
+                    ),
+                )),
+                None => diags.push(Diagnostic::warning(
+                    &self.file,
+                    spell.line,
+                    format!("SPELL {} is not on the {} list", spell.name, spell.class),
+                )),
+            }
+        }
+
+        diags.sort_by_key(|d| d.line);
+        diags
+    }
+}
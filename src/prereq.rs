@@ -0,0 +1,123 @@
+//
+// prereq.rs -- PCgen PRExxx prerequisite conditions
+//
+// Copyright (c) 2024 Jeff Garzik
+//
+// This file is part of the pcgtoolssoftware project covered under
+// the MIT License.  For the full license text, please see the LICENSE
+// file in the root directory of this project.
+// SPDX-License-Identifier: MIT
+
+use crate::token;
+use serde::{Deserialize, Serialize};
+
+// a single prerequisite condition, e.g. "!PREGAMEMODE:35e"
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Prereq {
+    pub negate: bool,
+    pub kind: String,
+    pub args: String,
+}
+
+impl Prereq {
+    // parse "[!]PRExxx:args" into a Prereq, or None if not a prereq
+    pub fn parse(s: &str) -> Option<Prereq> {
+        let (negate, s) = match s.strip_prefix('!') {
+            Some(rest) => (true, rest),
+            None => (false, s),
+        };
+
+        let (tag, args) = s.split_once(':')?;
+        let kind = tag.strip_prefix("PRE")?;
+        if kind.is_empty() || !kind.chars().all(|c| c.is_ascii_uppercase()) {
+            return None;
+        }
+
+        Some(Prereq {
+            negate,
+            kind: String::from(kind),
+            args: String::from(args),
+        })
+    }
+
+    // comma-separated arguments, for the PRExxx kinds using them
+    pub fn arg_list(&self) -> Vec<&str> {
+        token::sublist(&self.args)
+    }
+
+    // human-readable description, e.g. "Feat: Power Attack"
+    pub fn describe(&self) -> String {
+        let args = self.arg_list();
+
+        // most PRExxx tags begin with a count of items required
+        let (count, items) = match args.split_first() {
+            Some((n, rest)) if !rest.is_empty() && n.parse::<usize>().is_ok() => {
+                (n.parse::<usize>().unwrap(), rest.to_vec())
+            }
+            _ => (0, args),
+        };
+
+        let label = match self.kind.as_str() {
+            "ABILITY" => "Ability",
+            "ALIGN" => "Alignment",
+            "ATT" => "Base attack bonus",
+            "CAMPAIGN" => "Campaign",
+            "CLASS" => "Class",
+            "DEITY" => "Deity",
+            "FEAT" => "Feat",
+            "GAMEMODE" => "Game mode",
+            "LANG" => "Language",
+            "LEVEL" => "Character level",
+            "RACE" => "Race",
+            "SIZE" => "Size",
+            "SKILL" => "Skill",
+            "SPELL" => "Spell",
+            "STAT" => "Stat",
+            "TEMPLATE" => "Template",
+            "VAR" => "Variable",
+            "WEAPONPROF" => "Weapon proficiency",
+            _ => self.kind.as_str(),
+        };
+
+        // "Wizard=3" reads better as "Wizard 3"
+        let items: Vec<String> = items
+            .iter()
+            .filter(|item| !item.is_empty())
+            .map(|item| item.replace('=', " "))
+            .collect();
+
+        let mut desc = String::from(label);
+        desc.push_str(": ");
+        if count > 1 && count < items.len() {
+            desc.push_str(&format!("{} of ", count));
+        } else if count == 1 && items.len() > 1 {
+            desc.push_str("one of ");
+        }
+        desc.push_str(&items.join(", "));
+
+        if self.negate {
+            format!("not ({})", desc)
+        } else {
+            desc
+        }
+    }
+
+    // apply negation to a raw evaluation result
+    pub fn apply(&self, passed: bool) -> bool {
+        passed != self.negate
+    }
+}
+
+// separate trailing PRExxx conditions from a PCC tag value, returning
+// the remaining value and the conditions
+pub fn split_prereqs(s: &str) -> (String, Vec<Prereq>) {
+    let mut value = Vec::new();
+    let mut prereqs = Vec::new();
+    for piece in token::items(s) {
+        match Prereq::parse(piece) {
+            Some(prereq) if !value.is_empty() => prereqs.push(prereq),
+            _ => value.push(piece),
+        }
+    }
+    (value.join("|"), prereqs)
+}
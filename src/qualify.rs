@@ -0,0 +1,421 @@
+//
+// qualify.rs -- evaluation of PRExxx prerequisites against a character
+//
+// Copyright (c) 2024 Jeff Garzik
+//
+// This file is part of the pcgtoolssoftware project covered under
+// the MIT License.  For the full license text, please see the LICENSE
+// file in the root directory of this project.
+// SPDX-License-Identifier: MIT
+
+use crate::pcc::{Pcc, PccElem};
+use crate::pcg::Character;
+use crate::prereq::Prereq;
+use crate::text::Encoding;
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, fs, io, io::Error, path::Path};
+
+// alignment abbreviations, in the order of PCGen's numeric alignments
+const ALIGNMENTS: [&str; 9] = ["LG", "LN", "LE", "NG", "TN", "NE", "CG", "CN", "CE"];
+
+// a character, as far as prerequisites see it.  Read from a TOML file,
+// e.g.
+//
+//   race = "Elf"
+//   abilities = ["Power Attack"]
+//   [stats]
+//   STR = 13
+//   [classes]
+//   Fighter = 4
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Profile {
+    pub race: Option<String>,
+    // abbreviation, e.g. LG
+    pub alignment: Option<String>,
+    pub deity: Option<String>,
+    // stat abbreviation => score
+    pub stats: BTreeMap<String, i64>,
+    // class name => level
+    pub classes: BTreeMap<String, u32>,
+    // abilities, feats among them, by KEY or name
+    pub abilities: Vec<String>,
+    // skill name => ranks
+    pub skills: BTreeMap<String, f64>,
+    pub languages: Vec<String>,
+    pub spells: Vec<String>,
+    // equipment carried, by name
+    pub equipment: Vec<String>,
+    // base attack bonus
+    pub bab: Option<i64>,
+}
+
+impl Profile {
+    // read a TOML profile, or a saved .pcg character
+    pub fn read(path: &str, encoding: Encoding) -> io::Result<Profile> {
+        let is_pcg = Path::new(path)
+            .extension()
+            .is_some_and(|e| e.eq_ignore_ascii_case("pcg"));
+        if is_pcg {
+            return Ok(Profile::from(&Character::read(path, encoding)?));
+        }
+        toml::from_str(&fs::read_to_string(path)?).map_err(Error::other)
+    }
+
+    // total character level
+    pub fn level(&self) -> u32 {
+        self.classes.values().sum()
+    }
+
+    fn has(list: &[String], name: &str) -> bool {
+        list.iter().any(|n| n.eq_ignore_ascii_case(name))
+    }
+}
+
+impl From<&Character> for Profile {
+    fn from(ch: &Character) -> Profile {
+        let mut classes = BTreeMap::new();
+        for c in &ch.classes {
+            *classes.entry(c.name.clone()).or_insert(0) += c.level;
+        }
+        Profile {
+            race: ch.race.as_ref().map(|r| r.name.clone()),
+            alignment: ch.alignment.clone(),
+            deity: ch.deity.as_ref().map(|d| d.name.clone()),
+            stats: ch.stats.clone(),
+            classes,
+            abilities: ch.abilities.iter().map(|a| a.key.clone()).collect(),
+            skills: ch
+                .skills
+                .iter()
+                .map(|s| (s.name.clone(), s.ranks))
+                .collect(),
+            languages: ch.languages.iter().map(|l| l.name.clone()).collect(),
+            spells: ch.spells.iter().map(|s| s.name.clone()).collect(),
+            equipment: ch
+                .equipment
+                .iter()
+                .map(|i| i.base.clone().unwrap_or_else(|| i.name.clone()))
+                .collect(),
+            bab: None,
+        }
+    }
+}
+
+// the result of evaluating one prerequisite
+#[derive(Clone, Debug, Serialize)]
+pub struct Outcome {
+    // as written, e.g. PREFEAT:1,Dodge
+    pub prereq: String,
+    pub description: String,
+    // None for a prerequisite which cannot be evaluated
+    pub passed: Option<bool>,
+    pub reason: String,
+    // outcomes of the prerequisites within a PREMULT
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub nested: Vec<Outcome>,
+}
+
+// split arguments on ',', except within the brackets of PREMULT
+fn split_args(args: &str) -> Vec<&str> {
+    let mut out = Vec::new();
+    let mut depth: usize = 0;
+    let mut start = 0;
+    for (i, c) in args.char_indices() {
+        match c {
+            '[' => depth += 1,
+            ']' => depth = depth.saturating_sub(1),
+            ',' if depth == 0 => {
+                out.push(args[start..i].trim());
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    out.push(args[start..].trim());
+    out.retain(|a| !a.is_empty());
+    out
+}
+
+// the count of items required, and the items; a list without a
+// leading count needs any one item
+fn count_items<'a>(args: &[&'a str]) -> (usize, Vec<&'a str>) {
+    match args.split_first() {
+        Some((n, rest)) if !rest.is_empty() && n.parse::<usize>().is_ok() => {
+            (n.parse().unwrap(), rest.to_vec())
+        }
+        _ => (1, args.to_vec()),
+    }
+}
+
+// "Wizard=3" => ("Wizard", Some("3"))
+fn item_value(item: &str) -> (&str, Option<&str>) {
+    match item.split_once('=') {
+        Some((name, value)) => (name.trim(), Some(value.trim())),
+        None => (item.trim(), None),
+    }
+}
+
+// the check of a single item: whether it holds, and why
+type ItemCheck = Option<(bool, String)>;
+
+struct Evaluator<'a> {
+    pcc: &'a Pcc,
+    profile: &'a Profile,
+}
+
+impl Evaluator<'_> {
+    fn stat(&self, item: &str) -> ItemCheck {
+        let (stat, min) = item_value(item);
+        let min: i64 = min?.parse().ok()?;
+        let score = self
+            .profile
+            .stats
+            .iter()
+            .find(|(s, _)| s.eq_ignore_ascii_case(stat))
+            .map(|(_, v)| *v);
+        Some(match score {
+            Some(score) if score >= min => (true, format!("{} {} >= {}", stat, score, min)),
+            Some(score) => (false, format!("{} {} < {}", stat, score, min)),
+            None => (false, format!("{} unknown", stat)),
+        })
+    }
+
+    // does a class line mark a spellcaster?
+    fn is_spellcaster(&self, class: &str) -> bool {
+        self.pcc
+            .lookup_in("CLASS", class)
+            .is_some_and(|elem| elem.get("SPELLTYPE").is_some())
+    }
+
+    fn class(&self, item: &str) -> ItemCheck {
+        let (name, min) = item_value(item);
+        let min: u32 = min.map_or(Some(1), |m| m.parse().ok())?;
+        let level = self
+            .profile
+            .classes
+            .iter()
+            .filter(|(c, _)| match name.to_ascii_uppercase().as_str() {
+                "ANY" => true,
+                "SPELLCASTER" => self.is_spellcaster(c),
+                _ => c.eq_ignore_ascii_case(name),
+            })
+            .map(|(_, l)| *l)
+            .max()
+            .unwrap_or(0);
+        Some((
+            level >= min,
+            format!("{} level {}, needs {}", name, level, min),
+        ))
+    }
+
+    // does an ability of the character have a TYPE?
+    fn has_ability_type(&self, t: &str) -> Option<&str> {
+        self.profile
+            .abilities
+            .iter()
+            .find(|a| {
+                self.pcc
+                    .lookup_in("ABILITY", a)
+                    .is_some_and(|e| e.values("TYPE").iter().any(|v| v.eq_ignore_ascii_case(t)))
+            })
+            .map(|a| a.as_str())
+    }
+
+    fn ability(&self, item: &str) -> ItemCheck {
+        if item.starts_with("CATEGORY=") {
+            return None;
+        }
+        let t = item
+            .strip_prefix("TYPE=")
+            .or_else(|| item.strip_prefix("TYPE."));
+        Some(match t {
+            Some(t) => match self.has_ability_type(t) {
+                Some(a) => (true, format!("has {} of TYPE {}", a, t)),
+                None => (false, format!("lacks an ability of TYPE {}", t)),
+            },
+            None if Profile::has(&self.profile.abilities, item) => (true, format!("has {}", item)),
+            None => (false, format!("lacks {}", item)),
+        })
+    }
+
+    fn skill(&self, item: &str) -> ItemCheck {
+        let (name, min) = item_value(item);
+        let min: f64 = min?.parse().ok()?;
+        let ranks = self
+            .profile
+            .skills
+            .iter()
+            .find(|(s, _)| s.eq_ignore_ascii_case(name))
+            .map_or(0.0, |(_, r)| *r);
+        Some((
+            ranks >= min,
+            format!("{} ranks {}, needs {}", name, ranks, min),
+        ))
+    }
+
+    fn named(&self, what: &str, have: Option<&str>, item: &str) -> ItemCheck {
+        Some(match have {
+            Some(have) if have.eq_ignore_ascii_case(item) => {
+                (true, format!("{} is {}", what, have))
+            }
+            Some(have) => (false, format!("{} is {}, not {}", what, have, item)),
+            None => (false, format!("{} unknown", what)),
+        })
+    }
+
+    fn alignment(&self, item: &str) -> ItemCheck {
+        let want = match item.parse::<usize>() {
+            Ok(n) => *ALIGNMENTS.get(n)?,
+            Err(_) => item,
+        };
+        self.named("alignment", self.profile.alignment.as_deref(), want)
+    }
+
+    fn deity(&self, item: &str) -> ItemCheck {
+        let deity = self.profile.deity.as_deref();
+        match item.to_ascii_uppercase().as_str() {
+            "Y" | "YES" => Some((
+                deity.is_some(),
+                format!("deity {}", deity.unwrap_or("none")),
+            )),
+            "N" | "NO" => Some((
+                deity.is_none(),
+                format!("deity {}", deity.unwrap_or("none")),
+            )),
+            _ => self.named("deity", deity, item),
+        }
+    }
+
+    fn listed(&self, list: &[String], item: &str) -> ItemCheck {
+        Some(match Profile::has(list, item) {
+            true => (true, format!("has {}", item)),
+            false => (false, format!("lacks {}", item)),
+        })
+    }
+
+    // evaluate counted items, e.g. "2,STR=13,DEX=13"
+    fn counted<F: Fn(&str) -> ItemCheck>(&self, args: &[&str], check: F) -> (Option<bool>, String) {
+        let (count, items) = count_items(args);
+        let mut met = 0;
+        let mut reasons = Vec::new();
+        for item in items {
+            let Some((ok, reason)) = check(item) else {
+                continue;
+            };
+            met += ok as usize;
+            reasons.push(reason);
+        }
+        if reasons.is_empty() {
+            return (None, String::from("no item could be evaluated"));
+        }
+        let mut reason = reasons.join("; ");
+        if count > 1 {
+            reason = format!("{} (needs {}, has {})", reason, count, met);
+        }
+        (Some(met >= count), reason)
+    }
+
+    fn evaluate(&self, prereq: &Prereq) -> Outcome {
+        let args = split_args(&prereq.args);
+        let mut nested = Vec::new();
+        let level = self.profile.level();
+
+        let (passed, reason) = match prereq.kind.as_str() {
+            "STAT" => self.counted(&args, |i| self.stat(i)),
+            "CLASS" => self.counted(&args, |i| self.class(i)),
+            "ABILITY" | "FEAT" => self.counted(&args, |i| self.ability(i)),
+            "SKILL" => self.counted(&args, |i| self.skill(i)),
+            "RACE" => self.counted(&args, |i| {
+                self.named("race", self.profile.race.as_deref(), i)
+            }),
+            "ALIGN" => self.counted(&args, |i| self.alignment(i)),
+            "DEITY" => self.counted(&args, |i| self.deity(i)),
+            "LANG" => self.counted(&args, |i| self.listed(&self.profile.languages, i)),
+            "SPELL" => self.counted(&args, |i| self.listed(&self.profile.spells, i)),
+
+            // LEVEL:MIN=5,MAX=10, or in older data, LEVEL:5
+            "LEVEL" => {
+                let mut min = 0;
+                let mut max = u32::MAX;
+                for arg in &args {
+                    match item_value(arg) {
+                        ("MIN", Some(n)) => min = n.parse().unwrap_or(0),
+                        ("MAX", Some(n)) => max = n.parse().unwrap_or(u32::MAX),
+                        (n, None) => min = n.parse().unwrap_or(0),
+                        _ => {}
+                    }
+                }
+                (
+                    Some(level >= min && level <= max),
+                    format!("character level {}", level),
+                )
+            }
+
+            "ATT" | "BAB" => {
+                let min: Option<i64> = args.last().and_then(|a| a.parse().ok());
+                match (self.profile.bab, min) {
+                    (Some(bab), Some(min)) => (
+                        Some(bab >= min),
+                        format!("base attack bonus {}, needs {}", bab, min),
+                    ),
+                    _ => (None, String::from("base attack bonus unknown")),
+                }
+            }
+
+            // MULT:1,[PREFEAT:1,Dodge],[PRESKILL:1,Tumble=5]
+            "MULT" => {
+                let (count, items) = count_items(&args);
+                for item in items {
+                    let inner = item.trim_start_matches('[').trim_end_matches(']');
+                    match Prereq::parse(inner) {
+                        Some(p) => nested.push(self.evaluate(&p)),
+                        None => tracing::debug!("qualify: {} is not a prerequisite", inner),
+                    }
+                }
+                let met = nested.iter().filter(|o| o.passed == Some(true)).count();
+                let unknown = nested.iter().any(|o| o.passed.is_none());
+                let passed = if met >= count {
+                    Some(true)
+                } else if unknown {
+                    None
+                } else {
+                    Some(false)
+                };
+                (
+                    passed,
+                    format!("{} of {} met, needs {}", met, nested.len(), count),
+                )
+            }
+
+            _ => (None, format!("PRE{} is not evaluated", prereq.kind)),
+        };
+
+        Outcome {
+            prereq: format!(
+                "{}PRE{}:{}",
+                if prereq.negate { "!" } else { "" },
+                prereq.kind,
+                prereq.args
+            ),
+            description: prereq.describe(),
+            passed: passed.map(|p| prereq.apply(p)),
+            reason,
+            nested,
+        }
+    }
+}
+
+// evaluate a prerequisite for a character
+pub fn evaluate(pcc: &Pcc, profile: &Profile, prereq: &Prereq) -> Outcome {
+    Evaluator { pcc, profile }.evaluate(prereq)
+}
+
+// evaluate each PRExxx tag of an element, e.g. of a feat or class
+pub fn check(pcc: &Pcc, profile: &Profile, elem: &PccElem) -> Vec<Outcome> {
+    let eval = Evaluator { pcc, profile };
+    elem.attribs()
+        .filter_map(|(tag, value)| Prereq::parse(&format!("{}:{}", tag, value)))
+        .map(|p| eval.evaluate(&p))
+        .collect()
+}
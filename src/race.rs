@@ -0,0 +1,179 @@
+//
+// race.rs -- typed model of RACE data
+//
+// Copyright (c) 2024 Jeff Garzik
+//
+// This file is part of the pcgtoolssoftware project covered under
+// the MIT License.  For the full license text, please see the LICENSE
+// file in the root directory of this project.
+// SPDX-License-Identifier: MIT
+
+use crate::ability::AbilityGrant;
+use crate::auto::{AutoGrant, AutoKind};
+use crate::language;
+use crate::pcc::{Pcc, PccElem};
+use crate::token;
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+// a sense, e.g. "Darkvision (60')"
+#[derive(Clone, Debug, Serialize)]
+pub struct Vision {
+    pub kind: String,
+    // range in feet, if given
+    pub range: Option<u32>,
+}
+
+impl Vision {
+    fn parse(value: &str) -> Vision {
+        let value = value.trim();
+        match value.split_once('(') {
+            Some((kind, rest)) => Vision {
+                kind: kind.trim().to_string(),
+                range: rest
+                    .trim_end_matches(')')
+                    .trim_end_matches('\'')
+                    .trim()
+                    .parse()
+                    .ok(),
+            },
+            None => Vision {
+                kind: value.to_string(),
+                range: None,
+            },
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct Race {
+    pub name: String,
+    pub types: Vec<String>,
+    pub racetype: Option<String>,
+    pub subtypes: Vec<String>,
+    // size abbreviation, e.g. M
+    pub size: Option<String>,
+    // movement mode => speed in feet, e.g. Walk => 30
+    pub movement: BTreeMap<String, u32>,
+    pub vision: Vec<Vision>,
+    // stat abbreviation => adjustment, from BONUS:STAT
+    pub stat_mods: BTreeMap<String, i64>,
+    pub abilities: Vec<AbilityGrant>,
+    // AUTO:LANG, or the older LANGAUTO, languages known
+    pub languages: Vec<String>,
+    // LANGBONUS, languages which may be learned as bonus languages
+    pub bonus_languages: Vec<String>,
+    pub favored_class: Option<String>,
+    pub level_adjustment: Option<i64>,
+    pub source: String,
+    pub line: usize,
+}
+
+impl Race {
+    pub fn new(name: &str, elem: &PccElem) -> Race {
+        let mut race = Race {
+            name: name.to_string(),
+            types: elem.values("TYPE").into_iter().map(String::from).collect(),
+            racetype: elem.values("RACETYPE").first().map(|s| s.to_string()),
+            subtypes: elem
+                .values("RACESUBTYPE")
+                .into_iter()
+                .map(String::from)
+                .collect(),
+            size: None,
+            movement: BTreeMap::new(),
+            vision: Vec::new(),
+            stat_mods: BTreeMap::new(),
+            abilities: Vec::new(),
+            languages: Vec::new(),
+            bonus_languages: Vec::new(),
+            favored_class: None,
+            level_adjustment: None,
+            source: elem.source().to_string(),
+            line: elem.line(),
+        };
+
+        let list = |value: &str, sep: char| -> Vec<String> {
+            token::split_top(value, sep)
+                .into_iter()
+                .filter(|v| !v.is_empty() && !token::is_prereq(v))
+                .map(String::from)
+                .collect()
+        };
+
+        for (tag, value) in &elem.attribs {
+            match tag.as_str() {
+                "SIZE" => race.size = Some(value.to_string()),
+                "FAVCLASS" => race.favored_class = Some(value.to_string()),
+                "LEVELADJUSTMENT" => race.level_adjustment = value.trim().parse().ok(),
+
+                // MOVE:Walk,30,Fly,60 or, in older data, MOVE:30
+                "MOVE" => {
+                    let items = token::sublist(value);
+                    if let [speed] = items[..] {
+                        if let Ok(speed) = speed.trim().parse() {
+                            race.movement.insert(String::from("Walk"), speed);
+                        }
+                    }
+                    for pair in items.chunks(2) {
+                        if let [mode, speed] = pair {
+                            if let Ok(speed) = speed.trim().parse() {
+                                race.movement.insert(mode.to_string(), speed);
+                            }
+                        }
+                    }
+                }
+
+                // VISION:Darkvision (60')|Low-Light
+                "VISION" => race
+                    .vision
+                    .extend(list(value, '|').iter().map(|v| Vision::parse(v))),
+
+                // BONUS:STAT|STR,DEX|2
+                "BONUS" => {
+                    let mut items = token::items(value).into_iter();
+                    if items.next() != Some("STAT") {
+                        continue;
+                    }
+                    let stats = items.next().unwrap_or("");
+                    let Some(Ok(adj)) = items.next().map(|v| v.trim().parse::<i64>()) else {
+                        tracing::debug!("{}: stat bonus {} not a number", name, value);
+                        continue;
+                    };
+                    for stat in token::sublist(stats) {
+                        *race.stat_mods.entry(stat.to_string()).or_default() += adj;
+                    }
+                }
+
+                "ABILITY" => race.abilities.extend(AbilityGrant::parse(value)),
+
+                // AUTO:LANG|Common|Elven
+                "AUTO" => {
+                    if let Some(grant) = AutoGrant::parse(value) {
+                        if grant.kind == AutoKind::Lang {
+                            race.languages.extend(grant.names().map(String::from));
+                        }
+                    }
+                }
+
+                "LANGAUTO" => race
+                    .languages
+                    .extend(language::references(value).into_iter().map(String::from)),
+                "LANGBONUS" => race
+                    .bonus_languages
+                    .extend(language::references(value).into_iter().map(String::from)),
+
+                _ => {}
+            }
+        }
+
+        race
+    }
+}
+
+// all loaded races, in name order
+pub fn races(pcc: &Pcc) -> Vec<Race> {
+    let mut races: Vec<Race> = pcc.races().collect();
+    races.sort_by(|a, b| a.name.cmp(&b.name));
+    races
+}
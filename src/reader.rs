@@ -0,0 +1,229 @@
+//
+// reader.rs -- streaming parse of PCC and LST files, without building
+// the dictionary
+//
+// Copyright (c) 2024 Jeff Garzik
+//
+// This file is part of the pcgtoolssoftware project covered under
+// the MIT License.  For the full license text, please see the LICENSE
+// file in the root directory of this project.
+// SPDX-License-Identifier: MIT
+
+use crate::files;
+use crate::paths;
+use crate::pcc::{
+    compat_pcc_schema, dir_from_path, is_source_header, lst_path, other_gamemode, parse_pcc_schema,
+    pcc_prereq_met, LstFilter, PccConfig, PccTag,
+};
+use crate::prereq::{split_prereqs, Prereq};
+use crate::text;
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    io::{self, Error},
+    path::PathBuf,
+};
+
+// Callbacks of a streaming parse, in file order.  Each does nothing by
+// default, so that a visitor implements only those it needs.
+pub trait PccVisitor {
+    // a PCC file, before its tags
+    fn pcc_file(&mut self, _path: &str) {}
+
+    // a tag of the PCC file last begun, e.g. ("GAMEMODE", "35e")
+    fn pcc_tag(&mut self, _file: &str, _line: usize, _tag: &str, _value: &str) {}
+
+    // an LST file of a list, before its elements
+    fn lst_file(&mut self, _list: &str, _path: &str) {}
+
+    // the source tags of a source header line
+    fn source(&mut self, _file: &str, _line: usize, _tags: &[(&str, &str)]) {}
+
+    // an element line, before its attributes; ident is as written,
+    // e.g. "Fireball.MOD"
+    fn element(&mut self, _list: &str, _file: &str, _line: usize, _ident: &str) {}
+
+    // a tag of the element last begun; an untagged token has an empty
+    // value
+    fn attribute(&mut self, _tag: &str, _value: &str) {}
+
+    // the end of the element last begun
+    fn end_element(&mut self) {}
+}
+
+// Reads PCC files, and the LST files they reference, one line at a
+// time, reporting each to a visitor as it is read.  No elements are
+// kept: of the data, only the file being read is held in memory, with
+// the paths of the PCC files read and their GAMEMODE, CAMPAIGN and KEY
+// values.  .MOD, .COPY and .FORGET lines are reported as written, not
+// applied, and the lines of CLASS, KIT and BIOSET blocks are reported
+// as elements of their own.  PRExxx lines of PCC files are reported,
+// not acted on, and campaigns they require are not loaded.  As when
+// loading, PCC files of other than the configured game mode are
+// skipped, and a PCC line's PREGAMEMODE and PRECAMPAIGN conditions are
+// evaluated against the PCC files read so far.  INCLUDE and EXCLUDE
+// options of LST files are honored.
+pub struct PccReader {
+    config: PccConfig,
+    pccpaths: Vec<String>,
+    schema: BTreeMap<String, PccTag>,
+    // PCC files read, so that each is read once
+    visited: HashSet<PathBuf>,
+    // GAMEMODE, CAMPAIGN and KEY values read, for conditions
+    seen: HashMap<String, Vec<String>>,
+}
+
+impl PccReader {
+    // a reader of PCC files, named relative to the data directory
+    pub fn open<S: AsRef<str>>(config: &PccConfig, pccpaths: &[S]) -> io::Result<PccReader> {
+        let mut schema = compat_pcc_schema(config.compat);
+        if let Some(path) = &config.schema {
+            let text = files::read_to_string(path)?;
+            let tags =
+                parse_pcc_schema(&text).map_err(|e| Error::other(format!("{}: {}", path, e)))?;
+            schema.extend(tags);
+        }
+
+        Ok(PccReader {
+            config: config.clone(),
+            pccpaths: pccpaths.iter().map(|p| p.as_ref().to_string()).collect(),
+            schema,
+            visited: HashSet::new(),
+            seen: HashMap::new(),
+        })
+    }
+
+    pub fn parse_with<V: PccVisitor>(&mut self, visitor: &mut V) -> io::Result<()> {
+        self.visited.clear();
+        self.seen.clear();
+        for pccpath in self.pccpaths.clone() {
+            let path = paths::in_datadirs(&self.config.datadirs, &paths::native(&pccpath));
+            self.read_pcc(&path, visitor)?;
+        }
+        Ok(())
+    }
+
+    fn read_pcc<V: PccVisitor>(&mut self, fpath: &str, visitor: &mut V) -> io::Result<()> {
+        let fpath = paths::resolve(fpath);
+        let canon = files::canonicalize(&fpath).unwrap_or_else(|_| PathBuf::from(&fpath));
+        if !self.visited.insert(canon) {
+            return Ok(());
+        }
+        let basedir = dir_from_path(&fpath).unwrap_or_default();
+        let _span = tracing::warn_span!("pcc", path = %fpath).entered();
+
+        tracing::debug!("PccReader.read_pcc({})", fpath);
+
+        let lines = text::read_lines(&fpath, self.config.encoding)
+            .map_err(|e| Error::new(e.kind(), format!("{}: {}", fpath, e)))?;
+        if let Some(modes) = other_gamemode(&self.config, lines.iter().map(String::as_str)) {
+            tracing::debug!("Skipping {}: GAMEMODE {}", fpath, modes);
+            return Ok(());
+        }
+        visitor.pcc_file(&fpath);
+        for (idx, line) in lines.iter().enumerate() {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let Some((tag, value)) = line.split_once(':') else {
+                return Err(Error::other(format!(
+                    "{}:{}: PCC invalid line:colon",
+                    fpath,
+                    idx + 1
+                )));
+            };
+            visitor.pcc_tag(&fpath, idx + 1, tag, value);
+            if matches!(tag, "GAMEMODE" | "CAMPAIGN" | "KEY") {
+                let values = self.seen.entry(tag.to_string()).or_default();
+                values.extend(value.split('|').map(String::from));
+            }
+
+            let (value, prereqs) = split_prereqs(value);
+            let value = value.as_str();
+            if let Some(failed) = prereqs.iter().find(|p| !self.check_prereq(p)) {
+                tracing::debug!("PCC line skipped, {:?} not met: {}", failed, line);
+                continue;
+            }
+
+            // a negated tag, other than a PRExxx, is ignored, as when
+            // loading
+            match self.schema.get(tag) {
+                Some(PccTag::PccFile) => {
+                    let path = paths::native(value);
+                    let path = self.config.prefixed_path(&path).unwrap_or(path);
+                    self.read_pcc(&path, visitor)?;
+                }
+                Some(
+                    PccTag::BioSet
+                    | PccTag::Class
+                    | PccTag::DataTable
+                    | PccTag::Dynamic
+                    | PccTag::Kit
+                    | PccTag::LstFile,
+                ) => {
+                    let (lstpath, lstopts) = value.split_once('|').unwrap_or((value, ""));
+                    let lstpath = lst_path(&self.config, &basedir, lstpath);
+                    let lstpaths = match paths::has_wildcard(&lstpath) {
+                        true => paths::expand_wildcard(&lstpath),
+                        false => vec![lstpath],
+                    };
+                    for lstpath in lstpaths {
+                        self.read_lst(tag, &lstpath, lstopts, visitor)?;
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    // is a PREGAMEMODE or PRECAMPAIGN condition met by the PCC files
+    // read so far?  Others are taken as met.
+    fn check_prereq(&self, prereq: &Prereq) -> bool {
+        let values = |tag: &str| {
+            self.seen
+                .get(tag)
+                .map_or(Vec::new(), |v| v.iter().map(String::as_str).collect())
+        };
+        pcc_prereq_met(prereq, values).unwrap_or(true)
+    }
+
+    fn read_lst<V: PccVisitor>(
+        &self,
+        list: &str,
+        fpath: &str,
+        lstopts: &str,
+        visitor: &mut V,
+    ) -> io::Result<()> {
+        let _span = tracing::warn_span!("lst", list, path = fpath).entered();
+        let filter = LstFilter::parse(lstopts);
+        let lines = text::read_lines(fpath, self.config.encoding)
+            .map_err(|e| Error::new(e.kind(), format!("{}: {}", fpath, e)))?;
+
+        visitor.lst_file(list, fpath);
+        for (idx, line) in lines.iter().enumerate() {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if is_source_header(line) {
+                let tags: Vec<(&str, &str)> = line
+                    .split('\t')
+                    .filter_map(|token| token.split_once(':'))
+                    .collect();
+                visitor.source(fpath, idx + 1, &tags);
+                continue;
+            }
+            if !filter.allows(line) {
+                continue;
+            }
+
+            let mut tokens = line.split('\t');
+            visitor.element(list, fpath, idx + 1, tokens.next().unwrap_or(""));
+            for token in tokens.filter(|t| !t.trim().is_empty()) {
+                let (tag, value) = token.split_once(':').unwrap_or((token, ""));
+                visitor.attribute(tag, value);
+            }
+            visitor.end_element();
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,102 @@
+//
+// region.rs -- REGION and SUBREGION data: the regions of a setting,
+// and the elements of each
+//
+// Copyright (c) 2024 Jeff Garzik
+//
+// This file is part of the pcgtoolssoftware project covered under
+// the MIT License.  For the full license text, please see the LICENSE
+// file in the root directory of this project.
+// SPDX-License-Identifier: MIT
+
+use crate::pcc::{Pcc, PccElem};
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+
+// an element of a region: a template or race placing a character in
+// it, or a kit or bio setting offered only there
+#[derive(Clone, Debug, Serialize)]
+pub struct RegionElem {
+    pub list: String,
+    pub name: String,
+    pub subregion: Option<String>,
+    pub source: String,
+    pub line: usize,
+}
+
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct Region {
+    pub name: String,
+    pub subregions: BTreeSet<String>,
+    pub elements: Vec<RegionElem>,
+}
+
+// The regions of the loaded data, each with its subregions.  Regions
+// are named by the REGION and SUBREGION tags of templates and races,
+// and by the REGION lines of block files, such as kits, which restrict
+// the blocks following them.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct Regions {
+    pub regions: BTreeMap<String, Region>,
+}
+
+// The value of a REGION or SUBREGION tag, as a name: "Yes" names the
+// region for the element itself, as a template such as Waterdhavian
+// does.  None for "No", or no value.
+fn region_name<'a>(value: &'a str, elem: &'a PccElem) -> Option<&'a str> {
+    let value = value.trim();
+    if value.is_empty() || value.eq_ignore_ascii_case("NO") {
+        None
+    } else if value.eq_ignore_ascii_case("YES") {
+        Some(elem.name())
+    } else {
+        Some(value)
+    }
+}
+
+impl Regions {
+    pub fn new(pcc: &Pcc) -> Regions {
+        let mut regions = Regions::default();
+
+        for (list, lst) in pcc.lists() {
+            for (_, elem) in lst.sorted() {
+                let subregion = elem.get("SUBREGION").and_then(|v| region_name(v, elem));
+                let Some(name) = elem.get("REGION").and_then(|v| region_name(v, elem)) else {
+                    if let Some(subregion) = subregion {
+                        tracing::debug!(
+                            "{}:{}: SUBREGION:{} of no REGION",
+                            elem.source(),
+                            elem.line(),
+                            subregion
+                        );
+                    }
+                    continue;
+                };
+
+                let region = regions
+                    .regions
+                    .entry(name.to_string())
+                    .or_insert_with(|| Region {
+                        name: name.to_string(),
+                        ..Region::default()
+                    });
+                region.subregions.extend(subregion.map(String::from));
+                region.elements.push(RegionElem {
+                    list: list.to_string(),
+                    name: elem.display_name().to_string(),
+                    subregion: subregion.map(String::from),
+                    source: elem.source().to_string(),
+                    line: elem.line(),
+                });
+            }
+        }
+        regions
+    }
+
+    // lookup a region by name, ignoring case
+    pub fn region(&self, name: &str) -> Option<&Region> {
+        self.regions
+            .values()
+            .find(|r| r.name.eq_ignore_ascii_case(name))
+    }
+}
@@ -0,0 +1,206 @@
+//
+// remote.rs -- data files fetched over HTTP(S), with a download cache
+//
+// Copyright (c) 2024 Jeff Garzik
+//
+// This file is part of the pcgtoolssoftware project covered under
+// the MIT License.  For the full license text, please see the LICENSE
+// file in the root directory of this project.
+// SPDX-License-Identifier: MIT
+
+use crate::files::FileProvider;
+use crate::paths;
+use std::{
+    collections::HashMap,
+    env, fs,
+    io::{self, Error, ErrorKind, Read},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+// give up on a server that does not respond in this time
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+// The download cache of the current user: $XDG_CACHE_HOME, or
+// ~/.cache, then pcgtools/downloads.
+pub fn default_download_dir() -> PathBuf {
+    let base = match (env::var_os("XDG_CACHE_HOME"), env::var_os("HOME")) {
+        (Some(dir), _) if !dir.is_empty() => PathBuf::from(dir),
+        (_, Some(home)) => PathBuf::from(home).join(".cache"),
+        _ => env::temp_dir(),
+    };
+    base.join("pcgtools").join("downloads")
+}
+
+// the URL of a path, with "." and ".." segments resolved, as (URL,
+// host and path after the scheme)
+fn parse_url(path: &Path) -> Option<(String, String)> {
+    let path = path.to_string_lossy().replace('\\', "/");
+    if !paths::is_url(&path) {
+        return None;
+    }
+    let (scheme, rest) = path.split_once(':')?;
+
+    let mut parts: Vec<&str> = Vec::new();
+    for part in rest.split('/') {
+        match part {
+            "" | "." => {}
+            ".." if parts.len() > 1 => {
+                parts.pop();
+            }
+            ".." => {}
+            part => parts.push(part),
+        }
+    }
+    let rest = parts.join("/");
+    Some((format!("{}://{}", scheme.to_ascii_lowercase(), rest), rest))
+}
+
+// Files named by http:// and https:// URLs, fetched on first use, and
+// all other files through another provider.  Each file fetched is
+// kept in a download cache, mirroring its host and path, from which
+// it is read when offline, or when its server cannot be reached.
+pub struct HttpFiles {
+    inner: Arc<dyn FileProvider>,
+    cachedir: PathBuf,
+    offline: bool,
+    agent: ureq::Agent,
+    // the files of URLs read so far, or none for those not found
+    fetched: Mutex<HashMap<String, Option<Arc<Vec<u8>>>>>,
+}
+
+impl HttpFiles {
+    pub fn new(inner: Arc<dyn FileProvider>, cachedir: &Path, offline: bool) -> HttpFiles {
+        HttpFiles {
+            inner,
+            cachedir: cachedir.to_path_buf(),
+            offline,
+            agent: ureq::AgentBuilder::new().timeout(TIMEOUT).build(),
+            fetched: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn download(&self, url: &str) -> io::Result<Vec<u8>> {
+        tracing::debug!("GET {}", url);
+        match self.agent.get(url).call() {
+            Ok(resp) => {
+                let mut data = Vec::new();
+                resp.into_reader().read_to_end(&mut data)?;
+                Ok(data)
+            }
+            Err(ureq::Error::Status(404 | 410, _)) => {
+                Err(Error::new(ErrorKind::NotFound, "not found on server"))
+            }
+            Err(e) => Err(Error::other(e.to_string())),
+        }
+    }
+
+    // Fetch a URL, or, offline, read its downloaded copy.  A server
+    // reporting the file not found is believed; on any other failure
+    // the downloaded copy, if any, is used.
+    fn fetch(&self, url: &str, rest: &str) -> io::Result<Vec<u8>> {
+        let cached = self.cachedir.join(rest);
+        if !self.offline {
+            match self.download(url) {
+                Ok(data) => {
+                    let saved = cached
+                        .parent()
+                        .map_or(Ok(()), fs::create_dir_all)
+                        .and_then(|_| fs::write(&cached, &data));
+                    if let Err(e) = saved {
+                        tracing::warn!("{}: not saved: {}", cached.display(), e);
+                    }
+                    return Ok(data);
+                }
+                Err(e) if e.kind() == ErrorKind::NotFound => return Err(e),
+                Err(e) if cached.is_file() => {
+                    // errors of ureq name the URL
+                    tracing::warn!("{}; using downloaded copy", e);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+
+        fs::read(&cached).map_err(|e| match e.kind() {
+            ErrorKind::NotFound => Error::new(ErrorKind::NotFound, "not downloaded, and offline"),
+            _ => e,
+        })
+    }
+
+    // the contents of a URL, fetched once per run.  the lock is not
+    // held while fetching, so that LST files read in parallel are
+    // fetched in parallel.
+    fn get(&self, url: &str, rest: &str) -> io::Result<Arc<Vec<u8>>> {
+        let found = self
+            .fetched
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(url)
+            .cloned();
+        if let Some(found) = found {
+            return found.ok_or_else(|| Error::new(ErrorKind::NotFound, "not found on server"));
+        }
+
+        let res = self.fetch(url, rest).map(Arc::new);
+        let mut fetched = self.fetched.lock().unwrap_or_else(|e| e.into_inner());
+        match &res {
+            Ok(data) => {
+                fetched.insert(url.to_string(), Some(data.clone()));
+            }
+            Err(e) if e.kind() == ErrorKind::NotFound => {
+                fetched.insert(url.to_string(), None);
+            }
+            Err(_) => {}
+        }
+        res
+    }
+}
+
+impl FileProvider for HttpFiles {
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        match parse_url(path) {
+            Some((url, rest)) => Ok(self.get(&url, &rest)?.to_vec()),
+            None => self.inner.read(path),
+        }
+    }
+
+    // a server's directories cannot be listed
+    fn read_dir(&self, dir: &Path) -> io::Result<Vec<String>> {
+        match parse_url(dir) {
+            Some((url, _)) => Err(Error::new(
+                ErrorKind::Unsupported,
+                format!("{}: remote directories cannot be listed", url),
+            )),
+            None => self.inner.read_dir(dir),
+        }
+    }
+
+    fn is_file(&self, path: &Path) -> bool {
+        match parse_url(path) {
+            Some((url, rest)) => self.get(&url, &rest).is_ok(),
+            None => self.inner.is_file(path),
+        }
+    }
+
+    fn is_dir(&self, path: &Path) -> bool {
+        match parse_url(path) {
+            Some(_) => false,
+            None => self.inner.is_dir(path),
+        }
+    }
+
+    fn size(&self, path: &Path) -> io::Result<u64> {
+        match parse_url(path) {
+            Some((url, rest)) => Ok(self.get(&url, &rest)?.len() as u64),
+            None => self.inner.size(path),
+        }
+    }
+
+    fn canonicalize(&self, path: &Path) -> io::Result<PathBuf> {
+        match parse_url(path) {
+            Some((url, _)) => Ok(PathBuf::from(url)),
+            None => self.inner.canonicalize(path),
+        }
+    }
+}
@@ -0,0 +1,170 @@
+//
+// repl.rs -- interactive exploration of loaded data
+//
+// Copyright (c) 2024 Jeff Garzik
+//
+// This file is part of the pcgtoolssoftware project covered under
+// the MIT License.  For the full license text, please see the LICENSE
+// file in the root directory of this project.
+// SPDX-License-Identifier: MIT
+
+use crate::pcc::{Pcc, PccDatum, PccElem};
+use std::{
+    io,
+    io::{BufRead, Write},
+};
+
+const HELP: &str = "Commands:
+  lists                  list types, with element counts
+  show LIST              element names in a list
+  show LIST IDENT        all tags of an element
+  grep TAG=TEXT          elements whose TAG value contains TEXT
+  grep TEXT              elements whose name or any value contains TEXT
+  reload                 re-read all data files
+  help                   this message
+  quit                   exit";
+
+fn write_elem<W: Write>(w: &mut W, list: &str, ident: &str, elem: &PccElem) -> io::Result<()> {
+    writeln!(w, "{} {}  ({}:{})", list, ident, elem.source, elem.line)?;
+    for (k, v) in &elem.attribs {
+        writeln!(w, "  {}:{}", k, v)?;
+    }
+    Ok(())
+}
+
+// sorted (list name, elements) pairs
+fn sorted_lists(pcc: &Pcc) -> Vec<(&String, Vec<(&String, &PccElem)>)> {
+    let mut lists: Vec<(&String, Vec<(&String, &PccElem)>)> = pcc
+        .dict
+        .iter()
+        .filter_map(|(name, datum)| match datum {
+            PccDatum::List(lst) => {
+                let mut elems: Vec<(&String, &PccElem)> = lst.props.iter().collect();
+                elems.sort_by(|a, b| a.0.cmp(b.0));
+                Some((name, elems))
+            }
+            _ => None,
+        })
+        .collect();
+    lists.sort_by(|a, b| a.0.cmp(b.0));
+    lists
+}
+
+fn cmd_lists<W: Write>(pcc: &Pcc, w: &mut W) -> io::Result<()> {
+    for (name, elems) in sorted_lists(pcc) {
+        writeln!(w, "{:20} {}", name, elems.len())?;
+    }
+    Ok(())
+}
+
+fn cmd_show<W: Write>(pcc: &Pcc, w: &mut W, args: &str) -> io::Result<()> {
+    let (list, ident) = match args.split_once(' ') {
+        Some((list, ident)) => (list, Some(ident.trim())),
+        None => (args, None),
+    };
+
+    let Some(lst) = pcc.list(&list.to_uppercase()) else {
+        return writeln!(w, "No such list: {}", list);
+    };
+
+    match ident {
+        None => {
+            let mut idents: Vec<&String> = lst.props.keys().collect();
+            idents.sort();
+            for ident in idents {
+                writeln!(w, "{}", ident)?;
+            }
+        }
+
+        // exact match, else case-insensitive match
+        Some(ident) => {
+            let found = lst.props.get_key_value(ident).or_else(|| {
+                lst.props
+                    .iter()
+                    .find(|(k, _)| k.eq_ignore_ascii_case(ident))
+            });
+            match found {
+                Some((k, elem)) => write_elem(w, &list.to_uppercase(), k, elem)?,
+                None => writeln!(w, "No such element: {}", ident)?,
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn cmd_grep<W: Write>(pcc: &Pcc, w: &mut W, pattern: &str) -> io::Result<()> {
+    let (tag, text) = match pattern.split_once('=') {
+        Some((tag, text)) => (Some(tag), text.to_lowercase()),
+        None => (None, pattern.to_lowercase()),
+    };
+
+    let mut matches = 0;
+    for (list, elems) in sorted_lists(pcc) {
+        for (ident, elem) in elems {
+            let hit = match tag {
+                Some(tag) => elem
+                    .attribs
+                    .iter()
+                    .any(|(k, v)| k.eq_ignore_ascii_case(tag) && v.to_lowercase().contains(&text)),
+                None => {
+                    ident.to_lowercase().contains(&text)
+                        || elem
+                            .attribs
+                            .iter()
+                            .any(|(_, v)| v.to_lowercase().contains(&text))
+                }
+            };
+            if hit {
+                writeln!(w, "{} {}", list, ident)?;
+                matches += 1;
+            }
+        }
+    }
+
+    writeln!(w, "{} matches", matches)
+}
+
+// read commands from input until EOF or "quit".  reload is called to
+// re-read data files, on request.
+pub fn run<R, W, F>(pcc: &mut Pcc, reload: F, input: R, mut w: W) -> io::Result<()>
+where
+    R: BufRead,
+    W: Write,
+    F: Fn() -> io::Result<Pcc>,
+{
+    let mut lines = input.lines();
+    loop {
+        write!(w, "pcgtools> ")?;
+        w.flush()?;
+
+        let Some(line) = lines.next() else {
+            writeln!(w)?;
+            return Ok(());
+        };
+        let line = line?;
+        let line = line.trim();
+
+        let (cmd, args) = match line.split_once(' ') {
+            Some((cmd, args)) => (cmd, args.trim()),
+            None => (line, ""),
+        };
+
+        match cmd {
+            "" => {}
+            "lists" => cmd_lists(pcc, &mut w)?,
+            "show" if !args.is_empty() => cmd_show(pcc, &mut w, args)?,
+            "grep" if !args.is_empty() => cmd_grep(pcc, &mut w, args)?,
+            "reload" => match reload() {
+                Ok(newpcc) => {
+                    *pcc = newpcc;
+                    writeln!(w, "Reloaded {} files", pcc.files().len())?;
+                }
+                Err(e) => writeln!(w, "Reload failed: {}", e)?,
+            },
+            "help" | "?" => writeln!(w, "{}", HELP)?,
+            "quit" | "exit" => return Ok(()),
+            _ => writeln!(w, "Unknown command; try \"help\"")?,
+        }
+    }
+}
@@ -0,0 +1,123 @@
+//
+// search.rs -- full-text search of element names, DESC and SPROP text
+//
+// Copyright (c) 2024 Jeff Garzik
+//
+// This file is part of the pcgtoolssoftware project covered under
+// the MIT License.  For the full license text, please see the LICENSE
+// file in the root directory of this project.
+// SPDX-License-Identifier: MIT
+
+use crate::pcc::{Pcc, PccDatum};
+use regex::{Regex, RegexBuilder};
+use serde::Serialize;
+use std::fmt;
+
+// tags whose text is searched, besides the element name
+const SEARCHED_TAGS: [&str; 2] = ["DESC", "SPROP"];
+
+// characters of context shown either side of a match
+const CONTEXT: usize = 30;
+
+// a case-insensitive search pattern: plain text, or a regular
+// expression
+pub struct Pattern(Regex);
+
+impl Pattern {
+    pub fn text(text: &str) -> Pattern {
+        Pattern::regex(&regex::escape(text)).unwrap()
+    }
+
+    pub fn regex(re: &str) -> Result<Pattern, String> {
+        RegexBuilder::new(re)
+            .case_insensitive(true)
+            .build()
+            .map(Pattern)
+            .map_err(|e| e.to_string())
+    }
+
+    // byte range of the first match in text
+    fn find(&self, text: &str) -> Option<(usize, usize)> {
+        self.0.find(text).map(|m| (m.start(), m.end()))
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct SearchMatch {
+    pub list: String,
+    pub ident: String,
+    // NAME, or the tag whose text matched
+    pub field: String,
+    pub source: String,
+    pub line: usize,
+    // the matching text, with some context
+    pub snippet: String,
+}
+
+impl fmt::Display for SearchMatch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}:{}: {} {}: {}: {}",
+            self.source, self.line, self.list, self.ident, self.field, self.snippet
+        )
+    }
+}
+
+// the match within text, with up to CONTEXT characters either side
+fn snippet(text: &str, start: usize, end: usize) -> String {
+    let before: Vec<char> = text[..start].chars().collect();
+    let after: Vec<char> = text[end..].chars().collect();
+    let mut s = String::new();
+    if before.len() > CONTEXT {
+        s.push_str("...");
+    }
+    s.extend(&before[before.len().saturating_sub(CONTEXT)..]);
+    s.push_str(&text[start..end]);
+    s.extend(after.iter().take(CONTEXT));
+    if after.len() > CONTEXT {
+        s.push_str("...");
+    }
+    s
+}
+
+// search all lists, returning matches ordered by list, then element
+pub fn search(pcc: &Pcc, pattern: &Pattern) -> Vec<SearchMatch> {
+    let mut matches = Vec::new();
+    for (list, datum) in &pcc.dict {
+        let PccDatum::List(lst) = datum else {
+            continue;
+        };
+        for (ident, elem) in &lst.props {
+            let texts = SEARCHED_TAGS
+                .iter()
+                .flat_map(|tag| elem.values(tag).into_iter().map(move |v| (*tag, v)));
+            let names = [ident.as_str(), elem.name()];
+            let fields = names.iter().map(|n| ("NAME", *n)).chain(texts);
+
+            // one NAME match per element, though KEY and name may both
+            // match
+            let mut named = false;
+            for (field, text) in fields {
+                if field == "NAME" && named {
+                    continue;
+                }
+                let Some((start, end)) = pattern.find(text) else {
+                    continue;
+                };
+                named |= field == "NAME";
+                matches.push(SearchMatch {
+                    list: list.clone(),
+                    ident: ident.clone(),
+                    field: field.to_string(),
+                    source: elem.source().to_string(),
+                    line: elem.line(),
+                    snippet: snippet(text, start, end),
+                });
+            }
+        }
+    }
+
+    matches.sort_by(|a, b| (&a.list, &a.ident).cmp(&(&b.list, &b.ident)));
+    matches
+}
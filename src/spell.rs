@@ -0,0 +1,178 @@
+//
+// spell.rs -- typed model of SPELL data, per-class spell lists, and the
+// spells granted by SPELLS tags
+//
+// Copyright (c) 2024 Jeff Garzik
+//
+// This file is part of the pcgtoolssoftware project covered under
+// the MIT License.  For the full license text, please see the LICENSE
+// file in the root directory of this project.
+// SPDX-License-Identifier: MIT
+
+use crate::pcc::{Pcc, PccElem};
+use crate::prereq::Prereq;
+use crate::token;
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+// Parse spell level assignments, e.g. the CLASSES or DOMAINS values
+// "Wizard,Sorcerer=3" and "Cleric=4", into (name, level) pairs.
+// Groups qualified by a prerequisite, e.g. "[PRExxx]", are included.
+pub fn parse_levels<'a, I: IntoIterator<Item = &'a str>>(groups: I) -> Vec<(String, u32)> {
+    let mut levels = Vec::new();
+    for group in groups {
+        if token::is_prereq(group) {
+            continue;
+        }
+        let group = group.split('[').next().unwrap_or(group);
+        let Some((names, level)) = group.rsplit_once('=') else {
+            continue;
+        };
+        let Ok(level) = level.trim().parse() else {
+            continue;
+        };
+        for name in token::sublist(names).into_iter().filter(|n| !n.is_empty()) {
+            levels.push((name.to_string(), level));
+        }
+    }
+    levels
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct Spell {
+    // KEY, else name
+    pub key: String,
+    // as displayed; see PccElem::display_name
+    pub name: String,
+    pub school: Option<String>,
+    pub subschools: Vec<String>,
+    pub descriptors: Vec<String>,
+    // class name => spell level
+    pub classes: BTreeMap<String, u32>,
+    // domain name => spell level
+    pub domains: BTreeMap<String, u32>,
+    pub source: String,
+    pub line: usize,
+}
+
+impl Spell {
+    pub fn new(key: &str, elem: &PccElem) -> Spell {
+        let strings =
+            |tag: &str| -> Vec<String> { elem.values(tag).into_iter().map(String::from).collect() };
+        Spell {
+            key: key.to_string(),
+            name: elem.display_name().to_string(),
+            school: elem.values("SCHOOL").first().map(|s| s.to_string()),
+            subschools: strings("SUBSCHOOL"),
+            descriptors: strings("DESCRIPTOR"),
+            classes: parse_levels(elem.values("CLASSES")).into_iter().collect(),
+            domains: parse_levels(
+                elem.values("DOMAINS")
+                    .into_iter()
+                    .flat_map(|v| token::items(v)),
+            )
+            .into_iter()
+            .collect(),
+            source: elem.source().to_string(),
+            line: elem.line(),
+        }
+    }
+
+    // spell level for a class or domain, ignoring case
+    pub fn level_for(&self, name: &str) -> Option<u32> {
+        self.classes
+            .iter()
+            .chain(self.domains.iter())
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, level)| *level)
+    }
+}
+
+// all loaded spells, in name order
+pub fn spells(pcc: &Pcc) -> Vec<Spell> {
+    let mut spells: Vec<Spell> = pcc.spells().collect();
+    spells.sort_by(|a, b| a.name.cmp(&b.name));
+    spells
+}
+
+// the spell list of a class or domain: spell names by spell level
+pub fn spell_list(pcc: &Pcc, class: &str) -> BTreeMap<u32, Vec<String>> {
+    let mut list: BTreeMap<u32, Vec<String>> = BTreeMap::new();
+    for spell in spells(pcc) {
+        if let Some(level) = spell.level_for(class) {
+            list.entry(level).or_default().push(spell.name);
+        }
+    }
+    list
+}
+
+// a spell granted by a SPELLS tag, and its save DC formula, if given,
+// e.g. "Fireball,DC=14" or "Fireball,10+CHA"
+#[derive(Clone, Debug, Serialize)]
+pub struct GrantedSpell {
+    pub name: String,
+    pub dc: Option<String>,
+}
+
+// A SPELLS tag of an ability, item, race or template, granting spells
+// cast from a spellbook, e.g.
+// SPELLS:Innate|TIMES=3|TIMEUNIT=Day|CASTERLEVEL=TL|Light|Fireball,DC=14.
+// TIMES and CASTERLEVEL are formulas.  The conditions of the tag apply
+// to all its spells.
+#[derive(Clone, Debug, Serialize)]
+pub struct SpellsGrant {
+    pub book: String,
+    pub times: Option<String>,
+    // Day, Week, ...; PCGen assumes Day
+    pub timeunit: Option<String>,
+    pub caster_level: Option<String>,
+    pub spells: Vec<GrantedSpell>,
+    pub prereqs: Vec<Prereq>,
+}
+
+impl SpellsGrant {
+    // parse the value of a SPELLS tag; None for .CLEARALL, or a value
+    // granting no spells
+    pub fn parse(value: &str) -> Option<SpellsGrant> {
+        let items = token::items(value);
+        let (book, rest) = items.split_first()?;
+        if book.is_empty() || book.starts_with(".CLEAR") {
+            return None;
+        }
+
+        let mut grant = SpellsGrant {
+            book: book.to_string(),
+            times: None,
+            timeunit: None,
+            caster_level: None,
+            spells: Vec::new(),
+            prereqs: Vec::new(),
+        };
+        for item in rest.iter().filter(|item| !item.is_empty()) {
+            if let Some(times) = item.strip_prefix("TIMES=") {
+                grant.times = Some(times.to_string());
+            } else if let Some(unit) = item.strip_prefix("TIMEUNIT=") {
+                grant.timeunit = Some(unit.to_string());
+            } else if let Some(level) = item.strip_prefix("CASTERLEVEL=") {
+                grant.caster_level = Some(level.to_string());
+            } else if token::is_prereq(item) {
+                grant.prereqs.extend(Prereq::parse(item));
+            } else {
+                let (name, dc) = match item.split_once(',') {
+                    Some((name, dc)) => (name, Some(dc.strip_prefix("DC=").unwrap_or(dc))),
+                    None => (*item, None),
+                };
+                grant.spells.push(GrantedSpell {
+                    name: name.trim().to_string(),
+                    dc: dc.map(|dc| dc.trim().to_string()),
+                });
+            }
+        }
+        (!grant.spells.is_empty()).then_some(grant)
+    }
+
+    // the names of the spells granted
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.spells.iter().map(|spell| spell.name.as_str())
+    }
+}
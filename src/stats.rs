@@ -0,0 +1,81 @@
+//
+// stats.rs -- summary statistics of loaded data
+//
+// Copyright (c) 2024 Jeff Garzik
+//
+// This file is part of the pcgtoolssoftware project covered under
+// the MIT License.  For the full license text, please see the LICENSE
+// file in the root directory of this project.
+// SPDX-License-Identifier: MIT
+
+use crate::pcc::{Pcc, PccDatum};
+use serde::Serialize;
+use std::{collections::HashSet, fmt, time::Duration};
+
+#[derive(Serialize)]
+pub struct Stats {
+    pub lists: Vec<(String, usize)>,
+    pub elements: usize,
+    pub pcc_files: usize,
+    pub lst_files: usize,
+    pub unknown_tags: usize,
+    pub aliases: usize,
+    pub load_time: Option<Duration>,
+}
+
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let width = self.lists.iter().map(|(n, _)| n.len()).max().unwrap_or(0);
+        for (name, count) in &self.lists {
+            writeln!(f, "{:width$}  {:>8}", name, count, width = width)?;
+        }
+        writeln!(f)?;
+        writeln!(f, "Elements:     {}", self.elements)?;
+        writeln!(f, "PCC files:    {}", self.pcc_files)?;
+        writeln!(f, "LST files:    {}", self.lst_files)?;
+        writeln!(f, "Unknown tags: {}", self.unknown_tags)?;
+        writeln!(f, "Aliases:      {}", self.aliases)?;
+        if let Some(t) = self.load_time {
+            writeln!(f, "Load time:    {:.3}s", t.as_secs_f64())?;
+        }
+        Ok(())
+    }
+}
+
+// summarize a loaded dataset; load_time is supplied by the caller,
+// which timed the load
+pub fn stats(pcc: &Pcc, load_time: Option<Duration>) -> Stats {
+    let mut lists: Vec<(String, usize)> = pcc
+        .dict
+        .iter()
+        .filter_map(|(name, datum)| match datum {
+            PccDatum::List(lst) => Some((name.clone(), lst.props.len())),
+            _ => None,
+        })
+        .collect();
+    lists.sort();
+
+    // count distinct files; a file may be loaded via several PCC files
+    let pcc_files: HashSet<&str> = pcc
+        .files()
+        .iter()
+        .filter(|f| f.kind == "PCC")
+        .map(|f| f.path.as_str())
+        .collect();
+    let lst_files: HashSet<&str> = pcc
+        .files()
+        .iter()
+        .filter(|f| f.kind != "PCC")
+        .map(|f| f.path.as_str())
+        .collect();
+
+    Stats {
+        elements: lists.iter().map(|(_, n)| n).sum(),
+        lists,
+        pcc_files: pcc_files.len(),
+        lst_files: lst_files.len(),
+        unknown_tags: pcc.unknown_tags().len(),
+        aliases: pcc.aliases.len(),
+        load_time,
+    }
+}
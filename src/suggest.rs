@@ -0,0 +1,36 @@
+//
+// suggest.rs -- nearest-name suggestions for misspelt names
+//
+// Copyright (c) 2024 Jeff Garzik
+//
+// This file is part of the pcgtoolssoftware project covered under
+// the MIT License.  For the full license text, please see the LICENSE
+// file in the root directory of this project.
+// SPDX-License-Identifier: MIT
+
+// Jaro-Winkler similarity a candidate must reach to be suggested
+const MIN_SIMILARITY: f64 = 0.85;
+
+// the candidate most similar to name, ignoring case, if any is similar
+// enough to be a likely misspelling
+pub fn nearest<'a, I: IntoIterator<Item = &'a str>>(name: &str, candidates: I) -> Option<&'a str> {
+    let name = name.to_lowercase();
+    let mut best = None;
+    let mut best_score = MIN_SIMILARITY;
+    for cand in candidates {
+        let score = strsim::jaro_winkler(&name, &cand.to_lowercase());
+        if score > best_score {
+            best = Some(cand);
+            best_score = score;
+        }
+    }
+    best
+}
+
+// "; did you mean "X"?", or nothing if no candidate is near name
+pub fn did_you_mean<'a, I: IntoIterator<Item = &'a str>>(name: &str, candidates: I) -> String {
+    match nearest(name, candidates) {
+        Some(s) => format!("; did you mean \"{}\"?", s),
+        None => String::new(),
+    }
+}
@@ -0,0 +1,163 @@
+//
+// table.rs -- aligned tables of list elements, for terminal output
+//
+// Copyright (c) 2024 Jeff Garzik
+//
+// This file is part of the pcgtoolssoftware project covered under
+// the MIT License.  For the full license text, please see the LICENSE
+// file in the root directory of this project.
+// SPDX-License-Identifier: MIT
+
+use crate::pcc::PccElem;
+use crate::writer::list_tags;
+use std::{
+    env, fmt,
+    io::{self, IsTerminal},
+    str::FromStr,
+};
+
+// the column of element names
+pub const NAME_COLUMN: &str = "name";
+
+// cells longer than this are cut short, ending with "..."
+const MAX_CELL: usize = 48;
+
+// columns are separated by this many spaces
+const GUTTER: usize = 2;
+
+const BOLD: &str = "\x1b[1m";
+const CYAN: &str = "\x1b[36m";
+const RESET: &str = "\x1b[0m";
+
+// whether to color table output
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum ColorChoice {
+    // when writing to a terminal, and NO_COLOR is not set
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorChoice {
+    pub fn enabled(&self) -> bool {
+        match self {
+            ColorChoice::Auto => io::stdout().is_terminal() && env::var_os("NO_COLOR").is_none(),
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+        }
+    }
+}
+
+impl FromStr for ColorChoice {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<ColorChoice, String> {
+        match s.to_ascii_lowercase().as_str() {
+            "auto" => Ok(ColorChoice::Auto),
+            "always" => Ok(ColorChoice::Always),
+            "never" => Ok(ColorChoice::Never),
+            _ => Err(format!("unknown color choice {}", s)),
+        }
+    }
+}
+
+impl fmt::Display for ColorChoice {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match self {
+            ColorChoice::Auto => "auto",
+            ColorChoice::Always => "always",
+            ColorChoice::Never => "never",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+// The columns shown when none are chosen: the name, TYPE, and the
+// list's own tags in canonical order, each if any element has it.
+pub fn default_columns(list: &str, elems: &[(&str, &PccElem)]) -> Vec<String> {
+    let mut columns = vec![String::from(NAME_COLUMN)];
+    for tag in std::iter::once(&"TYPE").chain(list_tags(list)) {
+        if elems.iter().any(|(_, elem)| elem.get(tag).is_some()) {
+            columns.push(tag.to_string());
+        }
+    }
+    columns
+}
+
+// the text of a column of an element: its name, or the values of a
+// tag, ignoring case, with multi-valued tags split apart
+fn cell(column: &str, ident: &str, elem: &PccElem) -> String {
+    let text = if column.eq_ignore_ascii_case(NAME_COLUMN) {
+        ident.to_string()
+    } else {
+        elem.values(&column.to_ascii_uppercase()).join(", ")
+    };
+    match text.chars().count() > MAX_CELL {
+        true => {
+            let cut: String = text.chars().take(MAX_CELL - 3).collect();
+            format!("{}...", cut)
+        }
+        false => text,
+    }
+}
+
+// pad text to a width, then color it
+fn paint(text: &str, width: usize, color: Option<&str>) -> String {
+    let padded = format!("{:<width$}", text, width = width);
+    match color {
+        Some(code) => format!("{}{}{}", code, padded, RESET),
+        None => padded,
+    }
+}
+
+// Lay out elements, as (ident, element), as a table of columns: a
+// header of column names, then one row per element, in the order
+// given.  With color, the header is bold and names are cyan.
+pub fn render(elems: &[(&str, &PccElem)], columns: &[String], color: bool) -> Vec<String> {
+    let header: Vec<String> = columns
+        .iter()
+        .map(|c| match c.eq_ignore_ascii_case(NAME_COLUMN) {
+            true => String::from("Name"),
+            false => c.to_ascii_uppercase(),
+        })
+        .collect();
+    let rows: Vec<Vec<String>> = elems
+        .iter()
+        .map(|(ident, elem)| columns.iter().map(|c| cell(c, ident, elem)).collect())
+        .collect();
+
+    let widths: Vec<usize> = (0..columns.len())
+        .map(|i| {
+            std::iter::once(&header)
+                .chain(&rows)
+                .map(|row| row[i].chars().count())
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+
+    let line = |row: &[String], header: bool| -> String {
+        let cells: Vec<String> = row
+            .iter()
+            .zip(&widths)
+            .enumerate()
+            .map(|(i, (text, width))| {
+                // the last column is not padded
+                let width = if i + 1 == row.len() { 0 } else { *width };
+                let code = match (color, header) {
+                    (false, _) => None,
+                    (true, true) => Some(BOLD),
+                    (true, false) if columns[i].eq_ignore_ascii_case(NAME_COLUMN) => Some(CYAN),
+                    (true, false) => None,
+                };
+                paint(text, width, code)
+            })
+            .collect();
+        cells.join(&" ".repeat(GUTTER)).trim_end().to_string()
+    };
+
+    std::iter::once(line(&header, true))
+        .chain(rows.iter().map(|row| line(row, false)))
+        .collect()
+}
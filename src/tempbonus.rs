@@ -0,0 +1,124 @@
+//
+// tempbonus.rs -- TEMPBONUS tags: bonuses a character may switch on
+// for a while, e.g. from a spell cast upon it
+//
+// Copyright (c) 2024 Jeff Garzik
+//
+// This file is part of the pcgtoolssoftware project covered under
+// the MIT License.  For the full license text, please see the LICENSE
+// file in the root directory of this project.
+// SPDX-License-Identifier: MIT
+
+use crate::bonus::Bonus;
+use crate::pcc::{Pcc, PccElem};
+use crate::token;
+use serde::Serialize;
+
+// who a temporary bonus may be applied to
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub enum TempTarget {
+    // PC: the character having the element
+    Pc,
+    // ANYPC: any character, e.g. the subject of a spell
+    AnyPc,
+    // EQ, or EQ=Weapon: an item of the character, of a TYPE if given
+    Equipment(Option<String>),
+}
+
+impl TempTarget {
+    fn parse(s: &str) -> Option<TempTarget> {
+        let upper = s.to_ascii_uppercase();
+        match upper.as_str() {
+            "PC" => Some(TempTarget::Pc),
+            "ANYPC" => Some(TempTarget::AnyPc),
+            "EQ" => Some(TempTarget::Equipment(None)),
+            _ => {
+                let rest = upper.strip_prefix("EQ=").or(upper.strip_prefix("EQ."))?;
+                let eq_type = &s[s.len() - rest.len()..];
+                Some(TempTarget::Equipment(Some(eq_type.to_string())))
+            }
+        }
+    }
+}
+
+// a TEMPBONUS tag, e.g.
+// TEMPBONUS:ANYPC|BONUS:COMBAT|AC|2|TYPE=Deflection|PRESIZEGT:S.  The
+// conditions are those of the bonus, tested when it is applied.
+#[derive(Clone, Debug, Serialize)]
+pub struct TempBonus {
+    pub targets: Vec<TempTarget>,
+    pub bonus: Bonus,
+}
+
+impl TempBonus {
+    // parse the value of a TEMPBONUS tag; None if its targets or its
+    // BONUS are not understood
+    pub fn parse(value: &str) -> Option<TempBonus> {
+        let (targets, bonus) = value.split_once('|')?;
+        let targets = token::sublist(targets)
+            .into_iter()
+            .map(TempTarget::parse)
+            .collect::<Option<Vec<TempTarget>>>()?;
+        if targets.is_empty() {
+            return None;
+        }
+        let bonus = Bonus::parse(bonus.strip_prefix("BONUS:")?)?;
+        Some(TempBonus { targets, bonus })
+    }
+}
+
+// The temporary effect an element offers: its TEMPBONUS tags, and how
+// long the effect lasts, where the element says, as a spell does by
+// its DURATION.
+#[derive(Clone, Debug, Serialize)]
+pub struct TempEffect {
+    pub list: String,
+    pub key: String,
+    pub name: String,
+    pub duration: Option<String>,
+    pub bonuses: Vec<TempBonus>,
+    pub source: String,
+    pub line: usize,
+}
+
+impl TempEffect {
+    // the effect of an element, if it has TEMPBONUS tags
+    pub fn new(list: &str, elem: &PccElem) -> Option<TempEffect> {
+        let mut bonuses = Vec::new();
+        for value in elem.get_all("TEMPBONUS") {
+            match TempBonus::parse(value) {
+                Some(bonus) => bonuses.push(bonus),
+                None => tracing::warn!(
+                    "{}:{}: malformed TEMPBONUS:{}",
+                    elem.source(),
+                    elem.line(),
+                    value
+                ),
+            }
+        }
+        if bonuses.is_empty() {
+            return None;
+        }
+        Some(TempEffect {
+            list: list.to_string(),
+            key: elem.ident().to_string(),
+            name: elem.display_name().to_string(),
+            duration: elem.get("DURATION").map(String::from),
+            bonuses,
+            source: elem.source().to_string(),
+            line: elem.line(),
+        })
+    }
+}
+
+// the temporary effects of the loaded data, with the element offering
+// each, by list type, then in display order
+pub fn effects(pcc: &Pcc) -> Vec<(&PccElem, TempEffect)> {
+    pcc.lists()
+        .flat_map(|(list, lst)| {
+            lst.sorted()
+                .into_iter()
+                .filter_map(move |(_, elem)| Some((elem, TempEffect::new(list, elem)?)))
+        })
+        .collect()
+}
@@ -0,0 +1,112 @@
+//
+// text.rs -- text file decoding
+//
+// Copyright (c) 2024 Jeff Garzik
+//
+// This file is part of the pcgtoolssoftware project covered under
+// the MIT License.  For the full license text, please see the LICENSE
+// file in the root directory of this project.
+// SPDX-License-Identifier: MIT
+
+use crate::files;
+use encoding_rs::WINDOWS_1252;
+use serde::{Deserialize, Serialize};
+use std::{fmt, io, path::Path, str::FromStr};
+
+// character encoding of PCC and LST files
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum Encoding {
+    // UTF-8, falling back to Windows-1252 for files that are not
+    // valid UTF-8, as is common for older data sets
+    #[default]
+    Auto,
+    Utf8,
+    // Windows-1252, a superset of the printable Latin-1 characters
+    Windows1252,
+}
+
+impl FromStr for Encoding {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "auto" => Ok(Encoding::Auto),
+            "utf-8" | "utf8" => Ok(Encoding::Utf8),
+            "windows-1252" | "cp1252" | "latin1" | "latin-1" | "iso-8859-1" => {
+                Ok(Encoding::Windows1252)
+            }
+            _ => Err(format!(
+                "unknown encoding {} (expected auto, utf-8 or windows-1252)",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Encoding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Encoding::Auto => "auto",
+            Encoding::Utf8 => "utf-8",
+            Encoding::Windows1252 => "windows-1252",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+const UTF8_BOM: &[u8] = b"\xef\xbb\xbf";
+
+// decode raw file contents to text.  a leading UTF-8 byte order mark,
+// as written by many Windows editors, is dropped.
+pub fn decode(mut bytes: Vec<u8>, encoding: Encoding, path: &Path) -> io::Result<String> {
+    if bytes.starts_with(UTF8_BOM) {
+        bytes.drain(..UTF8_BOM.len());
+    }
+
+    match encoding {
+        Encoding::Utf8 => String::from_utf8(bytes).map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{}: {}", path.display(), e),
+            )
+        }),
+
+        Encoding::Windows1252 => Ok(WINDOWS_1252
+            .decode_without_bom_handling(&bytes)
+            .0
+            .into_owned()),
+
+        Encoding::Auto => match String::from_utf8(bytes) {
+            Ok(text) => Ok(text),
+            Err(e) => {
+                tracing::debug!("{}: not UTF-8, decoding as Windows-1252", path.display());
+                let bytes = e.into_bytes();
+                Ok(WINDOWS_1252
+                    .decode_without_bom_handling(&bytes)
+                    .0
+                    .into_owned())
+            }
+        },
+    }
+}
+
+// 64-bit FNV-1a; stable across builds, unlike std's DefaultHasher
+pub(crate) fn fnv1a(data: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for b in data {
+        hash ^= u64::from(*b);
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    hash
+}
+
+// read a text file, returning its lines, without line endings.  both
+// LF and CRLF line endings are accepted, as are stray trailing CRs.
+pub fn read_lines<P: AsRef<Path>>(path: P, encoding: Encoding) -> io::Result<Vec<String>> {
+    let path = path.as_ref();
+    let text = decode(files::read(path)?, encoding, path)?;
+    Ok(text
+        .lines()
+        .map(|line| String::from(line.trim_end_matches('\r')))
+        .collect())
+}
@@ -0,0 +1,112 @@
+//
+// token.rs -- splitting of tag values into their parts
+//
+// Copyright (c) 2024 Jeff Garzik
+//
+// This file is part of the pcgtoolssoftware project covered under
+// the MIT License.  For the full license text, please see the LICENSE
+// file in the root directory of this project.
+// SPDX-License-Identifier: MIT
+
+// Tag values are lists within lists: '|' separates the items of a
+// value, ',' the names of a sublist within an item, and '.' the parts
+// of a TYPE.  A separator nested inside parens or brackets belongs to
+// the nested text, as in "Fireball[PRELEVEL:1|PRECLASS:1,Wizard=1]".
+// Parsers of typed values split values here, rather than each by its
+// own rules.
+
+use std::borrow::Cow;
+
+// split a value on sep, except where sep is nested inside parens or
+// brackets, as in "foo.lst|(INCLUDE:a|b)|PREGAMEMODE:35e"
+pub fn split_top(s: &str, sep: char) -> Vec<&str> {
+    let mut pieces = Vec::new();
+    let mut depth: usize = 0;
+    let mut start = 0;
+    for (i, ch) in s.char_indices() {
+        match ch {
+            '(' | '[' => depth += 1,
+            ')' | ']' => depth = depth.saturating_sub(1),
+            ch if ch == sep && depth == 0 => {
+                pieces.push(&s[start..i]);
+                start = i + ch.len_utf8();
+            }
+            _ => {}
+        }
+    }
+    pieces.push(&s[start..]);
+    pieces
+}
+
+// the '|'-separated items of a value
+pub fn items(s: &str) -> Vec<&str> {
+    split_top(s, '|')
+}
+
+// the ','-separated names of a sublist, trimmed
+pub fn sublist(s: &str) -> Vec<&str> {
+    split_top(s, ',').into_iter().map(|n| n.trim()).collect()
+}
+
+// the '.'-separated parts of a TYPE, or similar, less empty parts
+pub fn dotted(s: &str) -> Vec<&str> {
+    s.split('.').filter(|part| !part.is_empty()).collect()
+}
+
+// Is an item a PRExxx condition, e.g. "PRELEVEL:5" or "!PRERACE:1,Elf"?
+pub fn is_prereq(item: &str) -> bool {
+    item.strip_prefix('!')
+        .unwrap_or(item)
+        .strip_prefix("PRE")
+        .is_some_and(|rest| rest.starts_with(|c: char| c.is_ascii_uppercase()))
+}
+
+// The text of a DESC or SAB value, and the arguments for its "%1",
+// "%2", ... placeholders: those items after the first which are not
+// PRExxx conditions, e.g. "Deals %1 damage|DmgDice|PRELEVEL:3" gives
+// ("Deals %1 damage", ["DmgDice"]).
+pub fn text_args(s: &str) -> (&str, Vec<&str>) {
+    let items = items(s);
+    let (text, rest) = items.split_first().expect("split yields one item");
+    let args = rest.iter().copied().filter(|arg| !is_prereq(arg)).collect();
+    (text, args)
+}
+
+// characters which PCGen data writes as entities, as they would
+// otherwise be read as separators or markup
+const ENTITIES: &[(&str, &str)] = &[
+    ("&nl;", "\n"),
+    ("&pipe;", "|"),
+    ("&comma;", ","),
+    ("&lt;", "<"),
+    ("&gt;", ">"),
+    ("&amp;", "&"),
+];
+
+// Decode the encoded characters of text, e.g. "&nl;" for a line break.
+// Values are decoded only after being split, so that encoded
+// separators are not split upon.
+pub fn decode(s: &str) -> Cow<'_, str> {
+    if !s.contains('&') {
+        return Cow::Borrowed(s);
+    }
+
+    let mut out = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(at) = rest.find('&') {
+        out.push_str(&rest[..at]);
+        rest = &rest[at..];
+        match ENTITIES.iter().find(|(entity, _)| rest.starts_with(entity)) {
+            Some((entity, ch)) => {
+                out.push_str(ch);
+                rest = &rest[entity.len()..];
+            }
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    Cow::Owned(out)
+}
@@ -0,0 +1,364 @@
+//
+// validate.rs -- cross-reference checking of loaded data
+//
+// Copyright (c) 2024 Jeff Garzik
+//
+// This file is part of the pcgtoolssoftware project covered under
+// the MIT License.  For the full license text, please see the LICENSE
+// file in the root directory of this project.
+// SPDX-License-Identifier: MIT
+
+use crate::auto::AutoGrant;
+use crate::choose::{Choose, Chooser, Selector};
+use crate::datacontrol::{DataControl, FactDef};
+use crate::diag::Diagnostic;
+use crate::language;
+use crate::pcc::{Pcc, PccElem};
+use crate::spell::SpellsGrant;
+use crate::token;
+use std::collections::HashSet;
+
+// lowercased RACETYPE values of loaded races
+fn racetypes(pcc: &Pcc) -> HashSet<String> {
+    let mut racetypes = HashSet::new();
+    if let Some(lst) = pcc.list("RACE") {
+        for elem in lst.props.values() {
+            for (k, v) in &elem.attribs {
+                if k == "RACETYPE" {
+                    racetypes.insert(v.to_lowercase());
+                }
+            }
+        }
+    }
+    racetypes
+}
+
+// references that name a group of elements, rather than a single one
+fn is_group_ref(name: &str) -> bool {
+    let upper = name.to_ascii_uppercase();
+    name.is_empty()
+        || name.starts_with('%')
+        || upper == "ALL"
+        || upper == "ANY"
+        || upper.starts_with("TYPE=")
+        || upper.starts_with("TYPE.")
+        || upper.starts_with("SPELLCASTER")
+        || upper.starts_with("RACETYPE=")
+        || upper.starts_with("RACESUBTYPE=")
+        || upper.starts_with("CHOOSE:")
+        || upper == "DEITYWEAPONS"
+        || upper == "LIST"
+}
+
+struct Validator<'a> {
+    pcc: &'a Pcc,
+    racetypes: HashSet<String>,
+    datacontrol: DataControl,
+    diags: Vec<Diagnostic>,
+    skipped: HashSet<String>,
+}
+
+impl Validator<'_> {
+    // check a single reference into the target list
+    fn check(&mut self, elem: &PccElem, ident: &str, tag: &str, target: &str, name: &str) {
+        let name = name.trim();
+        if is_group_ref(name) {
+            return;
+        }
+
+        // target list not loaded at all: note once, rather than
+        // flagging every reference
+        if self.pcc.list(target).is_none() {
+            if self.skipped.insert(target.to_string()) {
+                tracing::warn!("{} list not loaded, references not checked", target);
+            }
+            return;
+        }
+
+        if self.pcc.lookup_in(target, name).is_none() {
+            self.diags.push(Diagnostic::error(
+                &elem.source,
+                elem.line,
+                format!(
+                    "{}: unresolved {} reference \"{}\" in {}{}",
+                    ident,
+                    target,
+                    name,
+                    tag,
+                    self.pcc.did_you_mean(Some(target), name)
+                ),
+            ));
+        }
+    }
+
+    fn check_racetype(&mut self, elem: &PccElem, ident: &str, tag: &str, name: &str) {
+        if !self.racetypes.is_empty() && !self.racetypes.contains(&name.to_lowercase()) {
+            self.diags.push(Diagnostic::error(
+                &elem.source,
+                elem.line,
+                format!("{}: unknown RACETYPE \"{}\" in {}", ident, name, tag),
+            ));
+        }
+    }
+
+    // check the arguments of a CHOOSE, and the elements it names
+    fn check_choose(&mut self, elem: &PccElem, ident: &str, tag: &str, val: &str) {
+        let Some(choose) = Choose::parse(val) else {
+            self.diags.push(Diagnostic::error(
+                &elem.source,
+                elem.line,
+                format!("{}: {} names no chooser", ident, tag),
+            ));
+            return;
+        };
+        let problem = match &choose.chooser {
+            Chooser::Number { min, max, .. } if min > max => {
+                Some(format!("CHOOSE:NUMBER MIN={} exceeds MAX={}", min, max))
+            }
+            Chooser::String(strings) if strings.is_empty() => {
+                Some(String::from("CHOOSE:STRING offers no strings"))
+            }
+            Chooser::Elements {
+                list, selectors, ..
+            } => {
+                for selector in selectors {
+                    if let Selector::Name(name) = selector {
+                        self.check(elem, ident, tag, list, name);
+                    }
+                }
+                None
+            }
+            _ => None,
+        };
+        if let Some(problem) = problem {
+            self.diags.push(Diagnostic::error(
+                &elem.source,
+                elem.line,
+                format!("{}: {}", ident, problem),
+            ));
+        }
+    }
+
+    // check FACT or FACTSET values against their DATACONTROL definition
+    fn check_fact(
+        &mut self,
+        elem: &PccElem,
+        ident: &str,
+        tag: &str,
+        def: &FactDef,
+        values: &[&str],
+    ) {
+        for value in values {
+            if !def.accepts(value) {
+                self.diags.push(Diagnostic::error(
+                    &elem.source,
+                    elem.line,
+                    format!(
+                        "{}: {} {} value \"{}\" is not {}",
+                        ident, tag, def.name, value, def.format
+                    ),
+                ));
+            } else if self.pcc.list(&def.format).is_some() {
+                self.check(elem, ident, tag, &def.format, value);
+            }
+        }
+    }
+
+    // FACT:name|value and FACTSET:name|value|value, as defined by
+    // FACTDEF and FACTSETDEF in DATACONTROL files
+    fn check_facts(&mut self, list: &str, ident: &str, elem: &PccElem) {
+        if self.datacontrol.is_empty() {
+            return;
+        }
+
+        let mut present = HashSet::new();
+        for (tag, val) in &elem.attribs {
+            let is_set = match tag.as_str() {
+                "FACT" => false,
+                "FACTSET" => true,
+                _ => continue,
+            };
+            let mut items = token::items(val).into_iter();
+            let name = items.next().unwrap_or("");
+            let values: Vec<&str> = items.collect();
+            present.insert(name.to_lowercase());
+
+            let def = if is_set {
+                self.datacontrol.factset(list, name)
+            } else {
+                self.datacontrol.fact(list, name)
+            };
+            match def.cloned() {
+                Some(def) => self.check_fact(elem, ident, tag, &def, &values),
+                None => self.diags.push(Diagnostic::error(
+                    &elem.source,
+                    elem.line,
+                    format!(
+                        "{}: {} {} not defined for {} by DATACONTROL",
+                        ident, tag, name, list
+                    ),
+                )),
+            }
+        }
+
+        let missing: Vec<String> = self
+            .datacontrol
+            .required_facts(list)
+            .filter(|def| !present.contains(&def.name.to_lowercase()))
+            .map(|def| def.name.clone())
+            .collect();
+        for name in missing {
+            self.diags.push(Diagnostic::error(
+                &elem.source,
+                elem.line,
+                format!("{}: missing required FACT {}", ident, name),
+            ));
+        }
+    }
+
+    fn check_elem(&mut self, list: &str, ident: &str, elem: &PccElem) {
+        self.check_facts(list, ident, elem);
+
+        for (tag, val) in &elem.attribs {
+            let tag_name = tag.trim_start_matches('!');
+            match (list, tag_name) {
+                // CLASSES:Wizard,Sorcerer=3|Cleric=4
+                ("SPELL", "CLASSES") => {
+                    for group in token::items(val) {
+                        let names = group.split('=').next().unwrap_or("");
+                        for name in token::sublist(names) {
+                            self.check(elem, ident, tag, "CLASS", name);
+                        }
+                    }
+                }
+
+                // DOMAINS:Fire=3|Sun=4 (spells) or DOMAINS:Fire,Sun (deities)
+                ("SPELL", "DOMAINS") | ("DEITY", "DOMAINS") => {
+                    for group in token::items(val) {
+                        if token::is_prereq(group) {
+                            continue;
+                        }
+                        let names = group.split('=').next().unwrap_or("");
+                        for name in token::sublist(names) {
+                            self.check(elem, ident, tag, "DOMAIN", name);
+                        }
+                    }
+                }
+
+                ("DEITY", "DEITYWEAP") => {
+                    for name in token::items(val) {
+                        self.check(elem, ident, tag, "WEAPONPROF", name);
+                    }
+                }
+
+                // SPELLS:Innate|TIMES=1|Fireball,DC=14; kits write
+                // SPELLS in a syntax of their own
+                (list, "SPELLS") if list != "KIT" => match SpellsGrant::parse(val) {
+                    Some(grant) => {
+                        for name in grant.names() {
+                            self.check(elem, ident, tag, "SPELL", name);
+                        }
+                    }
+                    None if !val.starts_with(".CLEAR") => {
+                        self.diags.push(Diagnostic::error(
+                            &elem.source,
+                            elem.line,
+                            format!("{}: {} grants no spells", ident, tag),
+                        ));
+                    }
+                    None => {}
+                },
+
+                // CHOOSE:SKILL|Climb|TYPE=Knowledge
+                (_, "CHOOSE") => self.check_choose(elem, ident, tag, val),
+
+                // TEMPLATE:a|b, possibly "TEMPLATE:CHOOSE:a|b"
+                (_, "TEMPLATE") => {
+                    let val = val.strip_prefix("CHOOSE:").unwrap_or(val);
+                    for name in token::items(val) {
+                        self.check(elem, ident, tag, "TEMPLATE", name);
+                    }
+                }
+
+                // AUTO:WEAPONPROF|a|b, AUTO:LANG|a|b, AUTO:EQUIP|a|b, ...
+                (_, "AUTO") => {
+                    if let Some(grant) = AutoGrant::parse(val) {
+                        for name in grant.names() {
+                            let name = name.trim_end_matches("(%LIST)");
+                            self.check(elem, ident, tag, grant.kind.list(), name);
+                        }
+                    }
+                }
+
+                // LANGBONUS:Draconic,Gnoll or LANGAUTO:Common,Dwarven;
+                // kits write LANGBONUS in a syntax of their own
+                (list, "LANGBONUS" | "LANGAUTO") if list != "KIT" => {
+                    for name in language::references(val) {
+                        self.check(elem, ident, tag, "LANGUAGE", name);
+                    }
+                }
+
+                // PRECLASS:1,Wizard=3,Sorcerer=3
+                (_, "PRECLASS") => {
+                    for item in token::sublist(val).into_iter().skip(1) {
+                        let name = item.split('=').next().unwrap_or("");
+                        self.check(elem, ident, tag, "CLASS", name);
+                    }
+                }
+
+                // PRERACE:1,RACETYPE=Humanoid
+                (_, "PRERACE") => {
+                    for item in token::sublist(val).into_iter().skip(1) {
+                        if let Some(racetype) = item.strip_prefix("RACETYPE=") {
+                            self.check_racetype(elem, ident, tag, racetype);
+                        } else {
+                            self.check(elem, ident, tag, "RACE", item);
+                        }
+                    }
+                }
+
+                // PREDEITY:1,Zeus,Hera; PREDEITY:YES requires any deity
+                (_, "PREDEITY") => {
+                    for item in token::sublist(val).into_iter().skip(1) {
+                        let is_flag = ["Y", "N", "YES", "NO"]
+                            .iter()
+                            .any(|f| item.eq_ignore_ascii_case(f));
+                        if !is_flag {
+                            self.check(elem, ident, tag, "DEITY", item);
+                        }
+                    }
+                }
+
+                (_, "PRERACETYPE") => {
+                    for item in token::sublist(val).into_iter().skip(1) {
+                        self.check_racetype(elem, ident, tag, item);
+                    }
+                }
+
+                _ => {}
+            }
+        }
+    }
+}
+
+// check all cross-list references, returning a diagnostic for each
+// reference that does not resolve to a loaded element
+pub fn validate(pcc: &Pcc) -> Vec<Diagnostic> {
+    let mut v = Validator {
+        pcc,
+        racetypes: racetypes(pcc),
+        datacontrol: DataControl::new(pcc),
+        diags: Vec::new(),
+        skipped: HashSet::new(),
+    };
+
+    for (name, lst) in pcc.lists() {
+        for (ident, elem) in &lst.props {
+            v.check_elem(name, ident, elem);
+        }
+    }
+
+    v.diags
+        .sort_by(|a, b| (&a.file, a.line).cmp(&(&b.file, b.line)));
+    v.diags
+}
@@ -0,0 +1,385 @@
+//
+// variable.rs -- VARIABLE definitions and GLOBALMODIFIER modifiers of
+// the formula system, the DEFINE and DEFINESTAT tags of the data, and
+// evaluation of variable values
+//
+// Copyright (c) 2024 Jeff Garzik
+//
+// This file is part of the pcgtoolssoftware project covered under
+// the MIT License.  For the full license text, please see the LICENSE
+// file in the root directory of this project.
+// SPDX-License-Identifier: MIT
+
+use crate::class::Class;
+use crate::datacontrol::DataControl;
+use crate::formula;
+use crate::pcc::{Pcc, PccElem};
+use crate::token;
+use serde::Serialize;
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
+
+// a VARIABLE line, e.g. "GLOBAL:NUMBER=ArmorBonus" or
+// "LOCAL:PC.EQUIPMENT|NUMBER=EqCost"
+#[derive(Clone, Debug, Serialize)]
+pub struct Variable {
+    pub name: String,
+    // "Global", or the scope of a local variable, e.g. PC.EQUIPMENT
+    pub scope: String,
+    // NUMBER, STRING, BOOLEAN, ...
+    pub format: String,
+    pub explanation: Option<String>,
+    pub source: String,
+    pub line: usize,
+}
+
+impl Variable {
+    fn new(ident: &str, elem: &PccElem) -> Option<Variable> {
+        let (kind, def) = ident.split_once(':')?;
+        let (scope, def) = match kind {
+            "GLOBAL" => ("Global", def),
+            "LOCAL" => def.split_once('|')?,
+            _ => return None,
+        };
+        let (format, name) = def.split_once('=').unwrap_or(("NUMBER", def));
+        if name.is_empty() {
+            return None;
+        }
+        Some(Variable {
+            name: name.to_string(),
+            scope: scope.to_string(),
+            format: format.to_ascii_uppercase(),
+            explanation: elem.values("EXPLANATION").first().map(|v| v.to_string()),
+            source: elem.source().to_string(),
+            line: elem.line(),
+        })
+    }
+
+    pub fn is_global(&self) -> bool {
+        self.scope == "Global"
+    }
+}
+
+// A DEFINE or DEFINESTAT tag of an element, e.g. DEFINE:MonkACBonus|0
+// of CLASS Monk level 1, or DEFINESTAT:LOCK|STR|10 of RACE Golem.
+#[derive(Clone, Debug, Serialize)]
+pub struct Definition {
+    // the variable, or the stat of a DEFINESTAT
+    pub name: String,
+    // DEFINE, or the DEFINESTAT action, e.g. LOCK, NONSTAT or MINVALUE
+    pub action: String,
+    // the initial value, or the stat's value; none for UNLOCK, ...
+    pub formula: Option<String>,
+    // the defining element, e.g. "CLASS Monk level 1"
+    pub element: String,
+    pub source: String,
+    pub line: usize,
+}
+
+impl Definition {
+    // Parse a DEFINE or DEFINESTAT value of element.  The old
+    // DEFINE:LOCK.STR|10 and DEFINE:UNLOCK.STR forms are read as the
+    // DEFINESTAT they are now written as.
+    fn parse(tag: &str, value: &str, element: &str, elem: &PccElem) -> Option<Definition> {
+        let items = token::items(value);
+        let (action, name, formula) = match tag {
+            "DEFINE" => {
+                let (name, formula) = items.split_first()?;
+                match name.split_once('.') {
+                    Some((action @ ("LOCK" | "UNLOCK"), stat)) => (action, stat, formula.first()),
+                    _ => ("DEFINE", *name, formula.first()),
+                }
+            }
+            "DEFINESTAT" => match items.as_slice() {
+                [action, stat, rest @ ..] => (*action, *stat, rest.first()),
+                _ => return None,
+            },
+            _ => return None,
+        };
+        if name.is_empty() {
+            return None;
+        }
+        Some(Definition {
+            name: name.to_string(),
+            action: action.to_ascii_uppercase(),
+            formula: formula.map(|f| f.to_string()),
+            element: element.to_string(),
+            source: elem.source().to_string(),
+            line: elem.line(),
+        })
+    }
+
+    pub fn is_stat(&self) -> bool {
+        self.action != "DEFINE"
+    }
+}
+
+// a MODIFY value, e.g. "Movement|ADD|30|PRIORITY=100"
+#[derive(Clone, Debug, Serialize)]
+pub struct Modifier {
+    pub var: String,
+    // SET, ADD, MULTIPLY, DIVIDE, MIN or MAX
+    pub action: String,
+    pub formula: String,
+    pub priority: Option<i64>,
+    pub source: String,
+    pub line: usize,
+}
+
+impl Modifier {
+    pub fn parse(value: &str, source: &str, line: usize) -> Option<Modifier> {
+        let mut items = token::items(value).into_iter();
+        let var = items.next()?;
+        let action = items.next()?.to_ascii_uppercase();
+        let formula = items.next()?;
+        let priority = items
+            .find_map(|i| i.strip_prefix("PRIORITY="))
+            .and_then(|p| p.trim().parse().ok());
+        Some(Modifier {
+            var: var.to_string(),
+            action,
+            formula: formula.to_string(),
+            priority,
+            source: source.to_string(),
+            line,
+        })
+    }
+
+    // order of application for modifiers of equal PRIORITY
+    fn inherent_priority(&self) -> i64 {
+        match self.action.as_str() {
+            "SET" => 0,
+            "MULTIPLY" => 1,
+            "DIVIDE" => 2,
+            "ADD" => 3,
+            "MAX" => 5,
+            "MIN" => 6,
+            _ => 4,
+        }
+    }
+
+    fn apply(&self, value: f64, operand: f64) -> Result<f64, String> {
+        match self.action.as_str() {
+            "SET" => Ok(operand),
+            "ADD" => Ok(value + operand),
+            "MULTIPLY" => Ok(value * operand),
+            "DIVIDE" if operand == 0.0 => Err(String::from("division by zero")),
+            "DIVIDE" => Ok(value / operand),
+            "MIN" => Ok(value.min(operand)),
+            "MAX" => Ok(value.max(operand)),
+            _ => Err(format!("unknown MODIFY action {}", self.action)),
+        }
+    }
+}
+
+// the DEFINE and DEFINESTAT tags among the tags of an element, or of a
+// level of a class
+fn scan_definitions<'a>(
+    out: &mut Vec<Definition>,
+    element: &str,
+    elem: &PccElem,
+    tags: impl Iterator<Item = (&'a str, &'a str)>,
+) {
+    for (tag, value) in tags.filter(|(tag, _)| matches!(*tag, "DEFINE" | "DEFINESTAT")) {
+        match Definition::parse(tag, value, element, elem) {
+            Some(def) => out.push(def),
+            None => tracing::warn!(
+                "{}:{}: malformed {}:{}",
+                elem.source(),
+                elem.line(),
+                tag,
+                value
+            ),
+        }
+    }
+}
+
+// The DEFINE and DEFINESTAT tags of every element of the data, and of
+// each level of each class, with the element defining each.
+fn definitions(pcc: &Pcc) -> Vec<Definition> {
+    fn tag_strs((k, v): &(String, String)) -> (&str, &str) {
+        (k, v)
+    }
+    let mut out = Vec::new();
+    for (list, lst) in pcc.lists() {
+        for (ident, elem) in lst.iter() {
+            let element = format!("{} {}", list, elem.name());
+            if list != "CLASS" {
+                scan_definitions(&mut out, &element, elem, elem.attribs());
+                continue;
+            }
+            let class = Class::new(ident, elem);
+            scan_definitions(&mut out, &element, elem, class.attribs.iter().map(tag_strs));
+            for cl in &class.levels {
+                let element = format!("{} level {}", element, cl.level);
+                scan_definitions(&mut out, &element, elem, cl.tags.iter().map(tag_strs));
+            }
+        }
+    }
+    out
+}
+
+// the variables of the loaded data, and the global modifiers upon them
+#[derive(Debug, Default)]
+pub struct Variables {
+    pub variables: BTreeMap<String, Variable>,
+    // GLOBALMODIFIER modifiers, in order of application
+    pub modifiers: Vec<Modifier>,
+    // DEFAULTVARIABLEVALUE, variable format => default value
+    pub defaults: BTreeMap<String, String>,
+    // DEFINE tags of all lists, by variable, in list order
+    pub defines: BTreeMap<String, Vec<Definition>>,
+    // DEFINESTAT tags of all lists, by stat, in list order
+    pub stat_defines: BTreeMap<String, Vec<Definition>>,
+    // computed global values
+    values: RefCell<HashMap<String, f64>>,
+}
+
+impl Variables {
+    pub fn new(pcc: &Pcc) -> Variables {
+        let mut vars = Variables {
+            defaults: DataControl::new(pcc).defaults,
+            ..Variables::default()
+        };
+
+        if let Some(lst) = pcc.list("VARIABLE") {
+            for (ident, elem) in &lst.props {
+                match Variable::new(ident, elem) {
+                    Some(var) => {
+                        vars.variables.insert(var.name.clone(), var);
+                    }
+                    None => {
+                        tracing::warn!("{}:{}: malformed {}", elem.source(), elem.line(), ident)
+                    }
+                }
+            }
+        }
+
+        // each GLOBALMODIFIER line is a set of MODIFY tags, the first
+        // of which names the line
+        if let Some(lst) = pcc.list("GLOBALMODIFIER") {
+            for (ident, elem) in &lst.props {
+                let first = ident.strip_prefix("MODIFY:").into_iter();
+                for value in first.chain(elem.values("MODIFY")) {
+                    match Modifier::parse(value, elem.source(), elem.line()) {
+                        Some(m) => vars.modifiers.push(m),
+                        None => tracing::warn!(
+                            "{}:{}: malformed MODIFY:{}",
+                            elem.source(),
+                            elem.line(),
+                            value
+                        ),
+                    }
+                }
+            }
+        }
+        let order = |m: &Modifier| (m.priority.unwrap_or(0), m.inherent_priority());
+        vars.modifiers
+            .sort_by(|a, b| (order(a), &a.source, a.line).cmp(&(order(b), &b.source, b.line)));
+
+        for def in definitions(pcc) {
+            let by_name = if def.is_stat() {
+                &mut vars.stat_defines
+            } else {
+                &mut vars.defines
+            };
+            by_name.entry(def.name.clone()).or_default().push(def);
+        }
+
+        vars
+    }
+
+    // the names of all variables of the data, whether by VARIABLE or
+    // DEFINE
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        let mut names: Vec<&str> = self
+            .variables
+            .keys()
+            .chain(self.defines.keys())
+            .map(|name| name.as_str())
+            .collect();
+        names.sort_unstable();
+        names.dedup();
+        names.into_iter()
+    }
+
+    pub fn variable(&self, name: &str) -> Option<&Variable> {
+        self.variables.get(name)
+    }
+
+    // modifiers of a variable, in order of application
+    pub fn modifiers_of<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Modifier> {
+        self.modifiers.iter().filter(move |m| m.var == name)
+    }
+
+    // the default value of a NUMBER variable, before modification
+    fn default_value(&self, var: &Variable, stack: &mut Vec<String>) -> Result<f64, String> {
+        match self.defaults.get(&var.format) {
+            Some(default) => self.eval_with(default, stack),
+            None => Ok(0.0),
+        }
+    }
+
+    // the value of a global NUMBER variable, with its global
+    // modifiers applied
+    pub fn value(&self, name: &str) -> Result<f64, String> {
+        self.value_of(name, &mut Vec::new())
+    }
+
+    fn value_of(&self, name: &str, stack: &mut Vec<String>) -> Result<f64, String> {
+        if let Some(value) = self.values.borrow().get(name) {
+            return Ok(*value);
+        }
+        let Some(var) = self.variable(name) else {
+            return Err(format!("variable {} not defined", name));
+        };
+        if !var.is_global() {
+            return Err(format!("variable {} is local to {}", name, var.scope));
+        }
+        if var.format != "NUMBER" {
+            return Err(format!("variable {} is not a NUMBER", name));
+        }
+        if stack.iter().any(|s| s == name) {
+            stack.push(name.to_string());
+            return Err(format!("variable cycle: {}", stack.join(" -> ")));
+        }
+
+        stack.push(name.to_string());
+        let mut value = self.default_value(var, stack)?;
+        for m in self.modifiers_of(name) {
+            let operand = self
+                .eval_with(&m.formula, stack)
+                .map_err(|e| format!("{}:{}: MODIFY:{}: {}", m.source, m.line, name, e))?;
+            value = m.apply(value, operand)?;
+        }
+        stack.pop();
+
+        self.values.borrow_mut().insert(name.to_string(), value);
+        Ok(value)
+    }
+
+    fn eval_with(&self, expr: &str, stack: &mut Vec<String>) -> Result<f64, String> {
+        // formula::eval wants a plain lookup, so resolve the formula's
+        // variables first, reporting the first failure
+        let mut resolved = HashMap::new();
+        for name in formula::variables(expr)? {
+            resolved.insert(name.clone(), self.value_of(&name, stack)?);
+        }
+        formula::eval(expr, &|name: &str| resolved.get(name).copied())
+    }
+
+    // evaluate a formula over the global variables
+    pub fn eval(&self, expr: &str) -> Result<f64, String> {
+        self.eval_with(expr, &mut Vec::new())
+    }
+
+    // values of all global NUMBER variables; a variable which cannot
+    // be computed is reported by name
+    pub fn values(&self) -> BTreeMap<String, Result<f64, String>> {
+        self.variables
+            .values()
+            .filter(|v| v.is_global() && v.format == "NUMBER")
+            .map(|v| (v.name.clone(), self.value(&v.name)))
+            .collect()
+    }
+}
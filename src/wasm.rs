@@ -0,0 +1,64 @@
+//
+// wasm.rs -- JavaScript bindings, for web-based data editors
+//
+// Copyright (c) 2024 Jeff Garzik
+//
+// This file is part of the pcgtoolssoftware project covered under
+// the MIT License.  For the full license text, please see the LICENSE
+// file in the root directory of this project.
+// SPDX-License-Identifier: MIT
+
+use crate::compat::DataCompat;
+use crate::files::{self, MemoryFiles};
+use crate::output::OutputFormat;
+use crate::pcc::{DuplicatePolicy, Pcc, PccConfig};
+use crate::text::Encoding;
+use std::{collections::BTreeMap, sync::Arc};
+use wasm_bindgen::prelude::*;
+
+// Load data from files supplied by the caller, as the JSON text of an
+// object of path => file contents, e.g.
+//
+//   loadPcc(JSON.stringify({"mybook/mybook.pcc": "...", ...}))
+//
+// Paths are relative to the data directory, so that "@/" paths of
+// PCC files resolve among them.  Every PCC file supplied is loaded,
+// in path order, into one data set, which is returned as the JSON of
+// `pcgtools dump`.  Game mode system files are not loaded.
+#[wasm_bindgen(js_name = loadPcc)]
+pub fn load_pcc(files: &str) -> Result<String, JsError> {
+    let supplied: BTreeMap<String, String> = serde_json::from_str(files)?;
+    let mut memory = MemoryFiles::new();
+    for (path, text) in supplied {
+        memory.insert(&path, text.into_bytes());
+    }
+    let pccpaths: Vec<String> = memory
+        .paths()
+        .filter(|path| path.to_lowercase().ends_with(".pcc"))
+        .map(String::from)
+        .collect();
+    files::set_provider(Arc::new(memory));
+
+    let config = PccConfig {
+        datadirs: vec![String::new()],
+        gamemode: None,
+        systemdir: None,
+        vendordir: None,
+        homebrewdir: None,
+        cachedir: None,
+        encoding: Encoding::Auto,
+        provenance: false,
+        duplicates: DuplicatePolicy::Warn,
+        allow_unknown_tags: true,
+        schema: None,
+        migrate: false,
+        migrations: None,
+        compat: DataCompat::default(),
+    };
+    let mut pcc = Pcc::new(&config);
+    pcc.read_all(&pccpaths, true)?;
+
+    let mut json = Vec::new();
+    pcc.to_writer(OutputFormat::JsonCompact, &mut json)?;
+    Ok(String::from_utf8(json)?)
+}
@@ -0,0 +1,63 @@
+//
+// watch.rs -- wait for changes to loaded data files
+//
+// Copyright (c) 2024 Jeff Garzik
+//
+// This file is part of the pcgtoolssoftware project covered under
+// the MIT License.  For the full license text, please see the LICENSE
+// file in the root directory of this project.
+// SPDX-License-Identifier: MIT
+
+use crate::pcc::LoadedFile;
+use notify::{RecursiveMode, Watcher};
+use std::{
+    collections::HashSet,
+    io,
+    io::Error,
+    path::{Path, PathBuf},
+    sync::mpsc,
+    time::Duration,
+};
+
+// editors often save in bursts (write, rename, chmod); wait this long
+// after the first event for the burst to finish
+const SETTLE_TIME: Duration = Duration::from_millis(250);
+
+fn canonical(path: &str) -> PathBuf {
+    Path::new(path)
+        .canonicalize()
+        .unwrap_or_else(|_| PathBuf::from(path))
+}
+
+// block until any of the given files is modified, created or removed
+pub fn wait_for_change(files: &[LoadedFile]) -> io::Result<PathBuf> {
+    let paths: HashSet<PathBuf> = files.iter().map(|f| canonical(&f.path)).collect();
+
+    // watch parent directories rather than the files themselves, as
+    // editors frequently replace a file rather than writing in place
+    let dirs: HashSet<&Path> = paths.iter().filter_map(|p| p.parent()).collect();
+
+    let (tx, rx) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(tx).map_err(Error::other)?;
+    for dir in dirs {
+        watcher
+            .watch(dir, RecursiveMode::NonRecursive)
+            .map_err(Error::other)?;
+    }
+
+    loop {
+        let event = rx.recv().map_err(Error::other)?.map_err(Error::other)?;
+        if event.kind.is_access() {
+            continue;
+        }
+
+        if let Some(changed) = event.paths.iter().find(|p| paths.contains(*p)) {
+            tracing::debug!("watch: {:?} {}", event.kind, changed.display());
+
+            // drain the remainder of the burst
+            while rx.recv_timeout(SETTLE_TIME).is_ok() {}
+
+            return Ok(changed.clone());
+        }
+    }
+}
@@ -0,0 +1,642 @@
+//
+// writer.rs -- writing loaded lists back out as LST files
+//
+// Copyright (c) 2024 Jeff Garzik
+//
+// This file is part of the pcgtoolssoftware project covered under
+// the MIT License.  For the full license text, please see the LICENSE
+// file in the root directory of this project.
+// SPDX-License-Identifier: MIT
+
+use crate::diag::Diagnostic;
+use crate::kit::KIT_COMPONENTS;
+use crate::pcc::{
+    block_line_tag, block_name, is_source_header, region_line, LstForm, Pcc, PccDatum, PccElem,
+};
+use crate::text;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs::{self, File},
+    io::{self, BufWriter, Error, Write},
+    path::{Path, PathBuf},
+};
+
+// tags written first on an element line, in this order
+const LEADING_TAGS: [&str; 8] = [
+    "KEY",
+    "ABB",
+    "SORTKEY",
+    "NAMEISPI",
+    "OUTPUTNAME",
+    "CATEGORY",
+    "TYPE",
+    "VISIBLE",
+];
+
+// tags written last on an element line, in this order
+const TRAILING_TAGS: [&str; 7] = [
+    "DESCISPI",
+    "DESC",
+    "SOURCEPAGE",
+    "SOURCELONG",
+    "SOURCESHORT",
+    "SOURCEWEB",
+    "SOURCEDATE",
+];
+
+// source tags which a file's source header line gives every element
+const HEADER_TAGS: [&str; 4] = ["SOURCELONG", "SOURCESHORT", "SOURCEWEB", "SOURCEDATE"];
+
+// campaign tags written first in a PCC file, in this order; others
+// follow in name order
+const CAMPAIGN_TAGS: [&str; 16] = [
+    "CAMPAIGN",
+    "KEY",
+    "GAMEMODE",
+    "TYPE",
+    "BOOKTYPE",
+    "RANK",
+    "SETTING",
+    "GENRE",
+    "PUBNAMELONG",
+    "PUBNAMESHORT",
+    "PUBNAMEWEB",
+    "SOURCELONG",
+    "SOURCESHORT",
+    "SOURCEWEB",
+    "SOURCEDATE",
+    "ISOGL",
+];
+
+// lists a new campaign starts with
+pub const SCAFFOLD_LISTS: [&str; 8] = [
+    "ABILITY",
+    "CLASS",
+    "DEITY",
+    "EQUIPMENT",
+    "RACE",
+    "SKILL",
+    "SPELL",
+    "TEMPLATE",
+];
+
+// lines of a CLASS block following the class line
+const CLASS_LINES: [&str; 5] = [
+    "LEVEL",
+    "SUBCLASS",
+    "SUBCLASSLEVEL",
+    "SUBSTITUTIONCLASS",
+    "SUBSTITUTIONLEVEL",
+];
+
+// the tags stored from the lines following the first line of a block
+fn block_lines(block_tag: &str) -> &'static [&'static str] {
+    match block_tag {
+        "AGESET" => &["RACENAME"],
+        "CLASS" => &CLASS_LINES,
+        "STARTPACK" => KIT_COMPONENTS,
+        _ => &[],
+    }
+}
+
+// tags of a list written after the leading tags, in this order
+pub(crate) fn list_tags(list: &str) -> &'static [&'static str] {
+    match list {
+        "ABILITY" => &["MULT", "STACK", "CHOOSE", "SELECT", "ASPECT", "BENEFIT"],
+        "CLASS" => &[
+            "HD",
+            "MAXLEVEL",
+            "STARTSKILLPTS",
+            "SPELLSTAT",
+            "SPELLTYPE",
+            "MEMORIZE",
+            "CASTAS",
+        ],
+        "DEITY" => &[
+            "DOMAINS",
+            "ALIGN",
+            "FOLLOWERALIGN",
+            "DEITYWEAP",
+            "PANTHEON",
+            "TITLE",
+            "WORSHIPPERS",
+            "APPEARANCE",
+            "SYMBOL",
+        ],
+        "EQUIPMENT" => &[
+            "PROFICIENCY",
+            "COST",
+            "WT",
+            "SIZE",
+            "DAMAGE",
+            "CRITRANGE",
+            "CRITMULT",
+            "RANGE",
+            "ACCHECK",
+            "MAXDEX",
+            "SPELLFAILURE",
+            "BASEITEM",
+            "EQMOD",
+        ],
+        "RACE" => &[
+            "SIZE",
+            "MOVE",
+            "FACE",
+            "REACH",
+            "VISION",
+            "LEGS",
+            "HANDS",
+            "FAVCLASS",
+            "LEVELADJUSTMENT",
+            "CR",
+        ],
+        "SKILL" => &["KEYSTAT", "USEUNTRAINED", "ACHECK", "EXCLUSIVE", "CLASSES"],
+        "SPELL" => &[
+            "SCHOOL",
+            "SUBSCHOOL",
+            "DESCRIPTOR",
+            "CLASSES",
+            "DOMAINS",
+            "COMPS",
+            "CASTTIME",
+            "RANGE",
+            "TARGETAREA",
+            "DURATION",
+            "SAVEINFO",
+            "SPELLRES",
+        ],
+        "TEMPLATE" => &[
+            "SIZE",
+            "MOVE",
+            "VISION",
+            "HD",
+            "CR",
+            "LEVELADJUSTMENT",
+            "FAVOREDCLASS",
+        ],
+        _ => &[],
+    }
+}
+
+// the position of a tag on an element line of a list: leading tags,
+// then the list's own tags, then other tags, then PRExxx, then
+// trailing tags
+pub(crate) fn tag_rank(list: &str, tag: &str) -> usize {
+    let own = list_tags(list);
+    let others = LEADING_TAGS.len() + own.len();
+    if let Some(i) = LEADING_TAGS.iter().position(|t| *t == tag) {
+        i
+    } else if let Some(i) = own.iter().position(|t| *t == tag) {
+        LEADING_TAGS.len() + i
+    } else if let Some(i) = TRAILING_TAGS.iter().position(|t| *t == tag) {
+        others + 2 + i
+    } else if tag.starts_with("PRE") || tag.starts_with("!PRE") {
+        others + 1
+    } else {
+        others
+    }
+}
+
+// "TAG:value", or a bare TAG, as for a token without a tag
+fn token(tag: &str, value: &str) -> String {
+    match value.is_empty() {
+        true => tag.to_string(),
+        false => format!("{}:{}", tag, value),
+    }
+}
+
+// attribs in canonical order.  The sort is stable, so that the values
+// of a repeated tag keep their order.
+fn ordered<'a>(list: &str, attribs: &[(&'a str, &'a str)]) -> Vec<(&'a str, &'a str)> {
+    let mut attribs = attribs.to_vec();
+    attribs.sort_by_key(|(tag, _)| tag_rank(list, tag));
+    attribs
+}
+
+// the source tags every element shares, which a header line can give
+fn shared_source<'a>(elems: &[(&str, &'a PccElem)]) -> Vec<(&'static str, &'a str)> {
+    let Some((_, first)) = elems.first() else {
+        return Vec::new();
+    };
+    HEADER_TAGS
+        .iter()
+        .filter_map(|tag| {
+            let value = first.get(tag)?;
+            elems
+                .iter()
+                .all(|(_, e)| e.get(tag) == Some(value))
+                .then_some((*tag, value))
+        })
+        .collect()
+}
+
+// write a DATATABLE element as the table it was read from
+fn write_table<W: Write>(w: &mut W, ident: &str, elem: &PccElem) -> io::Result<()> {
+    writeln!(w, "STARTTABLE:{}", ident)?;
+    for (_, line) in elem.attribs() {
+        writeln!(w, "{}", line)?;
+    }
+    writeln!(w, "ENDTABLE:{}", ident)
+}
+
+// Write a loaded list as an LST file: a source header line of the
+// source tags all elements share, then one line per element, or one
+// block per element of a CLASS, KIT or BIOSET list, in ident order.
+// Tags are written in the canonical order of the list.  .MOD, .COPY and .CLEAR lines
+// have been applied during load, so each element is written whole.
+pub fn write_lst<W: Write>(pcc: &Pcc, list: &str, w: &mut W) -> io::Result<()> {
+    let Some(lst) = pcc.list(list) else {
+        return Err(Error::other(format!("List {} not loaded", list)));
+    };
+    let mut elems: Vec<(&str, &PccElem)> = lst.iter().collect();
+    elems.sort_by(|a, b| a.0.cmp(b.0));
+
+    let form = pcc.lst_form(list);
+    if form == LstForm::Tables {
+        for (ident, elem) in elems {
+            write_table(w, ident, elem)?;
+        }
+        return Ok(());
+    }
+
+    let header = shared_source(&elems);
+    if !header.is_empty() {
+        let tokens: Vec<String> = header.iter().map(|(t, v)| token(t, v)).collect();
+        writeln!(w, "{}", tokens.join("\t"))?;
+    }
+
+    let sublines = match form {
+        LstForm::Blocks(tag) => block_lines(tag),
+        _ => &[],
+    };
+    for (ident, elem) in elems {
+        let (lines, mut attribs): (Vec<_>, Vec<_>) =
+            elem.attribs().partition(|(tag, _)| sublines.contains(tag));
+
+        // the header line supplies the shared source tags
+        for shared in &header {
+            if let Some(i) = attribs.iter().position(|a| a == shared) {
+                attribs.remove(i);
+            }
+        }
+
+        let mut line = match form {
+            LstForm::Blocks(tag) => format!("{}:{}", tag, ident),
+            _ => ident.to_string(),
+        };
+        for (tag, value) in ordered(list, &attribs) {
+            line.push('\t');
+            line.push_str(&token(tag, value));
+        }
+        writeln!(w, "{}", line)?;
+
+        // a class level line is stored whole, level number first
+        for (tag, value) in lines {
+            match tag {
+                "LEVEL" => writeln!(w, "{}", value)?,
+                _ => writeln!(w, "{}", token(tag, value))?,
+            }
+        }
+    }
+    Ok(())
+}
+
+pub fn write_lst_file(pcc: &Pcc, list: &str, path: &Path) -> io::Result<()> {
+    tracing::debug!("write_lst_file({}, {})", list, path.display());
+
+    let mut w = BufWriter::new(File::create(path)?);
+    write_lst(pcc, list, &mut w)?;
+    w.flush()
+}
+
+// the file name of a list's LST file, e.g. SPELL => spell.lst
+pub fn lst_filename(list: &str) -> String {
+    format!("{}.lst", list.to_lowercase())
+}
+
+// an element's values of each tag, in order; the order of different
+// tags does not matter to the data
+fn tag_values(elem: &PccElem) -> BTreeMap<&str, Vec<&str>> {
+    let mut tags: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+    for (tag, value) in elem.attribs() {
+        tags.entry(tag).or_default().push(value);
+    }
+    tags
+}
+
+// the tags of the lines within blocks, across LST files of a block list
+fn block_line_tags<'a, I>(pcc: &Pcc, block_tag: &str, paths: I) -> io::Result<BTreeSet<String>>
+where
+    I: IntoIterator<Item = &'a str>,
+{
+    let mut tags = BTreeSet::new();
+    for path in paths {
+        for line in text::read_lines(path, pcc.config().encoding)? {
+            if line.trim().is_empty() || line.starts_with('#') || is_source_header(&line) {
+                continue;
+            }
+            let ident = line.split('\t').next().unwrap_or("");
+            if block_name(block_tag, ident).is_none() && region_line(ident).is_none() {
+                tags.insert(block_line_tag(ident).to_string());
+            }
+        }
+    }
+    Ok(tags)
+}
+
+// Write every loaded list to an LST file in dir, read the files back,
+// and report each element which was lost, gained, or whose values
+// changed along the way.  For a block list, also report tags which
+// were on lines of their own within blocks, but were written on the
+// first line of a block, or the other way around.
+pub fn roundtrip(pcc: &Pcc, dir: &Path) -> io::Result<Vec<Diagnostic>> {
+    fs::create_dir_all(dir)?;
+
+    let mut reread = Pcc::new(pcc.config());
+    if let Some(schema) = &pcc.config().schema {
+        reread.load_schema(schema)?;
+    }
+
+    let mut diags = Vec::new();
+    for (list, lst) in pcc.lists() {
+        let path = dir.join(lst_filename(list));
+        let fpath = path.to_string_lossy();
+        write_lst_file(pcc, list, &path)?;
+        reread.read_lst(list, "", &fpath, "")?;
+
+        let Some(relst) = reread.list(list) else {
+            diags.push(Diagnostic::error(
+                &fpath,
+                0,
+                format!("{}: list not re-read", list),
+            ));
+            continue;
+        };
+        let mut idents: Vec<&str> = lst.iter().map(|(ident, _)| ident).collect();
+        idents.sort();
+        for ident in idents {
+            let elem = lst.get(ident).unwrap();
+            let Some(again) = relst.get(ident) else {
+                diags.push(Diagnostic::error(
+                    &fpath,
+                    0,
+                    format!("{} {}: lost in round trip", list, ident),
+                ));
+                continue;
+            };
+
+            let (before, after) = (tag_values(elem), tag_values(again));
+            let tags: BTreeSet<&str> = before.keys().chain(after.keys()).copied().collect();
+            for tag in tags {
+                let (was, now) = (before.get(tag), after.get(tag));
+                if was != now {
+                    diags.push(Diagnostic::error(
+                        &fpath,
+                        again.line(),
+                        format!(
+                            "{} {}: {} was {:?}, re-read as {:?}",
+                            list,
+                            ident,
+                            tag,
+                            was.map_or(&[][..], |v| v.as_slice()),
+                            now.map_or(&[][..], |v| v.as_slice())
+                        ),
+                    ));
+                }
+            }
+        }
+        for (ident, again) in relst.iter() {
+            if lst.get(ident).is_none() {
+                diags.push(Diagnostic::error(
+                    &fpath,
+                    again.line(),
+                    format!("{} {}: gained in round trip", list, ident),
+                ));
+            }
+        }
+
+        let LstForm::Blocks(block_tag) = pcc.lst_form(list) else {
+            continue;
+        };
+        let sources: BTreeSet<&str> = pcc
+            .files()
+            .iter()
+            .filter(|file| file.kind == list)
+            .map(|file| file.path.as_str())
+            .collect();
+        let before = block_line_tags(pcc, block_tag, sources)?;
+        let after = block_line_tags(pcc, block_tag, [fpath.as_ref()])?;
+        // tags of elements since forgotten are not written at all
+        let written: BTreeSet<&str> = lst
+            .iter()
+            .flat_map(|(_, e)| e.attribs())
+            .map(|(t, _)| t)
+            .collect();
+        for tag in before.difference(&after) {
+            if written.contains(tag.as_str()) {
+                diags.push(Diagnostic::error(
+                    &fpath,
+                    0,
+                    format!(
+                        "{}: {} lines written as tags of {} lines",
+                        list, tag, block_tag
+                    ),
+                ));
+            }
+        }
+        for tag in after.difference(&before) {
+            diags.push(Diagnostic::error(
+                &fpath,
+                0,
+                format!("{}: {} tags written as lines of their own", list, tag),
+            ));
+        }
+    }
+
+    Ok(diags)
+}
+
+// the contents of a PCC file: campaign tags, then references to the
+// LST file of each list
+#[derive(Clone, Debug, Default)]
+pub struct PccFile {
+    // (tag, value), one per line
+    pub tags: Vec<(String, String)>,
+    // (list type, LST path relative to the PCC file)
+    pub lsts: Vec<(String, String)>,
+}
+
+impl PccFile {
+    // The campaign tags of the loaded data, and a reference to the LST
+    // file of each of lists, named as by lst_filename.  Tags repeated
+    // across the PCC files loaded are written once per value.
+    pub fn from_pcc(pcc: &Pcc, lists: &[&str]) -> PccFile {
+        let mut tags: Vec<(&String, &PccDatum)> = pcc
+            .dict
+            .iter()
+            .filter(|(_, datum)| !matches!(datum, PccDatum::List(_)))
+            .collect();
+        tags.sort_by_key(|(tag, _)| {
+            let rank = CAMPAIGN_TAGS.iter().position(|t| t == tag);
+            (rank.unwrap_or(CAMPAIGN_TAGS.len()), tag.as_str())
+        });
+
+        let mut file = PccFile::default();
+        for (tag, datum) in tags {
+            let text = datum.scalar_text().unwrap_or_default();
+            for value in text.lines() {
+                file.tags.push((tag.clone(), value.to_string()));
+            }
+        }
+        for list in lists {
+            file.lsts.push((list.to_string(), lst_filename(list)));
+        }
+        file
+    }
+
+    pub fn write<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        for (tag, value) in &self.tags {
+            writeln!(w, "{}:{}", tag, value)?;
+        }
+        if !self.tags.is_empty() && !self.lsts.is_empty() {
+            writeln!(w)?;
+        }
+        for (list, path) in &self.lsts {
+            writeln!(w, "{}:{}", list, path)?;
+        }
+        Ok(())
+    }
+
+    pub fn write_file(&self, path: &Path) -> io::Result<()> {
+        tracing::debug!("PccFile::write_file({})", path.display());
+
+        let mut w = BufWriter::new(File::create(path)?);
+        self.write(&mut w)?;
+        w.flush()
+    }
+}
+
+// the settings of a new, empty campaign
+#[derive(Clone, Debug)]
+pub struct Scaffold {
+    pub name: String,
+    pub gamemode: String,
+    pub source_short: String,
+    pub source_web: Option<String>,
+    pub lists: Vec<String>,
+}
+
+// a file name for a campaign name, e.g. "My Book" => my_book
+fn file_stem(name: &str) -> String {
+    name.chars()
+        .map(|c| match c.is_ascii_alphanumeric() {
+            true => c.to_ascii_lowercase(),
+            false => '_',
+        })
+        .collect()
+}
+
+// Create a skeleton campaign in dir: a PCC file with GAMEMODE and
+// SOURCE tags, and an LST file for each list, holding only a source
+// header line.  An existing PCC file is not overwritten.  Returns the
+// path of the PCC file.
+pub fn new_campaign(dir: &Path, scaffold: &Scaffold) -> io::Result<PathBuf> {
+    fs::create_dir_all(dir)?;
+
+    let mut pcc = PccFile::default();
+    let mut tag = |tag: &str, value: &str| pcc.tags.push((tag.to_string(), value.to_string()));
+    tag("CAMPAIGN", &scaffold.name);
+    tag("GAMEMODE", &scaffold.gamemode);
+    tag("SOURCELONG", &scaffold.name);
+    tag("SOURCESHORT", &scaffold.source_short);
+    if let Some(web) = &scaffold.source_web {
+        tag("SOURCEWEB", web);
+    }
+
+    let mut header = format!(
+        "SOURCELONG:{}\tSOURCESHORT:{}",
+        scaffold.name, scaffold.source_short
+    );
+    if let Some(web) = &scaffold.source_web {
+        header.push_str(&format!("\tSOURCEWEB:{}", web));
+    }
+
+    let path = dir.join(format!("{}.pcc", file_stem(&scaffold.name)));
+    let mut w = BufWriter::new(
+        File::create_new(&path)
+            .map_err(|e| Error::new(e.kind(), format!("{}: {}", path.display(), e)))?,
+    );
+
+    for list in &scaffold.lists {
+        let list = list.to_ascii_uppercase();
+        let lst = lst_filename(&list);
+        let lst_path = dir.join(&lst);
+        if !lst_path.exists() {
+            fs::write(&lst_path, format!("{}\n", header))?;
+        }
+        pcc.lsts.push((list, lst));
+    }
+
+    pcc.write(&mut w)?;
+    w.flush()?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pcc::tests::{load, test_dir};
+
+    const KITS: &str = "SOURCELONG:Kits\tSOURCESHORT:K\n\
+                        STARTPACK:Fighter Kit\tTYPE:Fighter\tEQUIPBUY:0\n\
+                        GEAR:Longsword\tQTY:1\n\
+                        GEAR:Dagger\tQTY:2\tLOCATION:Carried\n\
+                        FEAT:Power Attack\n\
+                        STARTPACK:Fighter Kit.MOD\n\
+                        GEAR:Rope\n";
+    const CLASSES: &str = "CLASS:Wizard\tHD:4\tTYPE:Base.PC\n\
+                           1\tBONUS:SAVE|Will|2|TYPE=Base\n\
+                           2\tCAST:1,1\n";
+
+    fn kits_and_classes(name: &str) -> Pcc {
+        load(
+            name,
+            &[
+                ("t.pcc", "CAMPAIGN:T\nKIT:k.lst\nCLASS:c.lst\n"),
+                ("k.lst", KITS),
+                ("c.lst", CLASSES),
+            ],
+        )
+    }
+
+    #[test]
+    fn blocks_survive_round_trip() {
+        let pcc = kits_and_classes("roundtrip");
+        let diags = roundtrip(&pcc, &test_dir("roundtrip-out")).unwrap();
+        assert!(diags.is_empty(), "{:?}", diags);
+    }
+
+    #[test]
+    fn block_components_written_as_lines() {
+        let pcc = kits_and_classes("blocks");
+        let mut w = Vec::new();
+        write_lst(&pcc, "KIT", &mut w).unwrap();
+        let text = String::from_utf8(w).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines[0], "SOURCELONG:Kits\tSOURCESHORT:K");
+        assert!(lines[1].starts_with("STARTPACK:Fighter Kit\t"));
+        for line in ["FEAT:Power Attack", "GEAR:Rope"] {
+            assert!(lines.contains(&line), "{}", text);
+        }
+
+        let mut w = Vec::new();
+        write_lst(&pcc, "CLASS", &mut w).unwrap();
+        let text = String::from_utf8(w).unwrap();
+        assert!(
+            text.contains("\n1\tBONUS:SAVE|Will|2|TYPE=Base\n"),
+            "{}",
+            text
+        );
+        assert!(text.contains("\n2\tCAST:1,1\n"), "{}", text);
+    }
+}